@@ -1,3 +1,108 @@
+//! Command-line wallet. Drives the wallet of a running `dmto-walletd` over
+//! its JSON-RPC socket at `<data_dir>/walletd.sock`.
+//!
+//! ```text
+//! cli balance
+//! cli send <amount> [memo]
+//! cli receive <token>
+//! cli pay <invoice> [amount]
+//! cli history
+//! ```
+//!
+//! Flags after the command configure it as for the daemon: `--config
+//! <path>` (or `DMTO_CONFIG`) names the TOML file, and `--data-dir` must
+//! match the daemon's.
+
+use std::process;
+
+use dmto_mint::config::Config;
+use serde_json::{Value, json};
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    process::exit(2);
+}
+
+const USAGE: &str = "usage: cli <balance | send <amount> [memo] | receive <token> | \
+                     pay <invoice> [amount] | history> [--config <path>] [--key value ...]";
+
+fn amount(value: &str) -> u64 {
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(format!("expected an amount, got `{}`", value)))
+}
+
+/// The JSON-RPC method and params for a command and its arguments.
+fn request(command: &str, args: &[String]) -> (&'static str, Value) {
+    match (command, args) {
+        ("balance", []) => ("balance", json!({})),
+        ("send", [value]) => ("send", json!({ "amount": amount(value) })),
+        ("send", [value, memo]) => ("send", json!({ "amount": amount(value), "memo": memo })),
+        ("receive", [token]) => {
+            let token: Value = serde_json::from_str(token)
+                .unwrap_or_else(|e| fail(format!("malformed token: {}", e)));
+            ("receive", json!({ "token": token }))
+        }
+        ("pay", [invoice]) => ("melt", json!({ "request": invoice })),
+        ("pay", [invoice, value]) => (
+            "melt",
+            json!({ "request": invoice, "amount": amount(value) }),
+        ),
+        ("history", []) => ("history", Value::Null),
+        _ => fail(USAGE),
+    }
+}
+
+#[cfg(unix)]
+fn call(config: &Config, method: &str, params: Value) -> Result<Value, String> {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+    };
+
+    let path = config.data_dir.join("walletd.sock");
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|e| format!("cannot reach dmto-walletd at {}: {}", path.display(), e))?;
+    let line = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    writeln!(stream, "{}", line).map_err(|e| e.to_string())?;
+
+    let mut response = String::new();
+    BufReader::new(stream)
+        .read_line(&mut response)
+        .map_err(|e| e.to_string())?;
+    let mut response: Value = serde_json::from_str(&response).map_err(|e| e.to_string())?;
+    match response.get("error") {
+        Some(error) => Err(error["message"]
+            .as_str()
+            .unwrap_or("unknown error")
+            .to_string()),
+        None => Ok(response["result"].take()),
+    }
+}
+
+#[cfg(not(unix))]
+fn call(_: &Config, _: &str, _: Value) -> Result<Value, String> {
+    Err("the wallet daemon needs Unix domain sockets".to_string())
+}
+
 fn main() {
-    println!("Hello, world!");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let flags = args
+        .iter()
+        .position(|a| a.starts_with("--"))
+        .unwrap_or(args.len());
+    let (command, rest) = match args[..flags].split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => fail(USAGE),
+    };
+    let (method, params) = request(command, rest);
+    let config = Config::load(None, std::env::vars(), &args[flags..]).unwrap_or_else(|e| fail(e));
+
+    match call(&config, method, params) {
+        Ok(result) => println!("{}", serde_json::to_string_pretty(&result).unwrap()),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
 }
@@ -1,27 +1,44 @@
+use std::fmt;
+
 use rand::RngCore;
 use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
 
-#[derive(Clone)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct BlindedMessage {
     pub blinded_point: PublicKey,
     pub blind_factor: Scalar,
 }
 
+/// Leaves the blinding factor out; with it the mint could link the note.
+impl fmt::Debug for BlindedMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlindedMessage")
+            .field("blinded_point", &self.blinded_point)
+            .field("blind_factor", &"<redacted>")
+            .finish()
+    }
+}
+
 fn random_scalar() -> Scalar {
     loop {
         let mut bytes = [0u8; 32];
         rand::thread_rng().fill_bytes(&mut bytes);
-        if let Ok(s) = Scalar::from_be_bytes(bytes) {
-            if s != Scalar::ZERO {
-                return s;
-            }
+        if let Ok(s) = Scalar::from_be_bytes(bytes)
+            && s != Scalar::ZERO
+        {
+            return s;
         }
     }
 }
 
 pub fn blind_message(y: &PublicKey) -> BlindedMessage {
+    blind_message_with(y, random_scalar())
+}
+
+/// Blinds `y` with a given factor, for wallets that derive their blinding
+/// factors so they can restore notes later.
+pub fn blind_message_with(y: &PublicKey, r: Scalar) -> BlindedMessage {
     let secp = Secp256k1::new();
-    let r = random_scalar();
 
     let r_g = PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&r.to_be_bytes()).unwrap());
 
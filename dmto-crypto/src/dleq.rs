@@ -0,0 +1,149 @@
+//! Discrete-log equality proofs (Chaum-Pedersen) that a blind signature
+//! `C' = a·B'` was made with the same key `a` as the published `A = a·G`.
+//!
+//! ```text
+//! mint:   r ← random, R1 = r·G, R2 = r·B'
+//!         e = SHA256(R1 ‖ R2 ‖ A ‖ C'), s = r + e·a
+//! check:  R1 = s·G − e·A, R2 = s·B' − e·C', e == SHA256(R1 ‖ R2 ‖ A ‖ C')
+//! ```
+
+use std::hash::{Hash, Hasher};
+
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey, constants};
+use serde::{Deserialize, Serialize};
+
+use crate::hash::{DefaultBackend, HashBackend};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dleq {
+    pub e: SecretKey,
+    pub s: SecretKey,
+}
+
+/// A `Dleq` kept with an unblinded note together with the blinding factor
+/// `r`, so anyone holding the note can rebuild `B' = Y + r·G` and
+/// `C' = C + r·A` and check the proof against the mint's public key alone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NoteDleq {
+    pub e: SecretKey,
+    pub s: SecretKey,
+    pub r: SecretKey,
+}
+
+// `SecretKey` does not implement `Hash`, so these hash the scalar bytes.
+impl Hash for Dleq {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.e.secret_bytes().hash(state);
+        self.s.secret_bytes().hash(state);
+    }
+}
+
+impl Hash for NoteDleq {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.e.secret_bytes().hash(state);
+        self.s.secret_bytes().hash(state);
+        self.r.secret_bytes().hash(state);
+    }
+}
+
+fn challenge<H: HashBackend>(
+    r1: &PublicKey,
+    r2: &PublicKey,
+    a: &PublicKey,
+    c: &PublicKey,
+) -> Option<SecretKey> {
+    let [r1, r2, a, c] = [r1, r2, a, c].map(|p| p.serialize());
+    SecretKey::from_slice(&H::digest(&[&r1, &r2, &a, &c])).ok()
+}
+
+/// `p·x − q·y`, or `None` if the result is the point at infinity.
+fn sub_mul(p: &PublicKey, x: &SecretKey, q: &PublicKey, y: &SecretKey) -> Option<PublicKey> {
+    let secp = Secp256k1::new();
+    let px = p.mul_tweak(&secp, &Scalar::from(*x)).ok()?;
+    let qy = q.mul_tweak(&secp, &Scalar::from(*y)).ok()?;
+    px.combine(&qy.negate(&secp)).ok()
+}
+
+pub fn prove(a: &SecretKey, blinded: &PublicKey, blind_sig: &PublicKey) -> Dleq {
+    prove_with::<DefaultBackend>(a, blinded, blind_sig)
+}
+
+pub fn prove_with<H: HashBackend>(
+    a: &SecretKey,
+    blinded: &PublicKey,
+    blind_sig: &PublicKey,
+) -> Dleq {
+    let secp = Secp256k1::new();
+    let big_a = PublicKey::from_secret_key(&secp, a);
+
+    loop {
+        let r = SecretKey::new(&mut rand::thread_rng());
+        let r1 = PublicKey::from_secret_key(&secp, &r);
+        let Ok(r2) = blinded.mul_tweak(&secp, &Scalar::from(r)) else {
+            continue;
+        };
+        let Some(e) = challenge::<H>(&r1, &r2, &big_a, blind_sig) else {
+            continue;
+        };
+        let Ok(ea) = a.mul_tweak(&Scalar::from(e)) else {
+            continue;
+        };
+        if let Ok(s) = r.add_tweak(&Scalar::from(ea)) {
+            return Dleq { e, s };
+        }
+    }
+}
+
+pub fn verify(big_a: &PublicKey, blinded: &PublicKey, blind_sig: &PublicKey, dleq: &Dleq) -> bool {
+    verify_with::<DefaultBackend>(big_a, blinded, blind_sig, dleq)
+}
+
+pub fn verify_with<H: HashBackend>(
+    big_a: &PublicKey,
+    blinded: &PublicKey,
+    blind_sig: &PublicKey,
+    dleq: &Dleq,
+) -> bool {
+    let one = SecretKey::from_slice(&constants::ONE).unwrap();
+    let g = PublicKey::from_secret_key(&Secp256k1::new(), &one);
+
+    let (Some(r1), Some(r2)) = (
+        sub_mul(&g, &dleq.s, big_a, &dleq.e),
+        sub_mul(blinded, &dleq.s, blind_sig, &dleq.e),
+    ) else {
+        return false;
+    };
+
+    challenge::<H>(&r1, &r2, big_a, blind_sig) == Some(dleq.e)
+}
+
+/// Verifies the DLEQ carried by an unblinded note `(Y, C)` signed with `A`.
+pub fn verify_unblinded(big_a: &PublicKey, y: &PublicKey, c: &PublicKey, proof: &NoteDleq) -> bool {
+    verify_unblinded_with::<DefaultBackend>(big_a, y, c, proof)
+}
+
+pub fn verify_unblinded_with<H: HashBackend>(
+    big_a: &PublicKey,
+    y: &PublicKey,
+    c: &PublicKey,
+    proof: &NoteDleq,
+) -> bool {
+    let secp = Secp256k1::new();
+    let r_g = PublicKey::from_secret_key(&secp, &proof.r);
+    let Ok(r_a) = big_a.mul_tweak(&secp, &Scalar::from(proof.r)) else {
+        return false;
+    };
+    let (Ok(blinded), Ok(blind_sig)) = (y.combine(&r_g), c.combine(&r_a)) else {
+        return false;
+    };
+
+    verify_with::<H>(
+        big_a,
+        &blinded,
+        &blind_sig,
+        &Dleq {
+            e: proof.e,
+            s: proof.s,
+        },
+    )
+}
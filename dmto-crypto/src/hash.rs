@@ -0,0 +1,187 @@
+use std::{fmt, thread};
+
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use sha2::{Digest, Sha256};
+
+/// The 256-bit digest behind `hash_to_curve` and DLEQ challenges. Mints with
+/// a faster implementation (hardware or FFI) plug it in through the `_with`
+/// variants of those functions; both sides must use the same function.
+/// Every backend here computes SHA-256, so they are interchangeable.
+pub trait HashBackend {
+    fn digest(parts: &[&[u8]]) -> [u8; 32];
+}
+
+/// SHA-256 from the `sha2` crate, which already selects SHA-NI or the ARMv8
+/// crypto extensions at runtime where the CPU has them.
+pub struct Sha2Backend;
+
+impl HashBackend for Sha2Backend {
+    fn digest(parts: &[&[u8]]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.finalize().into()
+    }
+}
+
+/// SHA-256 from `ring`, whose hand-written assembly is faster than `sha2` on
+/// CPUs without SHA extensions.
+#[cfg(feature = "ring")]
+pub struct RingBackend;
+
+#[cfg(feature = "ring")]
+impl HashBackend for RingBackend {
+    fn digest(parts: &[&[u8]]) -> [u8; 32] {
+        let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+        for part in parts {
+            context.update(part);
+        }
+        context.finish().as_ref().try_into().unwrap()
+    }
+}
+
+/// The backend behind the functions without a `_with` suffix: `RingBackend`
+/// with the `ring` feature, `Sha2Backend` otherwise.
+#[cfg(feature = "ring")]
+pub type DefaultBackend = RingBackend;
+#[cfg(not(feature = "ring"))]
+pub type DefaultBackend = Sha2Backend;
+
+/// Counter values `hash_to_curve` tries before giving up, as in the spec.
+///
+/// A digest fails when it is zero or not below the group order, with
+/// probability under 2^-127, so all 2^16 tries fail with probability under
+/// 2^-(127 * 2^16). The cap exists only to bound the work any input can
+/// cause.
+pub const MAX_COUNTER: u32 = 1 << 16;
+
+/// No counter below the cap gave a point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HashToCurveError {
+    pub max_counter: u32,
+}
+
+impl fmt::Display for HashToCurveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no curve point within {} attempts", self.max_counter)
+    }
+}
+
+impl std::error::Error for HashToCurveError {}
+
+/// `try_hash_to_curve`, for secrets this side generated.
+///
+/// # Panics
+///
+/// If no point is found within `MAX_COUNTER` tries, which in practice never
+/// happens. Use `try_hash_to_curve` on secrets from elsewhere.
+pub fn hash_to_curve(secret: &[u8]) -> PublicKey {
+    hash_to_curve_with::<DefaultBackend>(secret)
+}
+
+pub fn hash_to_curve_with<H: HashBackend>(secret: &[u8]) -> PublicKey {
+    try_hash_to_curve_with::<H>(secret, MAX_COUNTER).expect("hash_to_curve exhausted its counter")
+}
+
+pub fn try_hash_to_curve(secret: &[u8]) -> Result<PublicKey, HashToCurveError> {
+    try_hash_to_curve_with::<DefaultBackend>(secret, MAX_COUNTER)
+}
+
+/// Tries counters `0..max_counter` and returns the first that gives a point.
+pub fn try_hash_to_curve_with<H: HashBackend>(
+    secret: &[u8],
+    max_counter: u32,
+) -> Result<PublicKey, HashToCurveError> {
+    let secp = Secp256k1::new();
+    for ctr in 0..max_counter {
+        let hash = H::digest(&[b"ecash_hash_to_curve", secret, &ctr.to_be_bytes()]);
+
+        if let Ok(sk) = SecretKey::from_slice(&hash) {
+            return Ok(PublicKey::from_secret_key(&secp, &sk));
+        }
+    }
+    Err(HashToCurveError { max_counter })
+}
+
+/// `hash_to_curve` over many secrets, split across the available cores.
+/// Results are in input order.
+pub fn hash_to_curve_batch<S: AsRef<[u8]> + Sync>(secrets: &[S]) -> Vec<PublicKey> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = secrets.len().div_ceil(threads).max(64);
+    if secrets.len() <= chunk {
+        return secrets.iter().map(|s| hash_to_curve(s.as_ref())).collect();
+    }
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = secrets
+            .chunks(chunk)
+            .map(|part| {
+                scope.spawn(move || {
+                    part.iter()
+                        .map(|s| hash_to_curve(s.as_ref()))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, RngCore};
+
+    use super::*;
+
+    /// Digests at or above the group order, so no counter gives a point.
+    struct Invalid;
+
+    impl HashBackend for Invalid {
+        fn digest(_parts: &[&[u8]]) -> [u8; 32] {
+            [0xff; 32]
+        }
+    }
+
+    #[test]
+    fn random_messages_stay_under_the_cap() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..10_000 {
+            let mut secret = vec![0; rng.gen_range(0..128)];
+            rng.fill_bytes(&mut secret);
+            assert!(try_hash_to_curve(&secret).is_ok(), "{:?}", secret);
+        }
+    }
+
+    #[cfg(feature = "ring")]
+    #[test]
+    fn backends_agree() {
+        let parts: [&[u8]; 3] = [b"ecash_hash_to_curve", b"secret", &[0, 0, 0, 1]];
+        assert_eq!(RingBackend::digest(&parts), Sha2Backend::digest(&parts));
+        assert_eq!(
+            hash_to_curve_with::<RingBackend>(b"secret"),
+            hash_to_curve_with::<Sha2Backend>(b"secret")
+        );
+    }
+
+    #[test]
+    fn exhausted_cap_is_an_error() {
+        assert_eq!(
+            try_hash_to_curve_with::<Sha2Backend>(b"secret", 0),
+            Err(HashToCurveError { max_counter: 0 })
+        );
+        assert_eq!(
+            try_hash_to_curve_with::<Invalid>(b"secret", 1),
+            Err(HashToCurveError { max_counter: 1 })
+        );
+        assert_eq!(
+            try_hash_to_curve_with::<Invalid>(b"secret", MAX_COUNTER),
+            Err(HashToCurveError {
+                max_counter: MAX_COUNTER
+            })
+        );
+    }
+}
@@ -0,0 +1,8 @@
+//! The cryptographic primitives of the protocol: hashing to the curve,
+//! blinding, DLEQ proofs and Schnorr signing. No mint or wallet state, so
+//! both sides and light clients can depend on it alone.
+
+pub mod blind;
+pub mod dleq;
+pub mod hash;
+pub mod signing;
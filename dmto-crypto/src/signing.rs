@@ -0,0 +1,16 @@
+use secp256k1::{Keypair, Message, Secp256k1, XOnlyPublicKey, schnorr::Signature};
+use sha2::{Digest, Sha256};
+
+/// BIP-340 Schnorr signature over `SHA256(msg)`. Used for everything the mint
+/// signs with its long-term identity key.
+pub fn sign(keypair: &Keypair, msg: &[u8]) -> Signature {
+    let digest = Message::from_digest(Sha256::digest(msg).into());
+    Secp256k1::new().sign_schnorr_with_rng(&digest, keypair, &mut rand::thread_rng())
+}
+
+pub fn verify(pubkey: &XOnlyPublicKey, msg: &[u8], sig: &Signature) -> bool {
+    let digest = Message::from_digest(Sha256::digest(msg).into());
+    Secp256k1::verification_only()
+        .verify_schnorr(sig, &digest, pubkey)
+        .is_ok()
+}
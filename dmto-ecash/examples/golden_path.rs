@@ -0,0 +1,70 @@
+//! End-to-end flow over the public API: a mint running as a single-owner
+//! actor, two wallets talking to it through `MintTrait`, and an offline
+//! check of a token between them.
+//!
+//! ```text
+//! cargo run -p dmto-ecash --example golden_path
+//! ```
+//!
+//! Issuance uses `Wallet::mint_note` as the demo does; there is no
+//! quote-based minting or melting to a payment backend yet.
+
+use dmto_ecash::{
+    actor::MintActor, mint::Mint, mint::ProofState, token::Token, verifier::Verifier,
+    wallet::Wallet,
+};
+
+fn main() {
+    println!("=== Golden path ===");
+
+    // Same keyset ID on every run
+    let mint = Mint::new_deterministic(b"golden-path", &[1, 2, 4, 8, 16]);
+    let bundle = mint.export_keyset();
+    let identity = mint.identity.x_only_public_key().0;
+
+    // Alice is issued 24 directly before the mint goes online
+    let mut alice = Wallet::new();
+    alice.mint_note(&mint, 16);
+    alice.mint_note(&mint, 8);
+
+    let daemon = MintActor::spawn(mint);
+    let client = daemon.client();
+    println!("Mint online, keyset {}", client.info().unwrap().keyset_id);
+
+    // Alice sends 11 to Bob as a token
+    let notes = alice.split_out(&client, &[8, 2, 1]).expect("send failed");
+    let token = Token::new("mint.local", notes);
+    println!(
+        "Alice sent token of {}, kept {}",
+        token.amount().unwrap(),
+        alice.balance()
+    );
+
+    // Bob checks it offline, then redeems it
+    let mut verifier = Verifier::new();
+    assert!(verifier.import_bundle(&bundle, &identity));
+    let amount = verifier.verify_token(&token).expect("token invalid");
+    println!("Bob verified {} offline", amount);
+
+    let mut bob = Wallet::new();
+    let received = bob.receive_token(&client, &token).expect("receive failed");
+    println!("Bob received {}", received);
+    assert_eq!(bob.balance(), 11);
+
+    // The token's notes are now spent, and a second redeem is refused
+    let ys: Vec<_> = token.notes.iter().map(|n| n.y).collect();
+    let states = client.check_state(ys).unwrap();
+    assert!(states.iter().all(|s| *s == ProofState::Spent));
+    assert!(bob.receive_token(&client, &token).is_err());
+    println!("Replay refused");
+
+    // Bob tidies his wallet into the fewest notes
+    bob.consolidate(&client, 16).unwrap();
+    println!("Bob holds {} in {} notes", bob.balance(), bob.notes.len());
+
+    drop(client);
+    let mint = daemon.stop();
+    println!("Mint stopped with {} notes spent", mint.spent.len());
+
+    println!("=== Golden path completed ===");
+}
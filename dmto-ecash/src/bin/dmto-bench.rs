@@ -0,0 +1,127 @@
+//! Load test: simulated wallets hammering a mint with a mix of swaps,
+//! issuance and melts (see `dmto_mint::bench`), reporting throughput and
+//! latency percentiles per operation.
+//!
+//! Flags: `--wallets N`, `--ops N` (per wallet), `--mix
+//! swap=60,mint=25,melt=15`, `--amount N`, `--funding N` and `--target
+//! local|actor`. `local` calls the mint directly; `actor` goes through a
+//! `MintActor`, as a served mint would. Any other flags configure the mint
+//! as for `dmto-ecash`.
+//!
+//! `--hash N` instead times hashing and DLEQ proofs over N secrets under
+//! each hash backend compiled in; build with `--features ring` to compare
+//! ring against sha2.
+//!
+//! The crate has no network mint client yet, so only in-process mints can
+//! be measured.
+
+use std::{process, sync::Arc};
+
+use dmto_ecash::{
+    actor::MintActor,
+    bench::{self, BenchConfig, Mix},
+    config::Config,
+    lightning::FakeBackend,
+    mint::Mint,
+};
+
+enum Target {
+    Local,
+    Actor,
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    process::exit(2);
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> T {
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(format!("`--{}` needs a number", flag)))
+}
+
+fn main() {
+    let mut bench = BenchConfig::default();
+    let mut target = Target::Local;
+    let mut hash_iterations = None;
+    let mut mint_args = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.strip_prefix("--").map(|f| f.split_once('=')) {
+            Some(Some((flag, value))) => (flag.to_string(), Some(value.to_string())),
+            Some(None) => (arg[2..].to_string(), None),
+            None => fail(format!("unexpected argument `{}`", arg)),
+        };
+        if ![
+            "wallets", "ops", "mix", "amount", "funding", "target", "hash",
+        ]
+        .contains(&flag.as_str())
+        {
+            mint_args.push(arg);
+            if inline.is_none() {
+                mint_args.extend(args.next());
+            }
+            continue;
+        }
+        let value = inline
+            .or_else(|| args.next())
+            .unwrap_or_else(|| fail(format!("`--{}` needs a value", flag)));
+        match flag.as_str() {
+            "wallets" => bench.wallets = number(&flag, &value),
+            "ops" => bench.ops = number(&flag, &value),
+            "amount" => bench.amount = number(&flag, &value),
+            "funding" => bench.funding = number(&flag, &value),
+            "hash" => hash_iterations = Some(number(&flag, &value)),
+            "mix" => bench.mix = Mix::parse(&value).unwrap_or_else(|e| fail(e)),
+            "target" => {
+                target = match value.as_str() {
+                    "local" => Target::Local,
+                    "actor" => Target::Actor,
+                    "remote" => fail("no network mint client yet; use `local` or `actor`"),
+                    other => fail(format!("unknown target `{}`", other)),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if let Some(iterations) = hash_iterations {
+        println!(
+            "{} secrets, mean per call\n{:<6} {:>14} {:>12} {:>12}",
+            iterations, "hash", "hash_to_curve", "dleq prove", "dleq verify"
+        );
+        for report in bench::run_hash_backends(iterations) {
+            println!("{}", report);
+        }
+        return;
+    }
+
+    let config = Config::load(None, std::env::vars(), &mint_args).unwrap_or_else(|e| fail(e));
+    let lightning = Arc::new(FakeBackend::new());
+    let mut mint = Mint::from_config(&config);
+    mint.lightning = Some(lightning.clone());
+    mint.apply_chaos(&config.chaos);
+
+    println!(
+        "{} wallets x {} ops, {}",
+        bench.wallets, bench.ops, bench.mix
+    );
+    let report = match target {
+        Target::Local => bench::run(&mint, &lightning, &bench),
+        Target::Actor => {
+            let actor = MintActor::spawn(mint);
+            let report = bench::run(&actor.client(), &lightning, &bench);
+            actor.stop();
+            report
+        }
+    };
+    match report {
+        Ok(report) => println!("{}", report),
+        Err(e) => {
+            eprintln!("setup failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Journal replay debugger (see `dmto_mint::replay`): replays journal
+//! segments against a fresh mint, or one restored from `--snapshot`, under
+//! commands read from stdin.
+//!
+//! Flags: `--snapshot PATH` to start from, `--break OP` (repeatable) and
+//! `--expect PATH`, a later snapshot to diff against; the remaining
+//! arguments are segment files, in order.
+//!
+//! Commands: `step [N]`, `continue`, `until SEQ`, `break OP`, `delete OP`,
+//! `next`, `state`, `diff` (against `--expect`) and `quit`; each may be
+//! shortened to its first letter.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    process,
+};
+
+use dmto_ecash::{
+    journal::Journal,
+    mint::Mint,
+    replay::{MintState, Replayer, Stop},
+};
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    process::exit(2);
+}
+
+fn print_stop(stop: Stop) {
+    match stop {
+        Stop::Breakpoint { seq, operation } => println!("breakpoint: {} at #{}", operation, seq),
+        Stop::Reached(seq) => println!("stopped before #{}", seq),
+        Stop::End => println!("end of journal"),
+    }
+}
+
+fn main() {
+    let mut snapshot = None;
+    let mut expect = None;
+    let mut breakpoints = Vec::new();
+    let mut segments = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .unwrap_or_else(|| fail(format!("`{}` needs a value", flag)))
+        };
+        match arg.as_str() {
+            "--snapshot" => snapshot = Some(PathBuf::from(value(&arg))),
+            "--expect" => expect = Some(PathBuf::from(value(&arg))),
+            "--break" => breakpoints.push(value(&arg)),
+            flag if flag.starts_with("--") => fail(format!("unknown flag `{}`", flag)),
+            _ => segments.push(PathBuf::from(arg)),
+        }
+    }
+
+    let mint = match &snapshot {
+        Some(path) => {
+            Mint::restore(path, &[]).unwrap_or_else(|e| fail(format!("{}: {}", path.display(), e)))
+        }
+        None => Mint::from_keys(HashMap::new()),
+    };
+    let expected = expect.as_ref().map(|path| {
+        Mint::restore(path, &[]).unwrap_or_else(|e| fail(format!("{}: {}", path.display(), e)))
+    });
+    let mut entries = Vec::new();
+    for path in &segments {
+        entries.extend(
+            Journal::read_segment(path)
+                .unwrap_or_else(|e| fail(format!("{}: {}", path.display(), e))),
+        );
+    }
+
+    println!(
+        "{} entries after #{}",
+        entries.len(),
+        mint.journal.last_seq()
+    );
+    let mut replayer = Replayer::new(mint, entries);
+    for op in &breakpoints {
+        replayer.break_on(op);
+    }
+
+    let print_step = |step: &_| println!("{}", step);
+    let stdin = io::stdin();
+    loop {
+        print!("replay #{}> ", replayer.last_seq());
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+        let arg = words.next();
+
+        let result = match command {
+            "s" | "step" => {
+                let n = arg.map_or(Ok(1), str::parse).unwrap_or(0);
+                (0..n).try_for_each(|_| {
+                    match replayer.step()? {
+                        Some(step) => {
+                            println!("{}", step);
+                            if !step.diff.is_empty() {
+                                println!("{}", step.diff);
+                            }
+                        }
+                        None => println!("end of journal"),
+                    }
+                    Ok(())
+                })
+            }
+            "c" | "continue" => replayer.run(None, print_step).map(print_stop),
+            "u" | "until" => match arg.and_then(|a| a.parse().ok()) {
+                Some(seq) => replayer.run(Some(seq), print_step).map(print_stop),
+                None => {
+                    println!("until needs a sequence number");
+                    Ok(())
+                }
+            },
+            "b" | "break" => {
+                match arg {
+                    Some(op) => replayer.break_on(op),
+                    None => println!("break needs an operation ID"),
+                }
+                Ok(())
+            }
+            "d" | "delete" => {
+                if !arg.is_some_and(|op| replayer.clear_break(op)) {
+                    println!("no such breakpoint");
+                }
+                Ok(())
+            }
+            "n" | "next" => {
+                match replayer.next_entry() {
+                    Some(entry) => println!(
+                        "#{} [{}] {}",
+                        entry.seq,
+                        entry.operation.as_deref().unwrap_or("-"),
+                        entry.event
+                    ),
+                    None => println!("end of journal"),
+                }
+                Ok(())
+            }
+            "p" | "state" => {
+                let state = replayer.state();
+                println!(
+                    "#{}: {} spent, {} signed, {} in fees, {} refused",
+                    replayer.last_seq(),
+                    state.spent.len(),
+                    state.signed.len(),
+                    replayer.fees,
+                    replayer.refused
+                );
+                Ok(())
+            }
+            "diff" => {
+                match &expected {
+                    Some(expected) => {
+                        if expected.journal.last_seq() != replayer.last_seq() {
+                            println!(
+                                "note: expected state is as of #{}",
+                                expected.journal.last_seq()
+                            );
+                        }
+                        println!("{}", replayer.state().diff(&MintState::of(expected)));
+                    }
+                    None => println!("no --expect snapshot"),
+                }
+                Ok(())
+            }
+            "q" | "quit" => break,
+            other => {
+                println!("unknown command `{}`", other);
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            println!("replay failed: {}", e);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Wallet daemon: serves the JSON-RPC API from `dmto_wallet::rpc` on a Unix
+//! socket at `<data_dir>/walletd.sock`, one thread per connection, all
+//! sharing one wallet.
+//!
+//! The crate has no network mint client yet, so the daemon runs its mint
+//! in-process, configured like the demo, with a fake Lightning backend.
+
+#[cfg(unix)]
+fn main() {
+    use std::{fs, os::unix::net::UnixListener, sync::Arc, thread};
+
+    use dmto_ecash::{
+        actor::MintActor, config::Config, handle::WalletHandle, lightning::FakeBackend, mint::Mint,
+        rpc::RpcServer, wallet::Wallet,
+    };
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = match Config::load(None, std::env::vars(), &args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let mut mint = Mint::from_config(&config);
+    mint.lightning = Some(Arc::new(FakeBackend::new()));
+    mint.apply_chaos(&config.chaos);
+    let actor = MintActor::spawn(mint);
+    let wallet = WalletHandle::new(Wallet::new());
+
+    let path = config.data_dir.join("walletd.sock");
+    let listener = fs::create_dir_all(&config.data_dir)
+        .and_then(|_| match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        })
+        .and_then(|_| UnixListener::bind(&path));
+    let listener = match listener {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("cannot listen on {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+    println!("dmto-walletd listening on {}", path.display());
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let server = RpcServer::new(wallet.clone(), actor.client(), &config.mint_url);
+        thread::spawn(move || {
+            let _ = server.serve_stream(stream);
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("dmto-walletd needs Unix domain sockets");
+    std::process::exit(1);
+}
@@ -0,0 +1,5 @@
+//! The mint and wallet together, for binaries and examples that run both.
+//! The code lives in `dmto-mint` and `dmto-wallet`; a wallet that talks to
+//! a remote mint can depend on `dmto-wallet` alone.
+
+pub use dmto_mint::*;
@@ -1,28 +1,30 @@
 use rand::RngCore;
 
-use crate::{
+use dmto_ecash::{
     blind::{blind_message, unblind_signature},
+    config::Config,
     hash::hash_to_curve,
     mint::Mint,
     types::Note,
     wallet::Wallet,
 };
 
-mod blind;
-mod hash;
-mod mint;
-mod types;
-mod wallet;
-
 fn main() {
     println!("=== Real Chaumian Ecash Demo (Blind-DH / Cashu-style) ===");
 
-    let denoms = vec![1, 2, 4, 8];
-    let mint = Mint::new(&denoms);
-    println!("Mint initialized with denoms: {:?}", denoms);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = match Config::load(None, std::env::vars(), &args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    let mint = Mint::from_config(&config);
+    println!("Mint initialized with denoms: {:?}", config.denominations);
 
     // Alice mints ecash (direct issuance)
-    let mut alice = Wallet { notes: vec![] };
+    let mut alice = Wallet::new();
     alice.mint_note(&mint, 4);
     alice.mint_note(&mint, 2);
     println!("Alice minted ecash:");
@@ -31,7 +33,7 @@ fn main() {
     }
 
     // Bob prepares blinded outputs for swap
-    let mut bob = Wallet { notes: vec![] };
+    let mut bob = Wallet::new();
     let mut blinded_outputs = vec![];
     let mut bob_blinds = vec![];
     let mut bob_secrets = vec![];
@@ -50,13 +52,13 @@ fn main() {
 
     // Mint performs swap: burns Alice's notes, blindly signs Bob's
     let blind_sigs = mint
-        .swap(alice.notes.clone(), blinded_outputs)
+        .swap(alice.notes.to_vec(), blinded_outputs)
         .expect("swap failed");
 
     println!("Swap successful, mint reissued notes");
 
     // Bob unblinds and stores new notes
-    let values = vec![4u64, 2u64];
+    let values = [4u64, 2u64];
     for i in 0..values.len() {
         let value = values[i];
         let key = mint.keys.get(&value).unwrap();
@@ -67,9 +69,12 @@ fn main() {
 
         bob.notes.push(Note {
             value,
+            keyset_id: mint.keyset_id.clone(),
             secret: bob_secrets[i].clone(),
             y,
             c,
+            dleq: None,
+            witness: None,
         });
     }
     alice.notes.clear();
@@ -0,0 +1,214 @@
+//! Accounting mode for custodial deployments. Mint and melt quotes are
+//! created on behalf of an authenticated account (see `auth`), which is
+//! held to per-window limits and can be shown what it paid in and out.
+//!
+//! Only the Lightning side is attributed: the account behind a quote is
+//! known, but swaps stay anonymous and the notes themselves are never tied
+//! to an account. An account's balance is what it minted minus what it
+//! melted, not what it currently holds.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{auth::Principal, error::MintError, issue::MintQuote, melt::MeltQuote, mint::Mint};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountLimits {
+    pub window: Duration,
+    /// Most an account may quote for minting per window.
+    pub max_minted: u64,
+    /// Most an account may quote for melting per window, fee reserve included.
+    pub max_melted: u64,
+}
+
+impl Default for AccountLimits {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(24 * 60 * 60),
+            max_minted: u64::MAX,
+            max_melted: u64::MAX,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountBalance {
+    pub subject: String,
+    /// Value issued against the account's paid mint quotes.
+    pub minted: u64,
+    /// Value paid out for the account's melt quotes, fees included.
+    pub melted: u64,
+    /// Quoted in the current window, counted against the limits.
+    pub window_minted: u64,
+    pub window_melted: u64,
+}
+
+impl AccountBalance {
+    pub fn balance(&self) -> i128 {
+        self.minted as i128 - self.melted as i128
+    }
+}
+
+#[derive(Default)]
+struct Account {
+    balance: AccountBalance,
+    window_started: Option<Instant>,
+}
+
+#[derive(Clone, Copy)]
+enum Side {
+    Mint,
+    Melt,
+}
+
+pub struct Accounts {
+    pub limits: AccountLimits,
+    accounts: Mutex<HashMap<String, Account>>,
+    /// Account behind each quote, by quote ID.
+    owners: DashMap<String, String>,
+}
+
+impl Accounts {
+    pub fn new(limits: AccountLimits) -> Self {
+        Self {
+            limits,
+            accounts: Mutex::new(HashMap::new()),
+            owners: DashMap::new(),
+        }
+    }
+
+    /// Counts `amount` against the account's window, or refuses it.
+    fn reserve(&self, subject: &str, side: Side, amount: u64) -> Result<(), MintError> {
+        let mut accounts = self.accounts.lock().unwrap();
+        let account = accounts.entry(subject.to_string()).or_default();
+        account.balance.subject = subject.to_string();
+
+        let now = Instant::now();
+        if account
+            .window_started
+            .is_none_or(|t| now.duration_since(t) >= self.limits.window)
+        {
+            account.window_started = Some(now);
+            account.balance.window_minted = 0;
+            account.balance.window_melted = 0;
+        }
+
+        let (used, limit) = match side {
+            Side::Mint => (&mut account.balance.window_minted, self.limits.max_minted),
+            Side::Melt => (&mut account.balance.window_melted, self.limits.max_melted),
+        };
+        let remaining = limit.saturating_sub(*used);
+        if amount > remaining {
+            return Err(MintError::AccountLimitExceeded { remaining });
+        }
+        *used += amount;
+        Ok(())
+    }
+
+    fn settle(&self, quote_id: &str, side: Side, amount: u64) {
+        let Some((_, subject)) = self.owners.remove(quote_id) else {
+            return;
+        };
+        let mut accounts = self.accounts.lock().unwrap();
+        let account = accounts.entry(subject.clone()).or_default();
+        account.balance.subject = subject;
+        match side {
+            Side::Mint => account.balance.minted += amount,
+            Side::Melt => account.balance.melted += amount,
+        }
+    }
+
+    /// Records notes worth `amount` issued for a mint quote.
+    pub fn minted(&self, quote_id: &str, amount: u64) {
+        self.settle(quote_id, Side::Mint, amount);
+    }
+
+    /// Records a paid melt quote costing `amount` including fees.
+    pub fn melted(&self, quote_id: &str, amount: u64) {
+        self.settle(quote_id, Side::Melt, amount);
+    }
+
+    pub fn owner(&self, quote_id: &str) -> Option<String> {
+        self.owners.get(quote_id).map(|s| s.clone())
+    }
+
+    pub fn balance(&self, subject: &str) -> AccountBalance {
+        self.accounts
+            .lock()
+            .unwrap()
+            .get(subject)
+            .map(|a| a.balance.clone())
+            .unwrap_or_else(|| AccountBalance {
+                subject: subject.to_string(),
+                ..AccountBalance::default()
+            })
+    }
+
+    /// Every account seen, ordered by subject.
+    pub fn balances(&self) -> Vec<AccountBalance> {
+        let mut balances: Vec<_> = self
+            .accounts
+            .lock()
+            .unwrap()
+            .values()
+            .map(|a| a.balance.clone())
+            .collect();
+        balances.sort_by(|a, b| a.subject.cmp(&b.subject));
+        balances
+    }
+}
+
+impl Mint {
+    fn accounts(&self) -> Result<&Accounts, MintError> {
+        self.accounts
+            .as_ref()
+            .ok_or_else(|| MintError::Unauthorized("accounting mode is off".to_string()))
+    }
+
+    /// `mint_quote` on behalf of `account`, within its limits.
+    pub fn mint_quote_for(&self, account: &Principal, amount: u64) -> Result<MintQuote, MintError> {
+        let accounts = self.accounts()?;
+        accounts.reserve(&account.subject, Side::Mint, amount)?;
+        let quote = self.create_mint_quote(amount)?;
+        accounts
+            .owners
+            .insert(quote.id.clone(), account.subject.clone());
+        Ok(quote)
+    }
+
+    /// `melt_quote` on behalf of `account`, within its limits, or
+    /// `melt_quote_with_amount` when `amount` is given. The account is
+    /// charged the quoted amount plus the fee reserve.
+    pub fn melt_quote_for(
+        &self,
+        account: &Principal,
+        request: &str,
+        amount: Option<u64>,
+    ) -> Result<MeltQuote, MintError> {
+        let accounts = self.accounts()?;
+        let quote = self.create_melt_quote(request, amount)?;
+        let cost = quote.amount.saturating_add(quote.fee_reserve);
+        if let Err(e) = accounts.reserve(&account.subject, Side::Melt, cost) {
+            self.melt_quotes.remove(&quote.id);
+            return Err(e);
+        }
+        accounts
+            .owners
+            .insert(quote.id.clone(), account.subject.clone());
+        Ok(quote)
+    }
+
+    pub fn account_balance(&self, account: &Principal) -> Result<AccountBalance, MintError> {
+        Ok(self.accounts()?.balance(&account.subject))
+    }
+
+    pub fn account_balances(&self) -> Result<Vec<AccountBalance>, MintError> {
+        Ok(self.accounts()?.balances())
+    }
+}
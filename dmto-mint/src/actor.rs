@@ -0,0 +1,270 @@
+//! Single-owner alternative to sharing a `Mint` between threads: one thread
+//! owns the mint and handles requests strictly in arrival order, and callers
+//! talk to it through a cloneable `MintClient`. Embedders get deterministic
+//! single-writer behaviour without relying on the mint's internal locks.
+
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+use secp256k1::PublicKey;
+
+use crate::{
+    announce::Announcement,
+    anonymity::AnonymitySet,
+    api::KeysetKeys,
+    batchmelt::{BatchMeltRequest, BatchMeltResponse},
+    error::MintError,
+    issue::{MintQuote, MintRequest, MintResponse},
+    melt::{MeltQuote, MeltRequest, MeltResponse},
+    mint::{Mint, MintInfo, ProofState},
+    protocol::{Hello, Request, Response, SwapRequest, SwapResponse},
+    restore::{RestoreRequest, RestoreResponse},
+    stats::DashboardStats,
+};
+
+enum Command {
+    Info(Sender<MintInfo>),
+    Keys(Sender<Vec<(u64, PublicKey)>>),
+    Keysets(Sender<Vec<KeysetKeys>>),
+    Hello(Sender<Hello>),
+    Swap(
+        Request<SwapRequest>,
+        Sender<Result<Response<SwapResponse>, MintError>>,
+    ),
+    CheckState(Vec<PublicKey>, Sender<Vec<ProofState>>),
+    MeltQuote(String, Sender<Result<MeltQuote, MintError>>),
+    MeltQuoteWithAmount(String, u64, Sender<Result<MeltQuote, MintError>>),
+    Melt(
+        Request<MeltRequest>,
+        Sender<Result<Response<MeltResponse>, MintError>>,
+    ),
+    MeltQuoteState(String, Sender<Result<MeltQuote, MintError>>),
+    MeltBatch(
+        Request<BatchMeltRequest>,
+        Sender<Result<Response<BatchMeltResponse>, MintError>>,
+    ),
+    MintQuote(u64, Sender<Result<MintQuote, MintError>>),
+    MintQuoteOnchain(u64, Sender<Result<MintQuote, MintError>>),
+    GetQuote(String, Sender<Result<MintQuote, MintError>>),
+    Mint(
+        Request<MintRequest>,
+        Sender<Result<Response<MintResponse>, MintError>>,
+    ),
+    AnonymitySets(Sender<Vec<AnonymitySet>>),
+    Restore(
+        Request<RestoreRequest>,
+        Sender<Result<Response<RestoreResponse>, MintError>>,
+    ),
+    Stats(Sender<DashboardStats>),
+    Announcements(Sender<Vec<Announcement>>),
+}
+
+/// Owns the mint thread. Dropping the last `MintClient` and calling `stop`
+/// hands the mint back.
+pub struct MintActor {
+    client: MintClient,
+    thread: JoinHandle<Mint>,
+}
+
+/// Sends requests to a `MintActor`. Every call blocks until the actor has
+/// handled it. Calls after the actor stopped fail with `Unknown`.
+#[derive(Clone)]
+pub struct MintClient {
+    sender: Sender<Command>,
+}
+
+impl MintActor {
+    pub fn spawn(mint: Mint) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let thread = thread::spawn(move || run(mint, receiver));
+        Self {
+            client: MintClient { sender },
+            thread,
+        }
+    }
+
+    pub fn client(&self) -> MintClient {
+        self.client.clone()
+    }
+
+    /// Waits for every outstanding client to be dropped, then returns the
+    /// mint.
+    pub fn stop(self) -> Mint {
+        drop(self.client);
+        self.thread.join().unwrap()
+    }
+}
+
+fn run(mint: Mint, receiver: Receiver<Command>) -> Mint {
+    for command in receiver {
+        match command {
+            Command::Info(reply) => {
+                let _ = reply.send(mint.info());
+            }
+            Command::Keys(reply) => {
+                let mut keys: Vec<_> = mint.keys.iter().map(|(&v, k)| (v, k.pubkey)).collect();
+                keys.sort_by_key(|(v, _)| *v);
+                let _ = reply.send(keys);
+            }
+            Command::Keysets(reply) => {
+                let _ = reply.send(mint.keysets());
+            }
+            Command::Hello(reply) => {
+                let _ = reply.send(mint.hello());
+            }
+            Command::Swap(req, reply) => {
+                let _ = reply.send(mint.handle_swap(req));
+            }
+            Command::CheckState(ys, reply) => {
+                let _ = reply.send(mint.check_state(&ys));
+            }
+            Command::MeltQuote(request, reply) => {
+                let _ = reply.send(mint.melt_quote(&request));
+            }
+            Command::MeltQuoteWithAmount(request, amount, reply) => {
+                let _ = reply.send(mint.melt_quote_with_amount(&request, amount));
+            }
+            Command::Melt(req, reply) => {
+                let _ = reply.send(mint.melt(req));
+            }
+            Command::MeltQuoteState(id, reply) => {
+                let _ = reply.send(mint.melt_quote_state(&id));
+            }
+            Command::MeltBatch(req, reply) => {
+                let _ = reply.send(mint.melt_batch(req));
+            }
+            Command::MintQuote(amount, reply) => {
+                let _ = reply.send(mint.mint_quote(amount));
+            }
+            Command::MintQuoteOnchain(amount, reply) => {
+                let _ = reply.send(mint.mint_quote_onchain(amount));
+            }
+            Command::GetQuote(id, reply) => {
+                let _ = reply.send(mint.get_quote(&id));
+            }
+            Command::Mint(req, reply) => {
+                let _ = reply.send(mint.mint(req));
+            }
+            Command::AnonymitySets(reply) => {
+                let _ = reply.send(mint.anonymity_sets());
+            }
+            Command::Restore(req, reply) => {
+                let _ = reply.send(mint.restore_signatures(req));
+            }
+            Command::Stats(reply) => {
+                let _ = reply.send(mint.dashboard_stats());
+            }
+            Command::Announcements(reply) => {
+                let _ = reply.send(mint.announcements());
+            }
+        }
+    }
+    mint
+}
+
+fn stopped() -> MintError {
+    MintError::Unknown {
+        code: 0,
+        detail: "mint actor stopped".to_string(),
+    }
+}
+
+impl MintClient {
+    fn call<T>(&self, command: impl FnOnce(Sender<T>) -> Command) -> Result<T, MintError> {
+        let (reply, response) = mpsc::channel();
+        self.sender.send(command(reply)).map_err(|_| stopped())?;
+        response.recv().map_err(|_| stopped())
+    }
+
+    pub fn info(&self) -> Result<MintInfo, MintError> {
+        self.call(Command::Info)
+    }
+
+    pub fn keys(&self) -> Result<Vec<(u64, PublicKey)>, MintError> {
+        self.call(Command::Keys)
+    }
+
+    /// Every keyset the mint lists, as `Mint::keysets`.
+    pub fn keysets(&self) -> Result<Vec<KeysetKeys>, MintError> {
+        self.call(Command::Keysets)
+    }
+
+    pub fn hello(&self) -> Result<Hello, MintError> {
+        self.call(Command::Hello)
+    }
+
+    pub fn handle_swap(
+        &self,
+        req: Request<SwapRequest>,
+    ) -> Result<Response<SwapResponse>, MintError> {
+        self.call(|reply| Command::Swap(req, reply))?
+    }
+
+    pub fn check_state(&self, ys: Vec<PublicKey>) -> Result<Vec<ProofState>, MintError> {
+        self.call(|reply| Command::CheckState(ys, reply))
+    }
+
+    pub fn melt_quote(&self, request: &str) -> Result<MeltQuote, MintError> {
+        self.call(|reply| Command::MeltQuote(request.to_string(), reply))?
+    }
+
+    pub fn melt_quote_with_amount(
+        &self,
+        request: &str,
+        amount: u64,
+    ) -> Result<MeltQuote, MintError> {
+        self.call(|reply| Command::MeltQuoteWithAmount(request.to_string(), amount, reply))?
+    }
+
+    pub fn melt(&self, req: Request<MeltRequest>) -> Result<Response<MeltResponse>, MintError> {
+        self.call(|reply| Command::Melt(req, reply))?
+    }
+
+    pub fn melt_quote_state(&self, id: &str) -> Result<MeltQuote, MintError> {
+        self.call(|reply| Command::MeltQuoteState(id.to_string(), reply))?
+    }
+
+    pub fn melt_batch(
+        &self,
+        req: Request<BatchMeltRequest>,
+    ) -> Result<Response<BatchMeltResponse>, MintError> {
+        self.call(|reply| Command::MeltBatch(req, reply))?
+    }
+
+    pub fn mint_quote(&self, amount: u64) -> Result<MintQuote, MintError> {
+        self.call(|reply| Command::MintQuote(amount, reply))?
+    }
+
+    pub fn mint_quote_onchain(&self, amount: u64) -> Result<MintQuote, MintError> {
+        self.call(|reply| Command::MintQuoteOnchain(amount, reply))?
+    }
+
+    pub fn get_quote(&self, id: &str) -> Result<MintQuote, MintError> {
+        self.call(|reply| Command::GetQuote(id.to_string(), reply))?
+    }
+
+    pub fn mint(&self, req: Request<MintRequest>) -> Result<Response<MintResponse>, MintError> {
+        self.call(|reply| Command::Mint(req, reply))?
+    }
+
+    pub fn anonymity_sets(&self) -> Result<Vec<AnonymitySet>, MintError> {
+        self.call(Command::AnonymitySets)
+    }
+
+    pub fn restore_signatures(
+        &self,
+        req: Request<RestoreRequest>,
+    ) -> Result<Response<RestoreResponse>, MintError> {
+        self.call(|reply| Command::Restore(req, reply))?
+    }
+
+    pub fn dashboard_stats(&self) -> Result<DashboardStats, MintError> {
+        self.call(Command::Stats)
+    }
+
+    pub fn announcements(&self) -> Result<Vec<Announcement>, MintError> {
+        self.call(Command::Announcements)
+    }
+}
@@ -0,0 +1,77 @@
+//! Signing and publishing announcements. See `dmto_wallet::announce` for
+//! the wallet side.
+
+pub use dmto_wallet::announce::*;
+
+use dashmap::DashMap;
+
+use crate::{melt::quote_id, mint::Mint, signing};
+
+/// The announcements a mint is currently publishing.
+#[derive(Default)]
+pub struct Announcements {
+    pub(crate) active: DashMap<String, Announcement>,
+}
+
+impl Announcements {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Mint {
+    /// Signs and publishes an announcement for `ttl` seconds.
+    pub fn announce(
+        &self,
+        kind: NoticeKind,
+        severity: Severity,
+        title: &str,
+        body: &str,
+        ttl: u64,
+    ) -> Announcement {
+        let id = quote_id();
+        let published_at = self.clock.now();
+        let expires_at = published_at.saturating_add(ttl);
+        let signature = signing::sign(
+            &self.identity,
+            &message(&id, &kind, severity, title, body, published_at, expires_at),
+        );
+        let announcement = Announcement {
+            id,
+            kind,
+            severity,
+            title: title.to_string(),
+            body: body.to_string(),
+            published_at,
+            expires_at,
+            signature,
+        };
+        self.announcements
+            .active
+            .insert(announcement.id.clone(), announcement.clone());
+        announcement
+    }
+
+    /// Stops publishing an announcement before it expires.
+    pub fn withdraw(&self, id: &str) -> bool {
+        self.announcements.active.remove(id).is_some()
+    }
+
+    /// Unexpired announcements, most severe first, then newest first.
+    pub fn announcements(&self) -> Vec<Announcement> {
+        let now = self.clock.now();
+        self.announcements.active.retain(|_, a| !a.is_expired(now));
+        let mut list: Vec<_> = self
+            .announcements
+            .active
+            .iter()
+            .map(|a| a.clone())
+            .collect();
+        list.sort_by(|a, b| {
+            b.severity
+                .cmp(&a.severity)
+                .then(b.published_at.cmp(&a.published_at))
+        });
+        list
+    }
+}
@@ -0,0 +1,62 @@
+//! Counting signed and spent notes per keyset and denomination. See
+//! `dmto_wallet::anonymity` for the wallet side.
+
+pub use dmto_wallet::anonymity::*;
+
+use dashmap::DashMap;
+
+use crate::mint::Mint;
+
+#[derive(Default)]
+pub struct AnonymityCounters {
+    /// `(signed, spent)` per keyset and denomination.
+    counts: DashMap<(String, u64), (u64, u64)>,
+}
+
+impl AnonymityCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn signed(&self, keyset_id: &str, value: u64) {
+        self.counts
+            .entry((keyset_id.to_string(), value))
+            .or_default()
+            .0 += 1;
+    }
+
+    pub fn spent(&self, keyset_id: &str, value: u64) {
+        self.counts
+            .entry((keyset_id.to_string(), value))
+            .or_default()
+            .1 += 1;
+    }
+
+    /// Undoes `spent` for a note whose spend was rolled back.
+    pub fn released(&self, keyset_id: &str, value: u64) {
+        if let Some(mut c) = self.counts.get_mut(&(keyset_id.to_string(), value)) {
+            c.1 = c.1.saturating_sub(1);
+        }
+    }
+
+    /// Current sets, ordered by keyset and value.
+    pub fn sets(&self) -> Vec<AnonymitySet> {
+        let mut sets: Vec<_> = self
+            .counts
+            .iter()
+            .map(|e| AnonymitySet {
+                keyset_id: e.key().0.clone(),
+                value: e.key().1,
+                unspent: e.value().0.saturating_sub(e.value().1),
+            })
+            .collect();
+        sets.sort_by(|a, b| (&a.keyset_id, a.value).cmp(&(&b.keyset_id, b.value)));
+        sets
+    }
+}
+
+impl Mint {
+    pub fn anonymity_sets(&self) -> Vec<AnonymitySet> {
+        self.anonymity.sets()
+    }
+}
@@ -0,0 +1,224 @@
+//! `MintReader` and `MintTrait` for the in-process `Mint` and for
+//! `MintClient`, and `LocalMint` for `Mint`.
+
+pub use dmto_wallet::api::*;
+
+use secp256k1::{PublicKey, Secp256k1};
+
+use crate::{
+    actor::MintClient,
+    announce::Announcement,
+    anonymity::AnonymitySet,
+    batchmelt::{BatchMeltRequest, BatchMeltResponse},
+    conditions::Witness,
+    error::MintError,
+    issue::{MintQuote, MintRequest, MintResponse},
+    keyset::KeysetIdVersion,
+    melt::{MeltQuote, MeltRequest, MeltResponse},
+    mint::{Mint, MintInfo, ProofState},
+    protocol::{Hello, Request, Response, SwapRequest, SwapResponse},
+    restore::{RestoreRequest, RestoreResponse},
+    stats::DashboardStats,
+    types::Note,
+};
+
+impl Mint {
+    /// The active keyset, then the revoked keysets still open for
+    /// migration.
+    pub fn keysets(&self) -> Vec<KeysetKeys> {
+        let mut keysets = vec![self.current_keyset()];
+        keysets.extend(self.migrations.iter().map(|m| m.keyset()));
+        keysets
+    }
+
+    /// Public keys of the keyset the mint currently issues from.
+    pub fn current_keyset(&self) -> KeysetKeys {
+        let mut keys: Vec<_> = self.keys.iter().map(|(&v, k)| (v, k.pubkey)).collect();
+        keys.sort_by_key(|(v, _)| *v);
+        KeysetKeys {
+            keyset_id: self.keyset_id.clone(),
+            id_version: KeysetIdVersion::of(&self.keyset_id).unwrap_or_default(),
+            keys,
+        }
+    }
+}
+
+impl MintReader for Mint {
+    fn info(&self) -> Result<MintInfo, MintError> {
+        Ok(Mint::info(self))
+    }
+
+    fn keysets(&self) -> Result<Vec<KeysetKeys>, MintError> {
+        Ok(Mint::keysets(self))
+    }
+
+    fn hello(&self) -> Result<Hello, MintError> {
+        Ok(Mint::hello(self))
+    }
+
+    fn check_state(&self, ys: &[PublicKey]) -> Result<Vec<ProofState>, MintError> {
+        Ok(Mint::check_state(self, ys))
+    }
+
+    fn restore_signatures(
+        &self,
+        req: Request<RestoreRequest>,
+    ) -> Result<Response<RestoreResponse>, MintError> {
+        Mint::restore_signatures(self, req)
+    }
+}
+
+impl MintTrait for Mint {
+    fn handle_swap(&self, req: Request<SwapRequest>) -> Result<Response<SwapResponse>, MintError> {
+        Mint::handle_swap(self, req)
+    }
+
+    fn melt_quote(&self, request: &str) -> Result<MeltQuote, MintError> {
+        Mint::melt_quote(self, request)
+    }
+
+    fn melt_quote_with_amount(&self, request: &str, amount: u64) -> Result<MeltQuote, MintError> {
+        Mint::melt_quote_with_amount(self, request, amount)
+    }
+
+    fn melt(&self, req: Request<MeltRequest>) -> Result<Response<MeltResponse>, MintError> {
+        Mint::melt(self, req)
+    }
+
+    fn melt_quote_state(&self, id: &str) -> Result<MeltQuote, MintError> {
+        Mint::melt_quote_state(self, id)
+    }
+
+    fn melt_batch(
+        &self,
+        req: Request<BatchMeltRequest>,
+    ) -> Result<Response<BatchMeltResponse>, MintError> {
+        Mint::melt_batch(self, req)
+    }
+
+    fn mint_quote(&self, amount: u64) -> Result<MintQuote, MintError> {
+        Mint::mint_quote(self, amount)
+    }
+
+    fn mint_quote_onchain(&self, amount: u64) -> Result<MintQuote, MintError> {
+        Mint::mint_quote_onchain(self, amount)
+    }
+
+    fn get_quote(&self, id: &str) -> Result<MintQuote, MintError> {
+        Mint::get_quote(self, id)
+    }
+
+    fn mint(&self, req: Request<MintRequest>) -> Result<Response<MintResponse>, MintError> {
+        Mint::mint(self, req)
+    }
+
+    fn anonymity_sets(&self) -> Result<Vec<AnonymitySet>, MintError> {
+        Ok(Mint::anonymity_sets(self))
+    }
+
+    fn dashboard_stats(&self) -> Result<DashboardStats, MintError> {
+        Ok(Mint::dashboard_stats(self))
+    }
+
+    fn announcements(&self) -> Result<Vec<Announcement>, MintError> {
+        Ok(Mint::announcements(self))
+    }
+}
+
+impl LocalMint for Mint {
+    fn sign_direct(&self, value: u64, y: &PublicKey) -> Option<(String, PublicKey)> {
+        let key = self.keys.get(&value)?;
+        let c = y.mul_tweak(&Secp256k1::new(), &key.privkey.into()).ok()?;
+        Some((self.keyset_id.clone(), c))
+    }
+
+    fn verify_and_spend(&self, note: &Note) -> Result<(), MintError> {
+        Mint::verify_and_spend(self, note)
+    }
+
+    fn spent_witness(&self, y: &PublicKey) -> Option<Witness> {
+        Mint::spent_witness(self, y)
+    }
+}
+
+impl MintReader for MintClient {
+    fn info(&self) -> Result<MintInfo, MintError> {
+        MintClient::info(self)
+    }
+
+    fn keysets(&self) -> Result<Vec<KeysetKeys>, MintError> {
+        MintClient::keysets(self)
+    }
+
+    fn hello(&self) -> Result<Hello, MintError> {
+        MintClient::hello(self)
+    }
+
+    fn check_state(&self, ys: &[PublicKey]) -> Result<Vec<ProofState>, MintError> {
+        MintClient::check_state(self, ys.to_vec())
+    }
+
+    fn restore_signatures(
+        &self,
+        req: Request<RestoreRequest>,
+    ) -> Result<Response<RestoreResponse>, MintError> {
+        MintClient::restore_signatures(self, req)
+    }
+}
+
+impl MintTrait for MintClient {
+    fn handle_swap(&self, req: Request<SwapRequest>) -> Result<Response<SwapResponse>, MintError> {
+        MintClient::handle_swap(self, req)
+    }
+
+    fn melt_quote(&self, request: &str) -> Result<MeltQuote, MintError> {
+        MintClient::melt_quote(self, request)
+    }
+
+    fn melt_quote_with_amount(&self, request: &str, amount: u64) -> Result<MeltQuote, MintError> {
+        MintClient::melt_quote_with_amount(self, request, amount)
+    }
+
+    fn melt(&self, req: Request<MeltRequest>) -> Result<Response<MeltResponse>, MintError> {
+        MintClient::melt(self, req)
+    }
+
+    fn melt_quote_state(&self, id: &str) -> Result<MeltQuote, MintError> {
+        MintClient::melt_quote_state(self, id)
+    }
+
+    fn melt_batch(
+        &self,
+        req: Request<BatchMeltRequest>,
+    ) -> Result<Response<BatchMeltResponse>, MintError> {
+        MintClient::melt_batch(self, req)
+    }
+
+    fn mint_quote(&self, amount: u64) -> Result<MintQuote, MintError> {
+        MintClient::mint_quote(self, amount)
+    }
+
+    fn mint_quote_onchain(&self, amount: u64) -> Result<MintQuote, MintError> {
+        MintClient::mint_quote_onchain(self, amount)
+    }
+
+    fn get_quote(&self, id: &str) -> Result<MintQuote, MintError> {
+        MintClient::get_quote(self, id)
+    }
+
+    fn mint(&self, req: Request<MintRequest>) -> Result<Response<MintResponse>, MintError> {
+        MintClient::mint(self, req)
+    }
+
+    fn anonymity_sets(&self) -> Result<Vec<AnonymitySet>, MintError> {
+        MintClient::anonymity_sets(self)
+    }
+
+    fn dashboard_stats(&self) -> Result<DashboardStats, MintError> {
+        MintClient::dashboard_stats(self)
+    }
+
+    fn announcements(&self) -> Result<Vec<Announcement>, MintError> {
+        MintClient::announcements(self)
+    }
+}
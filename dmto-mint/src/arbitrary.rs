@@ -0,0 +1,205 @@
+//! Random but realistic values of the core types, for property-testing
+//! integrations. Built with the `arbitrary` feature.
+//!
+//! Everything is drawn from a caller-supplied `Rng`, so any framework can
+//! drive it. With proptest, map a seed strategy:
+//! `any::<u64>().prop_map(|s| Token::arbitrary(&mut StdRng::seed_from_u64(s)))`;
+//! with quickcheck, seed a `StdRng` from the `Gen`. Keys, secrets and
+//! amounts are reproducible from the seed. DLEQ proofs are not, but they
+//! always verify.
+
+use std::collections::HashMap;
+
+use rand::Rng;
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
+
+use crate::{
+    api::KeysetKeys,
+    blind::{blind_message_with, blind_sign, unblind_signature},
+    codec::to_hex,
+    conditions::Condition,
+    dleq::{self, NoteDleq},
+    hash::hash_to_curve,
+    keyset::KeysetIdVersion,
+    mint::{Mint, MintKey, keyset_id},
+    secret::{Kind, SecretData, WellKnownSecret},
+    token::Token,
+    types::{Amount, Note},
+};
+
+pub trait Arbitrary: Sized {
+    fn arbitrary(rng: &mut impl Rng) -> Self;
+}
+
+fn secret_key(rng: &mut impl Rng) -> SecretKey {
+    SecretKey::new(rng)
+}
+
+fn pubkey(rng: &mut impl Rng) -> PublicKey {
+    PublicKey::from_secret_key(&Secp256k1::new(), &secret_key(rng))
+}
+
+/// Mostly everyday amounts, with powers of two and values at the top of
+/// the range mixed in for overflow paths.
+impl Arbitrary for Amount {
+    fn arbitrary(rng: &mut impl Rng) -> Self {
+        Amount(match rng.gen_range(0..10) {
+            0 => 0,
+            1..=5 => rng.gen_range(1..=100_000),
+            6 | 7 => 1 << rng.gen_range(0..40),
+            8 => rng.r#gen(),
+            _ => u64::MAX - rng.gen_range(0..1000),
+        })
+    }
+}
+
+/// P2PK, HTLC and composite secrets.
+impl Arbitrary for WellKnownSecret {
+    fn arbitrary(rng: &mut impl Rng) -> Self {
+        let (kind, data, tags) = match rng.gen_range(0..3) {
+            0 => {
+                let tags = if rng.gen_bool(0.3) {
+                    vec![
+                        vec![
+                            "locktime".to_string(),
+                            rng.gen_range(0..2_000_000_000u64).to_string(),
+                        ],
+                        vec!["refund".to_string(), to_hex(&pubkey(rng).serialize())],
+                    ]
+                } else {
+                    Vec::new()
+                };
+                (Kind::P2PK, to_hex(&pubkey(rng).serialize()), tags)
+            }
+            1 => (Kind::HTLC, to_hex(&rng.r#gen::<[u8; 32]>()), Vec::new()),
+            _ => {
+                let condition = Condition::p2pk(pubkey(rng))
+                    .and(Condition::htlc(&rng.r#gen()))
+                    .with_refund(rng.gen_range(0..2_000_000_000), pubkey(rng));
+                (
+                    Kind::Composite,
+                    serde_json::to_string(&condition).unwrap(),
+                    Vec::new(),
+                )
+            }
+        };
+        WellKnownSecret(
+            kind,
+            SecretData {
+                nonce: to_hex(&rng.r#gen::<[u8; 32]>()),
+                data,
+                tags,
+            },
+        )
+    }
+}
+
+/// A note secret: usually 64 hex characters, sometimes a well-known secret.
+pub fn arbitrary_secret(rng: &mut impl Rng) -> Vec<u8> {
+    if rng.gen_bool(0.7) {
+        to_hex(&rng.r#gen::<[u8; 32]>()).into_bytes()
+    } else {
+        WellKnownSecret::arbitrary(rng).to_bytes()
+    }
+}
+
+/// Keys for every power of two from 1 up to between `2^3` and `2^20`.
+pub fn arbitrary_keys(rng: &mut impl Rng) -> HashMap<u64, MintKey> {
+    (0..=rng.gen_range(3..=20))
+        .map(|order| {
+            let value = 1u64 << order;
+            (value, MintKey::from_privkey(value, secret_key(rng)))
+        })
+        .collect()
+}
+
+impl Arbitrary for KeysetKeys {
+    fn arbitrary(rng: &mut impl Rng) -> Self {
+        let keys = arbitrary_keys(rng);
+        let mut public: Vec<(u64, PublicKey)> = keys.iter().map(|(&v, k)| (v, k.pubkey)).collect();
+        public.sort_by_key(|(v, _)| *v);
+        KeysetKeys {
+            keyset_id: keyset_id(&keys),
+            id_version: KeysetIdVersion::V1,
+            keys: public,
+        }
+    }
+}
+
+/// A note validly signed with one of `keys`, carrying a DLEQ proof half of
+/// the time.
+pub fn arbitrary_note(rng: &mut impl Rng, keys: &HashMap<u64, MintKey>) -> Note {
+    let mut values: Vec<&u64> = keys.keys().collect();
+    values.sort();
+    let key = &keys[values[rng.gen_range(0..values.len())]];
+
+    let secret = arbitrary_secret(rng);
+    let y = hash_to_curve(&secret);
+    let r = secret_key(rng);
+    let blinded = blind_message_with(&y, Scalar::from(r));
+    let blind_sig = blind_sign(&key.privkey, &blinded.blinded_point);
+    let dleq = rng.gen_bool(0.5).then(|| {
+        let proof = dleq::prove(&key.privkey, &blinded.blinded_point, &blind_sig);
+        NoteDleq {
+            e: proof.e,
+            s: proof.s,
+            r,
+        }
+    });
+
+    Note {
+        value: key.value,
+        keyset_id: keyset_id(keys),
+        secret,
+        y,
+        c: unblind_signature(&blind_sig, &blinded.blind_factor, &key.pubkey),
+        dleq,
+        witness: None,
+    }
+}
+
+impl Arbitrary for Note {
+    fn arbitrary(rng: &mut impl Rng) -> Self {
+        let keys = arbitrary_keys(rng);
+        arbitrary_note(rng, &keys)
+    }
+}
+
+/// One to eight notes from one keyset, sometimes with a memo.
+impl Arbitrary for Token {
+    fn arbitrary(rng: &mut impl Rng) -> Self {
+        let keys = arbitrary_keys(rng);
+        let notes = (0..rng.gen_range(1..=8))
+            .map(|_| arbitrary_note(rng, &keys))
+            .collect();
+        let token = Token::new(
+            &format!("https://mint{}.example", rng.gen_range(0..100)),
+            notes,
+        );
+        if rng.gen_bool(0.3) {
+            token.with_memo(&format!("memo {}", rng.gen_range(0..1000)))
+        } else {
+            token
+        }
+    }
+}
+
+/// `count` unspent notes signed by `mint`, so tests can redeem them there.
+/// Secrets are plain, so they pass any `SecretPolicy` allowing hex.
+pub fn notes_for(mint: &Mint, rng: &mut impl Rng, count: usize) -> Vec<Note> {
+    (0..count)
+        .map(|_| {
+            let mut note = arbitrary_note(rng, &mint.keys);
+            note.secret = to_hex(&rng.r#gen::<[u8; 32]>()).into_bytes();
+            note.y = hash_to_curve(&note.secret);
+            let key = &mint.keys[&note.value];
+            note.c = note
+                .y
+                .mul_tweak(&Secp256k1::new(), &Scalar::from(key.privkey))
+                .unwrap();
+            note.dleq = None;
+            note.keyset_id = mint.keyset_id.clone();
+            note
+        })
+        .collect()
+}
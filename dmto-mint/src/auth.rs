@@ -0,0 +1,478 @@
+//! Authentication for private mints. Selected routes, typically issuance,
+//! require a credential that one of the mint's `AuthProvider`s accepts;
+//! every other route stays open. Swaps and state checks should stay open
+//! on mints that want their notes to remain bearer instruments.
+//!
+//! The HTTP layer passes the `Authorization` header to `Mint::authorize`
+//! before dispatching. Two providers are built in:
+//!
+//! - `ApiKeyAuth`: `Authorization: ApiKey <key>`, checked against SHA-256
+//!   hashes of the issued keys, so the mint never stores the keys.
+//! - `OidcAuth`: `Authorization: Bearer <jwt>`, an ID or access token from an
+//!   OpenID Connect issuer, checked for issuer, audience and expiry. Tokens
+//!   may be signed with RS256, ES256 or ES256K. The issuer's keys are
+//!   configured, or fetched from its JWKS endpoint when a token names a key
+//!   the mint does not have yet.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use jsonwebtoken::{
+    Algorithm, DecodingKey,
+    jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet},
+};
+use secp256k1::{Message, PublicKey, Secp256k1, ecdsa};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    codec::{from_base64url, to_hex},
+    error::MintError,
+    mint::Mint,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Route {
+    Info,
+    Keys,
+    Swap,
+    CheckState,
+    MintQuote,
+    Mint,
+    MeltQuote,
+    Melt,
+    Restore,
+    /// Operator dashboard figures.
+    Stats,
+}
+
+impl Route {
+    pub const ALL: [Route; 10] = [
+        Route::Info,
+        Route::Keys,
+        Route::Swap,
+        Route::CheckState,
+        Route::MintQuote,
+        Route::Mint,
+        Route::MeltQuote,
+        Route::Melt,
+        Route::Restore,
+        Route::Stats,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Route::Info => "info",
+            Route::Keys => "keys",
+            Route::Swap => "swap",
+            Route::CheckState => "check_state",
+            Route::MintQuote => "mint_quote",
+            Route::Mint => "mint",
+            Route::MeltQuote => "melt_quote",
+            Route::Melt => "melt",
+            Route::Restore => "restore",
+            Route::Stats => "stats",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Route::ALL.into_iter().find(|r| r.name() == name)
+    }
+}
+
+/// Who a credential belongs to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Principal {
+    /// Name of the provider that accepted the credential.
+    pub provider: &'static str,
+    pub subject: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuthError {
+    /// The route is protected and the request carried no credential.
+    Missing,
+    /// The credential is not for this provider; the next one is tried.
+    NotApplicable,
+    Malformed(String),
+    InvalidCredential,
+    Expired,
+    WrongIssuer,
+    WrongAudience,
+    UnsupportedAlgorithm(String),
+    /// The issuer's keys could not be fetched.
+    Unavailable(String),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Missing => write!(f, "credential required"),
+            AuthError::NotApplicable => write!(f, "no provider accepts this credential"),
+            AuthError::Malformed(e) => write!(f, "malformed credential: {}", e),
+            AuthError::InvalidCredential => write!(f, "invalid credential"),
+            AuthError::Expired => write!(f, "credential expired"),
+            AuthError::WrongIssuer => write!(f, "token from an untrusted issuer"),
+            AuthError::WrongAudience => write!(f, "token not issued for this mint"),
+            AuthError::UnsupportedAlgorithm(alg) => write!(f, "unsupported algorithm {}", alg),
+            AuthError::Unavailable(e) => write!(f, "issuer keys unavailable: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+pub trait AuthProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Checks the `Authorization` header value at unix time `now`.
+    /// Returns `NotApplicable` for credentials of another scheme.
+    fn authenticate(&self, header: &str, now: u64) -> Result<String, AuthError>;
+}
+
+fn key_hash(key: &str) -> String {
+    to_hex(&Sha256::digest(key.as_bytes()))
+}
+
+/// Static API keys, stored as SHA-256 hashes.
+#[derive(Clone, Debug, Default)]
+pub struct ApiKeyAuth {
+    /// Key hash to subject.
+    pub keys: HashMap<String, String>,
+}
+
+impl ApiKeyAuth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_key(&mut self, key: &str, subject: &str) {
+        self.add_hash(&key_hash(key), subject);
+    }
+
+    /// Adds a key by its hex SHA-256 hash, as kept in configuration.
+    pub fn add_hash(&mut self, hash: &str, subject: &str) {
+        self.keys.insert(hash.to_lowercase(), subject.to_string());
+    }
+}
+
+impl AuthProvider for ApiKeyAuth {
+    fn name(&self) -> &'static str {
+        "api_key"
+    }
+
+    fn authenticate(&self, header: &str, _now: u64) -> Result<String, AuthError> {
+        let key = header
+            .strip_prefix("ApiKey ")
+            .ok_or(AuthError::NotApplicable)?;
+        self.keys
+            .get(&key_hash(key.trim()))
+            .cloned()
+            .ok_or(AuthError::InvalidCredential)
+    }
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JwtClaims {
+    iss: String,
+    sub: String,
+    /// A string or an array of strings.
+    aud: Value,
+    exp: u64,
+    #[serde(default)]
+    nbf: Option<u64>,
+}
+
+/// An issuer signing key. Tokens must name the algorithm the key is for.
+#[derive(Clone)]
+pub enum OidcKey {
+    Es256k(PublicKey),
+    Rs256(DecodingKey),
+    Es256(DecodingKey),
+}
+
+impl OidcKey {
+    /// The key in a JWK: RSA keys are for RS256 and P-256 keys for ES256.
+    pub fn from_jwk(jwk: &Jwk) -> Result<Self, AuthError> {
+        let key = DecodingKey::from_jwk(jwk).map_err(|e| AuthError::Malformed(e.to_string()))?;
+        match &jwk.algorithm {
+            AlgorithmParameters::RSA(_) => Ok(OidcKey::Rs256(key)),
+            AlgorithmParameters::EllipticCurve(p) if p.curve == EllipticCurve::P256 => {
+                Ok(OidcKey::Es256(key))
+            }
+            _ => Err(AuthError::UnsupportedAlgorithm("JWK key type".to_string())),
+        }
+    }
+
+    pub fn alg(&self) -> &'static str {
+        match self {
+            OidcKey::Es256k(_) => "ES256K",
+            OidcKey::Rs256(_) => "RS256",
+            OidcKey::Es256(_) => "ES256",
+        }
+    }
+
+    /// Checks the signature `sig` (base64url) over `signed`.
+    fn verify(&self, signed: &str, sig: &str) -> Result<(), AuthError> {
+        let valid = match self {
+            OidcKey::Es256k(key) => {
+                let sig = from_base64url(sig)
+                    .and_then(|b| ecdsa::Signature::from_compact(&b).ok())
+                    .ok_or_else(|| AuthError::Malformed("signature".to_string()))?;
+                let digest = Sha256::digest(signed.as_bytes());
+                Secp256k1::verification_only()
+                    .verify_ecdsa(&Message::from_digest(digest.into()), &sig, key)
+                    .is_ok()
+            }
+            OidcKey::Rs256(key) => {
+                jsonwebtoken::crypto::verify(sig, signed.as_bytes(), key, Algorithm::RS256)
+                    .unwrap_or(false)
+            }
+            OidcKey::Es256(key) => {
+                jsonwebtoken::crypto::verify(sig, signed.as_bytes(), key, Algorithm::ES256)
+                    .unwrap_or(false)
+            }
+        };
+        if !valid {
+            return Err(AuthError::InvalidCredential);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for OidcKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OidcKey::Es256k(key) => write!(f, "ES256K({})", key),
+            _ => write!(f, "{}", self.alg()),
+        }
+    }
+}
+
+/// Seconds between JWKS fetches, so tokens naming unknown keys cannot make
+/// the mint hammer the issuer.
+const JWKS_REFETCH: u64 = 300;
+
+/// OpenID Connect tokens from one issuer.
+#[derive(Clone, Debug)]
+pub struct OidcAuth {
+    pub issuer: String,
+    /// Client ID the tokens must be issued for.
+    pub audience: String,
+    /// The issuer's signing keys by `kid`.
+    pub keys: HashMap<String, OidcKey>,
+    /// The issuer's JWKS endpoint, fetched when a token names a key not in
+    /// `keys`. `None` accepts only the configured keys.
+    pub jwks_url: Option<String>,
+    /// Seconds of clock skew tolerated on `exp` and `nbf`.
+    pub leeway: u64,
+    fetched: Arc<RwLock<HashMap<String, OidcKey>>>,
+    last_fetch: Arc<Mutex<Option<u64>>>,
+}
+
+impl OidcAuth {
+    pub fn new(issuer: &str, audience: &str) -> Self {
+        Self {
+            issuer: issuer.to_string(),
+            audience: audience.to_string(),
+            keys: HashMap::new(),
+            jwks_url: None,
+            leeway: 60,
+            fetched: Arc::default(),
+            last_fetch: Arc::default(),
+        }
+    }
+
+    /// Adds an ES256K key.
+    pub fn add_key(&mut self, kid: &str, key: PublicKey) {
+        self.keys.insert(kid.to_string(), OidcKey::Es256k(key));
+    }
+
+    /// Adds the RS256 and ES256 keys of a JWKS document, skipping keys of
+    /// other types or without a `kid`. Returns how many were added.
+    pub fn add_jwks(&mut self, jwks: &str) -> Result<usize, AuthError> {
+        let keys = parse_jwks(jwks)?;
+        let count = keys.len();
+        self.keys.extend(keys);
+        Ok(count)
+    }
+
+    /// Fetches the JWKS at `jwks_url`, replacing the keys fetched before.
+    /// Returns how many keys it holds.
+    pub fn fetch_jwks(&self) -> Result<usize, AuthError> {
+        let url = self
+            .jwks_url
+            .as_ref()
+            .ok_or_else(|| AuthError::Unavailable("no JWKS endpoint".to_string()))?;
+        let unavailable = |e: &dyn fmt::Display| AuthError::Unavailable(e.to_string());
+        let body = ureq::get(url)
+            .call()
+            .map_err(|e| unavailable(&e))?
+            .into_string()
+            .map_err(|e| unavailable(&e))?;
+        let keys = parse_jwks(&body)?;
+        let count = keys.len();
+        *self.fetched.write().unwrap() = keys;
+        Ok(count)
+    }
+
+    fn key(&self, kid: Option<&str>, now: u64) -> Result<OidcKey, AuthError> {
+        let find = |keys: &HashMap<String, OidcKey>| match kid {
+            Some(kid) => keys.get(kid).cloned(),
+            None if keys.len() == 1 => keys.values().next().cloned(),
+            None => None,
+        };
+        if let Some(key) = find(&self.keys) {
+            return Ok(key);
+        }
+        if let Some(key) = find(&self.fetched.read().unwrap()) {
+            return Ok(key);
+        }
+        if self.jwks_url.is_none() {
+            return Err(AuthError::InvalidCredential);
+        }
+        {
+            let mut last = self.last_fetch.lock().unwrap();
+            if last.is_some_and(|t| now < t.saturating_add(JWKS_REFETCH)) {
+                return Err(AuthError::InvalidCredential);
+            }
+            *last = Some(now);
+        }
+        self.fetch_jwks()?;
+        find(&self.fetched.read().unwrap()).ok_or(AuthError::InvalidCredential)
+    }
+}
+
+fn parse_jwks(jwks: &str) -> Result<HashMap<String, OidcKey>, AuthError> {
+    let set: JwkSet =
+        serde_json::from_str(jwks).map_err(|e| AuthError::Malformed(e.to_string()))?;
+    Ok(set
+        .keys
+        .iter()
+        .filter_map(|jwk| Some((jwk.common.key_id.clone()?, OidcKey::from_jwk(jwk).ok()?)))
+        .collect())
+}
+
+impl AuthProvider for OidcAuth {
+    fn name(&self) -> &'static str {
+        "oidc"
+    }
+
+    fn authenticate(&self, header: &str, now: u64) -> Result<String, AuthError> {
+        let token = header
+            .strip_prefix("Bearer ")
+            .ok_or(AuthError::NotApplicable)?
+            .trim();
+        let malformed = |what: &str| AuthError::Malformed(what.to_string());
+        let mut parts = token.split('.');
+        let (Some(h), Some(c), Some(s), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(malformed("expected three JWT segments"));
+        };
+
+        let header: JwtHeader = from_base64url(h)
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .ok_or_else(|| malformed("header"))?;
+        if !["ES256K", "RS256", "ES256"].contains(&header.alg.as_str()) {
+            return Err(AuthError::UnsupportedAlgorithm(header.alg));
+        }
+        let key = self.key(header.kid.as_deref(), now)?;
+        if key.alg() != header.alg {
+            return Err(AuthError::InvalidCredential);
+        }
+        key.verify(&format!("{}.{}", h, c), s)?;
+
+        let claims: JwtClaims = from_base64url(c)
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .ok_or_else(|| malformed("claims"))?;
+        if claims.iss != self.issuer {
+            return Err(AuthError::WrongIssuer);
+        }
+        let audience_ok = match &claims.aud {
+            Value::String(aud) => *aud == self.audience,
+            Value::Array(auds) => auds.iter().any(|a| a.as_str() == Some(&self.audience)),
+            _ => false,
+        };
+        if !audience_ok {
+            return Err(AuthError::WrongAudience);
+        }
+        if now > claims.exp.saturating_add(self.leeway)
+            || claims
+                .nbf
+                .is_some_and(|nbf| now.saturating_add(self.leeway) < nbf)
+        {
+            return Err(AuthError::Expired);
+        }
+        Ok(claims.sub)
+    }
+}
+
+/// Which routes need a credential, and the providers that may accept one.
+#[derive(Clone, Default)]
+pub struct AuthGate {
+    pub providers: Vec<Arc<dyn AuthProvider>>,
+    pub protected: Vec<Route>,
+}
+
+impl AuthGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_protected(&self, route: Route) -> bool {
+        self.protected.contains(&route)
+    }
+
+    /// `Ok(None)` for open routes; for protected ones, the first provider's
+    /// verdict that applies to the credential.
+    pub fn check(
+        &self,
+        route: Route,
+        header: Option<&str>,
+        now: u64,
+    ) -> Result<Option<Principal>, AuthError> {
+        if !self.is_protected(route) {
+            return Ok(None);
+        }
+        let header = header.ok_or(AuthError::Missing)?;
+        for provider in &self.providers {
+            match provider.authenticate(header, now) {
+                Err(AuthError::NotApplicable) => continue,
+                Ok(subject) => {
+                    return Ok(Some(Principal {
+                        provider: provider.name(),
+                        subject,
+                    }));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(AuthError::NotApplicable)
+    }
+}
+
+impl Mint {
+    /// Checks the `Authorization` header for a request to `route`.
+    pub fn authorize(
+        &self,
+        route: Route,
+        header: Option<&str>,
+        now: u64,
+    ) -> Result<Option<Principal>, MintError> {
+        self.auth
+            .check(route, header, now)
+            .map_err(|e| MintError::Unauthorized(e.to_string()))
+    }
+}
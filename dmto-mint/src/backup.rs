@@ -0,0 +1,330 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use secp256k1::{Keypair, PublicKey, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    announce::Announcement,
+    codec::{from_hex, to_hex},
+    conditions::Witness,
+    fieldcrypt::{FieldKeys, SealFields},
+    hash::hash_to_curve_batch,
+    hold::preimage_context,
+    issue::MintQuote,
+    journal::Journal,
+    melt::MeltQuote,
+    mint::{Mint, MintKey, keyset_id},
+    rotation::Migration,
+    secret::Kind,
+    spendrecord::SpendRecord,
+};
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotBody {
+    seq: u64,
+    keyset_id: String,
+    keys: Vec<(u64, SecretKey)>,
+    identity: SecretKey,
+    spent: Vec<String>,
+    accepted_kinds: Vec<Kind>,
+    /// Signed outputs of version 1 snapshots, all of the snapshot's
+    /// keyset.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    signed: Vec<(u64, PublicKey)>,
+    /// Signed outputs with the keyset that signed them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    signed_by: Vec<(u64, PublicKey, String)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    migrations: Vec<MigrationBody>,
+    #[serde(default)]
+    spend_records: bool,
+    /// The journal entry that spent each note.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    spend_log: Vec<SpendRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    spent_witnesses: Vec<(PublicKey, Witness)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    mint_quotes: Vec<MintQuote>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    melt_quotes: Vec<MeltQuote>,
+    /// Preimages of unsettled hold invoices, by quote ID.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    hold_preimages: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    announcements: Vec<Announcement>,
+    /// Quote requests, preimages and payout transactions are sealed.
+    #[serde(default)]
+    sealed: bool,
+}
+
+/// A revoked keyset still open for migration.
+#[derive(Serialize, Deserialize)]
+struct MigrationBody {
+    keyset_id: String,
+    keys: Vec<(u64, SecretKey)>,
+    until: u64,
+    remaining: Vec<(u64, u64)>,
+}
+
+fn key_pairs(keys: &HashMap<u64, MintKey>) -> Vec<(u64, SecretKey)> {
+    let mut keys: Vec<(u64, SecretKey)> = keys.iter().map(|(&v, k)| (v, k.privkey)).collect();
+    keys.sort_by_key(|(v, _)| *v);
+    keys
+}
+
+/// The keys in `pairs`, failing unless they derive to `id`.
+fn keys_from(pairs: Vec<(u64, SecretKey)>, id: &str) -> io::Result<HashMap<u64, MintKey>> {
+    let keys: HashMap<u64, MintKey> = pairs
+        .into_iter()
+        .map(|(v, sk)| (v, MintKey::from_privkey(v, sk)))
+        .collect();
+    if keyset_id(&keys) != id {
+        return Err(invalid("snapshot keys do not match keyset id"));
+    }
+    Ok(keys)
+}
+
+/// Snapshots without a `version` field are version 0, which has the same
+/// layout as version 1. Version 2 records the keyset of each signed output,
+/// the quotes, hold preimages, spend records and announcements.
+const SNAPSHOT_VERSION: u16 = 2;
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile {
+    #[serde(default)]
+    version: u16,
+    checksum: String,
+    body: SnapshotBody,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Writes `bytes` to a temporary file beside `path`, syncs it and renames
+/// it over `path`, so a crash leaves either the old file or the new one.
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => fs::File::open(dir)?.sync_all(),
+        _ => Ok(()),
+    }
+}
+
+fn checksum(body: &SnapshotBody) -> io::Result<String> {
+    let bytes = serde_json::to_vec(body).map_err(io::Error::other)?;
+    Ok(to_hex(&Sha256::digest(bytes)))
+}
+
+impl Mint {
+    /// Writes a point-in-time backup of keys, spent set, quotes and the
+    /// rest of the mint's state to `path` and returns the journal sequence
+    /// number it is consistent with. Journal segments written with
+    /// `Journal::write_segment(_, seq)` carry every change made after it.
+    /// The file at `path` is replaced only once the new one is on disk.
+    /// With `field_keys` set, invoices and preimages in it are sealed.
+    ///
+    /// The journal lock is not held while the state is copied, so the
+    /// snapshot may already contain spends from entries after `seq`;
+    /// replaying those on restore is harmless.
+    pub fn snapshot(&self, path: &Path) -> io::Result<u64> {
+        let seq = self.journal.last_seq();
+        let keys = self.field_keys.as_ref();
+
+        let body = SnapshotBody {
+            seq,
+            keyset_id: self.keyset_id.clone(),
+            keys: key_pairs(&self.keys),
+            identity: self.identity.secret_key(),
+            spent: self.spent.iter().map(|s| to_hex(&s)).collect(),
+            accepted_kinds: self.accepted_kinds.clone(),
+            signed: Vec::new(),
+            signed_by: self
+                .signed_outputs
+                .iter()
+                .map(|e| (e.value().1, *e.key(), e.value().0.clone()))
+                .collect(),
+            migrations: self
+                .migrations
+                .iter()
+                .map(|m| MigrationBody {
+                    keyset_id: m.keyset_id.clone(),
+                    keys: key_pairs(&m.keys),
+                    until: m.until,
+                    remaining: m
+                        .remaining
+                        .lock()
+                        .unwrap()
+                        .iter()
+                        .map(|(&v, &n)| (v, n))
+                        .collect(),
+                })
+                .collect(),
+            spend_records: self.spend_records,
+            spend_log: self.spend_log.iter().map(|e| e.value().clone()).collect(),
+            spent_witnesses: self
+                .spent_witnesses
+                .iter()
+                .map(|e| (*e.key(), e.value().clone()))
+                .collect(),
+            mint_quotes: self
+                .mint_quotes
+                .iter()
+                .map(|q| match keys {
+                    Some(keys) => q.sealed(keys),
+                    None => q.clone(),
+                })
+                .collect(),
+            melt_quotes: self
+                .melt_quotes
+                .iter()
+                .map(|q| match keys {
+                    Some(keys) => q.sealed(keys),
+                    None => q.clone(),
+                })
+                .collect(),
+            hold_preimages: self
+                .hold_preimages
+                .iter()
+                .map(|e| {
+                    let preimage = match keys {
+                        Some(keys) => keys.seal(&preimage_context(e.key()), e.value()),
+                        None => e.value().clone(),
+                    };
+                    (e.key().clone(), preimage)
+                })
+                .collect(),
+            announcements: self
+                .announcements
+                .active
+                .iter()
+                .map(|a| a.clone())
+                .collect(),
+            sealed: keys.is_some(),
+        };
+        let file = SnapshotFile {
+            version: SNAPSHOT_VERSION,
+            checksum: checksum(&body)?,
+            body,
+        };
+
+        write_atomic(path, &serde_json::to_vec(&file).map_err(io::Error::other)?)?;
+        Ok(seq)
+    }
+
+    /// Rebuilds a mint from a snapshot plus the journal segments written after
+    /// it, in order. Fails if the checksum or keyset ID do not match, or if
+    /// the segments leave a gap in the sequence numbers, or if the snapshot
+    /// is sealed.
+    pub fn restore(snapshot: &Path, segments: &[&Path]) -> io::Result<Mint> {
+        Self::restore_with(snapshot, segments, None)
+    }
+
+    /// As `restore`, opening sealed fields with `keys`, which the restored
+    /// mint keeps as its `field_keys`. Fields that are not sealed are
+    /// refused unless `keys` allow plaintext.
+    pub fn restore_sealed(
+        snapshot: &Path,
+        segments: &[&Path],
+        keys: &FieldKeys,
+    ) -> io::Result<Mint> {
+        Self::restore_with(snapshot, segments, Some(keys))
+    }
+
+    fn restore_with(
+        snapshot: &Path,
+        segments: &[&Path],
+        field_keys: Option<&FieldKeys>,
+    ) -> io::Result<Mint> {
+        let file: SnapshotFile = serde_json::from_slice(&fs::read(snapshot)?)
+            .map_err(|_| invalid("malformed snapshot"))?;
+        if file.version > SNAPSHOT_VERSION {
+            return Err(invalid("snapshot version not supported"));
+        }
+        if checksum(&file.body)? != file.checksum {
+            return Err(invalid("snapshot checksum mismatch"));
+        }
+        let body = file.body;
+        if body.sealed && field_keys.is_none() {
+            return Err(invalid("snapshot is sealed"));
+        }
+
+        let keys = keys_from(body.keys, &body.keyset_id)?;
+
+        let mut mint = Mint::from_keys(keys);
+        for m in body.migrations {
+            mint.migrations.push(Migration {
+                keys: keys_from(m.keys, &m.keyset_id)?,
+                keyset_id: m.keyset_id,
+                until: m.until,
+                remaining: Mutex::new(m.remaining.into_iter().collect()),
+            });
+        }
+        mint.accepted_kinds = body.accepted_kinds;
+        mint.identity = Keypair::from_secret_key(&Secp256k1::new(), &body.identity);
+        mint.journal = Journal::starting_after(body.seq);
+        let spent = body
+            .spent
+            .iter()
+            .map(|s| from_hex(s).ok_or_else(|| invalid("malformed spent secret")))
+            .collect::<io::Result<Vec<_>>>()?;
+        for (secret, y) in spent.iter().zip(hash_to_curve_batch(&spent)) {
+            mint.mark_spent(secret, &y);
+        }
+        for (value, blinded) in body.signed {
+            mint.signed_outputs
+                .insert(blinded, (body.keyset_id.clone(), value));
+        }
+        for (value, blinded, keyset_id) in body.signed_by {
+            mint.signed_outputs.insert(blinded, (keyset_id, value));
+        }
+        mint.spend_records = body.spend_records;
+        for record in body.spend_log {
+            mint.spend_log.insert(record.y, record);
+        }
+        mint.spent_witnesses.extend(body.spent_witnesses);
+        for q in body.mint_quotes {
+            let q = match field_keys {
+                Some(keys) => q.opened(keys)?,
+                None => q,
+            };
+            mint.mint_quotes.insert(q.id.clone(), q);
+        }
+        for q in body.melt_quotes {
+            let q = match field_keys {
+                Some(keys) => q.opened(keys)?,
+                None => q,
+            };
+            mint.melt_quotes.insert(q.id.clone(), q);
+        }
+        for (id, preimage) in body.hold_preimages {
+            let preimage = match field_keys {
+                Some(keys) => keys.open(&preimage_context(&id), &preimage)?,
+                None => preimage,
+            };
+            mint.hold_preimages.insert(id, preimage);
+        }
+        mint.field_keys = field_keys.cloned();
+        for a in body.announcements {
+            mint.announcements.active.insert(a.id.clone(), a);
+        }
+
+        for path in segments {
+            mint.apply_journal(Journal::read_segment(path)?)?;
+        }
+
+        Ok(mint)
+    }
+}
@@ -0,0 +1,193 @@
+//! Paying a batch of melt quotes from one set of inputs. See
+//! `dmto_wallet::batchmelt` for the protocol.
+
+pub use dmto_wallet::batchmelt::*;
+
+use std::{collections::HashSet, thread};
+
+use crate::{
+    error::MintError,
+    journal::JournalEvent,
+    melt::{MeltQuote, MeltQuoteState},
+    mint::Mint,
+    operation,
+    pool::Priority,
+    protocol::{Request, Response},
+    types::Amount,
+};
+
+impl Mint {
+    /// Spends the inputs once and pays every quote, concurrently. Fails as
+    /// a whole, releasing the inputs, only if the request is invalid or no
+    /// payment is made.
+    pub fn melt_batch(
+        &self,
+        req: Request<BatchMeltRequest>,
+    ) -> Result<Response<BatchMeltResponse>, MintError> {
+        req.handle(|req| {
+            let operation = req.operation.clone();
+            self.traced(operation.as_deref(), "melt_batch", || {
+                self.run_melt_batch(req)
+            })
+        })
+    }
+
+    fn run_melt_batch(&self, req: BatchMeltRequest) -> Result<BatchMeltResponse, MintError> {
+        let mut seen = HashSet::new();
+        if let Some(dup) = req.quotes.iter().find(|id| !seen.insert(*id)) {
+            return Err(MintError::BadRequest(format!(
+                "quote {} appears twice",
+                dup
+            )));
+        }
+
+        let mut quotes = Vec::with_capacity(req.quotes.len());
+        let reset = |quotes: &[MeltQuote]| {
+            for q in quotes {
+                self.set_melt_state(&q.id, MeltQuoteState::Unpaid);
+            }
+        };
+        for id in &req.quotes {
+            match self.begin_melt(id) {
+                Ok(q) => quotes.push(q),
+                Err(e) => {
+                    reset(&quotes);
+                    return Err(e);
+                }
+            }
+        }
+        let mut requests = HashSet::new();
+        if let Some(dup) = quotes.iter().find(|q| !requests.insert(&q.request)) {
+            reset(&quotes);
+            return Err(MintError::BadRequest(format!(
+                "{} would be paid twice",
+                dup.request
+            )));
+        }
+        let (in_sum, fee) = match self.check_batch(&req, &quotes) {
+            Ok(sums) => sums,
+            Err(e) => {
+                reset(&quotes);
+                return Err(e);
+            }
+        };
+        if let Err(e) = self.spend_inputs(&req.inputs, Priority::Melt) {
+            reset(&quotes);
+            return Err(e);
+        }
+
+        let results: Vec<_> = thread::scope(|s| {
+            let handles: Vec<_> = quotes
+                .iter()
+                .map(|q| {
+                    let operation = operation::current();
+                    s.spawn(move || {
+                        let _guard = operation::enter(operation.as_deref());
+                        self.pay_melt(q, q.fee_reserve)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| {
+                    h.join().unwrap_or_else(|_| {
+                        Err(MintError::PaymentFailed("payment panicked".to_string()))
+                    })
+                })
+                .collect()
+        });
+        if results.iter().all(|r| r.is_err()) {
+            self.release_inputs(&req.inputs);
+            reset(&quotes);
+            let first = results.into_iter().find_map(|r| r.err());
+            return Err(first.unwrap_or(MintError::BadRequest("no quotes".to_string())));
+        }
+
+        let mut paid = 0;
+        let mut input_fee = fee;
+        let mut outcomes = Vec::with_capacity(quotes.len());
+        for (quote, result) in quotes.into_iter().zip(results) {
+            outcomes.push(match result {
+                Ok((proof, fee_paid)) => {
+                    paid += quote.amount + fee_paid;
+                    if let Some(accounts) = &self.accounts {
+                        accounts.melted(&quote.id, quote.amount + fee_paid);
+                    }
+                    let quote = self.complete_melt(&quote.id, proof, fee_paid);
+                    let receipt = self.melt_receipt(
+                        &req.inputs,
+                        std::mem::take(&mut input_fee),
+                        &quote,
+                        fee_paid,
+                        req.operation.clone(),
+                    );
+                    MeltOutcome {
+                        change: quote.fee_reserve.saturating_sub(fee_paid),
+                        quote,
+                        error: None,
+                        receipt: Some(receipt),
+                    }
+                }
+                Err(e) => {
+                    self.set_melt_state(&quote.id, MeltQuoteState::Unpaid);
+                    MeltOutcome {
+                        change: quote.amount + quote.fee_reserve,
+                        quote: self.melt_quote_state(&quote.id)?,
+                        error: Some(e.to_string()),
+                        receipt: None,
+                    }
+                }
+            });
+        }
+
+        self.record_redeemed(&req.inputs, paid);
+        if fee > 0 {
+            self.journal
+                .append(JournalEvent::Fee { amount: fee }, self.clock.now());
+        }
+        let change = self.sign_change(in_sum - fee - paid, &req.outputs, in_sum);
+        Ok(BatchMeltResponse { outcomes, change })
+    }
+
+    /// Checks the inputs cover every quote and returns their total and the
+    /// input fee.
+    fn check_batch(
+        &self,
+        req: &BatchMeltRequest,
+        quotes: &[MeltQuote],
+    ) -> Result<(u64, u64), MintError> {
+        self.check_canonical(req.is_canonical())?;
+        self.check_not_migrating(&req.inputs)?;
+        if req.inputs.len() > self.max_inputs {
+            return Err(MintError::TooManyInputs {
+                max: self.max_inputs,
+            });
+        }
+        if req.outputs.len() > self.max_outputs {
+            return Err(MintError::TooManyOutputs {
+                max: self.max_outputs,
+            });
+        }
+
+        let in_sum = Amount::checked_sum(req.inputs.iter().map(|n| n.value))
+            .ok_or(MintError::AmountOverflow)?;
+        let owed = Amount::checked_sum(quotes.iter().flat_map(|q| [q.amount, q.fee_reserve]))
+            .ok_or(MintError::AmountOverflow)?;
+        let fee = self.fee(req.inputs.len());
+        if in_sum
+            < owed
+                .checked_add(Amount(fee))
+                .ok_or(MintError::AmountOverflow)?
+        {
+            return Err(MintError::AmountMismatch {
+                inputs: in_sum.0,
+                outputs: owed.0,
+                fee,
+            });
+        }
+
+        self.monitor.check(&self.keyset_id)?;
+        self.check_outputs_unsigned(&req.outputs)?;
+        Ok((in_sum.0, fee))
+    }
+}
@@ -0,0 +1,393 @@
+//! Load generation for the mint: simulated wallets, one thread each, run a
+//! weighted mix of swaps, issuance and melts against one mint at once, and
+//! every operation is timed. `dmto-bench` runs it from the command line.
+//!
+//! Each wallet is funded through a mint quote before the clock starts.
+//! Issuance pays its quotes through the fake Lightning backend and melts
+//! pay fresh invoices from it, so the numbers measure the mint and not a
+//! payment network. Operations that fail, e.g. a melt from a wallet that
+//! ran dry, are counted but not timed.
+//!
+//! `run_hash` times the hashing-heavy primitives alone under each hash
+//! backend compiled in, to see what the `ring` feature buys a mint.
+
+use std::{
+    collections::BTreeMap,
+    fmt, thread,
+    time::{Duration, Instant},
+};
+
+use rand::{Rng, RngCore};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+use crate::{
+    api::MintTrait,
+    blind::{blind_message, blind_sign},
+    dleq,
+    error::{MintError, WalletError},
+    hash::{self, HashBackend},
+    lightning::FakeBackend,
+    wallet::{Wallet, split_amount},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Op {
+    Swap,
+    Mint,
+    Melt,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Swap => write!(f, "swap"),
+            Op::Mint => write!(f, "mint"),
+            Op::Melt => write!(f, "melt"),
+        }
+    }
+}
+
+/// Relative weights of each operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mix {
+    pub swap: u32,
+    pub mint: u32,
+    pub melt: u32,
+}
+
+impl Default for Mix {
+    fn default() -> Self {
+        Self {
+            swap: 60,
+            mint: 25,
+            melt: 15,
+        }
+    }
+}
+
+impl fmt::Display for Mix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "swap={},mint={},melt={}",
+            self.swap, self.mint, self.melt
+        )
+    }
+}
+
+impl Mix {
+    /// Reads `swap=60,mint=25,melt=15`. Operations left out get weight 0.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut mix = Mix {
+            swap: 0,
+            mint: 0,
+            melt: 0,
+        };
+        for part in s.split(',').filter(|p| !p.trim().is_empty()) {
+            let (op, weight) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected op=weight, got `{}`", part))?;
+            let weight = weight
+                .trim()
+                .parse()
+                .map_err(|_| format!("bad weight `{}`", weight))?;
+            match op.trim() {
+                "swap" => mix.swap = weight,
+                "mint" => mix.mint = weight,
+                "melt" => mix.melt = weight,
+                other => return Err(format!("unknown operation `{}`", other)),
+            }
+        }
+        if mix.total() == 0 {
+            return Err("all weights are 0".to_string());
+        }
+        Ok(mix)
+    }
+
+    fn total(&self) -> u32 {
+        self.swap + self.mint + self.melt
+    }
+
+    fn pick(&self, rng: &mut impl Rng) -> Op {
+        let n = rng.gen_range(0..self.total());
+        if n < self.swap {
+            Op::Swap
+        } else if n < self.swap + self.mint {
+            Op::Mint
+        } else {
+            Op::Melt
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BenchConfig {
+    pub wallets: usize,
+    /// Operations per wallet.
+    pub ops: usize,
+    pub mix: Mix,
+    /// Amount swapped, issued or melted per operation.
+    pub amount: u64,
+    /// What each wallet is funded with before the run.
+    pub funding: u64,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            wallets: 8,
+            ops: 100,
+            mix: Mix::default(),
+            amount: 8,
+            funding: 256,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OpStats {
+    pub failed: u64,
+    /// Latencies of the operations that succeeded, sorted.
+    pub latencies: Vec<Duration>,
+}
+
+impl OpStats {
+    pub fn ok(&self) -> usize {
+        self.latencies.len()
+    }
+
+    /// The latency `p` percent of operations stayed within (nearest rank).
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (p / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        self.latencies[rank.clamp(1, self.latencies.len()) - 1]
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BenchReport {
+    pub elapsed: Duration,
+    pub ops: BTreeMap<Op, OpStats>,
+}
+
+impl BenchReport {
+    /// Successful operations per second over the whole run.
+    pub fn throughput(&self) -> f64 {
+        let ok: usize = self.ops.values().map(OpStats::ok).sum();
+        ok as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+fn ms(d: Duration) -> String {
+    format!("{:.2}ms", d.as_secs_f64() * 1000.0)
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<6} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}",
+            "op", "ok", "failed", "p50", "p90", "p99", "max"
+        )?;
+        for (op, stats) in &self.ops {
+            writeln!(
+                f,
+                "{:<6} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}",
+                op.to_string(),
+                stats.ok(),
+                stats.failed,
+                ms(stats.percentile(50.0)),
+                ms(stats.percentile(90.0)),
+                ms(stats.percentile(99.0)),
+                ms(stats.percentile(100.0)),
+            )?;
+        }
+        write!(
+            f,
+            "{:.2}s, {:.1} ops/s",
+            self.elapsed.as_secs_f64(),
+            self.throughput()
+        )
+    }
+}
+
+/// Issues `amount` to `wallet` through a mint quote paid on `lightning`.
+fn fund(
+    wallet: &mut Wallet,
+    mint: &impl MintTrait,
+    lightning: &FakeBackend,
+    amount: u64,
+) -> Result<u64, WalletError> {
+    let quote = wallet.request_mint(mint, amount)?;
+    lightning.settle(&quote.request);
+    wallet.resume_quote(mint, &quote.id)
+}
+
+fn run_op(
+    op: Op,
+    wallet: &mut Wallet,
+    mint: &impl MintTrait,
+    lightning: &FakeBackend,
+    values: &[u64],
+    amount: u64,
+) -> Result<(), WalletError> {
+    match op {
+        Op::Swap => {
+            let notes = wallet.split_out(mint, values)?;
+            wallet.notes.extend(notes);
+        }
+        Op::Mint => {
+            fund(wallet, mint, lightning, amount)?;
+        }
+        Op::Melt => {
+            wallet.melt(mint, &lightning.invoice(amount))?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs `config` against `mint`, whose Lightning backend is `lightning`.
+pub fn run<M: MintTrait + Sync>(
+    mint: &M,
+    lightning: &FakeBackend,
+    config: &BenchConfig,
+) -> Result<BenchReport, WalletError> {
+    let info = mint.info()?;
+    let values = split_amount(config.amount, &info.denominations)
+        .ok_or(MintError::UnknownDenomination(config.amount))?;
+
+    let mut wallets = Vec::with_capacity(config.wallets);
+    for _ in 0..config.wallets {
+        let mut wallet = Wallet::new();
+        fund(&mut wallet, mint, lightning, config.funding)?;
+        wallets.push(wallet);
+    }
+
+    let start = Instant::now();
+    let results: Vec<Vec<(Op, Option<Duration>)>> = thread::scope(|s| {
+        let handles: Vec<_> = wallets
+            .into_iter()
+            .map(|mut wallet| {
+                let values = &values;
+                s.spawn(move || {
+                    let mut rng = rand::thread_rng();
+                    (0..config.ops)
+                        .map(|_| {
+                            let op = config.mix.pick(&mut rng);
+                            let started = Instant::now();
+                            let result =
+                                run_op(op, &mut wallet, mint, lightning, values, config.amount);
+                            (op, result.ok().map(|_| started.elapsed()))
+                        })
+                        .collect()
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let mut report = BenchReport {
+        elapsed: start.elapsed(),
+        ops: BTreeMap::new(),
+    };
+    for (op, latency) in results.into_iter().flatten() {
+        let stats = report.ops.entry(op).or_default();
+        match latency {
+            Some(latency) => stats.latencies.push(latency),
+            None => stats.failed += 1,
+        }
+    }
+    for stats in report.ops.values_mut() {
+        stats.latencies.sort_unstable();
+    }
+    Ok(report)
+}
+
+/// Mean time per call of the primitives that hash, under one backend.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HashReport {
+    pub backend: &'static str,
+    pub iterations: u32,
+    pub hash_to_curve: Duration,
+    pub prove: Duration,
+    pub verify: Duration,
+}
+
+fn us(d: Duration) -> String {
+    format!("{:.1}us", d.as_secs_f64() * 1e6)
+}
+
+impl fmt::Display for HashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<6} {:>14} {:>12} {:>12}",
+            self.backend,
+            us(self.hash_to_curve),
+            us(self.prove),
+            us(self.verify)
+        )
+    }
+}
+
+/// Times `hash_to_curve`, DLEQ proving and DLEQ verification with `H` over
+/// `iterations` random secrets.
+pub fn run_hash<H: HashBackend>(backend: &'static str, iterations: u32) -> HashReport {
+    let mut rng = rand::thread_rng();
+    let key = SecretKey::new(&mut rng);
+    let big_a = PublicKey::from_secret_key(&Secp256k1::new(), &key);
+    let secrets: Vec<[u8; 32]> = (0..iterations)
+        .map(|_| {
+            let mut secret = [0; 32];
+            rng.fill_bytes(&mut secret);
+            secret
+        })
+        .collect();
+
+    let start = Instant::now();
+    let ys: Vec<_> = secrets
+        .iter()
+        .map(|s| hash::hash_to_curve_with::<H>(s))
+        .collect();
+    let hash_to_curve = start.elapsed();
+
+    let signed: Vec<_> = ys
+        .iter()
+        .map(|y| {
+            let blinded = blind_message(y).blinded_point;
+            (blinded, blind_sign(&key, &blinded))
+        })
+        .collect();
+    let start = Instant::now();
+    let proofs: Vec<_> = signed
+        .iter()
+        .map(|(blinded, sig)| dleq::prove_with::<H>(&key, blinded, sig))
+        .collect();
+    let prove = start.elapsed();
+
+    let start = Instant::now();
+    for ((blinded, sig), proof) in signed.iter().zip(&proofs) {
+        assert!(dleq::verify_with::<H>(&big_a, blinded, sig, proof));
+    }
+    let verify = start.elapsed();
+
+    let per_call = |d: Duration| d / iterations.max(1);
+    HashReport {
+        backend,
+        iterations,
+        hash_to_curve: per_call(hash_to_curve),
+        prove: per_call(prove),
+        verify: per_call(verify),
+    }
+}
+
+/// `run_hash` for every backend compiled in.
+pub fn run_hash_backends(iterations: u32) -> Vec<HashReport> {
+    vec![
+        run_hash::<hash::Sha2Backend>("sha2", iterations),
+        #[cfg(feature = "ring")]
+        run_hash::<hash::RingBackend>("ring", iterations),
+    ]
+}
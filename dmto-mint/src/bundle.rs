@@ -0,0 +1,26 @@
+//! Signing keyset bundles. See `dmto_wallet::bundle` for the format.
+
+pub use dmto_wallet::bundle::*;
+
+use secp256k1::PublicKey;
+
+use crate::{mint::Mint, signing};
+
+impl Mint {
+    pub fn export_keyset(&self) -> KeysetBundle {
+        let mut keys: Vec<(u64, PublicKey)> =
+            self.keys.iter().map(|(&v, k)| (v, k.pubkey)).collect();
+        keys.sort_by_key(|(v, _)| *v);
+
+        let signature = signing::sign(
+            &self.identity,
+            &KeysetBundle::message(&self.keyset_id, &keys),
+        );
+        KeysetBundle {
+            keyset_id: self.keyset_id.clone(),
+            keys,
+            identity: self.identity.x_only_public_key().0,
+            signature,
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! Enforcing canonical order on incoming requests. See
+//! `dmto_wallet::canonical` for the order itself.
+
+pub use dmto_wallet::canonical::*;
+
+use crate::{error::MintError, mint::Mint};
+
+impl Mint {
+    /// Fails if `canonical_order` is set and the request was not in
+    /// canonical order.
+    pub(crate) fn check_canonical(&self, canonical: bool) -> Result<(), MintError> {
+        if self.canonical_order && !canonical {
+            return Err(MintError::BadRequest(
+                "inputs or outputs not in canonical order".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+//! Key ceremonies: generating a keyset from entropy contributed by several
+//! participants, with a signed transcript that can be published and audited
+//! later.
+//!
+//! Every participant first commits to `SHA256(entropy)`; only once all
+//! commitments are in are the entropies revealed, so no one can pick theirs
+//! after seeing the others. The seed is the hash of all entropies in
+//! commitment order and the keyset is derived from it with `derive_keys`.
+//!
+//! The transcript holds the commitments, a commitment to the seed, the
+//! derivation parameters and the resulting public keys, signed with the
+//! mint's identity key. It never contains the entropies or the seed; an
+//! auditor given them re-runs the derivation with `CeremonyTranscript::audit`.
+
+use std::fmt;
+
+use secp256k1::{Keypair, PublicKey, XOnlyPublicKey, schnorr::Signature};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{codec::to_hex, derivation::derive_keys, keyset::KeysetIdVersion, mint::Mint, signing};
+
+/// Describes the derivation recorded in transcripts.
+pub const DERIVATION: &str = "sha256(dmto-keyset || seed || index || value || counter)";
+
+pub fn commitment(entropy: &[u8]) -> String {
+    to_hex(&Sha256::digest(entropy))
+}
+
+fn combine(entropies: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(b"dmto-ceremony");
+    for e in entropies {
+        hasher.update((e.len() as u64).to_be_bytes());
+        hasher.update(e);
+    }
+    hasher.finalize().to_vec()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CeremonyError {
+    NoParticipants,
+    DuplicateParticipant(String),
+    UnknownParticipant(String),
+    /// Commitments closed with the first reveal.
+    CommitmentsClosed,
+    CommitmentMismatch(String),
+    MissingReveal(String),
+    /// An audit found the transcript does not match what it records.
+    TranscriptMismatch(String),
+}
+
+impl fmt::Display for CeremonyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CeremonyError::NoParticipants => write!(f, "no participants"),
+            CeremonyError::DuplicateParticipant(p) => write!(f, "{} already committed", p),
+            CeremonyError::UnknownParticipant(p) => write!(f, "{} has not committed", p),
+            CeremonyError::CommitmentsClosed => write!(f, "commitments are closed"),
+            CeremonyError::CommitmentMismatch(p) => {
+                write!(f, "entropy from {} does not match its commitment", p)
+            }
+            CeremonyError::MissingReveal(p) => write!(f, "{} has not revealed", p),
+            CeremonyError::TranscriptMismatch(what) => write!(f, "transcript mismatch: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for CeremonyError {}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntropyCommitment {
+    pub participant: String,
+    /// Hex `SHA256(entropy)`.
+    pub commitment: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CeremonyTranscript {
+    pub index: u32,
+    pub denominations: Vec<u64>,
+    pub commitments: Vec<EntropyCommitment>,
+    /// Hex `SHA256(seed)`.
+    pub seed_commitment: String,
+    pub derivation: String,
+    pub keyset_id: String,
+    pub keys: Vec<(u64, PublicKey)>,
+    pub timestamp: u64,
+    pub identity: XOnlyPublicKey,
+    pub signature: Signature,
+}
+
+/// A ceremony in progress.
+#[derive(Clone, Debug)]
+pub struct Ceremony {
+    pub index: u32,
+    pub denominations: Vec<u64>,
+    commitments: Vec<EntropyCommitment>,
+    reveals: Vec<Option<Vec<u8>>>,
+}
+
+/// What a finished ceremony hands the operator. `seed` must be kept as
+/// carefully as the keys; the transcript is for publishing.
+pub struct CeremonyOutput {
+    pub seed: Vec<u8>,
+    pub mint: Mint,
+    pub transcript: CeremonyTranscript,
+}
+
+impl Ceremony {
+    /// Starts a ceremony for keyset number `index`.
+    pub fn new(index: u32, denominations: &[u64]) -> Self {
+        let mut denominations = denominations.to_vec();
+        denominations.sort();
+        Self {
+            index,
+            denominations,
+            commitments: Vec::new(),
+            reveals: Vec::new(),
+        }
+    }
+
+    pub fn commit(&mut self, participant: &str, commitment: &str) -> Result<(), CeremonyError> {
+        if self.reveals.iter().any(Option::is_some) {
+            return Err(CeremonyError::CommitmentsClosed);
+        }
+        if self.position(participant).is_some() {
+            return Err(CeremonyError::DuplicateParticipant(participant.to_string()));
+        }
+        self.commitments.push(EntropyCommitment {
+            participant: participant.to_string(),
+            commitment: commitment.to_lowercase(),
+        });
+        self.reveals.push(None);
+        Ok(())
+    }
+
+    pub fn reveal(&mut self, participant: &str, entropy: &[u8]) -> Result<(), CeremonyError> {
+        let i = self
+            .position(participant)
+            .ok_or_else(|| CeremonyError::UnknownParticipant(participant.to_string()))?;
+        if commitment(entropy) != self.commitments[i].commitment {
+            return Err(CeremonyError::CommitmentMismatch(participant.to_string()));
+        }
+        self.reveals[i] = Some(entropy.to_vec());
+        Ok(())
+    }
+
+    /// Participants who committed but have not revealed.
+    pub fn pending(&self) -> Vec<String> {
+        self.commitments
+            .iter()
+            .zip(&self.reveals)
+            .filter(|(_, r)| r.is_none())
+            .map(|(c, _)| c.participant.clone())
+            .collect()
+    }
+
+    fn position(&self, participant: &str) -> Option<usize> {
+        self.commitments
+            .iter()
+            .position(|c| c.participant == participant)
+    }
+
+    /// Derives the keyset once everyone has revealed and signs the
+    /// transcript with `identity`, which also becomes the mint's identity.
+    pub fn finish(self, identity: Keypair, now: u64) -> Result<CeremonyOutput, CeremonyError> {
+        if self.commitments.is_empty() {
+            return Err(CeremonyError::NoParticipants);
+        }
+        if let Some(p) = self.pending().into_iter().next() {
+            return Err(CeremonyError::MissingReveal(p));
+        }
+        let entropies: Vec<&[u8]> = self.reveals.iter().flatten().map(Vec::as_slice).collect();
+        let seed = combine(&entropies);
+
+        let mut mint = Mint::from_keys(derive_keys(&seed, self.index, &self.denominations));
+        mint.identity = identity;
+        let mut keys: Vec<(u64, PublicKey)> =
+            mint.keys.iter().map(|(&v, k)| (v, k.pubkey)).collect();
+        keys.sort_by_key(|(v, _)| *v);
+
+        let mut transcript = CeremonyTranscript {
+            index: self.index,
+            denominations: self.denominations,
+            commitments: self.commitments,
+            seed_commitment: commitment(&seed),
+            derivation: DERIVATION.to_string(),
+            keyset_id: mint.keyset_id.clone(),
+            keys,
+            timestamp: now,
+            identity: identity.x_only_public_key().0,
+            signature: signing::sign(&identity, &[]),
+        };
+        transcript.signature = signing::sign(&identity, &transcript.message());
+
+        Ok(CeremonyOutput {
+            seed,
+            mint,
+            transcript,
+        })
+    }
+}
+
+impl CeremonyTranscript {
+    fn message(&self) -> Vec<u8> {
+        serde_json::to_vec(&(
+            self.index,
+            &self.denominations,
+            &self.commitments,
+            &self.seed_commitment,
+            &self.derivation,
+            &self.keyset_id,
+            &self.keys,
+            self.timestamp,
+            &self.identity,
+        ))
+        .unwrap()
+    }
+
+    /// Checks the signature and that the keyset ID matches the keys. Pass the
+    /// mint's identity key if it is known from elsewhere.
+    pub fn verify(&self, identity: Option<&XOnlyPublicKey>) -> bool {
+        if identity.is_some_and(|id| *id != self.identity) {
+            return false;
+        }
+        KeysetIdVersion::matches(&self.keyset_id, &self.keys)
+            && signing::verify(&self.identity, &self.message(), &self.signature)
+    }
+
+    /// Re-runs the ceremony from the revealed entropies, in commitment order,
+    /// and checks every recorded value against it.
+    pub fn audit(&self, entropies: &[Vec<u8>]) -> Result<(), CeremonyError> {
+        let mismatch = |what: &str| Err(CeremonyError::TranscriptMismatch(what.to_string()));
+        if !self.verify(None) {
+            return mismatch("signature");
+        }
+        if entropies.len() != self.commitments.len() {
+            return mismatch("number of participants");
+        }
+        for (c, e) in self.commitments.iter().zip(entropies) {
+            if commitment(e) != c.commitment {
+                return Err(CeremonyError::CommitmentMismatch(c.participant.clone()));
+            }
+        }
+
+        let seed = combine(&entropies.iter().map(Vec::as_slice).collect::<Vec<_>>());
+        if commitment(&seed) != self.seed_commitment {
+            return mismatch("seed commitment");
+        }
+        let mut keys: Vec<(u64, PublicKey)> = derive_keys(&seed, self.index, &self.denominations)
+            .into_iter()
+            .map(|(v, k)| (v, k.pubkey))
+            .collect();
+        keys.sort_by_key(|(v, _)| *v);
+        if keys != self.keys {
+            return mismatch("derived keys");
+        }
+        Ok(())
+    }
+}
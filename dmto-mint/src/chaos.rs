@@ -0,0 +1,172 @@
+//! Fault injection for integration environments: slow and failing storage
+//! and stalled Lightning payments, set from configuration (`chaos_*`), so
+//! the swap, issuance and melt state machines can be exercised against the
+//! failures they are built to survive.
+//!
+//! Faults are injected by wrapping the mint's `HoldStore` and
+//! `LightningBackend`; `Mint::apply_chaos` wraps whichever are set. A
+//! failed write returns an error without reaching the store, as a full disk
+//! would. A stalled payment hangs for `payment_stall` and then goes
+//! through, as one stuck on a slow route does.
+
+use std::{io, sync::Arc, thread, time::Duration};
+
+use rand::Rng;
+
+use crate::{
+    hold::{HeldIssuance, HoldStore},
+    lightning::{Invoice, InvoiceOptions, LightningBackend, Payment, PaymentError},
+    mint::Mint,
+};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Chaos {
+    /// Added to every storage call.
+    pub storage_latency: Duration,
+    /// Share of storage writes that fail, per thousand.
+    pub write_failure_ppk: u64,
+    /// How long a stalled payment hangs.
+    pub payment_stall: Duration,
+    /// Share of payments that stall, per thousand.
+    pub payment_stall_ppk: u64,
+}
+
+impl Chaos {
+    pub fn is_off(&self) -> bool {
+        self.storage_latency.is_zero()
+            && self.write_failure_ppk == 0
+            && (self.payment_stall.is_zero() || self.payment_stall_ppk == 0)
+    }
+
+    fn roll(ppk: u64) -> bool {
+        ppk > 0 && rand::thread_rng().gen_range(0..1000) < ppk
+    }
+}
+
+/// A `HoldStore` with `Chaos` storage faults.
+pub struct ChaosStore {
+    pub inner: Arc<dyn HoldStore>,
+    pub chaos: Chaos,
+}
+
+impl ChaosStore {
+    fn write(&self) -> io::Result<()> {
+        thread::sleep(self.chaos.storage_latency);
+        if Chaos::roll(self.chaos.write_failure_ppk) {
+            return Err(io::Error::other("injected write failure"));
+        }
+        Ok(())
+    }
+}
+
+impl HoldStore for ChaosStore {
+    fn persist(&self, record: &HeldIssuance) -> io::Result<()> {
+        self.write()?;
+        self.inner.persist(record)
+    }
+
+    fn remove(&self, quote_id: &str) -> io::Result<()> {
+        self.write()?;
+        self.inner.remove(quote_id)
+    }
+
+    fn pending(&self) -> io::Result<Vec<HeldIssuance>> {
+        thread::sleep(self.chaos.storage_latency);
+        self.inner.pending()
+    }
+}
+
+/// A `LightningBackend` whose payments stall as `Chaos` says.
+pub struct ChaosBackend {
+    pub inner: Arc<dyn LightningBackend>,
+    pub chaos: Chaos,
+}
+
+impl ChaosBackend {
+    fn maybe_stall(&self) {
+        if Chaos::roll(self.chaos.payment_stall_ppk) {
+            thread::sleep(self.chaos.payment_stall);
+        }
+    }
+}
+
+impl LightningBackend for ChaosBackend {
+    fn decode(&self, request: &str) -> Option<Invoice> {
+        self.inner.decode(request)
+    }
+
+    fn fee_reserve(&self, amount: u64) -> u64 {
+        self.inner.fee_reserve(amount)
+    }
+
+    fn pay(&self, request: &str, max_fee: u64) -> Result<Payment, PaymentError> {
+        self.maybe_stall();
+        self.inner.pay(request, max_fee)
+    }
+
+    fn pay_amountless(
+        &self,
+        request: &str,
+        amount: u64,
+        max_fee: u64,
+    ) -> Result<Payment, PaymentError> {
+        self.maybe_stall();
+        self.inner.pay_amountless(request, amount, max_fee)
+    }
+
+    fn create_invoice(
+        &self,
+        amount: u64,
+        options: &InvoiceOptions,
+    ) -> Result<String, PaymentError> {
+        self.inner.create_invoice(amount, options)
+    }
+
+    fn is_paid(&self, payment_hash: &str) -> bool {
+        self.inner.is_paid(payment_hash)
+    }
+
+    fn create_hold_invoice(
+        &self,
+        amount: u64,
+        payment_hash: &str,
+        options: &InvoiceOptions,
+    ) -> Result<String, PaymentError> {
+        self.inner
+            .create_hold_invoice(amount, payment_hash, options)
+    }
+
+    fn is_held(&self, payment_hash: &str) -> bool {
+        self.inner.is_held(payment_hash)
+    }
+
+    fn settle_hold(&self, preimage: &str) -> Result<(), PaymentError> {
+        self.inner.settle_hold(preimage)
+    }
+
+    fn cancel_hold(&self, payment_hash: &str) {
+        self.inner.cancel_hold(payment_hash)
+    }
+}
+
+impl Mint {
+    /// Wraps the mint's hold store and Lightning backend, whichever are
+    /// set, to inject `chaos`. Call after setting them.
+    pub fn apply_chaos(&mut self, chaos: &Chaos) {
+        if chaos.is_off() {
+            return;
+        }
+        if let Some(inner) = self.hold_store.take() {
+            self.hold_store = Some(Arc::new(ChaosStore {
+                inner,
+                chaos: chaos.clone(),
+            }));
+        }
+        if let Some(inner) = self.lightning.take() {
+            self.lightning = Some(Arc::new(ChaosBackend {
+                inner,
+                chaos: chaos.clone(),
+            }));
+        }
+    }
+}
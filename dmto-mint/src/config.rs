@@ -0,0 +1,583 @@
+//! Settings for the mint and wallet, merged from (lowest to highest
+//! priority) built-in defaults, a TOML file, `DMTO_*` environment variables
+//! and `--key value` command-line flags.
+//!
+//! The TOML file is the one named by `--config <path>`, or else by the
+//! `DMTO_CONFIG` environment variable; without either there is none.
+//!
+//! Denominations are given either as `max_order` (every power of two up to
+//! `2^max_order`) or as an explicit `denominations` list.
+//!
+//! Only flat TOML is read: `key = value` lines with string, integer or
+//! array values, and `#` comments.
+//!
+//! `auth_routes` lists the routes needing a credential. They accept API keys
+//! from `api_keys`, given as `<sha256 hex>:<subject>` so the file holds no
+//! keys, and OIDC tokens from `oidc_issuer` for `oidc_audience`, signed by
+//! one of `oidc_keys` (`<kid>:<public key hex>`, ES256K) or a key fetched
+//! from `oidc_jwks_url` (RS256 or ES256).
+//!
+//! `canonical_order = false` lets the mint accept requests whose inputs and
+//! outputs are not in canonical order, from wallets that predate it.
+//! `spend_records = true` has refusals of already spent inputs carry a
+//! signed record of when they were spent.
+//!
+//! `fee_reserve_ppk` and `fee_reserve_floor` replace the Lightning backend's
+//! melt fee reserve, and `melt_attempts` spreads it over that many routing
+//! attempts.
+//!
+//! `invoice_description` is the description of mint quote invoices, with
+//! `{quote_id}` and `{amount}` filled in; `invoice_description_hash = true`
+//! puts only its hash in the invoice. `invoice_expiry` sets how long they
+//! stay payable, `quote_ttl` by default.
+//!
+//! `chaos_storage_latency_ms`, `chaos_write_failure_ppk`,
+//! `chaos_payment_stall_ms` and `chaos_payment_stall_ppk` inject faults
+//! for integration environments (see `chaos`). Binaries apply them with
+//! `Mint::apply_chaos` once the backends are set.
+//!
+//! `accounting = true` attributes quotes to those accounts, each limited to
+//! `account_max_minted` and `account_max_melted` per `account_window`
+//! seconds.
+
+use std::{fmt, fs, path::Path, path::PathBuf, sync::Arc, time::Duration};
+
+use secp256k1::PublicKey;
+
+use crate::{
+    accounts::{AccountLimits, Accounts},
+    auth::{ApiKeyAuth, AuthGate, OidcAuth, Route},
+    chaos::Chaos,
+    codec::from_hex,
+    issue::InvoiceTemplate,
+    keyset::Keyset,
+    melt::FeeReserve,
+    mint::Mint,
+    strict::{JsonLimits, UnknownFields},
+};
+
+const ENV_PREFIX: &str = "DMTO_";
+const CONFIG_VAR: &str = "DMTO_CONFIG";
+const CONFIG_FLAG: &str = "config";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub denominations: Vec<u64>,
+    pub input_fee_ppk: u64,
+    pub max_inputs: usize,
+    pub max_outputs: usize,
+    pub canonical_order: bool,
+    pub spend_records: bool,
+    pub quote_ttl: u64,
+    pub data_dir: PathBuf,
+    pub mint_url: String,
+    pub json_limits: JsonLimits,
+    pub auth_routes: Vec<Route>,
+    /// `(sha256 hex, subject)` per API key.
+    pub api_keys: Vec<(String, String)>,
+    pub oidc_issuer: String,
+    pub oidc_audience: String,
+    pub oidc_keys: Vec<(String, PublicKey)>,
+    pub oidc_jwks_url: String,
+    pub accounting: bool,
+    pub account_limits: AccountLimits,
+    /// Lightning melt fee reserve; the backend's when neither is set.
+    pub fee_reserve_ppk: Option<u64>,
+    pub fee_reserve_floor: Option<u64>,
+    pub melt_attempts: u32,
+    pub invoice_template: InvoiceTemplate,
+    pub chaos: Chaos,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            denominations: Keyset::power2(15).denominations(),
+            input_fee_ppk: 0,
+            max_inputs: 1000,
+            max_outputs: 1000,
+            canonical_order: true,
+            spend_records: false,
+            quote_ttl: 3600,
+            data_dir: PathBuf::from("./data"),
+            mint_url: "http://localhost:3338".to_string(),
+            json_limits: JsonLimits::default(),
+            auth_routes: Vec::new(),
+            api_keys: Vec::new(),
+            oidc_issuer: String::new(),
+            oidc_audience: String::new(),
+            oidc_keys: Vec::new(),
+            oidc_jwks_url: String::new(),
+            accounting: false,
+            account_limits: AccountLimits::default(),
+            fee_reserve_ppk: None,
+            fee_reserve_floor: None,
+            melt_attempts: 1,
+            invoice_template: InvoiceTemplate::default(),
+            chaos: Chaos::default(),
+        }
+    }
+}
+
+/// Where a setting came from, for error messages.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    File { path: PathBuf, line: usize },
+    Env(String),
+    Flag,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::File { path, line } => write!(f, "{}:{}", path.display(), line),
+            Source::Env(var) => write!(f, "environment variable {}", var),
+            Source::Flag => write!(f, "command line"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Syntax {
+        source: Source,
+        message: String,
+    },
+    UnknownKey {
+        source: Source,
+        key: String,
+    },
+    BadValue {
+        source: Source,
+        key: String,
+        message: String,
+    },
+    /// The merged settings are inconsistent.
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            ConfigError::Syntax { source, message } => write!(f, "{}: {}", source, message),
+            ConfigError::UnknownKey { source, key } => {
+                write!(f, "{}: unknown setting `{}`", source, key)
+            }
+            ConfigError::BadValue {
+                source,
+                key,
+                message,
+            } => write!(f, "{}: `{}` {}", source, key, message),
+            ConfigError::Invalid(message) => write!(f, "invalid configuration: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn parse_num<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("expects a number, got `{}`", value))
+}
+
+/// A share per thousand.
+fn parse_ppk(value: &str) -> Result<u64, String> {
+    parse_num(value).and_then(|v| {
+        if v > 1000 {
+            return Err("must be at most 1000".to_string());
+        }
+        Ok(v)
+    })
+}
+
+/// Items of a comma-separated list, with any quotes removed.
+/// `line` up to a `#` that is not inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(|v| v.trim().trim_matches('"'))
+        .filter(|v| !v.is_empty())
+}
+
+/// `name:value` pairs of a list.
+fn pairs<T>(value: &str, parse: impl Fn(&str) -> Option<T>) -> Result<Vec<(String, T)>, String> {
+    list(value)
+        .map(|item| {
+            item.split_once(':')
+                .and_then(|(name, v)| Some((name.to_string(), parse(v)?)))
+                .ok_or_else(|| format!("expects `name:value` items, got `{}`", item))
+        })
+        .collect()
+}
+
+/// The config file named by `--config` in `args`, or else by `DMTO_CONFIG`
+/// in `env`.
+pub fn config_path(
+    env: &[(String, String)],
+    args: &[String],
+) -> Result<Option<PathBuf>, ConfigError> {
+    let mut args = args.iter();
+    let mut flag = None;
+    while let Some(arg) = args.next() {
+        if let Some(path) = arg.strip_prefix("--config=") {
+            flag = Some(path.to_string());
+        } else if arg == "--config" {
+            let path = args.next().ok_or_else(|| ConfigError::Syntax {
+                source: Source::Flag,
+                message: "`--config` needs a value".to_string(),
+            })?;
+            flag = Some(path.clone());
+        }
+    }
+    let path = flag.or_else(|| {
+        env.iter()
+            .find(|(var, _)| var == CONFIG_VAR)
+            .map(|(_, path)| path.clone())
+    });
+    Ok(path.filter(|p| !p.is_empty()).map(PathBuf::from))
+}
+
+impl Config {
+    /// Merges all layers. `env` is usually `std::env::vars()` and `args`
+    /// the arguments after the program name. The file read is `file` if
+    /// given, else the one `config_path` finds in `env` and `args`.
+    pub fn load(
+        file: Option<&Path>,
+        env: impl IntoIterator<Item = (String, String)>,
+        args: &[String],
+    ) -> Result<Self, ConfigError> {
+        let env: Vec<(String, String)> = env.into_iter().collect();
+        let mut config = Config::default();
+        let path = match file {
+            Some(path) => Some(path.to_path_buf()),
+            None => config_path(&env, args)?,
+        };
+        if let Some(path) = path {
+            config.merge_file(&path)?;
+        }
+        config.merge_env(env)?;
+        config.merge_args(args)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Sets `key` from its text form. Lists are comma separated.
+    pub fn set(&mut self, key: &str, value: &str, source: Source) -> Result<(), ConfigError> {
+        let result = match key {
+            "denominations" => value
+                .split(',')
+                .filter(|v| !v.trim().is_empty())
+                .map(parse_num)
+                .collect::<Result<_, _>>()
+                .map(|d| self.denominations = d),
+            "max_order" => parse_num(value).and_then(|order: u32| {
+                if order > Keyset::MAX_ORDER {
+                    return Err(format!("must be at most {}", Keyset::MAX_ORDER));
+                }
+                self.denominations = Keyset::power2(order).denominations();
+                Ok(())
+            }),
+            "input_fee_ppk" => parse_num(value).map(|v| self.input_fee_ppk = v),
+            "max_inputs" => parse_num(value).map(|v| self.max_inputs = v),
+            "max_outputs" => parse_num(value).map(|v| self.max_outputs = v),
+            "canonical_order" => match value.trim() {
+                "true" => Ok(true),
+                "false" => Ok(false),
+                _ => Err(format!("expects `true` or `false`, got `{}`", value)),
+            }
+            .map(|v| self.canonical_order = v),
+            "spend_records" => match value.trim() {
+                "true" => Ok(true),
+                "false" => Ok(false),
+                _ => Err(format!("expects `true` or `false`, got `{}`", value)),
+            }
+            .map(|v| self.spend_records = v),
+            "quote_ttl" => parse_num(value).map(|v| self.quote_ttl = v),
+            "fee_reserve_ppk" => parse_num(value).map(|v| self.fee_reserve_ppk = Some(v)),
+            "fee_reserve_floor" => parse_num(value).map(|v| self.fee_reserve_floor = Some(v)),
+            "melt_attempts" => parse_num(value).and_then(|v| {
+                if v == 0 {
+                    return Err("must be at least 1".to_string());
+                }
+                self.melt_attempts = v;
+                Ok(())
+            }),
+            "invoice_description" => {
+                self.invoice_template.description = value.to_string();
+                Ok(())
+            }
+            "invoice_description_hash" => match value.trim() {
+                "true" => Ok(true),
+                "false" => Ok(false),
+                _ => Err(format!("expects `true` or `false`, got `{}`", value)),
+            }
+            .map(|v| self.invoice_template.description_hash = v),
+            "chaos_storage_latency_ms" => {
+                parse_num(value).map(|v| self.chaos.storage_latency = Duration::from_millis(v))
+            }
+            "chaos_write_failure_ppk" => parse_ppk(value).map(|v| self.chaos.write_failure_ppk = v),
+            "chaos_payment_stall_ms" => {
+                parse_num(value).map(|v| self.chaos.payment_stall = Duration::from_millis(v))
+            }
+            "chaos_payment_stall_ppk" => parse_ppk(value).map(|v| self.chaos.payment_stall_ppk = v),
+            "invoice_expiry" => parse_num(value).map(|v| self.invoice_template.expiry = Some(v)),
+            "max_request_bytes" => parse_num(value).map(|v| self.json_limits.max_bytes = v),
+            "max_json_depth" => parse_num(value).map(|v| self.json_limits.max_depth = v),
+            "unknown_fields" => match value.trim() {
+                "reject" => Ok(UnknownFields::Reject),
+                "ignore" => Ok(UnknownFields::Ignore),
+                _ => Err(format!("expects `reject` or `ignore`, got `{}`", value)),
+            }
+            .map(|v| self.json_limits.unknown_fields = v),
+            "auth_routes" => list(value)
+                .map(|r| Route::from_name(r).ok_or_else(|| format!("unknown route `{}`", r)))
+                .collect::<Result<_, _>>()
+                .map(|r| self.auth_routes = r),
+            "api_keys" => pairs(value, |hash| {
+                (hash.len() == 64 && from_hex(hash).is_some()).then(|| hash.to_string())
+            })
+            .map(|keys| {
+                self.api_keys = keys
+                    .into_iter()
+                    .map(|(subject, hash)| (hash, subject))
+                    .collect()
+            }),
+            "oidc_issuer" => {
+                self.oidc_issuer = value.to_string();
+                Ok(())
+            }
+            "oidc_audience" => {
+                self.oidc_audience = value.to_string();
+                Ok(())
+            }
+            "oidc_jwks_url" => {
+                self.oidc_jwks_url = value.to_string();
+                Ok(())
+            }
+            "accounting" => match value.trim() {
+                "true" => Ok(true),
+                "false" => Ok(false),
+                _ => Err(format!("expects `true` or `false`, got `{}`", value)),
+            }
+            .map(|v| self.accounting = v),
+            "account_window" => {
+                parse_num(value).map(|v| self.account_limits.window = Duration::from_secs(v))
+            }
+            "account_max_minted" => parse_num(value).map(|v| self.account_limits.max_minted = v),
+            "account_max_melted" => parse_num(value).map(|v| self.account_limits.max_melted = v),
+            "oidc_keys" => pairs(value, |hex| PublicKey::from_slice(&from_hex(hex)?).ok())
+                .map(|keys| self.oidc_keys = keys),
+            "data_dir" => {
+                self.data_dir = PathBuf::from(value);
+                Ok(())
+            }
+            "mint_url" => {
+                self.mint_url = value.to_string();
+                Ok(())
+            }
+            _ => {
+                return Err(ConfigError::UnknownKey {
+                    source,
+                    key: key.to_string(),
+                });
+            }
+        };
+        result.map_err(|message| ConfigError::BadValue {
+            source,
+            key: key.to_string(),
+            message,
+        })
+    }
+
+    pub fn merge_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let text = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+
+        for (i, line) in text.lines().enumerate() {
+            let source = Source::File {
+                path: path.to_path_buf(),
+                line: i + 1,
+            };
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let syntax = |message: &str| ConfigError::Syntax {
+                source: source.clone(),
+                message: message.to_string(),
+            };
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| syntax("expected `key = value`"))?;
+            let value = value.trim();
+            let value = if let Some(s) = value.strip_prefix('"') {
+                s.strip_suffix('"')
+                    .ok_or_else(|| syntax("unterminated string"))?
+            } else if let Some(list) = value.strip_prefix('[') {
+                list.strip_suffix(']')
+                    .ok_or_else(|| syntax("unterminated array"))?
+            } else {
+                value
+            };
+            self.set(key.trim(), value, source)?;
+        }
+        Ok(())
+    }
+
+    /// Applies `DMTO_<KEY>` variables; others are ignored.
+    pub fn merge_env(
+        &mut self,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<(), ConfigError> {
+        for (var, value) in env {
+            if var == CONFIG_VAR {
+                continue;
+            }
+            if let Some(key) = var.strip_prefix(ENV_PREFIX) {
+                self.set(&key.to_lowercase(), &value, Source::Env(var.clone()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies `--key value` and `--key=value` flags. Underscores in keys
+    /// may be written as dashes. `--config` is skipped; it names the file
+    /// rather than setting anything.
+    pub fn merge_args(&mut self, args: &[String]) -> Result<(), ConfigError> {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(flag) = arg.strip_prefix("--") else {
+                return Err(ConfigError::Syntax {
+                    source: Source::Flag,
+                    message: format!("unexpected argument `{}`", arg),
+                });
+            };
+            let (key, value) = match flag.split_once('=') {
+                Some((key, value)) => (key, value.to_string()),
+                None => {
+                    let value = args.next().ok_or_else(|| ConfigError::Syntax {
+                        source: Source::Flag,
+                        message: format!("`--{}` needs a value", flag),
+                    })?;
+                    (flag, value.clone())
+                }
+            };
+            if key == CONFIG_FLAG {
+                continue;
+            }
+            self.set(&key.replace('-', "_"), &value, Source::Flag)?;
+        }
+        Ok(())
+    }
+
+    /// The providers and protected routes configured for the mint.
+    pub fn auth_gate(&self) -> AuthGate {
+        let mut gate = AuthGate::new();
+        gate.protected = self.auth_routes.clone();
+        if !self.api_keys.is_empty() {
+            let mut keys = ApiKeyAuth::new();
+            for (hash, subject) in &self.api_keys {
+                keys.add_hash(hash, subject);
+            }
+            gate.providers.push(Arc::new(keys));
+        }
+        if !self.oidc_issuer.is_empty() {
+            let mut oidc = OidcAuth::new(&self.oidc_issuer, &self.oidc_audience);
+            for (kid, key) in &self.oidc_keys {
+                oidc.add_key(kid, *key);
+            }
+            if !self.oidc_jwks_url.is_empty() {
+                oidc.jwks_url = Some(self.oidc_jwks_url.clone());
+            }
+            gate.providers.push(Arc::new(oidc));
+        }
+        gate
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.denominations.is_empty() {
+            return Err(ConfigError::Invalid("no denominations".to_string()));
+        }
+        if self.denominations.contains(&0) {
+            return Err(ConfigError::Invalid("denomination 0".to_string()));
+        }
+        let mut sorted = self.denominations.clone();
+        sorted.sort();
+        sorted.dedup();
+        if sorted.len() != self.denominations.len() {
+            return Err(ConfigError::Invalid("duplicate denominations".to_string()));
+        }
+        if self.max_inputs == 0 || self.max_outputs == 0 {
+            return Err(ConfigError::Invalid(
+                "max_inputs and max_outputs must be positive".to_string(),
+            ));
+        }
+        if self.json_limits.max_bytes == 0 || self.json_limits.max_depth == 0 {
+            return Err(ConfigError::Invalid(
+                "max_request_bytes and max_json_depth must be positive".to_string(),
+            ));
+        }
+        if !self.oidc_issuer.is_empty()
+            && (self.oidc_audience.is_empty()
+                || (self.oidc_keys.is_empty() && self.oidc_jwks_url.is_empty()))
+        {
+            return Err(ConfigError::Invalid(
+                "oidc_issuer needs oidc_audience and oidc_keys or oidc_jwks_url".to_string(),
+            ));
+        }
+        let has_provider = !self.api_keys.is_empty() || !self.oidc_issuer.is_empty();
+        if self.accounting && !has_provider {
+            return Err(ConfigError::Invalid(
+                "accounting needs api_keys or oidc_issuer".to_string(),
+            ));
+        }
+        if !self.auth_routes.is_empty() && !has_provider {
+            return Err(ConfigError::Invalid(
+                "auth_routes set but no api_keys or oidc_issuer".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Mint {
+    pub fn from_config(config: &Config) -> Self {
+        let mut mint = Mint::new(&config.denominations);
+        mint.input_fee_ppk = config.input_fee_ppk;
+        mint.max_inputs = config.max_inputs;
+        mint.max_outputs = config.max_outputs;
+        mint.canonical_order = config.canonical_order;
+        mint.spend_records = config.spend_records;
+        mint.quote_ttl = config.quote_ttl;
+        if config.fee_reserve_ppk.is_some() || config.fee_reserve_floor.is_some() {
+            mint.fee_reserve = Some(FeeReserve {
+                ppk: config.fee_reserve_ppk.unwrap_or(0),
+                floor: config.fee_reserve_floor.unwrap_or(0),
+            });
+        }
+        mint.melt_attempts = config.melt_attempts;
+        mint.invoice_template = config.invoice_template.clone();
+        mint.json_limits = config.json_limits.clone();
+        mint.auth = config.auth_gate();
+        if config.accounting {
+            mint.accounts = Some(Accounts::new(config.account_limits.clone()));
+        }
+        mint
+    }
+}
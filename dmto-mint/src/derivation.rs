@@ -0,0 +1,218 @@
+//! Deterministic keysets from a master seed, and an audit that re-derives
+//! them to catch tampered or corrupted private keys in the live keystore.
+
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+
+use secp256k1::{Keypair, PublicKey, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::mint::{Mint, MintKey, keyset_id};
+
+/// Private key for `value` in keyset number `index`. Hashes with a counter
+/// until the digest is a valid scalar.
+pub fn derive_key(seed: &[u8], index: u32, value: u64) -> SecretKey {
+    (0u32..)
+        .find_map(|counter| {
+            let mut hasher = Sha256::new();
+            hasher.update(b"dmto-keyset");
+            hasher.update(seed);
+            hasher.update(index.to_be_bytes());
+            hasher.update(value.to_be_bytes());
+            hasher.update(counter.to_be_bytes());
+            SecretKey::from_slice(&hasher.finalize()).ok()
+        })
+        .unwrap()
+}
+
+/// The mint's long-term identity key, derived from `seed`.
+pub fn derive_identity(seed: &[u8]) -> Keypair {
+    let mut hasher = Sha256::new();
+    hasher.update(b"dmto-identity");
+    hasher.update(seed);
+    let secret = (0u32..)
+        .find_map(|counter| {
+            let mut hasher = hasher.clone();
+            hasher.update(counter.to_be_bytes());
+            SecretKey::from_slice(&hasher.finalize()).ok()
+        })
+        .unwrap();
+    Keypair::from_secret_key(&Secp256k1::new(), &secret)
+}
+
+pub fn derive_keys(seed: &[u8], index: u32, denoms: &[u64]) -> HashMap<u64, MintKey> {
+    denoms
+        .iter()
+        .map(|&v| (v, MintKey::from_privkey(v, derive_key(seed, index, v))))
+        .collect()
+}
+
+/// One keyset derivation, as recorded in the operator's audit log.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DerivationRecord {
+    pub index: u32,
+    pub keyset_id: String,
+    pub denominations: Vec<u64>,
+    pub timestamp: u64,
+}
+
+/// Append-only record of every keyset derived from the seed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DerivationLog {
+    pub records: Vec<DerivationRecord>,
+}
+
+impl DerivationLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec(self).map_err(io::Error::other)?)
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        serde_json::from_slice(&fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Derives keyset `index` from `seed`, logs it and returns the mint.
+    pub fn derive_mint(&mut self, seed: &[u8], index: u32, denoms: &[u64], now: u64) -> Mint {
+        let mint = Mint::from_keys(derive_keys(seed, index, denoms));
+
+        let mut denominations = denoms.to_vec();
+        denominations.sort();
+        self.records.push(DerivationRecord {
+            index,
+            keyset_id: mint.keyset_id.clone(),
+            denominations,
+            timestamp: now,
+        });
+        mint
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuditFinding {
+    /// Re-deriving the logged keyset gives a different ID than was logged.
+    LogMismatch {
+        index: u32,
+        logged: String,
+        derived: String,
+    },
+    /// The live keyset does not appear in the log.
+    NotInLog {
+        keyset_id: String,
+    },
+    MissingKey {
+        value: u64,
+    },
+    /// A live key the seed does not produce for this keyset.
+    UnexpectedKey {
+        value: u64,
+    },
+    PrivkeyMismatch {
+        value: u64,
+    },
+    /// The stored public key does not belong to the stored private key.
+    PubkeyMismatch {
+        value: u64,
+    },
+    /// The mint's keyset ID does not match its own keys.
+    KeysetIdMismatch {
+        stored: String,
+        computed: String,
+    },
+}
+
+impl fmt::Display for AuditFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditFinding::LogMismatch {
+                index,
+                logged,
+                derived,
+            } => write!(
+                f,
+                "keyset {} logged as {} but derives to {}",
+                index, logged, derived
+            ),
+            AuditFinding::NotInLog { keyset_id } => {
+                write!(f, "keyset {} not in derivation log", keyset_id)
+            }
+            AuditFinding::MissingKey { value } => write!(f, "missing key for {}", value),
+            AuditFinding::UnexpectedKey { value } => write!(f, "unexpected key for {}", value),
+            AuditFinding::PrivkeyMismatch { value } => {
+                write!(f, "private key for {} does not match seed", value)
+            }
+            AuditFinding::PubkeyMismatch { value } => {
+                write!(f, "public key for {} does not match private key", value)
+            }
+            AuditFinding::KeysetIdMismatch { stored, computed } => {
+                write!(f, "keyset id {} should be {}", stored, computed)
+            }
+        }
+    }
+}
+
+impl Mint {
+    /// Re-derives every logged keyset from `seed` and cross-checks the live
+    /// keystore against the matching one. An empty result means the keys are
+    /// intact.
+    pub fn audit_derivation(&self, seed: &[u8], log: &DerivationLog) -> Vec<AuditFinding> {
+        let secp = Secp256k1::new();
+        let mut findings = Vec::new();
+        let mut live = None;
+
+        for record in &log.records {
+            let derived = derive_keys(seed, record.index, &record.denominations);
+            let id = keyset_id(&derived);
+            if id != record.keyset_id {
+                findings.push(AuditFinding::LogMismatch {
+                    index: record.index,
+                    logged: record.keyset_id.clone(),
+                    derived: id.clone(),
+                });
+            }
+            if id == self.keyset_id {
+                live = Some(derived);
+            }
+        }
+
+        let computed = keyset_id(&self.keys);
+        if computed != self.keyset_id {
+            findings.push(AuditFinding::KeysetIdMismatch {
+                stored: self.keyset_id.clone(),
+                computed,
+            });
+        }
+
+        for (&value, key) in &self.keys {
+            if PublicKey::from_secret_key(&secp, &key.privkey) != key.pubkey {
+                findings.push(AuditFinding::PubkeyMismatch { value });
+            }
+        }
+
+        let Some(derived) = live else {
+            findings.push(AuditFinding::NotInLog {
+                keyset_id: self.keyset_id.clone(),
+            });
+            return findings;
+        };
+
+        let mut values: Vec<u64> = derived.keys().chain(self.keys.keys()).copied().collect();
+        values.sort();
+        values.dedup();
+        for value in values {
+            match (derived.get(&value), self.keys.get(&value)) {
+                (Some(_), None) => findings.push(AuditFinding::MissingKey { value }),
+                (None, Some(_)) => findings.push(AuditFinding::UnexpectedKey { value }),
+                (Some(want), Some(have)) if want.privkey != have.privkey => {
+                    findings.push(AuditFinding::PrivkeyMismatch { value })
+                }
+                _ => {}
+            }
+        }
+        findings
+    }
+}
@@ -0,0 +1,143 @@
+//! A private mint inside an application, e.g. the currency of a game
+//! economy, set up in a few lines:
+//!
+//! ```no_run
+//! use dmto_mint::{embedded::EmbeddedMint, wallet::Wallet};
+//!
+//! let mint = EmbeddedMint::builder().data_dir("game-data").build().unwrap();
+//! let mut player = Wallet::new();
+//! mint.issue(&mut player, 500).unwrap();
+//! ```
+//!
+//! It bundles the fake Lightning backend, so the app issues notes itself
+//! instead of taking payments, and keeps its keys and spent set in a
+//! snapshot under `data_dir` (there is no database backend in this crate).
+//! The snapshot is written on `checkpoint` and when the mint is dropped;
+//! spends since the last checkpoint are lost if the process dies, so apps
+//! that move real value should checkpoint after each batch of work.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    error::WalletError, keyset::Keyset, lightning::FakeBackend, mint::Mint, wallet::Wallet,
+};
+
+const SNAPSHOT: &str = "mint.snapshot";
+
+pub struct EmbeddedMintBuilder {
+    denominations: Vec<u64>,
+    input_fee_ppk: u64,
+    quote_ttl: u64,
+    data_dir: Option<PathBuf>,
+}
+
+impl Default for EmbeddedMintBuilder {
+    fn default() -> Self {
+        Self {
+            denominations: Keyset::power2(20).denominations(),
+            input_fee_ppk: 0,
+            quote_ttl: 3600,
+            data_dir: None,
+        }
+    }
+}
+
+impl EmbeddedMintBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only used when the mint is first created; a restored mint keeps the
+    /// denominations of its keys.
+    pub fn denominations(mut self, denominations: &[u64]) -> Self {
+        self.denominations = denominations.to_vec();
+        self
+    }
+
+    pub fn input_fee_ppk(mut self, ppk: u64) -> Self {
+        self.input_fee_ppk = ppk;
+        self
+    }
+
+    pub fn quote_ttl(mut self, seconds: u64) -> Self {
+        self.quote_ttl = seconds;
+        self
+    }
+
+    /// Where the mint is persisted. Without one it lives in memory only.
+    pub fn data_dir(mut self, path: impl AsRef<Path>) -> Self {
+        self.data_dir = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Restores the mint from `data_dir` if it was saved there before,
+    /// otherwise creates it.
+    pub fn build(self) -> io::Result<EmbeddedMint> {
+        let mut mint = match &self.data_dir {
+            Some(dir) if dir.join(SNAPSHOT).exists() => Mint::restore(&dir.join(SNAPSHOT), &[])?,
+            Some(dir) => {
+                fs::create_dir_all(dir)?;
+                Mint::new(&self.denominations)
+            }
+            None => Mint::new(&self.denominations),
+        };
+        let lightning = Arc::new(FakeBackend::new());
+        mint.input_fee_ppk = self.input_fee_ppk;
+        mint.quote_ttl = self.quote_ttl;
+        mint.lightning = Some(lightning.clone());
+
+        let embedded = EmbeddedMint {
+            mint,
+            lightning,
+            data_dir: self.data_dir,
+        };
+        embedded.checkpoint()?;
+        Ok(embedded)
+    }
+}
+
+pub struct EmbeddedMint {
+    mint: Mint,
+    /// Settles the invoices behind mint quotes; `issue` does this for the
+    /// app.
+    pub lightning: Arc<FakeBackend>,
+    data_dir: Option<PathBuf>,
+}
+
+impl EmbeddedMint {
+    pub fn builder() -> EmbeddedMintBuilder {
+        EmbeddedMintBuilder::new()
+    }
+
+    /// The mint, to pass to wallet calls.
+    pub fn mint(&self) -> &Mint {
+        &self.mint
+    }
+
+    /// Issues `amount` into `wallet` without a payment. Returns the amount
+    /// issued.
+    pub fn issue(&self, wallet: &mut Wallet, amount: u64) -> Result<u64, WalletError> {
+        let quote = wallet.request_mint(&self.mint, amount)?;
+        self.lightning.settle(&quote.request);
+        wallet.resume_quote(&self.mint, &quote.id)
+    }
+
+    /// Writes the snapshot to `data_dir`, replacing the previous one only
+    /// once the new one is complete. Does nothing for in-memory mints.
+    pub fn checkpoint(&self) -> io::Result<()> {
+        let Some(dir) = &self.data_dir else {
+            return Ok(());
+        };
+        self.mint.snapshot(&dir.join(SNAPSHOT)).map(|_| ())
+    }
+}
+
+impl Drop for EmbeddedMint {
+    fn drop(&mut self) {
+        let _ = self.checkpoint();
+    }
+}
@@ -0,0 +1,330 @@
+//! Encryption of sensitive quote fields at rest: the invoices and addresses
+//! of mint and melt quotes and the preimages of paid invoices and held
+//! issuances. Whatever stores them sees only sealed strings, so a copy of
+//! the database alone reveals no payments.
+//!
+//! A field is sealed as `enc1:<key id>:<hex>` with XChaCha20-Poly1305 under
+//! a random nonce, with keys derived from their secrets by HKDF-SHA256. The key ID and a context naming the quote and field are
+//! the associated data, so sealed values cannot be swapped between records
+//! or keys. Keys are rotated by adding a new active key: fields sealed
+//! under older keys still open, and `reseal` moves them to the active one,
+//! after which the old key can be retired.
+//!
+//! Values that were never sealed are refused, so a store cannot be fed
+//! plaintext in place of sealed fields. To move a store written before
+//! encryption was turned on, `allow_plaintext` lets them open as they are
+//! until `reseal` has sealed them all.
+//!
+//! The mint seals its quote file (`save_quotes`), its hold store
+//! (`FileHoldStore::with_keys`) and its snapshots (`Mint::field_keys`).
+
+use std::{collections::BTreeMap, fmt, fs, io, path::Path};
+
+use chacha20poly1305::{
+    KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, Payload},
+};
+use hkdf::Hkdf;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    codec::{from_hex, to_hex},
+    issue::MintQuote,
+    melt::MeltQuote,
+    mint::Mint,
+};
+
+const PREFIX: &str = "enc1:";
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldError {
+    /// Sealed under a key that is not in the keyring.
+    UnknownKey(u32),
+    /// Wrong key, wrong context, or the value was altered.
+    BadTag,
+    /// Not sealed, and plaintext is not allowed.
+    NotSealed,
+    Malformed,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldError::UnknownKey(id) => write!(f, "field sealed under unknown key {}", id),
+            FieldError::BadTag => write!(f, "sealed field failed authentication"),
+            FieldError::NotSealed => write!(f, "field is not sealed"),
+            FieldError::Malformed => write!(f, "malformed sealed field"),
+        }
+    }
+}
+
+impl std::error::Error for FieldError {}
+
+impl From<FieldError> for io::Error {
+    fn from(err: FieldError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+#[derive(Clone)]
+struct FieldKey {
+    cipher: XChaCha20Poly1305,
+}
+
+impl FieldKey {
+    fn new(secret: &[u8]) -> Self {
+        let mut key = [0u8; 32];
+        Hkdf::<Sha256>::new(None, secret)
+            .expand(b"dmto-field-key", &mut key)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        Self {
+            cipher: XChaCha20Poly1305::new(&key.into()),
+        }
+    }
+}
+
+/// Associated data binding a sealed value to its key and context.
+fn aad(id: u32, context: &str) -> Vec<u8> {
+    let mut aad = id.to_be_bytes().to_vec();
+    aad.extend_from_slice(&(context.len() as u64).to_be_bytes());
+    aad.extend_from_slice(context.as_bytes());
+    aad
+}
+
+/// The keys fields are sealed with, by ID. New fields are sealed with the
+/// active key.
+#[derive(Clone)]
+pub struct FieldKeys {
+    keys: BTreeMap<u32, FieldKey>,
+    active: u32,
+    plaintext: bool,
+}
+
+impl FieldKeys {
+    /// A keyring whose only key, ID 1, is derived from `secret`.
+    pub fn new(secret: &[u8]) -> Self {
+        Self::with_active(1, secret)
+    }
+
+    /// A keyring whose only key, derived from `secret`, has ID `id`: the
+    /// active key after earlier rotations.
+    pub fn with_active(id: u32, secret: &[u8]) -> Self {
+        Self {
+            keys: BTreeMap::from([(id, FieldKey::new(secret))]),
+            active: id,
+            plaintext: false,
+        }
+    }
+
+    /// Whether values that were never sealed open as they are. Off by
+    /// default; turn it on only while migrating a store written before
+    /// encryption was turned on.
+    pub fn allow_plaintext(&mut self, allow: bool) {
+        self.plaintext = allow;
+    }
+
+    /// Adds an older key, so fields sealed under it still open.
+    pub fn add(&mut self, id: u32, secret: &[u8]) {
+        if id != self.active {
+            self.keys.insert(id, FieldKey::new(secret));
+        }
+    }
+
+    /// Adds a key derived from `secret` and makes it the active one,
+    /// returning its ID.
+    pub fn rotate(&mut self, secret: &[u8]) -> u32 {
+        let id = self.keys.keys().last().map_or(1, |id| id + 1);
+        self.keys.insert(id, FieldKey::new(secret));
+        self.active = id;
+        id
+    }
+
+    /// Drops key `id`, once nothing sealed under it is left. The active key
+    /// cannot be retired.
+    pub fn retire(&mut self, id: u32) -> bool {
+        id != self.active && self.keys.remove(&id).is_some()
+    }
+
+    pub fn active(&self) -> u32 {
+        self.active
+    }
+
+    pub fn is_sealed(value: &str) -> bool {
+        value.starts_with(PREFIX)
+    }
+
+    /// ID of the key `value` is sealed under, if it is sealed.
+    pub fn key_of(value: &str) -> Option<u32> {
+        value.strip_prefix(PREFIX)?.split_once(':')?.0.parse().ok()
+    }
+
+    pub fn seal(&self, context: &str, plaintext: &str) -> String {
+        let key = &self.keys[&self.active];
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let aad = aad(self.active, context);
+        let sealed = key
+            .cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext.as_bytes(),
+                    aad: &aad,
+                },
+            )
+            .expect("plaintext within the cipher's limit");
+
+        let mut out = nonce.to_vec();
+        out.extend_from_slice(&sealed);
+        format!("{}{}:{}", PREFIX, self.active, to_hex(&out))
+    }
+
+    pub fn open(&self, context: &str, value: &str) -> Result<String, FieldError> {
+        let Some(sealed) = value.strip_prefix(PREFIX) else {
+            if !self.plaintext {
+                return Err(FieldError::NotSealed);
+            }
+            return Ok(value.to_string());
+        };
+        let (id, hex) = sealed.split_once(':').ok_or(FieldError::Malformed)?;
+        let id: u32 = id.parse().map_err(|_| FieldError::Malformed)?;
+        let key = self.keys.get(&id).ok_or(FieldError::UnknownKey(id))?;
+        let bytes = from_hex(hex).ok_or(FieldError::Malformed)?;
+        if bytes.len() < NONCE_LEN + TAG_LEN {
+            return Err(FieldError::Malformed);
+        }
+        let (nonce, sealed) = bytes.split_at(NONCE_LEN);
+        let aad = aad(id, context);
+        let plaintext = key
+            .cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: sealed,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| FieldError::BadTag)?;
+        String::from_utf8(plaintext).map_err(|_| FieldError::Malformed)
+    }
+
+    /// Seals `value` under the active key, opening it first if it was
+    /// sealed under another. Plaintext is sealed only if allowed.
+    pub fn reseal(&self, context: &str, value: &str) -> Result<String, FieldError> {
+        if Self::key_of(value) == Some(self.active) {
+            return Ok(value.to_string());
+        }
+        Ok(self.seal(context, &self.open(context, value)?))
+    }
+
+    fn seal_opt(&self, context: &str, value: &Option<String>) -> Option<String> {
+        value.as_ref().map(|v| self.seal(context, v))
+    }
+
+    fn open_opt(
+        &self,
+        context: &str,
+        value: &Option<String>,
+    ) -> Result<Option<String>, FieldError> {
+        value.as_ref().map(|v| self.open(context, v)).transpose()
+    }
+}
+
+fn context(id: &str, field: &str) -> String {
+    format!("{}/{}", id, field)
+}
+
+/// A record with fields worth sealing at rest.
+pub trait SealFields: Sized {
+    /// A copy with the sensitive fields sealed.
+    fn sealed(&self, keys: &FieldKeys) -> Self;
+    fn opened(&self, keys: &FieldKeys) -> Result<Self, FieldError>;
+}
+
+impl SealFields for MintQuote {
+    /// A copy with the invoice or deposit address sealed.
+    fn sealed(&self, keys: &FieldKeys) -> Self {
+        Self {
+            request: keys.seal(&context(&self.id, "request"), &self.request),
+            ..self.clone()
+        }
+    }
+
+    fn opened(&self, keys: &FieldKeys) -> Result<Self, FieldError> {
+        Ok(Self {
+            request: keys.open(&context(&self.id, "request"), &self.request)?,
+            ..self.clone()
+        })
+    }
+}
+
+impl SealFields for MeltQuote {
+    /// A copy with the payee's invoice or address, the preimage and the
+    /// payout transaction sealed.
+    fn sealed(&self, keys: &FieldKeys) -> Self {
+        Self {
+            request: keys.seal(&context(&self.id, "request"), &self.request),
+            preimage: keys.seal_opt(&context(&self.id, "preimage"), &self.preimage),
+            txid: keys.seal_opt(&context(&self.id, "txid"), &self.txid),
+            ..self.clone()
+        }
+    }
+
+    fn opened(&self, keys: &FieldKeys) -> Result<Self, FieldError> {
+        Ok(Self {
+            request: keys.open(&context(&self.id, "request"), &self.request)?,
+            preimage: keys.open_opt(&context(&self.id, "preimage"), &self.preimage)?,
+            txid: keys.open_opt(&context(&self.id, "txid"), &self.txid)?,
+            ..self.clone()
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct QuoteFile {
+    mint_quotes: Vec<MintQuote>,
+    melt_quotes: Vec<MeltQuote>,
+}
+
+impl Mint {
+    /// Writes every quote to `path` with its sensitive fields sealed under
+    /// the active key. Saving after a `rotate` moves all of them to it.
+    pub fn save_quotes(&self, path: &Path, keys: &FieldKeys) -> io::Result<()> {
+        let file = QuoteFile {
+            mint_quotes: self.mint_quotes.iter().map(|q| q.sealed(keys)).collect(),
+            melt_quotes: self.melt_quotes.iter().map(|q| q.sealed(keys)).collect(),
+        };
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(&file)?)?;
+        fs::rename(tmp, path)
+    }
+
+    /// Loads quotes saved with `save_quotes` under any key in `keys`,
+    /// replacing quotes with the same ID. Returns how many were loaded.
+    pub fn load_quotes(&self, path: &Path, keys: &FieldKeys) -> io::Result<usize> {
+        let file: QuoteFile = serde_json::from_slice(&fs::read(path)?)?;
+        let mint_quotes = file
+            .mint_quotes
+            .iter()
+            .map(|q| q.opened(keys))
+            .collect::<Result<Vec<_>, _>>()?;
+        let melt_quotes = file
+            .melt_quotes
+            .iter()
+            .map(|q| q.opened(keys))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let count = mint_quotes.len() + melt_quotes.len();
+        for quote in mint_quotes {
+            self.mint_quotes.insert(quote.id.clone(), quote);
+        }
+        for quote in melt_quotes {
+            self.melt_quotes.insert(quote.id.clone(), quote);
+        }
+        Ok(count)
+    }
+}
@@ -0,0 +1,32 @@
+//! Signing proof-state attestations. See `dmto_wallet::freshness` for the
+//! format.
+
+pub use dmto_wallet::freshness::*;
+
+use secp256k1::PublicKey;
+
+use crate::{mint::Mint, signing};
+
+impl Mint {
+    /// Signs the current state of each `Y`. Returns `None` unless
+    /// `freshness_attestations` is enabled.
+    pub fn attest(&self, ys: &[PublicKey]) -> Option<Vec<FreshnessAttestation>> {
+        if !self.freshness_attestations {
+            return None;
+        }
+
+        let timestamp = self.clock.now();
+        let states = self.check_state(ys);
+        Some(
+            ys.iter()
+                .zip(states)
+                .map(|(y, state)| FreshnessAttestation {
+                    y: *y,
+                    state,
+                    timestamp,
+                    signature: signing::sign(&self.identity, &message(y, state, timestamp)),
+                })
+                .collect(),
+        )
+    }
+}
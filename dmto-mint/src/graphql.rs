@@ -0,0 +1,557 @@
+//! Read-only GraphQL endpoint for dashboards and explorers, so one request
+//! can fetch keysets with their fees, quote states and statistics that
+//! would otherwise take a REST call each.
+//!
+//! Schema:
+//!
+//! ```graphql
+//! type Query {
+//!   info: Info!
+//!   keysets: [Keyset!]!
+//!   keyset(id: String!): Keyset
+//!   mintQuote(id: String!): MintQuote
+//!   meltQuote(id: String!): MeltQuote
+//!   proofStates(ys: [String!]!): [ProofState!]!
+//!   anonymitySets: [AnonymitySet!]!
+//!   stats: Stats!
+//!   announcements: [Announcement!]!
+//! }
+//! ```
+//!
+//! The object types carry the fields of the REST responses in camel case
+//! (`MintInfo`, `MintQuote`, `DashboardStats`, ...). `Keyset` adds
+//! `idVersion`, `active` and `inputFeePpk`, and `Stats` totals over all
+//! days. Enums are their REST names as strings.
+//!
+//! Queries run on async-graphql, with its depth and complexity limits set.
+//! The schema is read-only: there are no mutations or subscriptions.
+//! `GraphqlServer` serves `POST /graphql` over plain HTTP/1.1 on any
+//! stream, one request per connection.
+
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Read, Write},
+    str::FromStr,
+};
+
+use async_graphql::{
+    EmptyMutation, EmptySubscription, Json, Object, Schema, SimpleObject, Variables,
+};
+use futures::executor::block_on;
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+    announce::{Announcement as MintAnnouncement, NoticeKind},
+    anonymity::AnonymitySet as MintAnonymitySet,
+    api::{KeysetKeys, MintTrait},
+    error::MintError,
+    issue::MintQuote as MintMintQuote,
+    melt::MeltQuote as MintMeltQuote,
+    mint::MintInfo,
+    secret::SecretPolicy as MintSecretPolicy,
+    stats::{DashboardStats, DayStats as MintDayStats},
+};
+
+/// Longest query text accepted.
+pub const MAX_QUERY_LEN: usize = 8 * 1024;
+/// Deepest nesting of selections in one query.
+pub const MAX_DEPTH: usize = 8;
+/// Most fields in one query, counted over every selection.
+pub const MAX_COMPLEXITY: usize = 256;
+/// Most `Y`s in one `proofStates`.
+pub const MAX_YS: usize = 1000;
+/// Largest HTTP request body accepted.
+pub const MAX_BODY: usize = 64 * 1024;
+
+pub type MintSchema<M> = Schema<Query<M>, EmptyMutation, EmptySubscription>;
+
+/// The schema over `mint`, with the limits above.
+pub fn schema<M: MintTrait + Send + Sync + 'static>(mint: M) -> MintSchema<M> {
+    Schema::build(Query(mint), EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// A GraphQL-over-HTTP request body.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphqlRequest {
+    pub query: String,
+    #[serde(default)]
+    pub variables: Value,
+    #[serde(default)]
+    pub operation_name: Option<String>,
+}
+
+impl GraphqlRequest {
+    pub fn new(query: &str) -> Self {
+        Self {
+            query: query.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn variables(mut self, variables: Value) -> Self {
+        self.variables = variables;
+        self
+    }
+}
+
+/// The REST name of a unit enum, e.g. `"Unpaid"`.
+fn name(value: impl Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::String(name)) => name,
+        other => other.map(|v| v.to_string()).unwrap_or_default(),
+    }
+}
+
+fn int(value: i128) -> i64 {
+    value.clamp(i64::MIN.into(), i64::MAX.into()) as i64
+}
+
+#[derive(SimpleObject)]
+pub struct Info {
+    version: String,
+    keyset_id: String,
+    keyset_id_version: String,
+    denominations: Vec<u64>,
+    max_order: Option<u32>,
+    accepted_kinds: Vec<String>,
+    input_fee_ppk: u64,
+    fee_rounding: String,
+    max_inputs: usize,
+    max_outputs: usize,
+    secret_policy: SecretPolicy,
+    max_restore_batch: usize,
+}
+
+impl From<MintInfo> for Info {
+    fn from(info: MintInfo) -> Self {
+        Self {
+            version: info.version,
+            keyset_id: info.keyset_id,
+            keyset_id_version: name(info.keyset_id_version),
+            denominations: info.denominations,
+            max_order: info.max_order,
+            accepted_kinds: info.accepted_kinds.iter().map(name).collect(),
+            input_fee_ppk: info.input_fee_ppk,
+            fee_rounding: name(info.fee_rounding),
+            max_inputs: info.max_inputs,
+            max_outputs: info.max_outputs,
+            secret_policy: info.secret_policy.into(),
+            max_restore_batch: info.max_restore_batch,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct SecretPolicy {
+    format: String,
+    min_len: usize,
+    max_len: usize,
+    allow_well_known: bool,
+}
+
+impl From<MintSecretPolicy> for SecretPolicy {
+    fn from(policy: MintSecretPolicy) -> Self {
+        Self {
+            format: name(policy.format),
+            min_len: policy.min_len,
+            max_len: policy.max_len,
+            allow_well_known: policy.allow_well_known,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Keyset {
+    id: String,
+    id_version: String,
+    active: bool,
+    input_fee_ppk: u64,
+    keys: Vec<Key>,
+}
+
+impl Keyset {
+    fn new(keyset: KeysetKeys, info: &MintInfo) -> Self {
+        Self {
+            active: keyset.keyset_id == info.keyset_id,
+            id: keyset.keyset_id,
+            id_version: name(keyset.id_version),
+            input_fee_ppk: info.input_fee_ppk,
+            keys: keyset
+                .keys
+                .iter()
+                .map(|(amount, pubkey)| Key {
+                    amount: *amount,
+                    pubkey: pubkey.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Key {
+    amount: u64,
+    pubkey: String,
+}
+
+#[derive(SimpleObject)]
+pub struct MintQuote {
+    id: String,
+    request: String,
+    amount: u64,
+    state: String,
+    expiry: u64,
+}
+
+impl From<MintMintQuote> for MintQuote {
+    fn from(quote: MintMintQuote) -> Self {
+        Self {
+            id: quote.id,
+            request: quote.request,
+            amount: quote.amount,
+            state: name(quote.state),
+            expiry: quote.expiry,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct MeltQuote {
+    id: String,
+    request: String,
+    amount: u64,
+    fee_reserve: u64,
+    state: String,
+    expiry: u64,
+    preimage: Option<String>,
+    fee_paid: u64,
+    fee_rate: Option<u64>,
+    txid: Option<String>,
+    confirmations: u32,
+    internal: bool,
+    amountless: bool,
+}
+
+impl From<MintMeltQuote> for MeltQuote {
+    fn from(quote: MintMeltQuote) -> Self {
+        Self {
+            id: quote.id,
+            request: quote.request,
+            amount: quote.amount,
+            fee_reserve: quote.fee_reserve,
+            state: name(quote.state),
+            expiry: quote.expiry,
+            preimage: quote.preimage,
+            fee_paid: quote.fee_paid,
+            fee_rate: quote.fee_rate,
+            txid: quote.txid,
+            confirmations: quote.confirmations,
+            internal: quote.internal,
+            amountless: quote.amountless,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct ProofState {
+    y: String,
+    state: String,
+}
+
+#[derive(SimpleObject)]
+pub struct AnonymitySet {
+    keyset_id: String,
+    value: u64,
+    unspent: u64,
+}
+
+impl From<MintAnonymitySet> for AnonymitySet {
+    fn from(set: MintAnonymitySet) -> Self {
+        Self {
+            keyset_id: set.keyset_id,
+            value: set.value,
+            unspent: set.unspent,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Stats {
+    active_keysets: Vec<String>,
+    days: Vec<DayStats>,
+    fees_collected: u64,
+    outstanding: i64,
+    journal_seq: u64,
+    total_issued: u64,
+    total_redeemed: u64,
+    notes_spent: u64,
+}
+
+impl From<DashboardStats> for Stats {
+    fn from(stats: DashboardStats) -> Self {
+        Self {
+            total_issued: stats.days.iter().map(|d| d.issued).sum(),
+            total_redeemed: stats.days.iter().map(|d| d.redeemed).sum(),
+            notes_spent: stats.days.iter().map(|d| d.notes_spent).sum(),
+            active_keysets: stats.active_keysets,
+            days: stats.days.into_iter().map(DayStats::from).collect(),
+            fees_collected: stats.fees_collected,
+            outstanding: int(stats.outstanding),
+            journal_seq: stats.journal_seq,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct DayStats {
+    day: u64,
+    issued: u64,
+    redeemed: u64,
+    fees: u64,
+    notes_spent: u64,
+    outstanding: i64,
+}
+
+impl From<MintDayStats> for DayStats {
+    fn from(day: MintDayStats) -> Self {
+        Self {
+            day: day.day,
+            issued: day.issued,
+            redeemed: day.redeemed,
+            fees: day.fees,
+            notes_spent: day.notes_spent,
+            outstanding: int(day.outstanding),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Announcement {
+    id: String,
+    /// The notice kind as in the REST response, e.g. `"Motd"` or
+    /// `{"Maintenance": {"starts": ..., "ends": ...}}`.
+    kind: Json<NoticeKind>,
+    severity: String,
+    title: String,
+    body: String,
+    published_at: u64,
+    expires_at: u64,
+    signature: String,
+}
+
+impl From<MintAnnouncement> for Announcement {
+    fn from(announcement: MintAnnouncement) -> Self {
+        Self {
+            id: announcement.id,
+            kind: Json(announcement.kind),
+            severity: name(announcement.severity),
+            title: announcement.title,
+            body: announcement.body,
+            published_at: announcement.published_at,
+            expires_at: announcement.expires_at,
+            signature: announcement.signature.to_string(),
+        }
+    }
+}
+
+/// Turns "no such quote" into null, as GraphQL does for missing objects.
+fn or_null<T, U: From<T>>(result: Result<T, MintError>) -> Result<Option<U>, MintError> {
+    match result {
+        Ok(value) => Ok(Some(value.into())),
+        Err(MintError::QuoteUnknown(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// The query root, resolving every field against `M`.
+pub struct Query<M>(pub M);
+
+#[Object(name = "Query")]
+impl<M: MintTrait + Send + Sync + 'static> Query<M> {
+    async fn info(&self) -> async_graphql::Result<Info> {
+        Ok(self.0.info()?.into())
+    }
+
+    async fn keysets(&self) -> async_graphql::Result<Vec<Keyset>> {
+        let info = self.0.info()?;
+        Ok(self
+            .0
+            .keysets()?
+            .into_iter()
+            .map(|keyset| Keyset::new(keyset, &info))
+            .collect())
+    }
+
+    async fn keyset(&self, id: String) -> async_graphql::Result<Option<Keyset>> {
+        let info = self.0.info()?;
+        Ok(self
+            .0
+            .keysets()?
+            .into_iter()
+            .find(|keyset| keyset.keyset_id == id)
+            .map(|keyset| Keyset::new(keyset, &info)))
+    }
+
+    async fn mint_quote(&self, id: String) -> async_graphql::Result<Option<MintQuote>> {
+        Ok(or_null(self.0.get_quote(&id))?)
+    }
+
+    async fn melt_quote(&self, id: String) -> async_graphql::Result<Option<MeltQuote>> {
+        Ok(or_null(self.0.melt_quote_state(&id))?)
+    }
+
+    async fn proof_states(&self, ys: Vec<String>) -> async_graphql::Result<Vec<ProofState>> {
+        if ys.len() > MAX_YS {
+            return Err(format!("at most {} ys per query", MAX_YS).into());
+        }
+        let points = ys
+            .iter()
+            .map(|y| PublicKey::from_str(y).ok())
+            .collect::<Option<Vec<_>>>()
+            .ok_or("ys must be a list of hex points")?;
+        let states = self.0.check_state(&points)?;
+        Ok(points
+            .iter()
+            .zip(states)
+            .map(|(y, state)| ProofState {
+                y: y.to_string(),
+                state: name(state),
+            })
+            .collect())
+    }
+
+    async fn anonymity_sets(&self) -> async_graphql::Result<Vec<AnonymitySet>> {
+        Ok(self
+            .0
+            .anonymity_sets()?
+            .into_iter()
+            .map(AnonymitySet::from)
+            .collect())
+    }
+
+    async fn stats(&self) -> async_graphql::Result<Stats> {
+        Ok(self.0.dashboard_stats()?.into())
+    }
+
+    async fn announcements(&self) -> async_graphql::Result<Vec<Announcement>> {
+        Ok(self
+            .0
+            .announcements()?
+            .into_iter()
+            .map(Announcement::from)
+            .collect())
+    }
+}
+
+fn error_response(message: impl fmt::Display) -> Value {
+    json!({ "errors": [{ "message": message.to_string() }] })
+}
+
+/// Runs a query against `schema`, returning the GraphQL response: `data`,
+/// plus `errors` for fields that failed. A query that does not parse or
+/// validate gets null `data`.
+pub fn execute<M: MintTrait + Send + Sync + 'static>(
+    schema: &MintSchema<M>,
+    request: &GraphqlRequest,
+) -> Value {
+    if request.query.len() > MAX_QUERY_LEN {
+        return error_response(format!("query longer than {} bytes", MAX_QUERY_LEN));
+    }
+    let mut query = async_graphql::Request::new(request.query.as_str())
+        .variables(Variables::from_json(request.variables.clone()));
+    if let Some(operation) = &request.operation_name {
+        query = query.operation_name(operation.as_str());
+    }
+    let response = block_on(schema.execute(query));
+    serde_json::to_value(response).unwrap_or_else(error_response)
+}
+
+/// Serves `execute` over HTTP: `POST /graphql` with a JSON
+/// `GraphqlRequest` body.
+pub struct GraphqlServer<M> {
+    pub schema: MintSchema<M>,
+}
+
+impl<M: MintTrait + Send + Sync + 'static> GraphqlServer<M> {
+    pub fn new(mint: M) -> Self {
+        Self {
+            schema: schema(mint),
+        }
+    }
+
+    /// Handles one request on `stream` and closes it.
+    pub fn serve_stream(&self, stream: impl Read + Write) -> io::Result<()> {
+        let mut reader = BufReader::new(stream);
+        let (status, body) = match self.read_request(&mut reader) {
+            Ok(request) => ("200 OK", execute(&self.schema, &request)),
+            Err((status, message)) => (status, error_response(message)),
+        };
+        let body = body.to_string();
+        let stream = reader.get_mut();
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+
+    fn read_request(
+        &self,
+        reader: &mut impl BufRead,
+    ) -> Result<GraphqlRequest, (&'static str, String)> {
+        let bad = |e: &dyn fmt::Display| ("400 Bad Request", e.to_string());
+        let mut head = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = (&mut *reader)
+                .take(MAX_BODY as u64)
+                .read_line(&mut line)
+                .map_err(|e| bad(&e))?;
+            if read == 0 || line == "\r\n" || line == "\n" {
+                break;
+            }
+            if head.len() >= 100 {
+                return Err((
+                    "431 Request Header Fields Too Large",
+                    "too many headers".into(),
+                ));
+            }
+            head.push(line.trim_end().to_string());
+        }
+
+        let mut request_line = head.first().map_or("", |l| l.as_str()).split_whitespace();
+        let (method, path) = (request_line.next(), request_line.next());
+        if path.map(|p| p.split('?').next()) != Some(Some("/graphql")) {
+            return Err(("404 Not Found", "only /graphql is served".into()));
+        }
+        if method != Some("POST") {
+            return Err(("405 Method Not Allowed", "use POST".into()));
+        }
+        let length = head
+            .iter()
+            .skip(1)
+            .filter_map(|h| h.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+            .ok_or(("411 Length Required", "Content-Length is required".into()))?;
+        if length > MAX_BODY {
+            return Err((
+                "413 Payload Too Large",
+                format!("body over {} bytes", MAX_BODY),
+            ));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).map_err(|e| bad(&e))?;
+        serde_json::from_slice(&body).map_err(|e| bad(&e))
+    }
+}
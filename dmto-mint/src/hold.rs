@@ -0,0 +1,272 @@
+//! Issuing against hold invoices, so a paid invoice cannot be lost to a
+//! crash between the payment and the notes.
+//!
+//! With a `HoldStore` set on the mint, mint quotes are hold invoices for a
+//! preimage only the mint knows. A payment to one is held by the Lightning
+//! node rather than settled. When the wallet asks for its notes, the mint
+//! signs them and writes the signatures and the preimage to the store; only
+//! then does it settle the payment and hand the signatures back.
+//!
+//! A crash before the write leaves the payment unsettled, and the payer is
+//! refunded when it times out. A crash after it leaves a record that
+//! `recover_holds` settles on restart. The signatures go back into the
+//! restore index, so the wallet can collect its notes with `restore`.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+};
+
+use rand::RngCore;
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    codec::{from_hex, to_hex},
+    error::MintError,
+    fieldcrypt::FieldKeys,
+    lightning::InvoiceOptions,
+    mint::Mint,
+};
+
+/// Signatures issued against a held payment that is not yet settled.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeldIssuance {
+    pub quote_id: String,
+    /// The keyset the signatures were made with, which may have been
+    /// rotated out by the time the record is recovered.
+    pub keyset_id: String,
+    pub preimage: String,
+    pub outputs: Vec<(u64, PublicKey)>,
+    pub signatures: Vec<PublicKey>,
+}
+
+pub trait HoldStore: Send + Sync {
+    /// Returns only once `record` would survive a crash.
+    fn persist(&self, record: &HeldIssuance) -> io::Result<()>;
+    fn remove(&self, quote_id: &str) -> io::Result<()>;
+    /// Records persisted and not yet removed.
+    fn pending(&self) -> io::Result<Vec<HeldIssuance>>;
+}
+
+/// One JSON file per record in a directory, synced before it is renamed
+/// into place. With keys, preimages are sealed (see `fieldcrypt`).
+pub struct FileHoldStore {
+    dir: PathBuf,
+    keys: RwLock<Option<FieldKeys>>,
+}
+
+impl FileHoldStore {
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            keys: RwLock::new(None),
+        })
+    }
+
+    /// Seals the preimages of records written from now on with `keys`.
+    pub fn with_keys(self, keys: FieldKeys) -> Self {
+        *self.keys.write().unwrap() = Some(keys);
+        self
+    }
+
+    /// Moves every record to a new key derived from `secret` and retires
+    /// the old ones, returning the new key's ID.
+    pub fn rotate(&self, secret: &[u8]) -> io::Result<u32> {
+        let mut guard = self.keys.write().unwrap();
+        let mut keys = match guard.clone() {
+            Some(mut keys) => {
+                keys.rotate(secret);
+                keys
+            }
+            None => FieldKeys::new(secret),
+        };
+        for mut record in self.read_all(guard.as_ref())? {
+            record.preimage = keys.seal(&preimage_context(&record.quote_id), &record.preimage);
+            self.write(&record)?;
+        }
+        let id = keys.active();
+        for old in 1..id {
+            keys.retire(old);
+        }
+        *guard = Some(keys);
+        Ok(id)
+    }
+
+    fn path(&self, quote_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.hold", quote_id))
+    }
+
+    fn write(&self, record: &HeldIssuance) -> io::Result<()> {
+        let tmp = self.dir.join(format!("{}.tmp", record.quote_id));
+        let file = fs::File::create(&tmp)?;
+        serde_json::to_writer(&file, record)?;
+        file.sync_all()?;
+        fs::rename(tmp, self.path(&record.quote_id))?;
+        fs::File::open(&self.dir)?.sync_all()
+    }
+
+    fn read_all(&self, keys: Option<&FieldKeys>) -> io::Result<Vec<HeldIssuance>> {
+        let mut records = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "hold") {
+                let mut record: HeldIssuance = serde_json::from_slice(&fs::read(path)?)?;
+                if let Some(keys) = keys {
+                    record.preimage =
+                        keys.open(&preimage_context(&record.quote_id), &record.preimage)?;
+                }
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+}
+
+pub(crate) fn preimage_context(quote_id: &str) -> String {
+    format!("{}/hold-preimage", quote_id)
+}
+
+impl HoldStore for FileHoldStore {
+    fn persist(&self, record: &HeldIssuance) -> io::Result<()> {
+        match &*self.keys.read().unwrap() {
+            Some(keys) => self.write(&HeldIssuance {
+                preimage: keys.seal(&preimage_context(&record.quote_id), &record.preimage),
+                ..record.clone()
+            }),
+            None => self.write(record),
+        }
+    }
+
+    fn remove(&self, quote_id: &str) -> io::Result<()> {
+        fs::remove_file(self.path(quote_id))
+    }
+
+    fn pending(&self) -> io::Result<Vec<HeldIssuance>> {
+        self.read_all(self.keys.read().unwrap().as_ref())
+    }
+}
+
+/// Records in memory, for tests and integration environments. Nothing
+/// survives a restart.
+#[derive(Default)]
+pub struct MemoryHoldStore {
+    records: Mutex<BTreeMap<String, HeldIssuance>>,
+}
+
+impl MemoryHoldStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl HoldStore for MemoryHoldStore {
+    fn persist(&self, record: &HeldIssuance) -> io::Result<()> {
+        self.records
+            .lock()
+            .unwrap()
+            .insert(record.quote_id.clone(), record.clone());
+        Ok(())
+    }
+
+    fn remove(&self, quote_id: &str) -> io::Result<()> {
+        self.records.lock().unwrap().remove(quote_id);
+        Ok(())
+    }
+
+    fn pending(&self) -> io::Result<Vec<HeldIssuance>> {
+        Ok(self.records.lock().unwrap().values().cloned().collect())
+    }
+}
+
+impl Mint {
+    /// Creates a hold invoice for a new mint quote, keeping its preimage.
+    pub(crate) fn create_hold_invoice(
+        &self,
+        id: &str,
+        amount: u64,
+        options: &InvoiceOptions,
+    ) -> Result<String, MintError> {
+        let backend = self
+            .lightning
+            .as_ref()
+            .ok_or_else(|| MintError::PaymentFailed("no lightning backend".to_string()))?;
+        let mut preimage = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut preimage);
+        let request = backend
+            .create_hold_invoice(amount, &to_hex(&Sha256::digest(preimage)), options)
+            .map_err(|e| MintError::PaymentFailed(e.to_string()))?;
+        self.hold_preimages
+            .insert(id.to_string(), to_hex(&preimage));
+        Ok(request)
+    }
+
+    /// Persists the signatures for a held quote, then settles its payment.
+    /// If the payment can no longer be settled, the record is dropped and
+    /// the signatures are not released.
+    pub(crate) fn settle_held(
+        &self,
+        quote_id: &str,
+        outputs: &[(u64, PublicKey)],
+        signatures: &[PublicKey],
+    ) -> Result<(), MintError> {
+        let (Some(store), Some(backend), Some(preimage)) = (
+            &self.hold_store,
+            &self.lightning,
+            self.hold_preimages.get(quote_id).map(|p| p.clone()),
+        ) else {
+            return Err(MintError::PaymentFailed(
+                "hold invoice unavailable".to_string(),
+            ));
+        };
+        let record = HeldIssuance {
+            quote_id: quote_id.to_string(),
+            keyset_id: self.keyset_id.clone(),
+            preimage,
+            outputs: outputs.to_vec(),
+            signatures: signatures.to_vec(),
+        };
+        store
+            .persist(&record)
+            .map_err(|e| MintError::PaymentFailed(format!("persisting signatures: {}", e)))?;
+
+        if let Err(e) = backend.settle_hold(&record.preimage) {
+            let _ = store.remove(quote_id);
+            return Err(MintError::PaymentFailed(e.to_string()));
+        }
+        self.hold_preimages.remove(quote_id);
+        Ok(())
+    }
+
+    /// Settles the payments of issuances persisted before a crash and puts
+    /// their signatures back in the restore index, then clears the store.
+    /// Call it at startup, after restoring the mint. Records of completed
+    /// issuances are kept until then, in case the response never reached
+    /// the wallet. Returns how many were settled or found settled; records
+    /// whose payment is gone are dropped.
+    pub fn recover_holds(&self) -> io::Result<usize> {
+        let (Some(store), Some(backend)) = (&self.hold_store, &self.lightning) else {
+            return Ok(0);
+        };
+        let mut settled = 0;
+        for record in store.pending()? {
+            let hash = from_hex(&record.preimage).map(|p| to_hex(&Sha256::digest(p)));
+            // Also catches payments settled just before the crash.
+            if backend.settle_hold(&record.preimage).is_ok()
+                || hash.is_some_and(|h| backend.is_paid(&h))
+            {
+                for (value, blinded) in &record.outputs {
+                    self.signed_outputs
+                        .insert(*blinded, (record.keyset_id.clone(), *value));
+                }
+                settled += 1;
+            }
+            store.remove(&record.quote_id)?;
+        }
+        Ok(settled)
+    }
+}
@@ -0,0 +1,171 @@
+//! Mint quotes and issuing notes once a quote is paid. See
+//! `dmto_wallet::issue` for the protocol.
+
+pub use dmto_wallet::issue::*;
+
+use crate::{
+    blind::blind_sign,
+    dleq::{self},
+    error::MintError,
+    journal::JournalEvent,
+    melt::quote_id,
+    mint::Mint,
+    onchain::is_onchain,
+    protocol::{Request, Response},
+    types::Amount,
+};
+
+impl Mint {
+    /// Creates an invoice for `amount` to be paid before notes are issued.
+    /// In accounting mode, quotes must go through `mint_quote_for`.
+    pub fn mint_quote(&self, amount: u64) -> Result<MintQuote, MintError> {
+        if self.accounts.is_some() {
+            return Err(MintError::Unauthorized("account required".to_string()));
+        }
+        self.create_mint_quote(amount)
+    }
+
+    pub(crate) fn create_mint_quote(&self, amount: u64) -> Result<MintQuote, MintError> {
+        let backend = self
+            .lightning
+            .as_ref()
+            .ok_or_else(|| MintError::PaymentFailed("no lightning backend".to_string()))?;
+        let id = quote_id();
+        let options = self.invoice_template.options(&id, amount, self.quote_ttl);
+        let request = if self.hold_store.is_some() {
+            self.create_hold_invoice(&id, amount, &options)?
+        } else {
+            backend
+                .create_invoice(amount, &options)
+                .map_err(|e| MintError::PaymentFailed(e.to_string()))?
+        };
+
+        let quote = MintQuote {
+            id,
+            request,
+            amount,
+            state: MintQuoteState::Unpaid,
+            expiry: self.clock.now() + options.expiry,
+        };
+        self.mint_quotes.insert(quote.id.clone(), quote.clone());
+        Ok(quote)
+    }
+
+    /// Looks a quote up, first asking the backend whether an unpaid one has
+    /// since been paid.
+    pub fn get_quote(&self, id: &str) -> Result<MintQuote, MintError> {
+        let mut quote = self
+            .mint_quotes
+            .get_mut(id)
+            .ok_or_else(|| MintError::QuoteUnknown(id.to_string()))?;
+        self.refresh(&mut quote);
+        Ok(quote.clone())
+    }
+
+    fn refresh(&self, quote: &mut MintQuote) {
+        if quote.state != MintQuoteState::Unpaid {
+            return;
+        }
+        let paid = if is_onchain(&quote.request) {
+            self.deposit_confirmed(&quote.request)
+        } else {
+            self.lightning.as_ref().is_some_and(|backend| {
+                backend.decode(&quote.request).is_some_and(|invoice| {
+                    if self.hold_preimages.contains_key(&quote.id) {
+                        backend.is_held(&invoice.payment_hash)
+                    } else {
+                        backend.is_paid(&invoice.payment_hash)
+                    }
+                })
+            })
+        };
+        if paid {
+            quote.state = MintQuoteState::Paid;
+        }
+    }
+
+    /// Signs `outputs` worth exactly the quote's amount, once, after the
+    /// invoice is paid.
+    pub fn mint(&self, req: Request<MintRequest>) -> Result<Response<MintResponse>, MintError> {
+        req.handle(|req| {
+            let operation = req.operation.clone();
+            self.traced(operation.as_deref(), "mint", || self.run_mint(req))
+        })
+    }
+
+    fn run_mint(&self, req: MintRequest) -> Result<MintResponse, MintError> {
+        let mut quote = self
+            .mint_quotes
+            .get_mut(&req.quote)
+            .ok_or_else(|| MintError::QuoteUnknown(req.quote.clone()))?;
+        self.refresh(&mut quote);
+        match quote.state {
+            MintQuoteState::Unpaid => return Err(MintError::QuoteUnpaid(quote.id.clone())),
+            MintQuoteState::Issued => {
+                return Err(MintError::QuoteAlreadyIssued(quote.id.clone()));
+            }
+            MintQuoteState::Paid => {}
+        }
+
+        self.check_canonical(req.is_canonical())?;
+        if req.outputs.len() > self.max_outputs {
+            return Err(MintError::TooManyOutputs {
+                max: self.max_outputs,
+            });
+        }
+        let out_sum = Amount::checked_sum(req.outputs.iter().map(|(v, _)| *v))
+            .ok_or(MintError::AmountOverflow)?;
+        if out_sum.0 != quote.amount {
+            return Err(MintError::AmountMismatch {
+                inputs: quote.amount,
+                outputs: out_sum.0,
+                fee: 0,
+            });
+        }
+        if let Some((value, _)) = req.outputs.iter().find(|(v, _)| !self.keys.contains_key(v)) {
+            return Err(MintError::UnknownDenomination(*value));
+        }
+        self.monitor.check(&self.keyset_id)?;
+        self.check_outputs_unsigned(req.outputs.iter().map(|(_, b)| b))?;
+
+        let mut signatures = Vec::new();
+        let mut dleqs = Vec::new();
+        for (value, blinded) in &req.outputs {
+            let key = &self.keys[value].privkey;
+            let sig = blind_sign(key, blinded);
+            dleqs.push(dleq::prove(key, blinded, &sig));
+            signatures.push(sig);
+        }
+        if self.hold_preimages.contains_key(&quote.id) {
+            self.settle_held(&quote.id, &req.outputs, &signatures)
+                .inspect_err(|_| quote.state = MintQuoteState::Unpaid)?;
+        }
+        for (value, blinded) in req.outputs {
+            self.anonymity.signed(&self.keyset_id, value);
+            self.usage.issued(value);
+            self.signed_outputs
+                .insert(blinded, (self.keyset_id.clone(), value));
+            self.journal.append(
+                JournalEvent::Signed {
+                    value,
+                    blinded,
+                    keyset_id: self.keyset_id.clone(),
+                },
+                self.clock.now(),
+            );
+        }
+        self.monitor.record(
+            &self.keyset_id,
+            signatures.len() as u64,
+            quote.amount,
+            quote.amount,
+        );
+        self.usage.amount(quote.amount);
+        if let Some(accounts) = &self.accounts {
+            accounts.minted(&quote.id, quote.amount);
+        }
+        quote.state = MintQuoteState::Issued;
+
+        Ok(MintResponse { signatures, dleqs })
+    }
+}
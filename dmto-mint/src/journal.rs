@@ -0,0 +1,369 @@
+use std::{fmt, fs, io, path::Path, sync::Mutex};
+
+use secp256k1::PublicKey;
+
+use crate::{
+    codec::{SIGNATURE_LEN, decode_signature, encode_signature, to_hex},
+    operation,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JournalEvent {
+    /// A note's secret was added to the spent set. `value` is 0 in entries
+    /// read from segments older than version 2.
+    Spent { secret: Vec<u8>, value: u64 },
+    /// A blinded message was signed for the given denomination with the
+    /// keys of `keyset_id`, which is empty in entries read from segments
+    /// older than version 4.
+    Signed {
+        value: u64,
+        blinded: PublicKey,
+        keyset_id: String,
+    },
+    /// A spent note was returned to the unspent set after a failed melt.
+    Released { secret: Vec<u8>, value: u64 },
+    /// Input fees kept by the mint on a swap or melt.
+    Fee { amount: u64 },
+    /// The mint's spend policy refused the note with this `Y`.
+    Refused { y: PublicKey, reason: String },
+    /// The keyset was revoked and signs nothing more.
+    KeysetRevoked { keyset_id: String },
+    /// The mint started signing with the keyset.
+    KeysetActivated { keyset_id: String },
+    /// Notes of a revoked keyset can be swapped for new ones until `until`.
+    MigrationOpened { keyset_id: String, until: u64 },
+    /// Notes of the keyset are no longer accepted at all.
+    MigrationClosed { keyset_id: String },
+}
+
+impl fmt::Display for JournalEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalEvent::Spent { secret, value } => {
+                write!(f, "spent {} ({})", to_hex(secret), value)
+            }
+            JournalEvent::Signed {
+                value,
+                blinded,
+                keyset_id,
+            } => write!(
+                f,
+                "signed {} ({}, {})",
+                to_hex(&blinded.serialize()),
+                value,
+                keyset_id
+            ),
+            JournalEvent::Released { secret, value } => {
+                write!(f, "released {} ({})", to_hex(secret), value)
+            }
+            JournalEvent::Fee { amount } => write!(f, "fee {}", amount),
+            JournalEvent::Refused { y, reason } => {
+                write!(f, "refused {}: {}", to_hex(&y.serialize()), reason)
+            }
+            JournalEvent::KeysetRevoked { keyset_id } => write!(f, "revoked {}", keyset_id),
+            JournalEvent::KeysetActivated { keyset_id } => write!(f, "activated {}", keyset_id),
+            JournalEvent::MigrationOpened { keyset_id, until } => {
+                write!(f, "migration of {} open until {}", keyset_id, until)
+            }
+            JournalEvent::MigrationClosed { keyset_id } => {
+                write!(f, "migration of {} closed", keyset_id)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub seq: u64,
+    pub timestamp: u64,
+    pub event: JournalEvent,
+    /// The operation whose request made the change, if it gave one. `None`
+    /// in entries read from segments older than version 3.
+    pub operation: Option<String>,
+}
+
+/// Segments start with this magic and a version byte. Segments written
+/// before the header existed count as version 0, which has the same entry
+/// layout as version 1. Version 2 adds the note value to `Spent` and
+/// `Released`, version 3 the operation ID to every entry, and version 4 the
+/// keyset ID to `Signed` and the keyset rotation events.
+const SEGMENT_MAGIC: &[u8] = b"DMJ";
+pub const SEGMENT_VERSION: u8 = 4;
+
+const TAG_SPENT: u8 = 1;
+const TAG_SIGNED: u8 = 2;
+const TAG_REFUSED: u8 = 3;
+const TAG_RELEASED: u8 = 4;
+const TAG_FEE: u8 = 5;
+const TAG_REVOKED: u8 = 6;
+const TAG_ACTIVATED: u8 = 7;
+const TAG_MIGRATION_OPENED: u8 = 8;
+const TAG_MIGRATION_CLOSED: u8 = 9;
+
+fn put_keyset_id(keyset_id: &str, out: &mut Vec<u8>) {
+    let id = &keyset_id.as_bytes()[..keyset_id.len().min(u8::MAX as usize)];
+    out.push(id.len() as u8);
+    out.extend_from_slice(id);
+}
+
+/// A keyset ID written by `put_keyset_id` at the start of `buf`, and the
+/// bytes it took.
+fn get_keyset_id(buf: &[u8]) -> Option<(String, usize)> {
+    let n = *buf.first()? as usize;
+    let id = String::from_utf8(buf.get(1..1 + n)?.to_vec()).ok()?;
+    Some((id, 1 + n))
+}
+
+impl JournalEntry {
+    /// `seq` (8) | `timestamp` (8) | tag (1) | payload. `Spent` and
+    /// `Released` carry a 2-byte length, the secret and the 8-byte value,
+    /// `Signed` the codec signature layout then a 1-byte length and the
+    /// keyset ID, `Refused` the 33-byte `Y` then
+    /// a 2-byte length and the reason, and `Fee` the 8-byte amount. The
+    /// keyset events carry a 1-byte length and the keyset ID, and
+    /// `MigrationOpened` then the 8-byte deadline. Then a 1-byte length
+    /// and the operation ID, empty if there is none.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.seq.to_be_bytes());
+        out.extend_from_slice(&self.timestamp.to_be_bytes());
+        match &self.event {
+            JournalEvent::Spent { secret, value } | JournalEvent::Released { secret, value } => {
+                let tag = match self.event {
+                    JournalEvent::Spent { .. } => TAG_SPENT,
+                    _ => TAG_RELEASED,
+                };
+                out.push(tag);
+                out.extend_from_slice(&(secret.len() as u16).to_be_bytes());
+                out.extend_from_slice(secret);
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+            JournalEvent::Signed {
+                value,
+                blinded,
+                keyset_id,
+            } => {
+                out.push(TAG_SIGNED);
+                encode_signature(*value, blinded, out);
+                put_keyset_id(keyset_id, out);
+            }
+            JournalEvent::Refused { y, reason } => {
+                out.push(TAG_REFUSED);
+                out.extend_from_slice(&y.serialize());
+                out.extend_from_slice(&(reason.len() as u16).to_be_bytes());
+                out.extend_from_slice(reason.as_bytes());
+            }
+            JournalEvent::Fee { amount } => {
+                out.push(TAG_FEE);
+                out.extend_from_slice(&amount.to_be_bytes());
+            }
+            JournalEvent::KeysetRevoked { keyset_id } => {
+                out.push(TAG_REVOKED);
+                put_keyset_id(keyset_id, out);
+            }
+            JournalEvent::KeysetActivated { keyset_id } => {
+                out.push(TAG_ACTIVATED);
+                put_keyset_id(keyset_id, out);
+            }
+            JournalEvent::MigrationOpened { keyset_id, until } => {
+                out.push(TAG_MIGRATION_OPENED);
+                put_keyset_id(keyset_id, out);
+                out.extend_from_slice(&until.to_be_bytes());
+            }
+            JournalEvent::MigrationClosed { keyset_id } => {
+                out.push(TAG_MIGRATION_CLOSED);
+                put_keyset_id(keyset_id, out);
+            }
+        }
+        let operation = self.operation.as_deref().unwrap_or_default().as_bytes();
+        let operation = &operation[..operation.len().min(u8::MAX as usize)];
+        out.push(operation.len() as u8);
+        out.extend_from_slice(operation);
+    }
+
+    /// Decodes one entry written by a segment of `version`.
+    pub fn decode(buf: &[u8], version: u8) -> Option<(Self, usize)> {
+        let seq = u64::from_be_bytes(buf.get(0..8)?.try_into().ok()?);
+        let timestamp = u64::from_be_bytes(buf.get(8..16)?.try_into().ok()?);
+        let (event, len) = match *buf.get(16)? {
+            tag @ (TAG_SPENT | TAG_RELEASED) => {
+                let n = u16::from_be_bytes(buf.get(17..19)?.try_into().ok()?) as usize;
+                let secret = buf.get(19..19 + n)?.to_vec();
+                let (value, len) = if version >= 2 {
+                    let value = u64::from_be_bytes(buf.get(19 + n..27 + n)?.try_into().ok()?);
+                    (value, 27 + n)
+                } else {
+                    (0, 19 + n)
+                };
+                let event = if tag == TAG_SPENT {
+                    JournalEvent::Spent { secret, value }
+                } else {
+                    JournalEvent::Released { secret, value }
+                };
+                (event, len)
+            }
+            TAG_SIGNED => {
+                let (value, blinded) = decode_signature(buf.get(17..)?)?;
+                let (keyset_id, n) = if version >= 4 {
+                    get_keyset_id(buf.get(17 + SIGNATURE_LEN..)?)?
+                } else {
+                    (String::new(), 0)
+                };
+                let event = JournalEvent::Signed {
+                    value,
+                    blinded,
+                    keyset_id,
+                };
+                (event, 17 + SIGNATURE_LEN + n)
+            }
+            TAG_REFUSED => {
+                let y = PublicKey::from_slice(buf.get(17..50)?).ok()?;
+                let n = u16::from_be_bytes(buf.get(50..52)?.try_into().ok()?) as usize;
+                let reason = String::from_utf8(buf.get(52..52 + n)?.to_vec()).ok()?;
+                (JournalEvent::Refused { y, reason }, 52 + n)
+            }
+            TAG_FEE => {
+                let amount = u64::from_be_bytes(buf.get(17..25)?.try_into().ok()?);
+                (JournalEvent::Fee { amount }, 25)
+            }
+            tag @ (TAG_REVOKED | TAG_ACTIVATED | TAG_MIGRATION_CLOSED) => {
+                let (keyset_id, n) = get_keyset_id(buf.get(17..)?)?;
+                let event = match tag {
+                    TAG_REVOKED => JournalEvent::KeysetRevoked { keyset_id },
+                    TAG_ACTIVATED => JournalEvent::KeysetActivated { keyset_id },
+                    _ => JournalEvent::MigrationClosed { keyset_id },
+                };
+                (event, 17 + n)
+            }
+            TAG_MIGRATION_OPENED => {
+                let (keyset_id, n) = get_keyset_id(buf.get(17..)?)?;
+                let until = u64::from_be_bytes(buf.get(17 + n..25 + n)?.try_into().ok()?);
+                (JournalEvent::MigrationOpened { keyset_id, until }, 25 + n)
+            }
+            _ => return None,
+        };
+        let (operation, len) = if version >= 3 {
+            let n = *buf.get(len)? as usize;
+            let operation = String::from_utf8(buf.get(len + 1..len + 1 + n)?.to_vec()).ok()?;
+            ((n > 0).then_some(operation), len + 1 + n)
+        } else {
+            (None, len)
+        };
+
+        Some((
+            JournalEntry {
+                seq,
+                timestamp,
+                event,
+                operation,
+            },
+            len,
+        ))
+    }
+}
+
+/// Append-only, sequence-numbered log of every state change the mint makes.
+/// A journal restored from a snapshot starts after `base` instead of at 0.
+#[derive(Default)]
+pub struct Journal {
+    base: u64,
+    entries: Mutex<Vec<JournalEntry>>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn starting_after(base: u64) -> Self {
+        Self {
+            base,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Appends `event`, stamped `timestamp` by the mint's clock, under the
+    /// operation current on this thread.
+    pub fn append(&self, event: JournalEvent, timestamp: u64) -> u64 {
+        let mut entries = self.entries.lock().unwrap();
+        let seq = self.base + entries.len() as u64 + 1;
+
+        entries.push(JournalEntry {
+            seq,
+            timestamp,
+            event,
+            operation: operation::current(),
+        });
+        seq
+    }
+
+    /// Re-appends an entry read back from a segment, keeping its original
+    /// sequence number and timestamp. Fails unless it directly follows the
+    /// current last entry.
+    pub fn replay(&self, entry: JournalEntry) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        if entry.seq != self.base + entries.len() as u64 + 1 {
+            return false;
+        }
+        entries.push(entry);
+        true
+    }
+
+    /// Sequence number of the last entry, or `base` if nothing was appended.
+    pub fn last_seq(&self) -> u64 {
+        self.base + self.entries.lock().unwrap().len() as u64
+    }
+
+    /// Entry `seq`, if this journal still holds it.
+    pub fn get(&self, seq: u64) -> Option<JournalEntry> {
+        let i = seq.checked_sub(self.base + 1)?;
+        self.entries.lock().unwrap().get(i as usize).cloned()
+    }
+
+    /// All entries with `seq > since` that this journal still holds.
+    pub fn since(&self, since: u64) -> Vec<JournalEntry> {
+        let entries = self.entries.lock().unwrap();
+        let skip = since.saturating_sub(self.base) as usize;
+        entries.iter().skip(skip).cloned().collect()
+    }
+
+    /// Writes entries after `since` as an incremental segment file and
+    /// returns the last sequence number it contains.
+    pub fn write_segment(&self, path: &Path, since: u64) -> io::Result<u64> {
+        let entries = self.since(since);
+        let mut buf = SEGMENT_MAGIC.to_vec();
+        buf.push(SEGMENT_VERSION);
+        for e in &entries {
+            e.encode(&mut buf);
+        }
+        fs::write(path, buf)?;
+        Ok(entries.last().map_or(since, |e| e.seq))
+    }
+
+    /// Reads a segment of any version up to `SEGMENT_VERSION`, including
+    /// headerless version 0 segments.
+    pub fn read_segment(path: &Path) -> io::Result<Vec<JournalEntry>> {
+        let buf = fs::read(path)?;
+        let mut pos = 0;
+        let mut version = 0;
+        if buf.starts_with(SEGMENT_MAGIC) {
+            version = buf.get(SEGMENT_MAGIC.len()).copied().unwrap_or(0);
+            if version > SEGMENT_VERSION {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("journal segment version {} not supported", version),
+                ));
+            }
+            pos = SEGMENT_MAGIC.len() + 1;
+        }
+
+        let mut entries = Vec::new();
+        while pos < buf.len() {
+            let (entry, len) = JournalEntry::decode(&buf[pos..], version).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "corrupt journal segment")
+            })?;
+            entries.push(entry);
+            pos += len;
+        }
+        Ok(entries)
+    }
+}
@@ -0,0 +1,322 @@
+//! A Lightning node embedded in the mint, so it can receive and pay without
+//! an external daemon. Built with the `ldk` feature.
+//!
+//! `LdkBackend` implements `LightningBackend` over an `LdkNode`, the calls
+//! the mint needs from an ldk-node `Node`. `ldk_node::Node` implements it by
+//! forwarding to its `bolt11_payment()`, `payment()` and `open_channel()`
+//! calls, so `LdkBackend::new(Arc::new(node))` is a backend for a running
+//! node; tests can implement `LdkNode` over a fake. Amounts cross the trait
+//! in millisatoshis, as ldk-node uses them.
+
+use std::{str::FromStr, sync::Arc, thread, time::Duration};
+
+use ldk_node::{
+    Node,
+    bitcoin::hashes::{Hash, sha256},
+    lightning::{
+        ln::channelmanager::PaymentId, ln::msgs::SocketAddress,
+        routing::router::RouteParametersConfig,
+    },
+    lightning_invoice::{self, Bolt11Invoice, Bolt11InvoiceDescription, Sha256},
+    payment::{PaymentKind, PaymentStatus},
+};
+use secp256k1::PublicKey;
+
+use crate::{
+    codec::{from_hex, to_hex},
+    lightning::{Description, Invoice, InvoiceOptions, LightningBackend, Payment, PaymentError},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodePaymentStatus {
+    Pending,
+    Succeeded,
+    Failed,
+}
+
+/// A payment the node sent or received, by payment hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodePayment {
+    pub status: NodePaymentStatus,
+    pub preimage: Option<String>,
+    pub fee_paid_msat: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Channel {
+    pub id: String,
+    pub peer: PublicKey,
+    pub capacity: u64,
+    /// What the node can currently send over the channel, in sats.
+    pub outbound: u64,
+    pub usable: bool,
+}
+
+pub trait LdkNode: Send + Sync {
+    /// Amount and payment hash of a BOLT11 invoice.
+    fn decode_invoice(&self, invoice: &str) -> Option<Invoice>;
+    /// Creates an invoice, mapping `description` to a
+    /// `Bolt11InvoiceDescription`. The description includes the quote ID by
+    /// default, see `InvoiceTemplate`.
+    fn receive(
+        &self,
+        amount_msat: u64,
+        description: &Description,
+        expiry: u32,
+    ) -> Result<String, String>;
+    /// Starts paying `invoice`, returning its payment hash.
+    /// `operation::current()` is the mint operation it is made for, to tag
+    /// the node's logs with.
+    fn send(&self, invoice: &str, max_fee_msat: u64) -> Result<String, String>;
+    /// As `send`, paying `amount_msat` to an invoice without an amount
+    /// (`send_using_amount`).
+    fn send_using_amount(
+        &self,
+        invoice: &str,
+        amount_msat: u64,
+        max_fee_msat: u64,
+    ) -> Result<String, String>;
+    fn payment(&self, payment_hash: &str) -> Option<NodePayment>;
+    /// Opens a channel of `amount` sats to `peer` at `address`, returning
+    /// the channel ID.
+    fn open_channel(&self, peer: PublicKey, address: &str, amount: u64) -> Result<String, String>;
+    fn channels(&self) -> Vec<Channel>;
+}
+
+pub struct LdkBackend<N> {
+    pub node: Arc<N>,
+    /// Fee reserve in parts per million of the amount, with `min_fee_reserve`
+    /// as a floor for small payments.
+    pub fee_reserve_ppm: u64,
+    pub min_fee_reserve: u64,
+    /// How often `pay` asks the node whether a payment has settled.
+    pub poll_interval: Duration,
+}
+
+impl<N: LdkNode> LdkBackend<N> {
+    pub fn new(node: Arc<N>) -> Self {
+        Self {
+            node,
+            fee_reserve_ppm: 10_000,
+            min_fee_reserve: 2,
+            poll_interval: Duration::from_millis(100),
+        }
+    }
+
+    /// Opens a channel from the mint's node, e.g. to an LSP, so it can pay.
+    pub fn open_channel(
+        &self,
+        peer: PublicKey,
+        address: &str,
+        amount: u64,
+    ) -> Result<String, PaymentError> {
+        self.node
+            .open_channel(peer, address, amount)
+            .map_err(PaymentError::Failed)
+    }
+
+    /// What the node can send right now over its usable channels.
+    pub fn outbound_capacity(&self) -> u64 {
+        self.node
+            .channels()
+            .iter()
+            .filter(|c| c.usable)
+            .map(|c| c.outbound)
+            .sum()
+    }
+
+    /// Waits for the node to settle or fail the payment with `hash`. There
+    /// is no timeout: the mint releases the inputs when a payment fails, so
+    /// one must not be reported failed while it can still complete.
+    fn wait(&self, hash: &str) -> Result<Payment, PaymentError> {
+        loop {
+            match self.node.payment(hash) {
+                Some(NodePayment {
+                    status: NodePaymentStatus::Succeeded,
+                    preimage: Some(preimage),
+                    fee_paid_msat,
+                }) => {
+                    return Ok(Payment {
+                        preimage,
+                        fee_paid: fee_paid_msat.div_ceil(1000),
+                    });
+                }
+                Some(NodePayment {
+                    status: NodePaymentStatus::Failed,
+                    ..
+                }) => return Err(PaymentError::Failed("payment failed".to_string())),
+                _ => thread::sleep(self.poll_interval),
+            }
+        }
+    }
+}
+
+impl<N: LdkNode> LightningBackend for LdkBackend<N> {
+    fn decode(&self, request: &str) -> Option<Invoice> {
+        self.node.decode_invoice(request)
+    }
+
+    fn fee_reserve(&self, amount: u64) -> u64 {
+        (amount.saturating_mul(self.fee_reserve_ppm) / 1_000_000).max(self.min_fee_reserve)
+    }
+
+    /// Sends the payment and waits for the node to settle or fail it.
+    fn pay(&self, request: &str, max_fee: u64) -> Result<Payment, PaymentError> {
+        if self
+            .decode(request)
+            .is_none_or(|invoice| invoice.amount == 0)
+        {
+            return Err(PaymentError::InvalidRequest);
+        }
+        let hash = self
+            .node
+            .send(request, max_fee.saturating_mul(1000))
+            .map_err(PaymentError::Failed)?;
+        self.wait(&hash)
+    }
+
+    fn pay_amountless(
+        &self,
+        request: &str,
+        amount: u64,
+        max_fee: u64,
+    ) -> Result<Payment, PaymentError> {
+        if amount == 0
+            || self
+                .decode(request)
+                .is_none_or(|invoice| invoice.amount != 0)
+        {
+            return Err(PaymentError::InvalidRequest);
+        }
+        let hash = self
+            .node
+            .send_using_amount(
+                request,
+                amount.saturating_mul(1000),
+                max_fee.saturating_mul(1000),
+            )
+            .map_err(PaymentError::Failed)?;
+        self.wait(&hash)
+    }
+
+    fn create_invoice(
+        &self,
+        amount: u64,
+        options: &InvoiceOptions,
+    ) -> Result<String, PaymentError> {
+        self.node
+            .receive(
+                amount.saturating_mul(1000),
+                &options.description,
+                options.expiry.try_into().unwrap_or(u32::MAX),
+            )
+            .map_err(PaymentError::Failed)
+    }
+
+    fn is_paid(&self, payment_hash: &str) -> bool {
+        self.node
+            .payment(payment_hash)
+            .is_some_and(|p| p.status == NodePaymentStatus::Succeeded)
+    }
+}
+
+/// Payments are looked up by payment hash, which ldk-node also uses as the
+/// payment ID of BOLT11 payments.
+impl LdkNode for Node {
+    fn decode_invoice(&self, invoice: &str) -> Option<Invoice> {
+        let invoice = Bolt11Invoice::from_str(invoice).ok()?;
+        Some(Invoice {
+            amount: invoice.amount_milli_satoshis().unwrap_or(0).div_ceil(1000),
+            payment_hash: to_hex(&invoice.payment_hash().to_byte_array()),
+        })
+    }
+
+    fn receive(
+        &self,
+        amount_msat: u64,
+        description: &Description,
+        expiry: u32,
+    ) -> Result<String, String> {
+        let description = match description {
+            Description::Direct(text) => Bolt11InvoiceDescription::Direct(
+                lightning_invoice::Description::new(text.clone()).map_err(|e| e.to_string())?,
+            ),
+            Description::Hash(hash) => {
+                let hash: [u8; 32] = from_hex(hash)
+                    .and_then(|h| h.try_into().ok())
+                    .ok_or("invalid description hash")?;
+                Bolt11InvoiceDescription::Hash(Sha256(sha256::Hash::from_byte_array(hash)))
+            }
+        };
+        self.bolt11_payment()
+            .receive(amount_msat, &description, expiry)
+            .map(|invoice| invoice.to_string())
+            .map_err(|e| e.to_string())
+    }
+
+    fn send(&self, invoice: &str, max_fee_msat: u64) -> Result<String, String> {
+        let invoice = Bolt11Invoice::from_str(invoice).map_err(|e| e.to_string())?;
+        self.bolt11_payment()
+            .send(&invoice, Some(route_parameters(max_fee_msat)))
+            .map(|id| to_hex(&id.0))
+            .map_err(|e| e.to_string())
+    }
+
+    fn send_using_amount(
+        &self,
+        invoice: &str,
+        amount_msat: u64,
+        max_fee_msat: u64,
+    ) -> Result<String, String> {
+        let invoice = Bolt11Invoice::from_str(invoice).map_err(|e| e.to_string())?;
+        self.bolt11_payment()
+            .send_using_amount(&invoice, amount_msat, Some(route_parameters(max_fee_msat)))
+            .map(|id| to_hex(&id.0))
+            .map_err(|e| e.to_string())
+    }
+
+    fn payment(&self, payment_hash: &str) -> Option<NodePayment> {
+        let id: [u8; 32] = from_hex(payment_hash)?.try_into().ok()?;
+        let details = Node::payment(self, &PaymentId(id))?;
+        let preimage = match details.kind {
+            PaymentKind::Bolt11 { preimage, .. } => preimage.map(|p| to_hex(&p.0)),
+            _ => None,
+        };
+        Some(NodePayment {
+            status: match details.status {
+                PaymentStatus::Pending => NodePaymentStatus::Pending,
+                PaymentStatus::Succeeded => NodePaymentStatus::Succeeded,
+                PaymentStatus::Failed => NodePaymentStatus::Failed,
+            },
+            preimage,
+            fee_paid_msat: details.fee_paid_msat.unwrap_or(0),
+        })
+    }
+
+    fn open_channel(&self, peer: PublicKey, address: &str, amount: u64) -> Result<String, String> {
+        let address = SocketAddress::from_str(address).map_err(|e| e.to_string())?;
+        Node::open_channel(self, peer, address, amount, None, None)
+            .map(|id| format!("{:032x}", id.0))
+            .map_err(|e| e.to_string())
+    }
+
+    fn channels(&self) -> Vec<Channel> {
+        self.list_channels()
+            .into_iter()
+            .map(|c| Channel {
+                id: format!("{:032x}", c.user_channel_id.0),
+                peer: c.counterparty_node_id,
+                capacity: c.channel_value_sats,
+                outbound: c.outbound_capacity_msat / 1000,
+                usable: c.is_usable,
+            })
+            .collect()
+    }
+}
+
+fn route_parameters(max_fee_msat: u64) -> RouteParametersConfig {
+    RouteParametersConfig {
+        max_total_routing_fee_msat: Some(max_fee_msat),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,63 @@
+//! The mint: keysets, the spent set and quote tables, issuance, swaps and
+//! melts, and everything around running one (journal, backups, replicas,
+//! auth, Lightning backends). Builds on `dmto-wallet` for the protocol
+//! types and re-exports its modules, so paths such as `types::Note` work
+//! from either crate.
+
+pub use dmto_crypto::{blind, dleq, hash, signing};
+#[cfg(feature = "compat")]
+pub use dmto_wallet::compat;
+pub use dmto_wallet::{
+    atomic, balance, clock, codec, compact, conditions, contacts, cosign, error, escrow, handle,
+    history, keyset, lightning, mixedsend, multimint, nfc, notestore, payjoin, protocol, rates,
+    remote, rpc, secret, streaming, subscription, sync, token, types, uri, verifier, versioned,
+    wallet, walletpolicy,
+};
+
+pub mod accounts;
+pub mod actor;
+pub mod announce;
+pub mod anonymity;
+pub mod api;
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+pub mod auth;
+pub mod backup;
+pub mod batchmelt;
+pub mod bench;
+pub mod bundle;
+pub mod canonical;
+pub mod ceremony;
+pub mod chaos;
+pub mod config;
+pub mod derivation;
+pub mod embedded;
+pub mod fieldcrypt;
+pub mod freshness;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod hold;
+pub mod issue;
+pub mod journal;
+#[cfg(feature = "ldk")]
+pub mod ldk;
+pub mod load;
+pub mod melt;
+pub mod mint;
+pub mod mock;
+pub mod onchain;
+pub mod operation;
+pub mod policy;
+pub mod pool;
+pub mod quota;
+pub mod receipt;
+pub mod replay;
+pub mod replica;
+pub mod restore;
+pub mod rotation;
+pub mod spendrecord;
+pub mod stats;
+pub mod strict;
+pub mod tenant;
+pub mod testing;
+pub mod usage;
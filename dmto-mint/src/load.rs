@@ -0,0 +1,90 @@
+use std::{
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+use crate::error::MintError;
+
+/// Bounds on how much work the mint accepts at once. Requests beyond
+/// `max_concurrent` wait in a queue of at most `max_queued`; anything past
+/// that is turned away with a retry-after hint.
+#[derive(Clone, Debug)]
+pub struct LoadLimits {
+    pub max_concurrent: usize,
+    pub max_queued: usize,
+    pub retry_after: Duration,
+}
+
+impl Default for LoadLimits {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 64,
+            max_queued: 256,
+            retry_after: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadMetrics {
+    pub in_flight: usize,
+    pub queued: usize,
+    pub accepted: u64,
+    pub rejected: u64,
+}
+
+pub struct Limiter {
+    pub limits: LoadLimits,
+    state: Mutex<LoadMetrics>,
+    freed: Condvar,
+}
+
+/// Held for the duration of a handler; releases its slot on drop.
+pub struct Permit<'a> {
+    limiter: &'a Limiter,
+}
+
+impl Limiter {
+    pub fn new(limits: LoadLimits) -> Self {
+        Self {
+            limits,
+            state: Mutex::new(LoadMetrics::default()),
+            freed: Condvar::new(),
+        }
+    }
+
+    pub fn acquire(&self) -> Result<Permit<'_>, MintError> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.in_flight >= self.limits.max_concurrent {
+            if state.queued >= self.limits.max_queued {
+                state.rejected += 1;
+                return Err(MintError::Overloaded {
+                    retry_after_ms: self.limits.retry_after.as_millis() as u64,
+                });
+            }
+
+            state.queued += 1;
+            while state.in_flight >= self.limits.max_concurrent {
+                state = self.freed.wait(state).unwrap();
+            }
+            state.queued -= 1;
+        }
+
+        state.in_flight += 1;
+        state.accepted += 1;
+        Ok(Permit { limiter: self })
+    }
+
+    pub fn metrics(&self) -> LoadMetrics {
+        *self.state.lock().unwrap()
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap();
+        state.in_flight -= 1;
+        self.limiter.freed.notify_one();
+    }
+}
@@ -0,0 +1,414 @@
+//! Melt quotes and paying them out of the inputs. See `dmto_wallet::melt`
+//! for the protocol.
+
+pub use dmto_wallet::melt::*;
+
+use secp256k1::PublicKey;
+
+use crate::{
+    blind::blind_sign,
+    error::MintError,
+    issue::MintQuoteState,
+    journal::JournalEvent,
+    lightning::{Payment, PaymentError},
+    mint::Mint,
+    onchain::is_onchain,
+    pool::Priority,
+    protocol::{Request, Response},
+    types::{Amount, Note},
+    wallet::split_amount,
+};
+
+pub(crate) enum Proof {
+    Preimage(String),
+    Txid(String),
+    Internal,
+}
+
+impl Mint {
+    /// In accounting mode, quotes must go through `melt_quote_for`.
+    pub fn melt_quote(&self, request: &str) -> Result<MeltQuote, MintError> {
+        if self.accounts.is_some() {
+            return Err(MintError::Unauthorized("account required".to_string()));
+        }
+        self.create_melt_quote(request, None)
+    }
+
+    /// Quotes paying `amount` to `request`: an invoice without an amount,
+    /// or a request whose own amount is `amount`.
+    pub fn melt_quote_with_amount(
+        &self,
+        request: &str,
+        amount: u64,
+    ) -> Result<MeltQuote, MintError> {
+        if self.accounts.is_some() {
+            return Err(MintError::Unauthorized("account required".to_string()));
+        }
+        self.create_melt_quote(request, Some(amount))
+    }
+
+    /// Quotes a Lightning invoice, or a BIP21 URI for an on-chain payment,
+    /// for `amount` if the wallet named one.
+    pub(crate) fn create_melt_quote(
+        &self,
+        request: &str,
+        amount: Option<u64>,
+    ) -> Result<MeltQuote, MintError> {
+        if amount == Some(0) {
+            return Err(MintError::BadRequest(
+                "melt amount must be positive".to_string(),
+            ));
+        }
+        let mut amountless = false;
+        let (amount, fee_reserve, fee_rate) = if is_onchain(request) {
+            let (onchain_amount, fee_reserve, fee_rate) = self.onchain_terms(request)?;
+            if amount.is_some_and(|a| a != onchain_amount) {
+                return Err(MintError::BadRequest(
+                    "amount does not match payment request".to_string(),
+                ));
+            }
+            (onchain_amount, fee_reserve, Some(fee_rate))
+        } else {
+            let backend = self
+                .lightning
+                .as_ref()
+                .ok_or_else(|| MintError::PaymentFailed("no lightning backend".to_string()))?;
+            let invoice = backend
+                .decode(request)
+                .ok_or_else(|| MintError::PaymentFailed("invalid payment request".to_string()))?;
+            let amount = match (invoice.amount, amount) {
+                (0, None) => {
+                    return Err(MintError::BadRequest(
+                        "invoice has no amount; name one to pay".to_string(),
+                    ));
+                }
+                (0, Some(amount)) => {
+                    amountless = true;
+                    amount
+                }
+                (invoice_amount, Some(amount)) if amount != invoice_amount => {
+                    return Err(MintError::BadRequest(
+                        "amount does not match payment request".to_string(),
+                    ));
+                }
+                (invoice_amount, _) => invoice_amount,
+            };
+            // Paying one of our own invoices costs no routing fee.
+            let fee_reserve = if self.internal_quote(&invoice.payment_hash).is_some() {
+                0
+            } else if let Some(policy) = &self.fee_reserve {
+                policy.reserve(amount)
+            } else {
+                backend.fee_reserve(amount)
+            };
+            (amount, fee_reserve, None)
+        };
+
+        let quote = MeltQuote {
+            id: quote_id(),
+            request: request.to_string(),
+            amount,
+            fee_reserve,
+            state: MeltQuoteState::Unpaid,
+            expiry: self.clock.now() + self.quote_ttl,
+            preimage: None,
+            fee_paid: 0,
+            fee_rate,
+            txid: None,
+            confirmations: 0,
+            internal: false,
+            amountless,
+        };
+        self.melt_quotes.insert(quote.id.clone(), quote.clone());
+        Ok(quote)
+    }
+
+    /// The quote as it stands, with on-chain confirmations brought up to
+    /// date.
+    pub fn melt_quote_state(&self, id: &str) -> Result<MeltQuote, MintError> {
+        let mut quote = self
+            .melt_quotes
+            .get_mut(id)
+            .ok_or_else(|| MintError::QuoteUnknown(id.to_string()))?;
+        self.refresh_confirmations(&mut quote);
+        Ok(quote.clone())
+    }
+
+    /// Spends the inputs, pays the quote's invoice or address and signs
+    /// change for the unused fee reserve. If the payment fails the inputs
+    /// are released and the quote goes back to `Unpaid`.
+    pub fn melt(&self, req: Request<MeltRequest>) -> Result<Response<MeltResponse>, MintError> {
+        req.handle(|req| {
+            let operation = req.operation.clone();
+            self.traced(operation.as_deref(), "melt", || self.run_melt(req))
+        })
+    }
+
+    fn run_melt(&self, req: MeltRequest) -> Result<MeltResponse, MintError> {
+        let quote = self.begin_melt(&req.quote)?;
+        let set_state = |state| self.set_melt_state(&quote.id, state);
+
+        let (in_sum, fee) = match self.check_melt(&req, &quote) {
+            Ok(sums) => sums,
+            Err(e) => {
+                set_state(MeltQuoteState::Unpaid);
+                return Err(e);
+            }
+        };
+        if let Err(e) = self.spend_inputs(&req.inputs, Priority::Melt) {
+            set_state(MeltQuoteState::Unpaid);
+            return Err(e);
+        }
+
+        let max_fee = in_sum - quote.amount - fee;
+        let (proof, fee_paid) = match self.pay_melt(&quote, max_fee) {
+            Ok(paid) => paid,
+            Err(e) => {
+                self.release_inputs(&req.inputs);
+                set_state(MeltQuoteState::Unpaid);
+                return Err(e);
+            }
+        };
+
+        self.record_redeemed(&req.inputs, quote.amount);
+        if fee > 0 {
+            self.journal
+                .append(JournalEvent::Fee { amount: fee }, self.clock.now());
+        }
+        if let Some(accounts) = &self.accounts {
+            accounts.melted(&quote.id, quote.amount + fee_paid);
+        }
+        let change_total = max_fee.saturating_sub(fee_paid);
+        let change = self.sign_change(change_total, &req.outputs, in_sum);
+
+        let quote = self.complete_melt(&quote.id, proof, fee_paid);
+        let receipt = self.melt_receipt(&req.inputs, fee, &quote, fee_paid, req.operation);
+        Ok(MeltResponse {
+            quote,
+            change,
+            receipt: Some(receipt),
+        })
+    }
+
+    /// Moves an unpaid, unexpired quote to `Pending` and returns it.
+    pub(crate) fn begin_melt(&self, id: &str) -> Result<MeltQuote, MintError> {
+        let mut quote = self
+            .melt_quotes
+            .get_mut(id)
+            .ok_or_else(|| MintError::QuoteUnknown(id.to_string()))?;
+        match quote.state {
+            MeltQuoteState::Pending => return Err(MintError::QuotePending(quote.id.clone())),
+            MeltQuoteState::Paid => return Err(MintError::QuoteAlreadyPaid(quote.id.clone())),
+            MeltQuoteState::Unpaid if self.clock.now() > quote.expiry => {
+                return Err(MintError::QuoteExpired(quote.id.clone()));
+            }
+            MeltQuoteState::Unpaid => {}
+        }
+        quote.state = MeltQuoteState::Pending;
+        Ok(quote.clone())
+    }
+
+    pub(crate) fn set_melt_state(&self, id: &str, state: MeltQuoteState) {
+        if let Some(mut q) = self.melt_quotes.get_mut(id) {
+            q.state = state;
+        }
+    }
+
+    /// Pays a pending quote on-chain, internally or over Lightning,
+    /// returning the proof and the fee paid.
+    pub(crate) fn pay_melt(
+        &self,
+        quote: &MeltQuote,
+        max_fee: u64,
+    ) -> Result<(Proof, u64), MintError> {
+        if quote.fee_rate.is_some() {
+            self.pay_onchain(quote, max_fee)
+                .map(|p| (Proof::Txid(p.txid), p.fee_paid))
+        } else if self.settle_internally(&quote.request) {
+            Ok((Proof::Internal, 0))
+        } else {
+            let amount = quote.amountless.then_some(quote.amount);
+            self.pay_lightning(&quote.request, amount, quote.fee_reserve)
+                .map(|p| (Proof::Preimage(p.preimage), p.fee_paid))
+        }
+    }
+
+    /// Returns spent inputs to the unspent set after a failed payment.
+    pub(crate) fn release_inputs(&self, inputs: &[Note]) {
+        for n in inputs {
+            self.unmark_spent(&n.secret, &n.y);
+            self.anonymity.released(self.input_keyset_id(n), n.value);
+            self.journal.append(
+                JournalEvent::Released {
+                    secret: n.secret.clone(),
+                    value: n.value,
+                },
+                self.clock.now(),
+            );
+        }
+    }
+
+    /// Marks a quote paid with its proof of payment.
+    pub(crate) fn complete_melt(&self, id: &str, proof: Proof, fee_paid: u64) -> MeltQuote {
+        let mut q = self.melt_quotes.get_mut(id).unwrap();
+        q.state = MeltQuoteState::Paid;
+        match proof {
+            Proof::Preimage(preimage) => q.preimage = Some(preimage),
+            Proof::Txid(txid) => q.txid = Some(txid),
+            Proof::Internal => q.internal = true,
+        }
+        q.fee_paid = fee_paid;
+        q.clone()
+    }
+
+    /// The unpaid mint quote on this mint whose invoice has `payment_hash`.
+    fn internal_quote(&self, payment_hash: &str) -> Option<String> {
+        let backend = self.lightning.as_ref()?;
+        let now = self.clock.now();
+        self.mint_quotes
+            .iter()
+            .find(|q| {
+                q.state == MintQuoteState::Unpaid
+                    && now <= q.expiry
+                    && backend
+                        .decode(&q.request)
+                        .is_some_and(|invoice| invoice.payment_hash == payment_hash)
+            })
+            .map(|q| q.id.clone())
+    }
+
+    /// Pays `request` by marking the mint quote it belongs to paid, if it is
+    /// one of this mint's unpaid invoices.
+    fn settle_internally(&self, request: &str) -> bool {
+        let Some(invoice) = self.lightning.as_ref().and_then(|b| b.decode(request)) else {
+            return false;
+        };
+        let Some(id) = self.internal_quote(&invoice.payment_hash) else {
+            return false;
+        };
+        match self.mint_quotes.get_mut(&id) {
+            Some(mut q) if q.state == MintQuoteState::Unpaid => {
+                q.state = MintQuoteState::Paid;
+                // Nothing will be paid to a hold invoice settled here.
+                if self.hold_preimages.remove(&id).is_some()
+                    && let Some(backend) = &self.lightning
+                {
+                    backend.cancel_hold(&invoice.payment_hash);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Pays `request` in up to `melt_attempts` tries, each allowed a larger
+    /// share of `max_fee`, so cheap routes are used when they exist. A route
+    /// that names the fee it needs is retried with that fee if it fits.
+    /// `amount` is paid to an invoice that has none.
+    fn pay_lightning(
+        &self,
+        request: &str,
+        amount: Option<u64>,
+        max_fee: u64,
+    ) -> Result<Payment, MintError> {
+        let backend = self
+            .lightning
+            .as_ref()
+            .ok_or_else(|| MintError::PaymentFailed("no lightning backend".to_string()))?;
+        let attempts = u128::from(self.melt_attempts.max(1));
+        let mut needed = 0;
+        let mut attempt = 1;
+        loop {
+            let share = (u128::from(max_fee) * attempt / attempts) as u64;
+            let limit = share.max(needed).min(max_fee);
+            let result = match amount {
+                Some(amount) => backend.pay_amountless(request, amount, limit),
+                None => backend.pay(request, limit),
+            };
+            match result {
+                Ok(payment) => return Ok(payment),
+                Err(PaymentError::FeeTooHigh { needed: n }) if n <= max_fee => needed = n,
+                Err(PaymentError::Failed(_)) if limit < max_fee => {}
+                Err(e) => return Err(MintError::PaymentFailed(e.to_string())),
+            }
+            if attempt >= attempts {
+                return Err(MintError::PaymentFailed(
+                    "no route within fee reserve".to_string(),
+                ));
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Checks the inputs cover the quote and returns their total and the
+    /// input fee.
+    fn check_melt(&self, req: &MeltRequest, quote: &MeltQuote) -> Result<(u64, u64), MintError> {
+        self.check_canonical(req.is_canonical())?;
+        self.check_not_migrating(&req.inputs)?;
+        if req.inputs.len() > self.max_inputs {
+            return Err(MintError::TooManyInputs {
+                max: self.max_inputs,
+            });
+        }
+        if req.outputs.len() > self.max_outputs {
+            return Err(MintError::TooManyOutputs {
+                max: self.max_outputs,
+            });
+        }
+
+        let in_sum = Amount::checked_sum(req.inputs.iter().map(|n| n.value))
+            .ok_or(MintError::AmountOverflow)?;
+        let fee = self.fee(req.inputs.len());
+        let due = Amount(quote.amount)
+            .checked_add(Amount(quote.fee_reserve))
+            .and_then(|a| a.checked_add(Amount(fee)))
+            .ok_or(MintError::AmountOverflow)?;
+        if in_sum < due {
+            return Err(MintError::AmountMismatch {
+                inputs: in_sum.0,
+                outputs: quote.amount + quote.fee_reserve,
+                fee,
+            });
+        }
+
+        self.monitor.check(&self.keyset_id)?;
+        self.check_outputs_unsigned(&req.outputs)?;
+        Ok((in_sum.0, fee))
+    }
+
+    /// Signs as much of `amount` as fits on `blanks`, largest denomination
+    /// first. Change that does not fit is kept by the mint.
+    pub(crate) fn sign_change(
+        &self,
+        amount: u64,
+        blanks: &[PublicKey],
+        backed: u64,
+    ) -> Vec<(u64, PublicKey)> {
+        let values = split_amount(amount, &self.info().denominations).unwrap_or_default();
+
+        let change: Vec<(u64, PublicKey)> = values
+            .into_iter()
+            .zip(blanks)
+            .map(|(value, blinded)| {
+                self.journal.append(
+                    JournalEvent::Signed {
+                        value,
+                        blinded: *blinded,
+                        keyset_id: self.keyset_id.clone(),
+                    },
+                    self.clock.now(),
+                );
+                self.anonymity.signed(&self.keyset_id, value);
+                self.usage.issued(value);
+                self.signed_outputs
+                    .insert(*blinded, (self.keyset_id.clone(), value));
+                (value, blind_sign(&self.keys[&value].privkey, blinded))
+            })
+            .collect();
+
+        let signed = change.iter().map(|(v, _)| v).sum();
+        self.monitor
+            .record(&self.keyset_id, change.len() as u64, signed, backed);
+        change
+    }
+}
@@ -0,0 +1,812 @@
+//! The mint's keys and state. Protocol types shared with wallets live in
+//! `dmto_wallet::mint`.
+
+pub use dmto_wallet::mint::*;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use dashmap::{DashMap, DashSet};
+use rand::RngCore;
+use secp256k1::{Keypair, PublicKey, Secp256k1, SecretKey};
+
+use crate::{
+    accounts::Accounts,
+    announce::Announcements,
+    anonymity::AnonymityCounters,
+    auth::AuthGate,
+    blind::blind_sign,
+    clock::{Clock, SystemClock},
+    conditions::Witness,
+    derivation::{derive_identity, derive_keys},
+    dleq,
+    error::MintError,
+    fieldcrypt::FieldKeys,
+    hold::HoldStore,
+    issue::{InvoiceTemplate, MintQuote},
+    journal::{Journal, JournalEvent},
+    keyset::{Keyset, KeysetIdVersion},
+    lightning::LightningBackend,
+    load::{Limiter, LoadLimits},
+    melt::{FeeReserve, MeltQuote},
+    onchain::BitcoinBackend,
+    operation::OperationLog,
+    policy::SpendPolicy,
+    pool::{Priority, VerifyPool, signature_valid},
+    protocol::{Capability, Hello, Request, Response, SwapRequest, SwapResponse},
+    quota::{QuotaConfig, SigningMonitor},
+    restore::{RestoreLimits, RestoreThrottle},
+    rotation::Migration,
+    secret::{Kind, SecretPolicy, WellKnownSecret},
+    spendrecord::SpendRecord,
+    strict::JsonLimits,
+    types::{Amount, Note},
+    usage::UsageStats,
+};
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct MintKey {
+    pub value: u64,
+    pub privkey: SecretKey,
+    pub pubkey: PublicKey,
+}
+
+/// The public key determines the private one.
+impl Hash for MintKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state);
+        self.pubkey.hash(state);
+    }
+}
+
+impl fmt::Debug for MintKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MintKey")
+            .field("value", &self.value)
+            .field("privkey", &"<redacted>")
+            .field("pubkey", &self.pubkey)
+            .finish()
+    }
+}
+
+impl MintKey {
+    pub fn from_privkey(value: u64, privkey: SecretKey) -> Self {
+        let pubkey = PublicKey::from_secret_key(&Secp256k1::new(), &privkey);
+        Self {
+            value,
+            privkey,
+            pubkey,
+        }
+    }
+
+    pub fn new(value: u64) -> Self {
+        let secp = Secp256k1::new();
+        let mut sk = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut sk);
+
+        let privkey = SecretKey::new(&mut rand::thread_rng());
+        let pubkey = PublicKey::from_secret_key(&secp, &privkey);
+
+        Self {
+            value,
+            privkey,
+            pubkey,
+        }
+    }
+}
+
+pub struct Mint {
+    pub keyset_id: String,
+    pub keys: HashMap<u64, MintKey>,
+    pub spent: DashSet<Vec<u8>>,
+    /// The spent set indexed by `Y`, so state can be queried without
+    /// revealing secrets.
+    pub spent_ys: DashSet<PublicKey>,
+    /// The journal entry that spent each note, by `Y`. Filled only while
+    /// `spend_records` is on.
+    pub spend_log: DashMap<PublicKey, SpendRecord>,
+    /// Refusals for already spent inputs carry a signed record of when they
+    /// were spent (see `spendrecord`). Off by default.
+    pub spend_records: bool,
+    /// Witnesses that spent conditional notes, published so counterparties
+    /// can learn revealed preimages.
+    pub spent_witnesses: DashMap<PublicKey, Witness>,
+    /// Spending-condition kinds this mint will redeem. Well-known secrets of
+    /// any other kind are refused rather than treated as plain secrets.
+    pub accepted_kinds: Vec<Kind>,
+    /// Fee per input in parts per thousand of the smallest unit, rounded up
+    /// over the whole request.
+    pub input_fee_ppk: u64,
+    pub max_inputs: usize,
+    /// Cap on blind signatures issued for one request, change included.
+    pub max_outputs: usize,
+    /// Refuse requests whose inputs and outputs are not in canonical order
+    /// (see `canonical`). On by default.
+    pub canonical_order: bool,
+    /// Plain input secrets outside this policy are refused.
+    pub secret_policy: SecretPolicy,
+    pub limiter: Limiter,
+    pub journal: Journal,
+    pub monitor: SigningMonitor,
+    /// Keysets revoked by `emergency_rotate` whose notes can still be
+    /// swapped (see `rotation`).
+    pub migrations: Vec<Migration>,
+    /// Long-term key the mint signs announcements and bundles with.
+    pub identity: Keypair,
+    /// Whether `attest` hands out signed freshness attestations.
+    pub freshness_attestations: bool,
+    /// Worker pool for input signature checks. `None` checks inline on the
+    /// request thread.
+    pub verify_pool: Option<VerifyPool>,
+    /// Consulted for every input; `None` (the default) refuses nothing.
+    pub spend_policy: Option<Box<dyn SpendPolicy>>,
+    /// Pays melt invoices. Without one, melting is unavailable.
+    pub lightning: Option<Arc<dyn LightningBackend>>,
+    /// When set, mint quotes are hold invoices, settled only once their
+    /// signatures are in this store.
+    pub hold_store: Option<Arc<dyn HoldStore>>,
+    /// Preimages of the unsettled hold invoices, by quote ID.
+    pub hold_preimages: DashMap<String, String>,
+    /// Seals invoices and preimages in snapshots (see `fieldcrypt`). `None`
+    /// (the default) writes them in the clear.
+    pub field_keys: Option<FieldKeys>,
+    /// Pays on-chain melts. Without one, only Lightning melts are quoted.
+    pub bitcoin: Option<Arc<dyn BitcoinBackend>>,
+    /// Confirmations an on-chain deposit needs before notes are issued.
+    pub deposit_confirmations: u32,
+    /// Fee reserve for Lightning melts. `None` (the default) uses the
+    /// backend's.
+    pub fee_reserve: Option<FeeReserve>,
+    /// Routing attempts per Lightning melt, each allowed more of the fee
+    /// reserve. The unused reserve is returned as change.
+    pub melt_attempts: u32,
+    pub mint_quotes: DashMap<String, MintQuote>,
+    pub melt_quotes: DashMap<String, MeltQuote>,
+    /// Records every request made under an operation ID; `None` (the
+    /// default) records nothing.
+    pub operation_log: Option<Arc<dyn OperationLog>>,
+    /// Seconds a quote stays valid.
+    pub quote_ttl: u64,
+    /// Description and expiry of mint quote invoices.
+    pub invoice_template: InvoiceTemplate,
+    /// Unspent signatures per denomination, published to wallets.
+    pub anonymity: AnonymityCounters,
+    pub usage: UsageStats,
+    /// Every blinded message signed, with the keyset that signed it and its
+    /// denomination, so wallets can restore notes.
+    pub signed_outputs: DashMap<PublicKey, (String, u64)>,
+    pub restore_throttle: RestoreThrottle,
+    /// Limits for decoding request bodies with `decode_request`.
+    pub json_limits: JsonLimits,
+    /// Routes needing a credential; none by default.
+    pub auth: AuthGate,
+    /// Accounting mode: quotes are attributed to authenticated accounts.
+    /// `None` (the default) keeps quotes anonymous.
+    pub accounts: Option<Accounts>,
+    /// Time source for quote expiries, locktimes and attestations.
+    pub clock: Arc<dyn Clock>,
+    /// Signed notices for wallets.
+    pub announcements: Announcements,
+}
+
+/// Keyset ID: version byte `00` followed by the first 7 bytes of
+/// SHA256 over the public keys concatenated in ascending denomination order.
+pub fn keyset_id(keys: &HashMap<u64, MintKey>) -> String {
+    let pubkeys: Vec<(u64, PublicKey)> = keys.iter().map(|(&v, k)| (v, k.pubkey)).collect();
+    keyset_id_from_pubkeys(&pubkeys)
+}
+
+impl Mint {
+    pub fn new(denoms: &[u64]) -> Self {
+        let keys = denoms.iter().map(|&v| (v, MintKey::new(v))).collect();
+        Self::from_keys(keys)
+    }
+
+    /// A mint with every power-of-two denomination up to `2^max_order`.
+    pub fn power2(max_order: u32) -> Self {
+        Self::new(&Keyset::power2(max_order).denominations())
+    }
+
+    /// A mint whose keys, keyset ID and identity are all derived from
+    /// `seed`, so they are the same on every run. For tests and demos.
+    pub fn new_deterministic(seed: &[u8], denoms: &[u64]) -> Self {
+        let mut mint = Self::from_keys(derive_keys(seed, 0, denoms));
+        mint.identity = derive_identity(seed);
+        mint
+    }
+
+    pub fn from_keys(keys: HashMap<u64, MintKey>) -> Self {
+        Self {
+            keyset_id: keyset_id(&keys),
+            keys,
+            spent: DashSet::new(),
+            spent_ys: DashSet::new(),
+            spend_log: DashMap::new(),
+            spend_records: false,
+            spent_witnesses: DashMap::new(),
+            accepted_kinds: vec![Kind::P2PK, Kind::HTLC, Kind::Composite],
+            input_fee_ppk: 0,
+            max_inputs: 1000,
+            max_outputs: 1000,
+            canonical_order: true,
+            secret_policy: SecretPolicy::default(),
+            limiter: Limiter::new(LoadLimits::default()),
+            journal: Journal::new(),
+            monitor: SigningMonitor::new(QuotaConfig::default()),
+            migrations: Vec::new(),
+            identity: Keypair::new(&Secp256k1::new(), &mut rand::thread_rng()),
+            freshness_attestations: false,
+            verify_pool: None,
+            spend_policy: None,
+            lightning: None,
+            hold_store: None,
+            hold_preimages: DashMap::new(),
+            field_keys: None,
+            bitcoin: None,
+            deposit_confirmations: 3,
+            fee_reserve: None,
+            melt_attempts: 1,
+            mint_quotes: DashMap::new(),
+            melt_quotes: DashMap::new(),
+            operation_log: None,
+            quote_ttl: 3600,
+            invoice_template: InvoiceTemplate::default(),
+            anonymity: AnonymityCounters::new(),
+            usage: UsageStats::new(),
+            signed_outputs: DashMap::new(),
+            restore_throttle: RestoreThrottle::new(RestoreLimits::default()),
+            json_limits: JsonLimits::default(),
+            auth: AuthGate::new(),
+            accounts: None,
+            clock: Arc::new(SystemClock),
+            announcements: Announcements::new(),
+        }
+    }
+
+    pub fn info(&self) -> MintInfo {
+        let mut denominations: Vec<u64> = self.keys.keys().copied().collect();
+        denominations.sort();
+
+        MintInfo {
+            version: format!("dmto/{}", env!("CARGO_PKG_VERSION")),
+            keyset_id: self.keyset_id.clone(),
+            keyset_id_version: KeysetIdVersion::of(&self.keyset_id).unwrap_or_default(),
+            max_order: Keyset::from_denominations(&denominations).map(|k| k.max_order),
+            denominations,
+            accepted_kinds: self.accepted_kinds.clone(),
+            input_fee_ppk: self.input_fee_ppk,
+            fee_rounding: FeeRounding::Up,
+            max_inputs: self.max_inputs,
+            max_outputs: self.max_outputs,
+            secret_policy: self.secret_policy.clone(),
+            max_restore_batch: self.restore_throttle.limits.max_batch,
+        }
+    }
+
+    pub fn fee(&self, inputs: usize) -> u64 {
+        input_fee(inputs, self.input_fee_ppk)
+    }
+
+    pub fn hello(&self) -> Hello {
+        let mut capabilities = vec![Capability::Swap];
+        if !self.accepted_kinds.is_empty() {
+            capabilities.push(Capability::SpendingConditions);
+        }
+        Hello::new(capabilities)
+    }
+
+    pub fn handle_swap(
+        &self,
+        req: Request<SwapRequest>,
+    ) -> Result<Response<SwapResponse>, MintError> {
+        req.handle(|req| {
+            self.check_canonical(req.is_canonical())?;
+            let _permit = self.limiter.acquire()?;
+
+            let SwapRequest {
+                inputs,
+                outputs,
+                operation,
+            } = req;
+            self.traced(operation.as_deref(), "swap", || {
+                let terms = self.swap_terms(&inputs, self.fee(inputs.len()), operation.clone());
+                let signatures = self.swap(inputs, outputs.clone())?;
+                let dleqs = outputs
+                    .iter()
+                    .zip(&signatures)
+                    .map(|((value, blinded), sig)| {
+                        dleq::prove(&self.keys[value].privkey, blinded, sig)
+                    })
+                    .collect();
+                Ok(SwapResponse {
+                    signatures,
+                    dleqs,
+                    receipt: Some(self.sign_receipt(terms)),
+                })
+            })
+        })
+    }
+
+    /// Adds a note to the spent set. Returns `false` if it was already there.
+    pub fn mark_spent(&self, secret: &[u8], y: &PublicKey) -> bool {
+        if !self.spent.insert(secret.to_vec()) {
+            return false;
+        }
+        self.spent_ys.insert(*y);
+        true
+    }
+
+    /// Removes a note from the spent set, for inputs of a melt whose
+    /// payment failed.
+    pub fn unmark_spent(&self, secret: &[u8], y: &PublicKey) {
+        self.spent.remove(secret);
+        self.spent_ys.remove(y);
+        self.spend_log.remove(y);
+        self.spent_witnesses.remove(y);
+    }
+
+    pub fn check_state(&self, ys: &[PublicKey]) -> Vec<ProofState> {
+        ys.iter()
+            .map(|y| {
+                if self.spent_ys.contains(y) {
+                    ProofState::Spent
+                } else {
+                    ProofState::Unspent
+                }
+            })
+            .collect()
+    }
+
+    pub fn spent_witness(&self, y: &PublicKey) -> Option<Witness> {
+        self.spent_witnesses.get(y).map(|w| w.clone())
+    }
+
+    pub fn verify_and_spend(&self, note: &Note) -> Result<(), MintError> {
+        let key = self.check_note(note)?;
+        if !signature_valid(&key.privkey, note) {
+            return Err(MintError::InvalidSignature);
+        }
+        self.spend_verified(note)
+    }
+
+    /// Every check on an input short of its signature. Returns its key.
+    fn check_note(&self, note: &Note) -> Result<&MintKey, MintError> {
+        let key = self.input_key(note)?;
+
+        if key.value != note.value {
+            return Err(MintError::UnknownDenomination(note.value));
+        }
+
+        self.secret_policy
+            .validate(&note.secret)
+            .map_err(|e| MintError::InvalidSecret(e.to_string()))?;
+
+        if let Some(secret) = WellKnownSecret::from_bytes(&note.secret)
+            && !self.accepted_kinds.contains(&secret.kind())
+        {
+            return Err(MintError::UnsupportedSecretKind(secret.kind()));
+        }
+
+        if !note.conditions_met_at(self.clock.now()) {
+            return Err(MintError::ConditionsNotMet);
+        }
+
+        if let Some(reason) = self.spend_policy.as_ref().and_then(|p| p.refuse(note)) {
+            self.journal.append(
+                JournalEvent::Refused {
+                    y: note.y,
+                    reason: reason.clone(),
+                },
+                self.clock.now(),
+            );
+            return Err(MintError::Refused(reason));
+        }
+        Ok(key)
+    }
+
+    fn spend_verified(&self, note: &Note) -> Result<(), MintError> {
+        if !self.mark_spent(&note.secret, &note.y) {
+            return Err(MintError::AlreadySpent(
+                self.spend_record(&note.y).map(Box::new),
+            ));
+        }
+        if let Some(witness) = &note.witness {
+            self.spent_witnesses.insert(note.y, witness.clone());
+        }
+
+        let seq = self.journal.append(
+            JournalEvent::Spent {
+                secret: note.secret.clone(),
+                value: note.value,
+            },
+            self.clock.now(),
+        );
+        self.record_spend(note.y, seq);
+        self.anonymity.spent(self.input_keyset_id(note), note.value);
+        Ok(())
+    }
+
+    /// Verifies and spends `inputs`, all or none: every input is checked,
+    /// with signatures on the worker pool when one is configured, before any
+    /// is spent, and inputs spent before one that loses a race are released.
+    /// An input repeated in the request counts as already spent.
+    pub(crate) fn spend_inputs(
+        &self,
+        inputs: &[Note],
+        priority: Priority,
+    ) -> Result<(), MintError> {
+        let mut ys = HashSet::new();
+        if inputs.iter().any(|n| !ys.insert(n.y)) {
+            return Err(MintError::AlreadySpent(None));
+        }
+
+        let jobs = inputs
+            .iter()
+            .map(|n| Ok((n.clone(), self.check_note(n)?.privkey)))
+            .collect::<Result<Vec<_>, MintError>>()?;
+        let valid = match &self.verify_pool {
+            Some(pool) => !pool.verify(priority, jobs)?.contains(&false),
+            None => jobs.iter().all(|(n, key)| signature_valid(key, n)),
+        };
+        if !valid {
+            return Err(MintError::InvalidSignature);
+        }
+
+        for (i, n) in inputs.iter().enumerate() {
+            if let Err(e) = self.spend_verified(n) {
+                self.release_inputs(&inputs[..i]);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuses blinded messages this mint has signed before, or that repeat
+    /// within `outputs`. Signing the same `B_` twice would hand out two
+    /// signatures for one note, for instance to a client retrying a request.
+    pub(crate) fn check_outputs_unsigned<'a>(
+        &self,
+        outputs: impl IntoIterator<Item = &'a PublicKey>,
+    ) -> Result<(), MintError> {
+        let mut seen = HashSet::new();
+        for blinded in outputs {
+            if !seen.insert(blinded) || self.signed_outputs.contains_key(blinded) {
+                return Err(MintError::OutputAlreadySigned(*blinded));
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts a successful swap or melt of `inputs` moving `amount` in the
+    /// usage statistics.
+    pub(crate) fn record_redeemed(&self, inputs: &[Note], amount: u64) {
+        for n in inputs {
+            self.usage.redeemed(n.value);
+        }
+        self.usage.inputs(inputs.len());
+        self.usage.amount(amount);
+    }
+
+    pub fn swap(
+        &self,
+        inputs: Vec<Note>,
+        outputs: Vec<(u64, PublicKey)>,
+    ) -> Result<Vec<PublicKey>, MintError> {
+        if inputs.len() > self.max_inputs {
+            return Err(MintError::TooManyInputs {
+                max: self.max_inputs,
+            });
+        }
+        if outputs.len() > self.max_outputs {
+            return Err(MintError::TooManyOutputs {
+                max: self.max_outputs,
+            });
+        }
+
+        let in_sum =
+            Amount::checked_sum(inputs.iter().map(|n| n.value)).ok_or(MintError::AmountOverflow)?;
+        let out_sum = Amount::checked_sum(outputs.iter().map(|(v, _)| *v))
+            .ok_or(MintError::AmountOverflow)?;
+        let fee = Amount(self.fee(inputs.len()));
+        let due = out_sum.checked_add(fee).ok_or(MintError::AmountOverflow)?;
+        if in_sum != due {
+            return Err(MintError::AmountMismatch {
+                inputs: in_sum.0,
+                outputs: out_sum.0,
+                fee: fee.0,
+            });
+        }
+
+        // Resolved before anything is spent, so an output of a denomination
+        // the keyset lacks refuses the swap with the inputs untouched.
+        let keys = outputs
+            .iter()
+            .map(|&(value, _)| {
+                self.keys
+                    .get(&value)
+                    .ok_or(MintError::UnknownDenomination(value))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.monitor.check(&self.keyset_id)?;
+        self.check_outputs_unsigned(outputs.iter().map(|(_, b)| b))?;
+
+        let migrated = self.reserve_migrated(&inputs)?;
+        if let Err(e) = self.spend_inputs(&inputs, Priority::Swap) {
+            self.release_migrated(&migrated);
+            return Err(e);
+        }
+        self.record_redeemed(&inputs, out_sum.0);
+        if fee.0 > 0 {
+            self.journal
+                .append(JournalEvent::Fee { amount: fee.0 }, self.clock.now());
+        }
+
+        let mut sigs = Vec::new();
+        for ((value, blinded), key) in outputs.into_iter().zip(keys) {
+            sigs.push(blind_sign(&key.privkey, &blinded));
+            self.anonymity.signed(&self.keyset_id, value);
+            self.usage.issued(value);
+            self.signed_outputs
+                .insert(blinded, (self.keyset_id.clone(), value));
+            self.journal.append(
+                JournalEvent::Signed {
+                    value,
+                    blinded,
+                    keyset_id: self.keyset_id.clone(),
+                },
+                self.clock.now(),
+            );
+        }
+        self.monitor
+            .record(&self.keyset_id, sigs.len() as u64, out_sum.0, in_sum.0);
+
+        Ok(sigs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::RngCore;
+
+    use super::*;
+    use crate::{
+        blind::blind_message,
+        canonical::{sort_inputs, sort_outputs},
+        conditions::Condition,
+        error::WalletError,
+        hash::hash_to_curve,
+        protocol::PROTOCOL_VERSION,
+        testing::TestMintBuilder,
+        token::Token,
+        wallet::Wallet,
+    };
+
+    /// Notes of `values` signed directly by `mint`.
+    fn notes(mint: &Mint, values: &[u64]) -> Vec<Note> {
+        let mut wallet = Wallet::new();
+        for &value in values {
+            wallet.mint_note(mint, value);
+        }
+        wallet.notes.take_all()
+    }
+
+    fn outputs(values: &[u64]) -> Vec<(u64, PublicKey)> {
+        values
+            .iter()
+            .map(|&value| {
+                let mut secret = [0; 32];
+                rand::thread_rng().fill_bytes(&mut secret);
+                (value, blind_message(&hash_to_curve(&secret)).blinded_point)
+            })
+            .collect()
+    }
+
+    fn all_unspent(mint: &Mint, notes: &[Note]) -> bool {
+        let ys: Vec<_> = notes.iter().map(|n| n.y).collect();
+        mint.check_state(&ys)
+            .iter()
+            .all(|s| *s == ProofState::Unspent)
+    }
+
+    #[test]
+    fn invalid_input_leaves_the_others_unspent() {
+        let mint = TestMintBuilder::new().build();
+        let mut inputs = notes(&mint, &[4, 2]);
+        inputs[1].c = inputs[0].c;
+
+        let result = mint.swap(inputs.clone(), outputs(&[4, 2]));
+        assert_eq!(result, Err(MintError::InvalidSignature));
+        assert!(all_unspent(&mint, &inputs));
+    }
+
+    #[test]
+    fn repeated_input_is_refused_before_spending() {
+        let mint = TestMintBuilder::new().build();
+        let note = notes(&mint, &[4]).remove(0);
+        let inputs = vec![note.clone(), note];
+
+        let result = mint.swap(inputs.clone(), outputs(&[8]));
+        assert_eq!(result, Err(MintError::AlreadySpent(None)));
+        assert!(all_unspent(&mint, &inputs));
+    }
+
+    #[test]
+    fn spent_input_releases_the_ones_before_it() {
+        let mint = TestMintBuilder::new().build();
+        let inputs = notes(&mint, &[4, 2]);
+        mint.mark_spent(&inputs[1].secret, &inputs[1].y);
+
+        assert!(matches!(
+            mint.swap(inputs.clone(), outputs(&[4, 2])),
+            Err(MintError::AlreadySpent(_))
+        ));
+        assert!(all_unspent(&mint, &inputs[..1]));
+    }
+
+    #[test]
+    fn locked_send_is_not_cancellable() {
+        let mint = TestMintBuilder::new().build();
+        let mut wallet = Wallet::new();
+        wallet.mint_note(&mint, 8);
+        wallet.mint_note(&mint, 4);
+        let payee = SecretKey::new(&mut rand::thread_rng()).public_key(&Secp256k1::new());
+
+        let locked = wallet
+            .send_locked(&mint, 4, &Condition::p2pk(payee))
+            .unwrap();
+        let id = Token::new("", locked.clone()).id();
+        assert!(wallet.history.iter().any(|tx| tx.id == id));
+        assert_eq!(
+            wallet.cancel_send(&mint, &id),
+            Err(WalletError::UnknownSend(id))
+        );
+        assert!(all_unspent(&mint, &locked));
+
+        let token = wallet.send_offline(&mint, "", 8).unwrap();
+        assert_eq!(wallet.cancel_send(&mint, &token.id()), Ok(8));
+    }
+
+    #[test]
+    fn unknown_keyset_is_refused() {
+        let mint = TestMintBuilder::new().build();
+        let mut inputs = notes(&mint, &[4]);
+        inputs[0].keyset_id = "00deadbeefdeadbe".to_string();
+
+        assert_eq!(
+            mint.swap(inputs.clone(), outputs(&[4])),
+            Err(MintError::KeysetUnknown(inputs[0].keyset_id.clone()))
+        );
+        assert!(all_unspent(&mint, &inputs));
+    }
+
+    #[test]
+    fn unknown_output_denomination_leaves_the_inputs_unspent() {
+        let mint = TestMintBuilder::new().build();
+        let inputs = notes(&mint, &[2, 1]);
+
+        assert_eq!(
+            mint.swap(inputs.clone(), outputs(&[3])),
+            Err(MintError::UnknownDenomination(3))
+        );
+        assert!(all_unspent(&mint, &inputs));
+    }
+
+    #[test]
+    fn repeated_blinded_message_is_refused() {
+        let mint = TestMintBuilder::new().build();
+
+        let inputs = notes(&mint, &[2, 2]);
+        let twice = vec![outputs(&[2])[0]; 2];
+        assert_eq!(
+            mint.swap(inputs.clone(), twice.clone()),
+            Err(MintError::OutputAlreadySigned(twice[0].1))
+        );
+        assert!(all_unspent(&mint, &inputs));
+
+        let signed = outputs(&[4]);
+        mint.swap(inputs, signed.clone()).unwrap();
+        let inputs = notes(&mint, &[4]);
+        assert_eq!(
+            mint.swap(inputs.clone(), signed.clone()),
+            Err(MintError::OutputAlreadySigned(signed[0].1))
+        );
+        assert!(all_unspent(&mint, &inputs));
+    }
+
+    #[test]
+    fn out_of_order_outputs_are_refused() {
+        let mint = TestMintBuilder::new().build();
+        let swap = |inputs: &[Note], outputs: Vec<(u64, PublicKey)>| {
+            mint.handle_swap(Request {
+                version: PROTOCOL_VERSION,
+                body: SwapRequest {
+                    inputs: inputs.to_vec(),
+                    outputs,
+                    operation: None,
+                },
+            })
+        };
+
+        let mut inputs = notes(&mint, &[4, 2]);
+        sort_inputs(&mut inputs);
+        let mut reversed = outputs(&[4, 2]);
+        sort_outputs(&mut reversed);
+        reversed.reverse();
+        assert!(matches!(
+            swap(&inputs, reversed.clone()),
+            Err(MintError::BadRequest(_))
+        ));
+        assert!(all_unspent(&mint, &inputs));
+
+        reversed.reverse();
+        assert!(swap(&inputs, reversed).is_ok());
+    }
+
+    #[test]
+    fn wallet_canonicalizes_outputs_and_returns_them_as_asked() {
+        let mint = TestMintBuilder::new().build();
+        let mut wallet = Wallet::new();
+        wallet.mint_note(&mint, 8);
+
+        let sent = wallet.split_out(&mint, &[1, 4, 2]).unwrap();
+        let values: Vec<u64> = sent.iter().map(|n| n.value).collect();
+        assert_eq!(values, [1, 4, 2]);
+    }
+
+    #[test]
+    fn revoked_notes_migrate_once() {
+        let mut mint = TestMintBuilder::new().build();
+        let mut wallet = Wallet::new();
+        wallet.mint_note(&mint, 8);
+        let old = wallet.split_out(&mint, &[4, 4]).unwrap();
+        let rotation = mint.emergency_rotate(3600);
+        let remaining = || mint.migrations[0].remaining.lock().unwrap()[&4];
+        assert_eq!(remaining(), 2);
+
+        // A swap that fails after reserving gives its share back.
+        let mut forged = old.clone();
+        forged[1].c = forged[0].c;
+        assert_eq!(
+            mint.swap(forged, outputs(&[8])),
+            Err(MintError::InvalidSignature)
+        );
+        assert_eq!(remaining(), 2);
+
+        mint.swap(old.clone(), outputs(&[8])).unwrap();
+        assert_eq!(remaining(), 0);
+        assert!(matches!(
+            mint.swap(old, outputs(&[8])),
+            Err(MintError::Refused(reason)) if reason.contains(&rotation.revoked)
+        ));
+    }
+
+    #[test]
+    fn spend_record_points_at_the_spending_entry() {
+        let mut mint = TestMintBuilder::new().build();
+        mint.spend_records = true;
+        let inputs = notes(&mint, &[4, 2]);
+        mint.swap(inputs.clone(), outputs(&[4, 2])).unwrap();
+
+        let identity = mint.identity.x_only_public_key().0;
+        for note in &inputs {
+            let signed = mint.spend_record(&note.y).unwrap();
+            assert!(signed.covers(note) && signed.verify(&identity));
+            let entry = mint.journal.get(signed.record.seq).unwrap();
+            assert!(matches!(
+                entry.event,
+                JournalEvent::Spent { secret, .. } if secret == note.secret
+            ));
+        }
+
+        let record = mint.spend_record(&inputs[0].y).unwrap().record;
+        assert!(matches!(
+            mint.swap(inputs, outputs(&[4, 2])),
+            Err(MintError::AlreadySpent(Some(signed))) if signed.record == record
+        ));
+    }
+}
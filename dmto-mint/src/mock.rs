@@ -0,0 +1,232 @@
+//! A `MintTrait` implementation for wallet tests: a real in-process mint
+//! behind a script of faults to inject, one per call, in order.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+use crate::{
+    announce::Announcement,
+    anonymity::AnonymitySet,
+    api::{KeysetKeys, MintReader, MintTrait},
+    batchmelt::{BatchMeltRequest, BatchMeltResponse},
+    error::MintError,
+    issue::{MintQuote, MintRequest, MintResponse},
+    melt::{MeltQuote, MeltRequest, MeltResponse},
+    mint::{Mint, MintInfo, ProofState},
+    protocol::{Hello, Request, Response, SwapRequest, SwapResponse},
+    restore::{RestoreRequest, RestoreResponse},
+    stats::DashboardStats,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Call {
+    Info,
+    Keysets,
+    Hello,
+    Swap,
+    CheckState,
+    MeltQuote,
+    Melt,
+    MeltQuoteState,
+    MeltBatch,
+    MintQuote,
+    MintQuoteOnchain,
+    GetQuote,
+    Mint,
+    AnonymitySets,
+    Restore,
+    Stats,
+    Announcements,
+}
+
+#[derive(Clone, Debug)]
+pub enum Fault {
+    /// Sleep before handling the call.
+    Delay(Duration),
+    /// Fail without reaching the mint.
+    Fail(MintError),
+    /// Handle the call, then lose the response as a flaky network would.
+    /// For swaps the inputs are spent but the wallet sees an error.
+    DropResponse,
+    /// Swap only: replace the first blind signature with a random point.
+    WrongSignature,
+    /// Swap only: return just the first `n` signatures.
+    Truncate(usize),
+}
+
+pub struct MockMint {
+    pub mint: Mint,
+    script: Mutex<HashMap<Call, VecDeque<Fault>>>,
+    calls: Mutex<Vec<Call>>,
+}
+
+fn dropped() -> MintError {
+    MintError::Unknown {
+        code: 0,
+        detail: "connection reset".to_string(),
+    }
+}
+
+impl MockMint {
+    pub fn new(mint: Mint) -> Self {
+        Self {
+            mint,
+            script: Mutex::new(HashMap::new()),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Queues `fault` for the next `call` not already scripted. Calls with an
+    /// empty script behave normally.
+    pub fn inject(&self, call: Call, fault: Fault) -> &Self {
+        self.script
+            .lock()
+            .unwrap()
+            .entry(call)
+            .or_default()
+            .push_back(fault);
+        self
+    }
+
+    /// Every call made so far, in order.
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Logs `call` and runs its next scripted fault, if any. Returns the
+    /// fault left to apply after handling.
+    fn begin(&self, call: Call) -> Result<Option<Fault>, MintError> {
+        self.calls.lock().unwrap().push(call);
+        let fault = self
+            .script
+            .lock()
+            .unwrap()
+            .get_mut(&call)
+            .and_then(VecDeque::pop_front);
+        match fault {
+            Some(Fault::Delay(d)) => {
+                thread::sleep(d);
+                Ok(None)
+            }
+            Some(Fault::Fail(err)) => Err(err),
+            other => Ok(other),
+        }
+    }
+
+    fn plain<T>(&self, call: Call, f: impl FnOnce() -> T) -> Result<T, MintError> {
+        let fault = self.begin(call)?;
+        let result = f();
+        match fault {
+            Some(Fault::DropResponse) => Err(dropped()),
+            _ => Ok(result),
+        }
+    }
+}
+
+impl MintReader for MockMint {
+    fn info(&self) -> Result<MintInfo, MintError> {
+        self.plain(Call::Info, || self.mint.info())
+    }
+
+    fn keysets(&self) -> Result<Vec<KeysetKeys>, MintError> {
+        self.plain(Call::Keysets, || MintReader::keysets(&self.mint))?
+    }
+
+    fn hello(&self) -> Result<Hello, MintError> {
+        self.plain(Call::Hello, || self.mint.hello())
+    }
+
+    fn check_state(&self, ys: &[PublicKey]) -> Result<Vec<ProofState>, MintError> {
+        self.plain(Call::CheckState, || self.mint.check_state(ys))
+    }
+
+    fn restore_signatures(
+        &self,
+        req: Request<RestoreRequest>,
+    ) -> Result<Response<RestoreResponse>, MintError> {
+        self.plain(Call::Restore, || self.mint.restore_signatures(req))?
+    }
+}
+
+impl MintTrait for MockMint {
+    fn handle_swap(&self, req: Request<SwapRequest>) -> Result<Response<SwapResponse>, MintError> {
+        let fault = self.begin(Call::Swap)?;
+        let mut resp = self.mint.handle_swap(req)?;
+        match fault {
+            Some(Fault::DropResponse) => return Err(dropped()),
+            Some(Fault::WrongSignature) => {
+                if let Some(sig) = resp.body.signatures.first_mut() {
+                    let sk = SecretKey::new(&mut rand::thread_rng());
+                    *sig = PublicKey::from_secret_key(&Secp256k1::new(), &sk);
+                }
+            }
+            Some(Fault::Truncate(n)) => {
+                resp.body.signatures.truncate(n);
+                resp.body.dleqs.truncate(n);
+            }
+            _ => {}
+        }
+        Ok(resp)
+    }
+
+    fn melt_quote(&self, request: &str) -> Result<MeltQuote, MintError> {
+        self.plain(Call::MeltQuote, || self.mint.melt_quote(request))?
+    }
+
+    fn melt_quote_with_amount(&self, request: &str, amount: u64) -> Result<MeltQuote, MintError> {
+        self.plain(Call::MeltQuote, || {
+            self.mint.melt_quote_with_amount(request, amount)
+        })?
+    }
+
+    fn melt(&self, req: Request<MeltRequest>) -> Result<Response<MeltResponse>, MintError> {
+        self.plain(Call::Melt, || self.mint.melt(req))?
+    }
+
+    fn melt_quote_state(&self, id: &str) -> Result<MeltQuote, MintError> {
+        self.plain(Call::MeltQuoteState, || self.mint.melt_quote_state(id))?
+    }
+
+    fn melt_batch(
+        &self,
+        req: Request<BatchMeltRequest>,
+    ) -> Result<Response<BatchMeltResponse>, MintError> {
+        self.plain(Call::MeltBatch, || self.mint.melt_batch(req))?
+    }
+
+    fn mint_quote(&self, amount: u64) -> Result<MintQuote, MintError> {
+        self.plain(Call::MintQuote, || self.mint.mint_quote(amount))?
+    }
+
+    fn mint_quote_onchain(&self, amount: u64) -> Result<MintQuote, MintError> {
+        self.plain(Call::MintQuoteOnchain, || {
+            self.mint.mint_quote_onchain(amount)
+        })?
+    }
+
+    fn get_quote(&self, id: &str) -> Result<MintQuote, MintError> {
+        self.plain(Call::GetQuote, || self.mint.get_quote(id))?
+    }
+
+    fn mint(&self, req: Request<MintRequest>) -> Result<Response<MintResponse>, MintError> {
+        self.plain(Call::Mint, || self.mint.mint(req))?
+    }
+
+    fn anonymity_sets(&self) -> Result<Vec<AnonymitySet>, MintError> {
+        self.plain(Call::AnonymitySets, || self.mint.anonymity_sets())
+    }
+
+    fn dashboard_stats(&self) -> Result<DashboardStats, MintError> {
+        self.plain(Call::Stats, || self.mint.dashboard_stats())
+    }
+
+    fn announcements(&self) -> Result<Vec<Announcement>, MintError> {
+        self.plain(Call::Announcements, || self.mint.announcements())
+    }
+}
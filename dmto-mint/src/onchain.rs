@@ -0,0 +1,88 @@
+//! On-chain mint and melt quotes. See `dmto_wallet::onchain` for the
+//! protocol and the backend trait.
+
+pub use dmto_wallet::onchain::*;
+
+use crate::{
+    error::MintError,
+    issue::{MintQuote, MintQuoteState},
+    melt::{MeltQuote, quote_id},
+    mint::Mint,
+};
+
+impl Mint {
+    fn bitcoin(&self) -> Result<&dyn BitcoinBackend, MintError> {
+        self.bitcoin
+            .as_deref()
+            .ok_or_else(|| MintError::PaymentFailed("no bitcoin backend".to_string()))
+    }
+
+    /// Quotes a deposit of `amount` to a fresh address. Like `mint_quote`,
+    /// refused in accounting mode.
+    pub fn mint_quote_onchain(&self, amount: u64) -> Result<MintQuote, MintError> {
+        if self.accounts.is_some() {
+            return Err(MintError::Unauthorized("account required".to_string()));
+        }
+        let address = self
+            .bitcoin()?
+            .new_address()
+            .map_err(|e| MintError::PaymentFailed(e.to_string()))?;
+
+        let quote = MintQuote {
+            id: quote_id(),
+            request: payment_uri(&address, amount),
+            amount,
+            state: MintQuoteState::Unpaid,
+            expiry: self.clock.now() + self.quote_ttl,
+        };
+        self.mint_quotes.insert(quote.id.clone(), quote.clone());
+        Ok(quote)
+    }
+
+    /// Whether the deposit a mint quote asks for has enough confirmations.
+    pub(crate) fn deposit_confirmed(&self, request: &str) -> bool {
+        let (Some(backend), Some((address, amount))) = (&self.bitcoin, parse_payment_uri(request))
+        else {
+            return false;
+        };
+        backend.received(&address, self.deposit_confirmations) >= amount
+    }
+
+    /// The amount, fee reserve and fee rate to quote an on-chain payment
+    /// at, using the backend's current rate.
+    pub(crate) fn onchain_terms(&self, request: &str) -> Result<(u64, u64, u64), MintError> {
+        let backend = self.bitcoin()?;
+        let invalid = || MintError::PaymentFailed("invalid payment request".to_string());
+        let (address, amount) = parse_payment_uri(request).ok_or_else(invalid)?;
+        if !backend.check_address(&address) || amount == 0 {
+            return Err(invalid());
+        }
+
+        let fee_rate = backend.fee_rate();
+        Ok((amount, fee_rate.saturating_mul(PAYOUT_VSIZE), fee_rate))
+    }
+
+    /// Pays an on-chain quote at the rate it was quoted at.
+    pub(crate) fn pay_onchain(
+        &self,
+        quote: &MeltQuote,
+        max_fee: u64,
+    ) -> Result<OnchainPayment, MintError> {
+        let backend = self.bitcoin()?;
+        let (address, amount) = parse_payment_uri(&quote.request)
+            .ok_or_else(|| MintError::PaymentFailed("invalid payment request".to_string()))?;
+        let fee_rate = quote.fee_rate.unwrap_or_else(|| backend.fee_rate());
+        backend
+            .send(&address, amount, fee_rate, max_fee)
+            .map_err(|e| MintError::PaymentFailed(e.to_string()))
+    }
+
+    /// Brings a paid on-chain quote's confirmation count up to date.
+    pub(crate) fn refresh_confirmations(&self, quote: &mut MeltQuote) {
+        if let (Some(txid), Some(backend)) = (&quote.txid, &self.bitcoin)
+            && let Some(confirmations) = backend.confirmations(txid)
+        {
+            quote.confirmations = confirmations;
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! Recording the outcome of traced operations. See
+//! `dmto_wallet::operation`.
+
+pub use dmto_wallet::operation::*;
+
+use crate::{error::MintError, mint::Mint};
+
+impl Mint {
+    /// Runs `f` as `operation`, recording the outcome in the operation log.
+    pub(crate) fn traced<T>(
+        &self,
+        operation: Option<&str>,
+        action: &str,
+        f: impl FnOnce() -> Result<T, MintError>,
+    ) -> Result<T, MintError> {
+        if operation.is_some_and(|id| !valid_id(id)) {
+            return Err(MintError::BadRequest("invalid operation ID".to_string()));
+        }
+        let _guard = enter(operation);
+        let result = f();
+        if let (Some(log), Some(operation)) = (&self.operation_log, operation) {
+            log.record(operation, action, result.as_ref().err());
+        }
+        result
+    }
+}
@@ -0,0 +1,40 @@
+//! Optional hook letting an operator refuse specific notes, for example on a
+//! regulator's request. Refusals are written to the journal.
+
+use std::sync::Arc;
+
+use dashmap::DashSet;
+use secp256k1::PublicKey;
+
+use crate::types::Note;
+
+pub trait SpendPolicy: Send + Sync {
+    /// A reason to refuse spending `note`, or `None` to allow it.
+    fn refuse(&self, note: &Note) -> Option<String>;
+}
+
+/// Lets the operator keep a handle to a policy installed on the mint.
+impl<P: SpendPolicy> SpendPolicy for Arc<P> {
+    fn refuse(&self, note: &Note) -> Option<String> {
+        (**self).refuse(note)
+    }
+}
+
+/// Refuses notes whose `Y` is listed. Entries can be added while the mint
+/// is running.
+#[derive(Default)]
+pub struct Blacklist {
+    pub ys: DashSet<PublicKey>,
+}
+
+impl Blacklist {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SpendPolicy for Blacklist {
+    fn refuse(&self, note: &Note) -> Option<String> {
+        self.ys.contains(&note.y).then(|| "blacklisted".to_string())
+    }
+}
@@ -0,0 +1,174 @@
+//! Worker threads for input signature checks, so one large swap is spread
+//! across cores and queued work is served melts first.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex, mpsc},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use secp256k1::{Secp256k1, SecretKey};
+
+use crate::{error::MintError, hash::try_hash_to_curve, types::Note};
+
+/// Queue priority. Melts settle external payments and are latency
+/// sensitive, so they are always taken before swaps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    Melt,
+    Swap,
+}
+
+#[derive(Clone, Debug)]
+pub struct PoolConfig {
+    pub workers: usize,
+    /// Most notes waiting across both priorities. Requests that do not fit
+    /// are turned away whole.
+    pub max_queued: usize,
+    pub retry_after: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            max_queued: 4096,
+            retry_after: Duration::from_millis(200),
+        }
+    }
+}
+
+struct Job {
+    index: usize,
+    note: Note,
+    key: SecretKey,
+    reply: mpsc::Sender<(usize, bool)>,
+}
+
+#[derive(Default)]
+struct Queue {
+    melt: VecDeque<Job>,
+    swap: VecDeque<Job>,
+    shutdown: bool,
+}
+
+impl Queue {
+    fn len(&self) -> usize {
+        self.melt.len() + self.swap.len()
+    }
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+}
+
+pub struct VerifyPool {
+    pub config: PoolConfig,
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+/// Whether `note.c` is `key·Y` for `Y = hash_to_curve(secret)`.
+pub fn signature_valid(key: &SecretKey, note: &Note) -> bool {
+    try_hash_to_curve(&note.secret).is_ok_and(|y| y == note.y)
+        && note
+            .y
+            .mul_tweak(&Secp256k1::new(), &(*key).into())
+            .is_ok_and(|expected| expected == note.c)
+}
+
+impl VerifyPool {
+    pub fn new(config: PoolConfig) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue::default()),
+            ready: Condvar::new(),
+        });
+        let workers = (0..config.workers.max(1))
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || work(&shared))
+            })
+            .collect();
+
+        Self {
+            config,
+            shared,
+            workers,
+        }
+    }
+
+    pub fn queued(&self) -> usize {
+        self.shared.queue.lock().unwrap().len()
+    }
+
+    /// Checks each note's signature against its key and returns the results
+    /// in order. Fails with `Overloaded` if the queue has no room for all of
+    /// them.
+    pub fn verify(
+        &self,
+        priority: Priority,
+        notes: Vec<(Note, SecretKey)>,
+    ) -> Result<Vec<bool>, MintError> {
+        let count = notes.len();
+        let (reply, results) = mpsc::channel();
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            if queue.len() + count > self.config.max_queued {
+                return Err(MintError::Overloaded {
+                    retry_after_ms: self.config.retry_after.as_millis() as u64,
+                });
+            }
+            let lane = match priority {
+                Priority::Melt => &mut queue.melt,
+                Priority::Swap => &mut queue.swap,
+            };
+            for (index, (note, key)) in notes.into_iter().enumerate() {
+                lane.push_back(Job {
+                    index,
+                    note,
+                    key,
+                    reply: reply.clone(),
+                });
+            }
+        }
+        self.shared.ready.notify_all();
+        drop(reply);
+
+        let mut valid = vec![false; count];
+        for (index, ok) in results {
+            valid[index] = ok;
+        }
+        Ok(valid)
+    }
+}
+
+fn work(shared: &Shared) {
+    loop {
+        let job = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if let Some(job) = queue.melt.pop_front().or_else(|| queue.swap.pop_front()) {
+                    break job;
+                }
+                if queue.shutdown {
+                    return;
+                }
+                queue = shared.ready.wait(queue).unwrap();
+            }
+        };
+        let ok = signature_valid(&job.key, &job.note);
+        let _ = job.reply.send((job.index, ok));
+    }
+}
+
+impl Drop for VerifyPool {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().shutdown = true;
+        self.shared.ready.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
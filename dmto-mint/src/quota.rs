@@ -0,0 +1,132 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::error::MintError;
+
+#[derive(Clone, Debug)]
+pub struct QuotaConfig {
+    pub window: Duration,
+    /// Maximum blind signatures per keyset per window.
+    pub max_signatures: u64,
+    /// Stop signing with a keyset once it raises an alarm, until an operator
+    /// calls `resume`.
+    pub auto_pause: bool,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(60),
+            max_signatures: 100_000,
+            auto_pause: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Alarm {
+    QuotaExceeded {
+        keyset_id: String,
+        signatures: u64,
+        limit: u64,
+    },
+    /// More value was signed than was redeemed or paid for in the window,
+    /// which honest operation never does.
+    UnbackedIssuance {
+        keyset_id: String,
+        signed: u64,
+        backed: u64,
+    },
+}
+
+#[derive(Default)]
+struct Window {
+    started: Option<Instant>,
+    signatures: u64,
+    signed: u64,
+    backed: u64,
+}
+
+#[derive(Default)]
+struct State {
+    windows: HashMap<String, Window>,
+    paused: HashSet<String>,
+    alarms: Vec<Alarm>,
+}
+
+/// Early warning for key compromise: counts signatures issued per keyset in
+/// a rolling window and compares issued value with value that backs it.
+#[derive(Default)]
+pub struct SigningMonitor {
+    pub config: QuotaConfig,
+    state: Mutex<State>,
+}
+
+impl SigningMonitor {
+    pub fn new(config: QuotaConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn check(&self, keyset_id: &str) -> Result<(), MintError> {
+        if self.state.lock().unwrap().paused.contains(keyset_id) {
+            return Err(MintError::KeysetPaused(keyset_id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Records `signatures` blind signatures worth `signed` in total, issued
+    /// against `backed` worth of redeemed inputs or paid quotes.
+    pub fn record(&self, keyset_id: &str, signatures: u64, signed: u64, backed: u64) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        let window = state.windows.entry(keyset_id.to_string()).or_default();
+        if window
+            .started
+            .is_none_or(|t| now.duration_since(t) >= self.config.window)
+        {
+            *window = Window {
+                started: Some(now),
+                ..Window::default()
+            };
+        }
+        window.signatures += signatures;
+        window.signed += signed;
+        window.backed += backed;
+
+        let mut raised = Vec::new();
+        if window.signatures > self.config.max_signatures {
+            raised.push(Alarm::QuotaExceeded {
+                keyset_id: keyset_id.to_string(),
+                signatures: window.signatures,
+                limit: self.config.max_signatures,
+            });
+        }
+        if window.signed > window.backed {
+            raised.push(Alarm::UnbackedIssuance {
+                keyset_id: keyset_id.to_string(),
+                signed: window.signed,
+                backed: window.backed,
+            });
+        }
+
+        if !raised.is_empty() && self.config.auto_pause {
+            state.paused.insert(keyset_id.to_string());
+        }
+        state.alarms.extend(raised);
+    }
+
+    pub fn resume(&self, keyset_id: &str) {
+        self.state.lock().unwrap().paused.remove(keyset_id);
+    }
+
+    pub fn take_alarms(&self) -> Vec<Alarm> {
+        std::mem::take(&mut self.state.lock().unwrap().alarms)
+    }
+}
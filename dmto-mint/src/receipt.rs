@@ -0,0 +1,56 @@
+//! Issuing signed fee receipts. See `dmto_wallet::receipt` for the format.
+
+pub use dmto_wallet::receipt::*;
+
+use crate::{melt::MeltQuote, mint::Mint, signing, types::Note};
+
+impl Mint {
+    /// Terms of a swap of `inputs` charged `input_fee`, to be signed once
+    /// the swap went through.
+    pub(crate) fn swap_terms(
+        &self,
+        inputs: &[Note],
+        input_fee: u64,
+        operation: Option<String>,
+    ) -> FeeTerms {
+        FeeTerms {
+            kind: ReceiptKind::Swap,
+            inputs: inputs_hash(inputs),
+            input_total: inputs.iter().map(|n| n.value).sum(),
+            input_fee,
+            quote: None,
+            fee_reserve: 0,
+            network_fee: 0,
+            operation,
+            issued_at: self.clock.now(),
+        }
+    }
+
+    /// Signs a receipt for a melt of `inputs` against `quote`, which paid
+    /// `network_fee` out of its reserve.
+    pub(crate) fn melt_receipt(
+        &self,
+        inputs: &[Note],
+        input_fee: u64,
+        quote: &MeltQuote,
+        network_fee: u64,
+        operation: Option<String>,
+    ) -> FeeReceipt {
+        self.sign_receipt(FeeTerms {
+            kind: ReceiptKind::Melt,
+            inputs: inputs_hash(inputs),
+            input_total: inputs.iter().map(|n| n.value).sum(),
+            input_fee,
+            quote: Some(quote.id.clone()),
+            fee_reserve: quote.fee_reserve,
+            network_fee,
+            operation,
+            issued_at: self.clock.now(),
+        })
+    }
+
+    pub(crate) fn sign_receipt(&self, terms: FeeTerms) -> FeeReceipt {
+        let signature = signing::sign(&self.identity, &terms.message());
+        FeeReceipt { terms, signature }
+    }
+}
@@ -0,0 +1,286 @@
+//! Offline replay of the mint journal, for debugging inconsistencies found
+//! in production. A `Replayer` applies journal entries one at a time to a
+//! fresh mint, or to one restored from a snapshot, reporting what each entry
+//! changed and flagging entries that make no sense against the state
+//! before them, e.g. a secret spent twice. It can stop before the entries
+//! of chosen operations, and `MintState` diffs the replayed state against
+//! another mint's, such as a later production snapshot. `dmto-replay` runs
+//! it from the command line.
+//!
+//! Replay goes through `Mint::apply_journal`, as a replica does, so only
+//! the spent set and the signed outputs are rebuilt. Fees and refusals are
+//! tallied by the replayer.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt, io,
+};
+
+use crate::{
+    codec::to_hex,
+    journal::{JournalEntry, JournalEvent},
+    mint::Mint,
+};
+
+/// The parts of a mint's state the journal rebuilds, hex encoded.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MintState {
+    /// Secrets in the spent set.
+    pub spent: BTreeSet<String>,
+    /// Signed blinded messages and their denominations.
+    pub signed: BTreeMap<String, u64>,
+}
+
+impl MintState {
+    pub fn of(mint: &Mint) -> Self {
+        Self {
+            spent: mint.spent.iter().map(|s| to_hex(&s)).collect(),
+            signed: mint
+                .signed_outputs
+                .iter()
+                .map(|e| (to_hex(&e.key().serialize()), e.value().1))
+                .collect(),
+        }
+    }
+
+    /// What changed going from `self` to `other`.
+    pub fn diff(&self, other: &MintState) -> StateDiff {
+        let signed_in = |a: &MintState, b: &MintState| -> Vec<(String, u64)> {
+            a.signed
+                .iter()
+                .filter(|(k, v)| b.signed.get(*k) != Some(v))
+                .map(|(k, v)| (k.clone(), *v))
+                .collect()
+        };
+        StateDiff {
+            spent: other.spent.difference(&self.spent).cloned().collect(),
+            released: self.spent.difference(&other.spent).cloned().collect(),
+            signed: signed_in(other, self),
+            unsigned: signed_in(self, other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    /// Secrets added to the spent set.
+    pub spent: Vec<String>,
+    /// Secrets removed from it.
+    pub released: Vec<String>,
+    /// Signed outputs added, or whose denomination changed.
+    pub signed: Vec<(String, u64)>,
+    /// Signed outputs removed, or whose denomination changed.
+    pub unsigned: Vec<(String, u64)>,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.spent.is_empty()
+            && self.released.is_empty()
+            && self.signed.is_empty()
+            && self.unsigned.is_empty()
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+        let mut lines = Vec::new();
+        lines.extend(self.spent.iter().map(|s| format!("+spent  {}", s)));
+        lines.extend(self.released.iter().map(|s| format!("-spent  {}", s)));
+        lines.extend(
+            self.signed
+                .iter()
+                .map(|(b, v)| format!("+signed {} ({})", b, v)),
+        );
+        lines.extend(
+            self.unsigned
+                .iter()
+                .map(|(b, v)| format!("-signed {} ({})", b, v)),
+        );
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// One applied entry.
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub entry: JournalEntry,
+    /// What the entry changed.
+    pub diff: StateDiff,
+    /// Why the entry is inconsistent with the state before it, if it is.
+    pub anomaly: Option<String>,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} [{}] {}",
+            self.entry.seq,
+            self.entry.operation.as_deref().unwrap_or("-"),
+            self.entry.event
+        )?;
+        if let Some(anomaly) = &self.anomaly {
+            write!(f, "\n  !! {}", anomaly)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why a replay stopped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// Before entry `seq`, of a breakpoint operation.
+    Breakpoint { seq: u64, operation: String },
+    /// Before entry `seq`, the first at or after the one asked for.
+    Reached(u64),
+    /// Every entry was applied.
+    End,
+}
+
+pub struct Replayer {
+    /// The mint being rebuilt.
+    pub mint: Mint,
+    entries: Vec<JournalEntry>,
+    pos: usize,
+    breakpoints: HashSet<String>,
+    /// The entry last stopped at, so continuing does not stop there again.
+    stopped_at: Option<usize>,
+    /// Input fees in the entries applied so far.
+    pub fees: u64,
+    /// Refusals in the entries applied so far.
+    pub refused: u64,
+}
+
+impl Replayer {
+    /// Replays `entries`, in sequence order, against `mint`. Entries the
+    /// mint's journal already holds are skipped.
+    pub fn new(mint: Mint, mut entries: Vec<JournalEntry>) -> Self {
+        entries.sort_by_key(|e| e.seq);
+        let pos = entries.partition_point(|e| e.seq <= mint.journal.last_seq());
+        Self {
+            mint,
+            entries,
+            pos,
+            breakpoints: HashSet::new(),
+            stopped_at: None,
+            fees: 0,
+            refused: 0,
+        }
+    }
+
+    /// Stops before entries of `operation`, except those directly after
+    /// another of its entries.
+    pub fn break_on(&mut self, operation: &str) {
+        self.breakpoints.insert(operation.to_string());
+    }
+
+    pub fn clear_break(&mut self, operation: &str) -> bool {
+        self.breakpoints.remove(operation)
+    }
+
+    /// The entry the next step applies.
+    pub fn next_entry(&self) -> Option<&JournalEntry> {
+        self.entries.get(self.pos)
+    }
+
+    /// Sequence number of the last entry applied.
+    pub fn last_seq(&self) -> u64 {
+        self.mint.journal.last_seq()
+    }
+
+    pub fn state(&self) -> MintState {
+        MintState::of(&self.mint)
+    }
+
+    /// Applies the next entry. `None` once every entry was applied. Fails
+    /// at a gap in the sequence numbers.
+    pub fn step(&mut self) -> io::Result<Option<Step>> {
+        let Some(entry) = self.entries.get(self.pos).cloned() else {
+            return Ok(None);
+        };
+        let mut diff = StateDiff::default();
+        let mut anomaly = None;
+        match &entry.event {
+            JournalEvent::Spent { secret, .. } => {
+                if self.mint.spent.contains(secret) {
+                    anomaly = Some("secret spent again while still spent".to_string());
+                } else {
+                    diff.spent.push(to_hex(secret));
+                }
+            }
+            JournalEvent::Released { secret, .. } => {
+                if self.mint.spent.contains(secret) {
+                    diff.released.push(to_hex(secret));
+                } else {
+                    anomaly = Some("released a secret that was not spent".to_string());
+                }
+            }
+            JournalEvent::Signed { value, blinded, .. } => {
+                let key = to_hex(&blinded.serialize());
+                match self.mint.signed_outputs.get(blinded).map(|v| v.1) {
+                    Some(old) => {
+                        anomaly = Some("blinded message signed again".to_string());
+                        if old != *value {
+                            diff.unsigned.push((key.clone(), old));
+                            diff.signed.push((key, *value));
+                        }
+                    }
+                    None => diff.signed.push((key, *value)),
+                }
+            }
+            _ => {}
+        }
+
+        self.mint.apply_journal([entry.clone()])?;
+        match entry.event {
+            JournalEvent::Fee { amount } => self.fees += amount,
+            JournalEvent::Refused { .. } => self.refused += 1,
+            _ => {}
+        }
+        self.pos += 1;
+        Ok(Some(Step {
+            entry,
+            diff,
+            anomaly,
+        }))
+    }
+
+    /// Steps until a breakpoint, or the entry numbered `until`, is next,
+    /// or every entry was applied. Each applied step is passed to
+    /// `on_step`.
+    pub fn run(&mut self, until: Option<u64>, mut on_step: impl FnMut(&Step)) -> io::Result<Stop> {
+        loop {
+            let Some(next) = self.entries.get(self.pos) else {
+                return Ok(Stop::End);
+            };
+            if self.stopped_at != Some(self.pos) {
+                if until.is_some_and(|seq| next.seq >= seq) {
+                    self.stopped_at = Some(self.pos);
+                    return Ok(Stop::Reached(next.seq));
+                }
+                let starts_op = next.operation.as_ref().filter(|op| {
+                    self.breakpoints.contains(*op)
+                        && self
+                            .pos
+                            .checked_sub(1)
+                            .is_none_or(|p| self.entries[p].operation.as_ref() != Some(op))
+                });
+                if let Some(operation) = starts_op {
+                    let stop = Stop::Breakpoint {
+                        seq: next.seq,
+                        operation: operation.clone(),
+                    };
+                    self.stopped_at = Some(self.pos);
+                    return Ok(stop);
+                }
+            }
+            if let Some(step) = self.step()? {
+                on_step(&step);
+            }
+        }
+    }
+}
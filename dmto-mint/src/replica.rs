@@ -0,0 +1,280 @@
+//! Read replicas. A replica is a mint holding the primary's keys that
+//! applies the primary's journal instead of handling writes, so it can
+//! answer keyset queries, state checks and restores while swaps, melts and
+//! issuance go to the primary. `ReplicatedMint` routes each request to the
+//! right one.
+//!
+//! In process, `Mint::replica` copies the primary and `follow` keeps the
+//! copy caught up. A replica elsewhere is restored from the primary's
+//! snapshot (see `backup`) and fed the journal segments written since with
+//! `apply_journal`.
+//!
+//! A replica is only as fresh as the last entry it applied: a note spent on
+//! the primary since still reads as unspent there. Wallets polling their
+//! pending sends tolerate that; nothing that decides whether a note can be
+//! spent may ask a replica.
+
+use std::{
+    io,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use secp256k1::PublicKey;
+
+use crate::{
+    announce::Announcement,
+    anonymity::AnonymitySet,
+    api::{KeysetKeys, MintReader, MintTrait},
+    batchmelt::{BatchMeltRequest, BatchMeltResponse},
+    error::MintError,
+    hash::{hash_to_curve, hash_to_curve_batch},
+    issue::{MintQuote, MintRequest, MintResponse},
+    journal::{Journal, JournalEntry, JournalEvent},
+    melt::{MeltQuote, MeltRequest, MeltResponse},
+    mint::{Mint, MintInfo, ProofState},
+    protocol::{Hello, Request, Response, SwapRequest, SwapResponse},
+    restore::{RestoreRequest, RestoreResponse, RestoreThrottle},
+    stats::DashboardStats,
+};
+
+impl Mint {
+    /// A replica of this mint: the same keys, identity and published
+    /// limits, and the spent set as of now, following the journal from its
+    /// last entry.
+    pub fn replica(&self) -> Mint {
+        let seq = self.journal.last_seq();
+        let mut replica = Mint::from_keys(self.keys.clone());
+        replica.keyset_id = self.keyset_id.clone();
+        replica.identity = self.identity;
+        replica.accepted_kinds = self.accepted_kinds.clone();
+        replica.input_fee_ppk = self.input_fee_ppk;
+        replica.max_inputs = self.max_inputs;
+        replica.max_outputs = self.max_outputs;
+        replica.secret_policy = self.secret_policy.clone();
+        replica.restore_throttle = RestoreThrottle::new(self.restore_throttle.limits.clone());
+        replica.clock = self.clock.clone();
+        replica.journal = Journal::starting_after(seq);
+
+        // Copied after `seq` was read, so the copy may already hold changes
+        // from later entries; applying those again is harmless.
+        let spent: Vec<Vec<u8>> = self.spent.iter().map(|s| s.key().clone()).collect();
+        for (secret, y) in spent.iter().zip(hash_to_curve_batch(&spent)) {
+            replica.mark_spent(secret, &y);
+        }
+        for output in self.signed_outputs.iter() {
+            replica
+                .signed_outputs
+                .insert(*output.key(), output.value().clone());
+        }
+        replica
+    }
+
+    /// Applies the primary's journal entries in order, skipping any already
+    /// applied. Returns how many were new. Fails at a gap, keeping the
+    /// entries before it.
+    pub fn apply_journal(
+        &self,
+        entries: impl IntoIterator<Item = JournalEntry>,
+    ) -> io::Result<usize> {
+        let mut applied = 0;
+        for entry in entries {
+            if entry.seq <= self.journal.last_seq() {
+                continue;
+            }
+            if entry.seq != self.journal.last_seq() + 1 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "gap in journal segments",
+                ));
+            }
+            let seq = entry.seq;
+            let mut spent = None;
+            match &entry.event {
+                JournalEvent::Spent { secret, .. } => {
+                    let y = hash_to_curve(secret);
+                    self.mark_spent(secret, &y);
+                    spent = Some(y);
+                }
+                JournalEvent::Released { secret, .. } => {
+                    self.unmark_spent(secret, &hash_to_curve(secret));
+                }
+                JournalEvent::Signed {
+                    value,
+                    blinded,
+                    keyset_id,
+                } => {
+                    // Entries older than version 4 do not say; they predate
+                    // rotation, so the keyset is this mint's.
+                    let keyset_id = if keyset_id.is_empty() {
+                        self.keyset_id.clone()
+                    } else {
+                        keyset_id.clone()
+                    };
+                    self.signed_outputs.insert(*blinded, (keyset_id, *value));
+                }
+                _ => {}
+            }
+            self.journal.replay(entry);
+            // Once replayed, so the record can be read back from the journal.
+            if let Some(y) = spent {
+                self.record_spend(y, seq);
+            }
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    /// Applies everything `primary` journaled since this replica's last
+    /// entry.
+    pub fn catch_up(&self, primary: &Mint) -> io::Result<usize> {
+        self.apply_journal(primary.journal.since(self.journal.last_seq()))
+    }
+}
+
+/// A replica kept caught up from its own thread.
+pub struct Follower {
+    stop: Sender<()>,
+    thread: JoinHandle<io::Result<()>>,
+}
+
+impl Follower {
+    /// Stops following. Fails if the replica stopped on its own because its
+    /// journal no longer matched the primary's.
+    pub fn stop(self) -> io::Result<()> {
+        let _ = self.stop.send(());
+        self.thread.join().unwrap()
+    }
+}
+
+/// Catches `replica` up with `primary` every `poll` until stopped.
+pub fn follow(primary: Arc<Mint>, replica: Arc<Mint>, poll: Duration) -> Follower {
+    let (stop, stopped) = mpsc::channel();
+    let thread = thread::spawn(move || {
+        loop {
+            replica.catch_up(&primary)?;
+            match stopped.recv_timeout(poll) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return Ok(()),
+            }
+        }
+    });
+    Follower { stop, thread }
+}
+
+/// Sends `MintReader` requests to the replicas in turn and everything else
+/// to the primary. A read a replica cannot answer at all (`Unknown`, e.g.
+/// its actor stopped) is retried on the primary.
+pub struct ReplicatedMint<P, R> {
+    pub primary: P,
+    pub replicas: Vec<R>,
+    next: AtomicUsize,
+}
+
+impl<P: MintTrait, R: MintReader> ReplicatedMint<P, R> {
+    pub fn new(primary: P, replicas: Vec<R>) -> Self {
+        Self {
+            primary,
+            replicas,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    fn read<T>(&self, f: impl Fn(&dyn MintReader) -> Result<T, MintError>) -> Result<T, MintError> {
+        if self.replicas.is_empty() {
+            return f(&self.primary);
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.replicas.len();
+        match f(&self.replicas[index]) {
+            Err(MintError::Unknown { .. }) => f(&self.primary),
+            result => result,
+        }
+    }
+}
+
+impl<P: MintTrait, R: MintReader> MintReader for ReplicatedMint<P, R> {
+    fn info(&self) -> Result<MintInfo, MintError> {
+        self.read(|m| m.info())
+    }
+
+    fn keysets(&self) -> Result<Vec<KeysetKeys>, MintError> {
+        self.read(|m| m.keysets())
+    }
+
+    fn hello(&self) -> Result<Hello, MintError> {
+        self.read(|m| m.hello())
+    }
+
+    fn check_state(&self, ys: &[PublicKey]) -> Result<Vec<ProofState>, MintError> {
+        self.read(|m| m.check_state(ys))
+    }
+
+    fn restore_signatures(
+        &self,
+        req: Request<RestoreRequest>,
+    ) -> Result<Response<RestoreResponse>, MintError> {
+        self.read(|m| m.restore_signatures(req.clone()))
+    }
+}
+
+impl<P: MintTrait, R: MintReader> MintTrait for ReplicatedMint<P, R> {
+    fn handle_swap(&self, req: Request<SwapRequest>) -> Result<Response<SwapResponse>, MintError> {
+        self.primary.handle_swap(req)
+    }
+
+    fn melt_quote(&self, request: &str) -> Result<MeltQuote, MintError> {
+        self.primary.melt_quote(request)
+    }
+
+    fn melt_quote_with_amount(&self, request: &str, amount: u64) -> Result<MeltQuote, MintError> {
+        self.primary.melt_quote_with_amount(request, amount)
+    }
+
+    fn melt(&self, req: Request<MeltRequest>) -> Result<Response<MeltResponse>, MintError> {
+        self.primary.melt(req)
+    }
+
+    fn melt_quote_state(&self, id: &str) -> Result<MeltQuote, MintError> {
+        self.primary.melt_quote_state(id)
+    }
+
+    fn melt_batch(
+        &self,
+        req: Request<BatchMeltRequest>,
+    ) -> Result<Response<BatchMeltResponse>, MintError> {
+        self.primary.melt_batch(req)
+    }
+
+    fn mint_quote(&self, amount: u64) -> Result<MintQuote, MintError> {
+        self.primary.mint_quote(amount)
+    }
+
+    fn mint_quote_onchain(&self, amount: u64) -> Result<MintQuote, MintError> {
+        self.primary.mint_quote_onchain(amount)
+    }
+
+    fn get_quote(&self, id: &str) -> Result<MintQuote, MintError> {
+        self.primary.get_quote(id)
+    }
+
+    fn mint(&self, req: Request<MintRequest>) -> Result<Response<MintResponse>, MintError> {
+        self.primary.mint(req)
+    }
+
+    fn anonymity_sets(&self) -> Result<Vec<AnonymitySet>, MintError> {
+        self.primary.anonymity_sets()
+    }
+
+    fn dashboard_stats(&self) -> Result<DashboardStats, MintError> {
+        self.primary.dashboard_stats()
+    }
+
+    fn announcements(&self) -> Result<Vec<Announcement>, MintError> {
+        self.primary.announcements()
+    }
+}
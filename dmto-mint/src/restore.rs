@@ -0,0 +1,94 @@
+//! Answering restore requests, rate-limited by `RestoreThrottle`. See
+//! `dmto_wallet::restore` for the protocol.
+
+pub use dmto_wallet::restore::*;
+
+use std::{sync::Mutex, time::Instant};
+
+use crate::{
+    blind::blind_sign,
+    dleq::{self},
+    error::MintError,
+    mint::Mint,
+    protocol::{Request, Response},
+};
+
+pub struct RestoreThrottle {
+    pub limits: RestoreLimits,
+    /// Start of the current window and messages taken in it.
+    state: Mutex<(Option<Instant>, usize)>,
+}
+
+impl RestoreThrottle {
+    pub fn new(limits: RestoreLimits) -> Self {
+        Self {
+            limits,
+            state: Mutex::new((None, 0)),
+        }
+    }
+
+    fn take(&self, n: usize) -> Result<(), MintError> {
+        if n > self.limits.max_batch {
+            return Err(MintError::TooManyOutputs {
+                max: self.limits.max_batch,
+            });
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let started = match state.0 {
+            Some(t) if now.duration_since(t) < self.limits.window => t,
+            _ => {
+                *state = (Some(now), 0);
+                now
+            }
+        };
+        if state.1 + n > self.limits.max_per_window {
+            let retry_after = self.limits.window - now.duration_since(started);
+            return Err(MintError::Overloaded {
+                retry_after_ms: retry_after.as_micros().div_ceil(1000) as u64,
+            });
+        }
+        state.1 += n;
+        Ok(())
+    }
+}
+
+impl Mint {
+    /// Signs again the blinded messages in `req` that this mint signed
+    /// before with its active keyset. Messages it never signed, or signed
+    /// with a keyset since rotated out, are left out of the response.
+    pub fn restore_signatures(
+        &self,
+        req: Request<RestoreRequest>,
+    ) -> Result<Response<RestoreResponse>, MintError> {
+        req.handle(|req| self.run_restore(req))
+    }
+
+    fn run_restore(&self, req: RestoreRequest) -> Result<RestoreResponse, MintError> {
+        self.restore_throttle.take(req.outputs.len())?;
+        let _permit = self.limiter.acquire()?;
+
+        let signatures = req
+            .outputs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, blinded)| {
+                let value = self
+                    .signed_outputs
+                    .get(blinded)
+                    .filter(|e| e.0 == self.keyset_id)?
+                    .1;
+                let key = &self.keys.get(&value)?.privkey;
+                let signature = blind_sign(key, blinded);
+                Some(RestoredSignature {
+                    index,
+                    value,
+                    signature,
+                    dleq: dleq::prove(key, blinded, &signature),
+                })
+            })
+            .collect();
+        Ok(RestoreResponse { signatures })
+    }
+}
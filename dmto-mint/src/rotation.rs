@@ -0,0 +1,234 @@
+//! Revoking keysets and the migration windows that follow. See
+//! `dmto_wallet::rotation` for the wallet side.
+
+pub use dmto_wallet::rotation::*;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use crate::{
+    announce::{NoticeKind, Severity},
+    api::KeysetKeys,
+    error::MintError,
+    journal::JournalEvent,
+    keyset::KeysetIdVersion,
+    mint::{Mint, MintKey, keyset_id},
+    operation,
+    types::Note,
+};
+
+/// A revoked keyset whose notes can still be swapped for notes of the
+/// active keyset.
+pub struct Migration {
+    pub keyset_id: String,
+    pub keys: HashMap<u64, MintKey>,
+    /// Unix time the window closes at.
+    pub until: u64,
+    /// Notes per denomination that can still migrate. Starts at the number
+    /// of signatures the keyset had issued for the denomination, notes
+    /// spent before the rotation included, since the spent set does not
+    /// record values.
+    pub remaining: Mutex<BTreeMap<u64, u64>>,
+}
+
+impl Migration {
+    pub fn is_open(&self, now: u64) -> bool {
+        now < self.until
+    }
+
+    pub fn keyset(&self) -> KeysetKeys {
+        let mut keys: Vec<_> = self.keys.iter().map(|(&v, k)| (v, k.pubkey)).collect();
+        keys.sort_by_key(|(v, _)| *v);
+        KeysetKeys {
+            keyset_id: self.keyset_id.clone(),
+            id_version: KeysetIdVersion::of(&self.keyset_id).unwrap_or_default(),
+            keys,
+        }
+    }
+
+    fn covers(&self, keyset_id: &str) -> bool {
+        keyset_id == self.keyset_id || self.keyset().matches(keyset_id)
+    }
+}
+
+impl Mint {
+    /// Replaces the active keyset with fresh random keys for the same
+    /// denominations, leaving its notes `window` seconds to migrate.
+    pub fn emergency_rotate(&mut self, window: u64) -> Rotation {
+        let keys = self.keys.keys().map(|&v| (v, MintKey::new(v))).collect();
+        self.emergency_rotate_to(keys, window)
+    }
+
+    /// As `emergency_rotate`, activating `keys`, e.g. the next keyset
+    /// derived from the operator's seed.
+    pub fn emergency_rotate_to(&mut self, keys: HashMap<u64, MintKey>, window: u64) -> Rotation {
+        let _op = operation::begin();
+        let revoked = self.keyset_id.clone();
+        self.journal.append(
+            JournalEvent::KeysetRevoked {
+                keyset_id: revoked.clone(),
+            },
+            self.clock.now(),
+        );
+
+        let mut remaining = BTreeMap::new();
+        for e in self.signed_outputs.iter().filter(|e| e.0 == revoked) {
+            *remaining.entry(e.1).or_default() += 1;
+        }
+        let activated = keyset_id(&keys);
+        let old = std::mem::replace(&mut self.keys, keys);
+        self.keyset_id = activated.clone();
+        self.journal.append(
+            JournalEvent::KeysetActivated {
+                keyset_id: activated.clone(),
+            },
+            self.clock.now(),
+        );
+
+        let until = self.clock.now().saturating_add(window);
+        self.migrations.push(Migration {
+            keyset_id: revoked.clone(),
+            keys: old,
+            until,
+            remaining: Mutex::new(remaining),
+        });
+        self.journal.append(
+            JournalEvent::MigrationOpened {
+                keyset_id: revoked.clone(),
+                until,
+            },
+            self.clock.now(),
+        );
+
+        let announcement = self.announce(
+            NoticeKind::Incident,
+            Severity::Critical,
+            "Keyset revoked",
+            &format!(
+                "Keyset {} was revoked and replaced by {}. Swap notes of {} for new \
+                 ones before {}; they cannot be melted.",
+                revoked, activated, revoked, until
+            ),
+            window,
+        );
+        Rotation {
+            operation: operation::current().unwrap_or_default(),
+            revoked,
+            activated,
+            until,
+            announcement,
+        }
+    }
+
+    /// Stops accepting notes of a revoked keyset before its window ends.
+    pub fn close_migration(&mut self, keyset_id: &str) -> bool {
+        let before = self.migrations.len();
+        self.migrations.retain(|m| m.keyset_id != keyset_id);
+        if self.migrations.len() == before {
+            return false;
+        }
+        self.journal.append(
+            JournalEvent::MigrationClosed {
+                keyset_id: keyset_id.to_string(),
+            },
+            self.clock.now(),
+        );
+        true
+    }
+
+    fn migration_of(&self, keyset_id: &str) -> Option<&Migration> {
+        self.migrations.iter().find(|m| m.covers(keyset_id))
+    }
+
+    /// ID of the keyset `input_key` takes `note`'s key from, which spends of
+    /// it are counted under.
+    pub(crate) fn input_keyset_id(&self, note: &Note) -> &str {
+        self.migration_of(&note.keyset_id)
+            .map_or(self.keyset_id.as_str(), |m| m.keyset_id.as_str())
+    }
+
+    /// The key `note` is checked against: its revoked keyset's while that
+    /// keyset's window is open, otherwise the active keyset's. Notes of any
+    /// other keyset are refused with `KeysetUnknown`.
+    pub(crate) fn input_key(&self, note: &Note) -> Result<&MintKey, MintError> {
+        let keys = match self.migration_of(&note.keyset_id) {
+            Some(m) if !m.is_open(self.clock.now()) => {
+                return Err(MintError::KeysetPaused(m.keyset_id.clone()));
+            }
+            Some(m) => &m.keys,
+            None if note.keyset_id == self.keyset_id
+                || self.current_keyset().matches(&note.keyset_id) =>
+            {
+                &self.keys
+            }
+            None => return Err(MintError::KeysetUnknown(note.keyset_id.clone())),
+        };
+        keys.get(&note.value)
+            .ok_or(MintError::UnknownDenomination(note.value))
+    }
+
+    /// Refuses inputs of revoked keysets outside a swap.
+    pub(crate) fn check_not_migrating(&self, inputs: &[Note]) -> Result<(), MintError> {
+        match inputs.iter().find_map(|n| self.migration_of(&n.keyset_id)) {
+            Some(m) => Err(MintError::KeysetPaused(m.keyset_id.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Takes the inputs of revoked keysets off what each may still migrate,
+    /// returning what was taken for `release_migrated`. Refuses the whole
+    /// request if any denomination would go past it.
+    pub(crate) fn reserve_migrated(
+        &self,
+        inputs: &[Note],
+    ) -> Result<Vec<(usize, u64, u64)>, MintError> {
+        let mut wanted: BTreeMap<(usize, u64), u64> = BTreeMap::new();
+        let mut first = HashMap::new();
+        for n in inputs {
+            if let Some(i) = self.migrations.iter().position(|m| m.covers(&n.keyset_id)) {
+                *wanted.entry((i, n.value)).or_default() += 1;
+                first.entry((i, n.value)).or_insert(n.y);
+            }
+        }
+
+        let mut taken = Vec::new();
+        for ((i, value), count) in wanted {
+            let migration = &self.migrations[i];
+            let mut remaining = migration.remaining.lock().unwrap();
+            let left = remaining.entry(value).or_default();
+            if *left < count {
+                drop(remaining);
+                self.release_migrated(&taken);
+                let reason = format!(
+                    "more notes of {} from revoked keyset {} than it signed",
+                    value, migration.keyset_id
+                );
+                self.journal.append(
+                    JournalEvent::Refused {
+                        y: first[&(i, value)],
+                        reason: reason.clone(),
+                    },
+                    self.clock.now(),
+                );
+                return Err(MintError::Refused(reason));
+            }
+            *left -= count;
+            taken.push((i, value, count));
+        }
+        Ok(taken)
+    }
+
+    /// Returns notes taken by `reserve_migrated` for a swap that failed.
+    pub(crate) fn release_migrated(&self, taken: &[(usize, u64, u64)]) {
+        for &(i, value, count) in taken {
+            *self.migrations[i]
+                .remaining
+                .lock()
+                .unwrap()
+                .entry(value)
+                .or_default() += count;
+        }
+    }
+}
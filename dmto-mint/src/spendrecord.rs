@@ -0,0 +1,37 @@
+//! Recording and signing spend records. See `dmto_wallet::spendrecord`
+//! for the format.
+
+pub use dmto_wallet::spendrecord::*;
+
+use secp256k1::PublicKey;
+
+use crate::{mint::Mint, signing};
+
+impl Mint {
+    /// Remembers that journal entry `seq` spent the note with this `Y`.
+    pub(crate) fn record_spend(&self, y: PublicKey, seq: u64) {
+        if !self.spend_records {
+            return;
+        }
+        if let Some(entry) = self.journal.get(seq) {
+            let record = SpendRecord {
+                y,
+                seq,
+                timestamp: entry.timestamp,
+                operation: entry.operation,
+            };
+            self.spend_log.insert(y, record);
+        }
+    }
+
+    /// A signed record of the entry that spent the note with this `Y`, if
+    /// `spend_records` is on.
+    pub fn spend_record(&self, y: &PublicKey) -> Option<SignedSpendRecord> {
+        if !self.spend_records {
+            return None;
+        }
+        let record = self.spend_log.get(y)?.clone();
+        let signature = signing::sign(&self.identity, &record.message());
+        Some(SignedSpendRecord { record, signature })
+    }
+}
@@ -0,0 +1,67 @@
+//! Computing dashboard figures from the journal. See `dmto_wallet::stats`
+//! for the types.
+
+pub use dmto_wallet::stats::*;
+
+use std::collections::BTreeMap;
+
+use crate::{
+    journal::{JournalEntry, JournalEvent},
+    mint::Mint,
+};
+
+/// Buckets `entries` into UTC days and runs the liability total across them.
+pub fn daily(entries: &[JournalEntry]) -> Vec<DayStats> {
+    let mut days: BTreeMap<u64, DayStats> = BTreeMap::new();
+    for entry in entries {
+        let day = entry.timestamp - entry.timestamp % SECS_PER_DAY;
+        let stats = days.entry(day).or_insert_with(|| DayStats {
+            day,
+            ..DayStats::default()
+        });
+        match &entry.event {
+            JournalEvent::Signed { value, .. } => stats.issued += value,
+            JournalEvent::Spent { value, .. } => {
+                stats.redeemed += value;
+                stats.notes_spent += 1;
+            }
+            JournalEvent::Released { value, .. } => {
+                stats.redeemed = stats.redeemed.saturating_sub(*value);
+                stats.notes_spent = stats.notes_spent.saturating_sub(1);
+            }
+            JournalEvent::Fee { amount } => stats.fees += amount,
+            JournalEvent::Refused { .. }
+            | JournalEvent::KeysetRevoked { .. }
+            | JournalEvent::KeysetActivated { .. }
+            | JournalEvent::MigrationOpened { .. }
+            | JournalEvent::MigrationClosed { .. } => {}
+        }
+    }
+
+    let mut outstanding = 0i128;
+    days.into_values()
+        .map(|mut stats| {
+            outstanding += stats.issued as i128 - stats.redeemed as i128;
+            stats.outstanding = outstanding;
+            stats
+        })
+        .collect()
+}
+
+impl Mint {
+    pub fn dashboard_stats(&self) -> DashboardStats {
+        let journal_seq = self.journal.last_seq();
+        let days = daily(&self.journal.since(0));
+        let active_keysets = match self.monitor.check(&self.keyset_id) {
+            Ok(()) => vec![self.keyset_id.clone()],
+            Err(_) => Vec::new(),
+        };
+        DashboardStats {
+            active_keysets,
+            fees_collected: days.iter().map(|d| d.fees).sum(),
+            outstanding: days.last().map_or(0, |d| d.outstanding),
+            days,
+            journal_seq,
+        }
+    }
+}
@@ -0,0 +1,18 @@
+//! Decoding request bodies under the mint's JSON limits. See
+//! `dmto_wallet::strict`.
+
+pub use dmto_wallet::strict::*;
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{error::MintError, mint::Mint};
+
+impl Mint {
+    /// Decodes a request body under this mint's `json_limits`.
+    pub fn decode_request<T: Serialize + DeserializeOwned>(
+        &self,
+        body: &[u8],
+    ) -> Result<T, MintError> {
+        decode(body, &self.json_limits).map_err(|e| MintError::BadRequest(e.to_string()))
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use crate::mint::Mint;
+
+/// Hosts several isolated mints in one process. Each tenant has its own keys,
+/// spent set and journal; requests are routed to a tenant by `Host` header or,
+/// failing that, by the longest matching path prefix.
+#[derive(Default)]
+pub struct MintHost {
+    pub tenants: HashMap<String, Mint>,
+    hosts: HashMap<String, String>,
+    prefixes: Vec<(String, String)>,
+}
+
+impl MintHost {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_tenant(&mut self, name: &str, mint: Mint) {
+        self.tenants.insert(name.to_string(), mint);
+    }
+
+    pub fn route_host(&mut self, hostname: &str, tenant: &str) {
+        self.hosts
+            .insert(hostname.to_ascii_lowercase(), tenant.to_string());
+    }
+
+    /// `prefix` should start with `/` and not end with one, e.g. `/alice`.
+    pub fn route_prefix(&mut self, prefix: &str, tenant: &str) {
+        self.prefixes.push((prefix.to_string(), tenant.to_string()));
+        self.prefixes
+            .sort_by_key(|(p, _)| std::cmp::Reverse(p.len()));
+    }
+
+    /// Returns the tenant serving this request and the path with any routing
+    /// prefix stripped.
+    pub fn route<'a>(&self, host: &str, path: &'a str) -> Option<(&Mint, &'a str)> {
+        let host = host.split(':').next().unwrap_or(host).to_ascii_lowercase();
+        if let Some(name) = self.hosts.get(&host) {
+            return self.tenants.get(name).map(|m| (m, path));
+        }
+
+        for (prefix, name) in &self.prefixes {
+            if let Some(rest) = path.strip_prefix(prefix.as_str())
+                && (rest.is_empty() || rest.starts_with('/'))
+            {
+                return self.tenants.get(name).map(|m| (m, rest));
+            }
+        }
+
+        None
+    }
+}
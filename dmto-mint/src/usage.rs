@@ -0,0 +1,170 @@
+//! Operator-side usage statistics for tuning the denomination set and fee
+//! schedule: how often each denomination is issued and redeemed, how many
+//! inputs requests spend and what amounts they move.
+
+use std::{collections::BTreeMap, fmt, sync::Mutex};
+
+use crate::mint::{Mint, input_fee};
+
+/// Counts in power-of-two buckets: bucket `i` holds values in
+/// `[2^i, 2^(i+1))`, and bucket 0 also holds 0.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Histogram {
+    pub buckets: Vec<u64>,
+}
+
+impl Histogram {
+    pub fn record(&mut self, value: u64) {
+        let bucket = value.checked_ilog2().unwrap_or(0) as usize;
+        if self.buckets.len() <= bucket {
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += 1;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Lower bound of the bucket holding the median sample.
+    pub fn median(&self) -> Option<u64> {
+        let half = self.total().div_ceil(2);
+        let mut seen = 0;
+        for (i, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= half && count > 0 {
+                return Some(if i == 0 { 0 } else { 1 << i });
+            }
+        }
+        None
+    }
+}
+
+#[derive(Default)]
+struct Usage {
+    issued: BTreeMap<u64, u64>,
+    redeemed: BTreeMap<u64, u64>,
+    inputs: Histogram,
+    amounts: Histogram,
+}
+
+#[derive(Default)]
+pub struct UsageStats {
+    state: Mutex<Usage>,
+}
+
+impl UsageStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn issued(&self, value: u64) {
+        *self.state.lock().unwrap().issued.entry(value).or_default() += 1;
+    }
+
+    pub fn redeemed(&self, value: u64) {
+        *self
+            .state
+            .lock()
+            .unwrap()
+            .redeemed
+            .entry(value)
+            .or_default() += 1;
+    }
+
+    /// Records a swap or melt spending `inputs` notes.
+    pub fn inputs(&self, inputs: usize) {
+        self.state.lock().unwrap().inputs.record(inputs as u64);
+    }
+
+    /// Records the amount a swap, melt or mint request moved.
+    pub fn amount(&self, amount: u64) {
+        self.state.lock().unwrap().amounts.record(amount);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DenominationUsage {
+    pub value: u64,
+    pub issued: u64,
+    pub redeemed: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsageReport {
+    /// Every active denomination, in ascending order.
+    pub denominations: Vec<DenominationUsage>,
+    pub inputs_per_request: Histogram,
+    pub amounts: Histogram,
+    pub input_fee_ppk: u64,
+}
+
+impl UsageReport {
+    /// Denominations never issued, which could be dropped from the keyset.
+    pub fn unused(&self) -> Vec<u64> {
+        self.denominations
+            .iter()
+            .filter(|d| d.issued == 0)
+            .map(|d| d.value)
+            .collect()
+    }
+}
+
+fn write_histogram(f: &mut fmt::Formatter<'_>, name: &str, h: &Histogram) -> fmt::Result {
+    writeln!(f, "{} ({} requests):", name, h.total())?;
+    for (i, count) in h.buckets.iter().enumerate().filter(|(_, c)| **c > 0) {
+        let low: u64 = if i == 0 { 0 } else { 1 << i };
+        writeln!(f, "  {:>20}+ {:>10}", low, count)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for UsageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>20} {:>10} {:>10}",
+            "denomination", "issued", "redeemed"
+        )?;
+        for d in &self.denominations {
+            writeln!(f, "{:>20} {:>10} {:>10}", d.value, d.issued, d.redeemed)?;
+        }
+        write_histogram(f, "inputs per request", &self.inputs_per_request)?;
+        write_histogram(f, "amount per request", &self.amounts)?;
+
+        let median = self.inputs_per_request.median().unwrap_or(0);
+        writeln!(
+            f,
+            "input fee {} ppk, about {} per median request",
+            self.input_fee_ppk,
+            input_fee(median as usize, self.input_fee_ppk)
+        )?;
+        let unused = self.unused();
+        if !unused.is_empty() {
+            writeln!(f, "never issued: {:?}", unused)?;
+        }
+        Ok(())
+    }
+}
+
+impl Mint {
+    pub fn usage_report(&self) -> UsageReport {
+        let usage = self.usage.state.lock().unwrap();
+        let mut values: Vec<u64> = self.keys.keys().copied().collect();
+        values.sort_unstable();
+
+        UsageReport {
+            denominations: values
+                .into_iter()
+                .map(|value| DenominationUsage {
+                    value,
+                    issued: usage.issued.get(&value).copied().unwrap_or(0),
+                    redeemed: usage.redeemed.get(&value).copied().unwrap_or(0),
+                })
+                .collect(),
+            inputs_per_request: usage.inputs.clone(),
+            amounts: usage.amounts.clone(),
+            input_fee_ppk: self.input_fee_ppk,
+        }
+    }
+}
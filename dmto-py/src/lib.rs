@@ -0,0 +1,262 @@
+//! The `dmto` Python module: tokens, an in-process mint and wallet, and the
+//! blind-signature steps for tests against other implementations.
+//!
+//! Build it with maturin (`maturin develop -m dmto-py/Cargo.toml`), or with
+//! `cargo build --release -p dmto-py --features extension-module` and copy
+//! the library to `dmto.so` on the Python path. Points and scalars are hex;
+//! tokens and wallet results are plain dicts and lists.
+//!
+//! ```python
+//! import dmto
+//!
+//! mint = dmto.Mint([1, 2, 4, 8, 16, 32, 64])
+//! wallet = dmto.Wallet(mint, "http://localhost:3338")
+//! wallet.receive(token)
+//! print(wallet.balance())
+//! print(wallet.send(5).inspect())
+//! ```
+
+use dmto_mint::{
+    actor::{MintActor, MintClient},
+    blind::{blind_message, blind_message_with, blind_sign as sign_blinded, unblind_signature},
+    codec::{from_hex, to_hex},
+    handle::WalletHandle,
+    hash::hash_to_curve as hash_secret,
+    mint::Mint as LocalMint,
+    rpc::RpcServer,
+    token::Token as WalletToken,
+    wallet::Wallet as LocalWallet,
+};
+use pyo3::{
+    create_exception,
+    exceptions::PyException,
+    prelude::*,
+    types::{PyBytes, PyString},
+};
+use secp256k1::{PublicKey, Scalar, SecretKey};
+use serde_json::{Value, json};
+
+create_exception!(dmto, DmtoError, PyException, "An error reported by dmto.");
+
+fn error(e: impl std::fmt::Display) -> PyErr {
+    DmtoError::new_err(e.to_string())
+}
+
+fn bytes(hex: &str) -> PyResult<Vec<u8>> {
+    from_hex(hex).ok_or_else(|| error(format!("invalid hex: {}", hex)))
+}
+
+fn point(hex: &str) -> PyResult<PublicKey> {
+    PublicKey::from_slice(&bytes(hex)?).map_err(error)
+}
+
+fn scalar(hex: &str) -> PyResult<Scalar> {
+    let b: [u8; 32] = bytes(hex)?
+        .try_into()
+        .map_err(|_| error("scalar must be 32 bytes"))?;
+    Scalar::from_be_bytes(b).map_err(error)
+}
+
+/// Converts JSON into Python objects through the `json` module.
+fn to_python(py: Python<'_>, value: &Value) -> PyResult<Py<PyAny>> {
+    Ok(py
+        .import("json")?
+        .call_method1("loads", (value.to_string(),))?
+        .unbind())
+}
+
+fn to_json(value: &Bound<'_, PyAny>) -> PyResult<Value> {
+    let text: String = value
+        .py()
+        .import("json")?
+        .call_method1("dumps", (value,))?
+        .extract()?;
+    serde_json::from_str(&text).map_err(error)
+}
+
+/// `Y` for `secret`, as hex.
+#[pyfunction]
+fn hash_to_curve(secret: &[u8]) -> String {
+    to_hex(&hash_secret(secret).serialize())
+}
+
+/// Blinds the hex point `y` with blinding factor `r`, or a random one.
+/// Returns `(blinded, r)` in hex.
+#[pyfunction]
+#[pyo3(signature = (y, r=None))]
+fn blind(y: &str, r: Option<&str>) -> PyResult<(String, String)> {
+    let y = point(y)?;
+    let blinded = match r {
+        Some(r) => blind_message_with(&y, scalar(r)?),
+        None => blind_message(&y),
+    };
+    Ok((
+        to_hex(&blinded.blinded_point.serialize()),
+        to_hex(&blinded.blind_factor.to_be_bytes()),
+    ))
+}
+
+/// The mint's signature `C_ = k·B_`.
+#[pyfunction]
+fn blind_sign(privkey: &str, blinded: &str) -> PyResult<String> {
+    let key = SecretKey::from_slice(&bytes(privkey)?).map_err(error)?;
+    Ok(to_hex(&sign_blinded(&key, &point(blinded)?).serialize()))
+}
+
+/// Unblinds `C_` into `C = C_ - r·K`.
+#[pyfunction]
+fn unblind(blind_sig: &str, r: &str, mint_pubkey: &str) -> PyResult<String> {
+    let c = unblind_signature(&point(blind_sig)?, &scalar(r)?, &point(mint_pubkey)?);
+    Ok(to_hex(&c.serialize()))
+}
+
+/// A token, read from JSON or the compact binary form.
+#[pyclass(name = "Token")]
+struct Token(WalletToken);
+
+#[pymethods]
+impl Token {
+    /// Parses a token from a JSON string or dict.
+    #[staticmethod]
+    fn from_json(token: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let value = match token.cast::<PyString>() {
+            Ok(text) => serde_json::from_str(text.to_str()?).map_err(error)?,
+            Err(_) => to_json(token)?,
+        };
+        serde_json::from_value(value).map(Self).map_err(error)
+    }
+
+    #[staticmethod]
+    fn from_compact(buf: &[u8]) -> PyResult<Self> {
+        WalletToken::from_compact(buf)
+            .map(Self)
+            .ok_or_else(|| error("invalid compact token"))
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(&self.0).map_err(error)
+    }
+
+    fn to_compact<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.to_compact())
+    }
+
+    #[getter]
+    fn id(&self) -> String {
+        self.0.id()
+    }
+
+    #[getter]
+    fn mint_url(&self) -> &str {
+        &self.0.mint_url
+    }
+
+    #[getter]
+    fn amount(&self) -> PyResult<u64> {
+        self.0.amount().map_err(error)
+    }
+
+    #[getter]
+    fn memo(&self) -> Option<&str> {
+        self.0.memo.as_deref()
+    }
+
+    /// What a UI needs to preview the token, as `Token::inspect` gives it.
+    fn inspect(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let info = self.0.inspect().map_err(error)?;
+        to_python(
+            py,
+            &json!({
+                "id": self.0.id(),
+                "mint_url": info.mint_url,
+                "unit": info.unit,
+                "amount": info.amount,
+                "proof_count": info.proof_count,
+                "keyset_ids": info.keyset_ids,
+                "locking_conditions": info.locking_conditions,
+                "has_dleq": info.has_dleq,
+                "memo": info.memo,
+            }),
+        )
+    }
+
+    fn __repr__(&self) -> String {
+        match self.0.amount() {
+            Ok(amount) => format!("Token({} {}, {})", amount, self.0.unit, self.0.mint_url),
+            Err(_) => format!("Token(overflowing {}, {})", self.0.unit, self.0.mint_url),
+        }
+    }
+}
+
+/// An in-process mint with the given denominations. It stops once it and
+/// its wallets are dropped.
+#[pyclass]
+struct Mint(MintActor);
+
+#[pymethods]
+impl Mint {
+    #[new]
+    fn new(denominations: Vec<u64>) -> Self {
+        Self(MintActor::spawn(LocalMint::new(&denominations)))
+    }
+}
+
+/// A wallet backed by a `Mint`, with the methods of the JSON-RPC API in
+/// `dmto_wallet::rpc`.
+#[pyclass]
+struct Wallet(RpcServer<MintClient>);
+
+impl Wallet {
+    fn call(&self, method: &str, params: Value) -> PyResult<Value> {
+        self.0.call(method, params).map_err(|e| error(e.message))
+    }
+}
+
+#[pymethods]
+impl Wallet {
+    #[new]
+    fn new(mint: &Mint, mint_url: &str) -> Self {
+        let handle = WalletHandle::new(LocalWallet::new());
+        Self(RpcServer::new(handle, mint.0.client(), mint_url))
+    }
+
+    fn balance(&self) -> u64 {
+        self.0.handle.balance()
+    }
+
+    #[pyo3(signature = (amount, memo=None))]
+    fn send(&self, amount: u64, memo: Option<&str>) -> PyResult<Token> {
+        let token = self.call("send", json!({ "amount": amount, "memo": memo }))?;
+        serde_json::from_value(token).map(Token).map_err(error)
+    }
+
+    /// Redeems `token`, returning the amount received.
+    fn receive(&self, token: &Token) -> PyResult<u64> {
+        let result = self.call("receive", json!({ "token": token.0 }))?;
+        Ok(result["amount"].as_u64().unwrap_or(0))
+    }
+
+    /// Pays `request`, returning the preimage.
+    fn melt(&self, request: &str) -> PyResult<Option<String>> {
+        let result = self.call("melt", json!({ "request": request }))?;
+        Ok(result["preimage"].as_str().map(String::from))
+    }
+
+    /// The wallet's transactions, as dicts.
+    fn history(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        to_python(py, &self.call("history", Value::Null)?)
+    }
+}
+
+#[pymodule]
+fn dmto(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("DmtoError", m.py().get_type::<DmtoError>())?;
+    m.add_class::<Token>()?;
+    m.add_class::<Mint>()?;
+    m.add_class::<Wallet>()?;
+    m.add_function(wrap_pyfunction!(hash_to_curve, m)?)?;
+    m.add_function(wrap_pyfunction!(blind, m)?)?;
+    m.add_function(wrap_pyfunction!(blind_sign, m)?)?;
+    m.add_function(wrap_pyfunction!(unblind, m)?)?;
+    Ok(())
+}
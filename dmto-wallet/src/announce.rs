@@ -0,0 +1,125 @@
+//! Notices from the mint operator to wallets: a message of the day,
+//! maintenance windows, deprecations and incident reports. Each is signed
+//! with the mint's identity key and expires, so a wallet can show them
+//! without trusting the transport and stops showing them on time.
+
+use secp256k1::{XOnlyPublicKey, schnorr::Signature};
+use serde::{Deserialize, Serialize};
+
+use crate::{api::MintTrait, error::MintError, signing, wallet::Wallet};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoticeKind {
+    Motd,
+    /// The mint expects to be unavailable between these Unix times.
+    Maintenance {
+        starts: u64,
+        ends: u64,
+    },
+    Deprecation,
+    Incident,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Announcement {
+    pub id: String,
+    pub kind: NoticeKind,
+    pub severity: Severity,
+    pub title: String,
+    pub body: String,
+    pub published_at: u64,
+    pub expires_at: u64,
+    pub signature: Signature,
+}
+
+/// The bytes the mint signs.
+pub fn message(
+    id: &str,
+    kind: &NoticeKind,
+    severity: Severity,
+    title: &str,
+    body: &str,
+    published_at: u64,
+    expires_at: u64,
+) -> Vec<u8> {
+    serde_json::to_vec(&(
+        "dmto-announcement",
+        id,
+        kind,
+        severity,
+        title,
+        body,
+        published_at,
+        expires_at,
+    ))
+    .unwrap()
+}
+
+impl Announcement {
+    pub fn verify(&self, identity: &XOnlyPublicKey) -> bool {
+        signing::verify(
+            identity,
+            &message(
+                &self.id,
+                &self.kind,
+                self.severity,
+                &self.title,
+                &self.body,
+                self.published_at,
+                self.expires_at,
+            ),
+            &self.signature,
+        )
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+}
+
+/// A change in the notices a wallet shows, for apps to surface.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NoticeEvent {
+    Published(Announcement),
+    /// Expired or withdrawn by the mint.
+    Cleared(String),
+}
+
+impl Wallet {
+    /// Fetches the mint's announcements and reports what changed since the
+    /// last check. Announcements not signed by `identity` are dropped.
+    pub fn check_announcements(
+        &mut self,
+        mint: &impl MintTrait,
+        identity: &XOnlyPublicKey,
+        now: u64,
+    ) -> Result<Vec<NoticeEvent>, MintError> {
+        let fetched: Vec<Announcement> = mint
+            .announcements()?
+            .into_iter()
+            .filter(|a| a.verify(identity) && !a.is_expired(now))
+            .collect();
+
+        let mut events: Vec<NoticeEvent> = self
+            .notices
+            .keys()
+            .filter(|id| !fetched.iter().any(|a| &a.id == *id))
+            .map(|id| NoticeEvent::Cleared(id.clone()))
+            .collect();
+        events.extend(
+            fetched
+                .iter()
+                .filter(|a| !self.notices.contains_key(&a.id))
+                .map(|a| NoticeEvent::Published(a.clone())),
+        );
+        self.notices = fetched.into_iter().map(|a| (a.id.clone(), a)).collect();
+        Ok(events)
+    }
+}
@@ -0,0 +1,68 @@
+//! Anonymity sets: how many unspent signatures the mint has issued per
+//! keyset and denomination. A note is indistinguishable only among the other
+//! unspent notes of its denomination, so spending one from a set of three
+//! tells the mint a lot about who is spending it.
+//!
+//! The mint publishes only these aggregate counts, which it counts from
+//! startup; signatures issued before a restart are not included.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{api::MintTrait, error::MintError, types::Note, wallet::Wallet};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnonymitySet {
+    pub keyset_id: String,
+    pub value: u64,
+    /// Signatures issued minus notes spent.
+    pub unspent: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnonymityWarning {
+    pub keyset_id: String,
+    pub value: u64,
+    pub unspent: u64,
+    /// How many of the notes being checked fall into this set.
+    pub notes: usize,
+}
+
+impl Wallet {
+    /// Flags the denominations among `notes` whose anonymity set at the mint
+    /// is smaller than `min_anonymity_set`. Notes in a set the mint has not
+    /// published count as a set of zero.
+    pub fn anonymity_warnings(
+        &self,
+        mint: &impl MintTrait,
+        notes: &[Note],
+    ) -> Result<Vec<AnonymityWarning>, MintError> {
+        if self.min_anonymity_set == 0 {
+            return Ok(Vec::new());
+        }
+        let sets = mint.anonymity_sets()?;
+
+        let mut warnings: Vec<AnonymityWarning> = Vec::new();
+        for n in notes {
+            if let Some(w) = warnings
+                .iter_mut()
+                .find(|w| w.keyset_id == n.keyset_id && w.value == n.value)
+            {
+                w.notes += 1;
+                continue;
+            }
+            let unspent = sets
+                .iter()
+                .find(|s| s.keyset_id == n.keyset_id && s.value == n.value)
+                .map_or(0, |s| s.unspent);
+            if unspent < self.min_anonymity_set {
+                warnings.push(AnonymityWarning {
+                    keyset_id: n.keyset_id.clone(),
+                    value: n.value,
+                    unspent,
+                    notes: 1,
+                });
+            }
+        }
+        Ok(warnings)
+    }
+}
@@ -0,0 +1,191 @@
+//! The interface wallets use to talk to a mint, so the same wallet code runs
+//! against the in-process `Mint`, a `MintClient` for a mint actor, or a fake.
+//!
+//! It is split in two: `MintReader` holds the requests answered from keys
+//! and the spent set alone, which a replica can serve (see `replica`), and
+//! `MintTrait` adds everything that changes state or needs the quote tables.
+
+use std::sync::Arc;
+
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    announce::Announcement,
+    anonymity::AnonymitySet,
+    batchmelt::{BatchMeltRequest, BatchMeltResponse},
+    codec::{KeyEncoding, encode_keys},
+    conditions::Witness,
+    error::MintError,
+    issue::{MintQuote, MintRequest, MintResponse},
+    keyset::KeysetIdVersion,
+    melt::{MeltQuote, MeltRequest, MeltResponse},
+    mint::{MintInfo, ProofState},
+    protocol::{Hello, Request, Response, SwapRequest, SwapResponse},
+    restore::{RestoreRequest, RestoreResponse},
+    stats::DashboardStats,
+    types::Note,
+};
+
+/// A keyset's public keys, in ascending denomination order.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeysetKeys {
+    pub keyset_id: String,
+    /// How `keyset_id` was derived. Responses without it are `V1`.
+    #[serde(default)]
+    pub id_version: KeysetIdVersion,
+    pub keys: Vec<(u64, PublicKey)>,
+}
+
+impl KeysetKeys {
+    pub fn key(&self, value: u64) -> Option<&PublicKey> {
+        self.keys.iter().find(|(v, _)| *v == value).map(|(_, k)| k)
+    }
+
+    /// Whether notes carrying `keyset_id` belong to this keyset: it is the
+    /// keyset's ID, or the ID of the same keys under another version.
+    pub fn matches(&self, keyset_id: &str) -> bool {
+        keyset_id == self.keyset_id || KeysetIdVersion::matches(keyset_id, &self.keys)
+    }
+}
+
+/// Read-only requests: keysets, proof state and restores.
+pub trait MintReader {
+    fn info(&self) -> Result<MintInfo, MintError>;
+    fn keysets(&self) -> Result<Vec<KeysetKeys>, MintError>;
+    fn hello(&self) -> Result<Hello, MintError>;
+    fn check_state(&self, ys: &[PublicKey]) -> Result<Vec<ProofState>, MintError>;
+    fn restore_signatures(
+        &self,
+        req: Request<RestoreRequest>,
+    ) -> Result<Response<RestoreResponse>, MintError>;
+
+    /// The active keyset in the compact binary layout, for wallets on
+    /// metered links. About half the size of the JSON keys response.
+    fn compact_keys(&self, encoding: KeyEncoding) -> Result<Vec<u8>, MintError> {
+        Ok(encode_keys(&self.active_keyset()?, encoding))
+    }
+
+    /// Public keys of the keyset the mint currently issues from.
+    fn active_keyset(&self) -> Result<KeysetKeys, MintError> {
+        let info = self.info()?;
+        self.keysets()?
+            .into_iter()
+            .find(|k| k.keyset_id == info.keyset_id)
+            .ok_or(MintError::KeysetPaused(info.keyset_id))
+    }
+}
+
+pub trait MintTrait: MintReader {
+    fn handle_swap(&self, req: Request<SwapRequest>) -> Result<Response<SwapResponse>, MintError>;
+    fn melt_quote(&self, request: &str) -> Result<MeltQuote, MintError>;
+    /// Quotes paying `amount` to an invoice without an amount.
+    fn melt_quote_with_amount(&self, request: &str, amount: u64) -> Result<MeltQuote, MintError>;
+    fn melt(&self, req: Request<MeltRequest>) -> Result<Response<MeltResponse>, MintError>;
+    fn melt_quote_state(&self, id: &str) -> Result<MeltQuote, MintError>;
+    fn melt_batch(
+        &self,
+        req: Request<BatchMeltRequest>,
+    ) -> Result<Response<BatchMeltResponse>, MintError>;
+    fn mint_quote(&self, amount: u64) -> Result<MintQuote, MintError>;
+    fn mint_quote_onchain(&self, amount: u64) -> Result<MintQuote, MintError>;
+    fn get_quote(&self, id: &str) -> Result<MintQuote, MintError>;
+    fn mint(&self, req: Request<MintRequest>) -> Result<Response<MintResponse>, MintError>;
+    fn anonymity_sets(&self) -> Result<Vec<AnonymitySet>, MintError>;
+    fn dashboard_stats(&self) -> Result<DashboardStats, MintError>;
+    fn announcements(&self) -> Result<Vec<Announcement>, MintError>;
+}
+
+/// Shortcuts only an in-process mint offers: signing and spending without
+/// blinding, and reading the witnesses of spent notes.
+pub trait LocalMint: MintTrait {
+    /// Signs `y` directly with the active key for `value`. Returns the
+    /// keyset ID and the signature.
+    fn sign_direct(&self, value: u64, y: &PublicKey) -> Option<(String, PublicKey)>;
+    fn verify_and_spend(&self, note: &Note) -> Result<(), MintError>;
+    fn spent_witness(&self, y: &PublicKey) -> Option<Witness>;
+}
+
+/// A mint shared with other threads, e.g. a replica a `replica::follow`
+/// thread keeps caught up.
+impl<M: MintReader + ?Sized> MintReader for Arc<M> {
+    fn info(&self) -> Result<MintInfo, MintError> {
+        (**self).info()
+    }
+
+    fn keysets(&self) -> Result<Vec<KeysetKeys>, MintError> {
+        (**self).keysets()
+    }
+
+    fn hello(&self) -> Result<Hello, MintError> {
+        (**self).hello()
+    }
+
+    fn check_state(&self, ys: &[PublicKey]) -> Result<Vec<ProofState>, MintError> {
+        (**self).check_state(ys)
+    }
+
+    fn restore_signatures(
+        &self,
+        req: Request<RestoreRequest>,
+    ) -> Result<Response<RestoreResponse>, MintError> {
+        (**self).restore_signatures(req)
+    }
+}
+
+impl<M: MintTrait + ?Sized> MintTrait for Arc<M> {
+    fn handle_swap(&self, req: Request<SwapRequest>) -> Result<Response<SwapResponse>, MintError> {
+        (**self).handle_swap(req)
+    }
+
+    fn melt_quote(&self, request: &str) -> Result<MeltQuote, MintError> {
+        (**self).melt_quote(request)
+    }
+
+    fn melt_quote_with_amount(&self, request: &str, amount: u64) -> Result<MeltQuote, MintError> {
+        (**self).melt_quote_with_amount(request, amount)
+    }
+
+    fn melt(&self, req: Request<MeltRequest>) -> Result<Response<MeltResponse>, MintError> {
+        (**self).melt(req)
+    }
+
+    fn melt_quote_state(&self, id: &str) -> Result<MeltQuote, MintError> {
+        (**self).melt_quote_state(id)
+    }
+
+    fn melt_batch(
+        &self,
+        req: Request<BatchMeltRequest>,
+    ) -> Result<Response<BatchMeltResponse>, MintError> {
+        (**self).melt_batch(req)
+    }
+
+    fn mint_quote(&self, amount: u64) -> Result<MintQuote, MintError> {
+        (**self).mint_quote(amount)
+    }
+
+    fn mint_quote_onchain(&self, amount: u64) -> Result<MintQuote, MintError> {
+        (**self).mint_quote_onchain(amount)
+    }
+
+    fn get_quote(&self, id: &str) -> Result<MintQuote, MintError> {
+        (**self).get_quote(id)
+    }
+
+    fn mint(&self, req: Request<MintRequest>) -> Result<Response<MintResponse>, MintError> {
+        (**self).mint(req)
+    }
+
+    fn anonymity_sets(&self) -> Result<Vec<AnonymitySet>, MintError> {
+        (**self).anonymity_sets()
+    }
+
+    fn dashboard_stats(&self) -> Result<DashboardStats, MintError> {
+        (**self).dashboard_stats()
+    }
+
+    fn announcements(&self) -> Result<Vec<Announcement>, MintError> {
+        (**self).announcements()
+    }
+}
@@ -0,0 +1,387 @@
+//! Atomic exchange of ecash at two different mints.
+//!
+//! The initiator picks a preimage and locks its notes at mint A to
+//! `HTLC(hash) AND P2PK(responder)`, refundable to itself after `timeout`.
+//! The responder locks its notes at mint B to `HTLC(hash) AND
+//! P2PK(initiator)`, refundable after the earlier `timeout -
+//! RESPONDER_MARGIN`. Claiming at mint B reveals the preimage, which the
+//! responder then uses (from the `Preimage` message or from mint B's spent
+//! witnesses) to claim at mint A.
+//!
+//! Messages are plain serde values and can travel over any transport.
+
+use std::fmt;
+
+use rand::RngCore;
+use secp256k1::{Keypair, PublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    api::LocalMint,
+    codec::{from_hex, to_hex},
+    conditions::Condition,
+    error::WalletError,
+    types::Note,
+    wallet::Wallet,
+};
+
+/// How much earlier the responder's refund opens than the initiator's, so the
+/// responder always has time to claim after the preimage is revealed.
+pub const RESPONDER_MARGIN: u64 = 3600;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum SwapMessage {
+    Propose {
+        hash: String,
+        initiator: PublicKey,
+        give: u64,
+        want: u64,
+        timeout: u64,
+    },
+    Accept {
+        responder: PublicKey,
+    },
+    InitiatorLocked {
+        notes: Vec<Note>,
+    },
+    ResponderLocked {
+        notes: Vec<Note>,
+    },
+    Preimage {
+        preimage: String,
+    },
+}
+
+#[derive(Debug)]
+pub enum SwapError {
+    UnexpectedMessage,
+    /// The counterparty's notes are not locked as agreed.
+    BadLock,
+    ClaimFailed,
+    RefundNotYetAvailable,
+    Wallet(WalletError),
+}
+
+impl From<WalletError> for SwapError {
+    fn from(err: WalletError) -> Self {
+        SwapError::Wallet(err)
+    }
+}
+
+impl fmt::Display for SwapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SwapError::UnexpectedMessage => write!(f, "unexpected message"),
+            SwapError::BadLock => write!(f, "counterparty notes not locked as agreed"),
+            SwapError::ClaimFailed => write!(f, "claim failed"),
+            SwapError::RefundNotYetAvailable => write!(f, "refund locktime not reached"),
+            SwapError::Wallet(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SwapError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwapState {
+    Proposed,
+    Locked,
+    Completed,
+    Refunded,
+}
+
+fn lock(hash: &str, claimer: PublicKey, refund_to: PublicKey, locktime: u64) -> Condition {
+    Condition::HTLC(hash.to_string())
+        .and(Condition::P2PK(claimer))
+        .with_refund(locktime, refund_to)
+}
+
+fn check_lock(notes: &[Note], condition: &Condition, amount: u64) -> Result<(), SwapError> {
+    let locked = notes
+        .iter()
+        .all(|n| Condition::from_secret(&n.secret).is_some_and(|c| c == *condition));
+    let total: u64 = notes.iter().map(|n| n.value).sum();
+    if !locked || total != amount {
+        return Err(SwapError::BadLock);
+    }
+    Ok(())
+}
+
+/// Signs `notes` with `keypair`, adds the preimage if given, and redeems them.
+fn redeem(
+    notes: &[Note],
+    keypair: &Keypair,
+    preimage: Option<&[u8]>,
+    wallet: &mut Wallet,
+    mint: &impl LocalMint,
+) -> Result<(), SwapError> {
+    let mut notes = notes.to_vec();
+    for n in &mut notes {
+        if let Some(p) = preimage {
+            n.set_preimage(p);
+        }
+        n.sign_witness(keypair);
+    }
+    if !wallet.receive(mint, notes) {
+        return Err(SwapError::ClaimFailed);
+    }
+    Ok(())
+}
+
+/// Side that holds the preimage and gives `give` at its own mint for `want`
+/// at the responder's.
+pub struct Initiator {
+    pub state: SwapState,
+    keypair: Keypair,
+    preimage: [u8; 32],
+    hash: String,
+    give: u64,
+    want: u64,
+    timeout: u64,
+    responder: Option<PublicKey>,
+    locked: Vec<Note>,
+}
+
+impl Initiator {
+    pub fn new(keypair: Keypair, give: u64, want: u64, timeout: u64) -> (Self, SwapMessage) {
+        let mut preimage = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut preimage);
+        let hash = to_hex(&Sha256::digest(preimage));
+
+        let msg = SwapMessage::Propose {
+            hash: hash.clone(),
+            initiator: keypair.public_key(),
+            give,
+            want,
+            timeout,
+        };
+        let swap = Self {
+            state: SwapState::Proposed,
+            keypair,
+            preimage,
+            hash,
+            give,
+            want,
+            timeout,
+            responder: None,
+            locked: Vec::new(),
+        };
+        (swap, msg)
+    }
+
+    /// Locks `give` at mint A once the responder has accepted.
+    pub fn on_accept(
+        &mut self,
+        msg: SwapMessage,
+        wallet: &mut Wallet,
+        mint: &impl LocalMint,
+    ) -> Result<SwapMessage, SwapError> {
+        let (SwapState::Proposed, SwapMessage::Accept { responder }) = (self.state, msg) else {
+            return Err(SwapError::UnexpectedMessage);
+        };
+
+        let condition = lock(
+            &self.hash,
+            responder,
+            self.keypair.public_key(),
+            self.timeout,
+        );
+        self.locked = wallet.send_locked(mint, self.give, &condition)?;
+        self.responder = Some(responder);
+        self.state = SwapState::Locked;
+
+        Ok(SwapMessage::InitiatorLocked {
+            notes: self.locked.clone(),
+        })
+    }
+
+    /// Checks the responder's lock at mint B and claims it, revealing the
+    /// preimage.
+    pub fn on_responder_locked(
+        &mut self,
+        msg: SwapMessage,
+        wallet: &mut Wallet,
+        mint: &impl LocalMint,
+    ) -> Result<SwapMessage, SwapError> {
+        let (SwapState::Locked, SwapMessage::ResponderLocked { notes }, Some(responder)) =
+            (self.state, msg, self.responder)
+        else {
+            return Err(SwapError::UnexpectedMessage);
+        };
+
+        let condition = lock(
+            &self.hash,
+            self.keypair.public_key(),
+            responder,
+            self.timeout - RESPONDER_MARGIN,
+        );
+        check_lock(&notes, &condition, self.want)?;
+        redeem(&notes, &self.keypair, Some(&self.preimage), wallet, mint)?;
+        self.state = SwapState::Completed;
+
+        Ok(SwapMessage::Preimage {
+            preimage: to_hex(&self.preimage),
+        })
+    }
+
+    /// Takes the locked notes back after `timeout` if the swap stalled.
+    pub fn refund(
+        &mut self,
+        wallet: &mut Wallet,
+        mint: &impl LocalMint,
+        now: u64,
+    ) -> Result<(), SwapError> {
+        if self.state != SwapState::Locked {
+            return Err(SwapError::UnexpectedMessage);
+        }
+        if now < self.timeout {
+            return Err(SwapError::RefundNotYetAvailable);
+        }
+        redeem(&self.locked, &self.keypair, None, wallet, mint)?;
+        self.state = SwapState::Refunded;
+        Ok(())
+    }
+}
+
+/// Side that accepts a proposal, giving the initiator's `want` at its own
+/// mint for the initiator's `give`.
+pub struct Responder {
+    pub state: SwapState,
+    keypair: Keypair,
+    hash: String,
+    initiator: PublicKey,
+    give: u64,
+    want: u64,
+    timeout: u64,
+    theirs: Vec<Note>,
+    locked: Vec<Note>,
+}
+
+impl Responder {
+    pub fn on_propose(
+        keypair: Keypair,
+        msg: SwapMessage,
+    ) -> Result<(Self, SwapMessage), SwapError> {
+        let SwapMessage::Propose {
+            hash,
+            initiator,
+            give,
+            want,
+            timeout,
+        } = msg
+        else {
+            return Err(SwapError::UnexpectedMessage);
+        };
+        if timeout <= RESPONDER_MARGIN {
+            return Err(SwapError::BadLock);
+        }
+
+        let accept = SwapMessage::Accept {
+            responder: keypair.public_key(),
+        };
+        let swap = Self {
+            state: SwapState::Proposed,
+            keypair,
+            hash,
+            initiator,
+            give: want,
+            want: give,
+            timeout,
+            theirs: Vec::new(),
+            locked: Vec::new(),
+        };
+        Ok((swap, accept))
+    }
+
+    /// Checks the initiator's lock at mint A and locks `give` at mint B.
+    pub fn on_initiator_locked(
+        &mut self,
+        msg: SwapMessage,
+        wallet: &mut Wallet,
+        mint: &impl LocalMint,
+    ) -> Result<SwapMessage, SwapError> {
+        let (SwapState::Proposed, SwapMessage::InitiatorLocked { notes }) = (self.state, msg)
+        else {
+            return Err(SwapError::UnexpectedMessage);
+        };
+
+        let theirs = lock(
+            &self.hash,
+            self.keypair.public_key(),
+            self.initiator,
+            self.timeout,
+        );
+        check_lock(&notes, &theirs, self.want)?;
+
+        let ours = lock(
+            &self.hash,
+            self.initiator,
+            self.keypair.public_key(),
+            self.timeout - RESPONDER_MARGIN,
+        );
+        self.locked = wallet.send_locked(mint, self.give, &ours)?;
+        self.theirs = notes;
+        self.state = SwapState::Locked;
+
+        Ok(SwapMessage::ResponderLocked {
+            notes: self.locked.clone(),
+        })
+    }
+
+    /// Reads the preimage from mint B's spent witnesses, in case the
+    /// initiator claimed without sending it.
+    pub fn learn_preimage(&self, mint: &impl LocalMint) -> Option<Vec<u8>> {
+        self.locked
+            .iter()
+            .filter_map(|n| mint.spent_witness(&n.y)?.preimage)
+            .filter_map(|p| from_hex(&p))
+            .find(|p| to_hex(&Sha256::digest(p)) == self.hash)
+    }
+
+    /// Claims the initiator's notes at mint A with the revealed preimage.
+    pub fn complete(
+        &mut self,
+        preimage: &[u8],
+        wallet: &mut Wallet,
+        mint: &impl LocalMint,
+    ) -> Result<(), SwapError> {
+        if self.state != SwapState::Locked || to_hex(&Sha256::digest(preimage)) != self.hash {
+            return Err(SwapError::UnexpectedMessage);
+        }
+        redeem(&self.theirs, &self.keypair, Some(preimage), wallet, mint)?;
+        self.state = SwapState::Completed;
+        Ok(())
+    }
+
+    pub fn on_preimage(
+        &mut self,
+        msg: SwapMessage,
+        wallet: &mut Wallet,
+        mint: &impl LocalMint,
+    ) -> Result<(), SwapError> {
+        let SwapMessage::Preimage { preimage } = msg else {
+            return Err(SwapError::UnexpectedMessage);
+        };
+        let preimage = from_hex(&preimage).ok_or(SwapError::UnexpectedMessage)?;
+        self.complete(&preimage, wallet, mint)
+    }
+
+    /// Takes this side's notes back after its (earlier) refund locktime.
+    pub fn refund(
+        &mut self,
+        wallet: &mut Wallet,
+        mint: &impl LocalMint,
+        now: u64,
+    ) -> Result<(), SwapError> {
+        if self.state != SwapState::Locked {
+            return Err(SwapError::UnexpectedMessage);
+        }
+        if now < self.timeout - RESPONDER_MARGIN {
+            return Err(SwapError::RefundNotYetAvailable);
+        }
+        redeem(&self.locked, &self.keypair, None, wallet, mint)?;
+        self.state = SwapState::Refunded;
+        Ok(())
+    }
+}
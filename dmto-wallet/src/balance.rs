@@ -0,0 +1,77 @@
+//! Balance net of input fees. Every note spent costs the mint's input fee,
+//! so a wallet holding `gross` can send less than that. `Wallet::balances`
+//! estimates how much less, assuming the notes are spent in operations
+//! about the size of the wallet's usual sends and melts, so a UI can show
+//! an amount the user can actually send.
+
+use crate::{history::Direction, mint::MintInfo, wallet::Wallet};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Balance {
+    /// Total value of the wallet's notes.
+    pub gross: u64,
+    /// `gross` less the estimated input fees of spending it.
+    pub spendable: u64,
+    /// Inputs per operation the estimate assumes.
+    pub inputs_per_operation: usize,
+}
+
+impl Balance {
+    pub fn fees(&self) -> u64 {
+        self.gross - self.spendable
+    }
+}
+
+impl Wallet {
+    /// Gross and spendable balance at the mint described by `info`, with
+    /// operations sized by `typical_inputs`.
+    pub fn balances(&self, info: &MintInfo) -> Balance {
+        self.balances_for(info, self.typical_inputs())
+    }
+
+    /// Gross and spendable balance if every note is spent in operations of
+    /// `inputs_per_operation` inputs, the last one taking what is left.
+    pub fn balances_for(&self, info: &MintInfo, inputs_per_operation: usize) -> Balance {
+        let gross = self.balance();
+        let notes = self.notes.len();
+        let per_op = inputs_per_operation.clamp(1, notes.max(1));
+        let full = (notes / per_op) as u64;
+        let fees = info
+            .fee(per_op)
+            .saturating_mul(full)
+            .saturating_add(info.fee(notes % per_op));
+        Balance {
+            gross,
+            spendable: gross.saturating_sub(fees),
+            inputs_per_operation: per_op,
+        }
+    }
+
+    /// Notes a typical spend takes: the number of the wallet's notes,
+    /// largest first, covering the median outgoing amount in its history.
+    /// With no outgoing history, every note, as if the balance were sent in
+    /// one go.
+    pub fn typical_inputs(&self) -> usize {
+        let mut amounts: Vec<u64> = self
+            .history
+            .iter()
+            .filter(|tx| tx.direction == Direction::Outgoing)
+            .map(|tx| tx.amount)
+            .collect();
+        if amounts.is_empty() {
+            return self.notes.len();
+        }
+        amounts.sort_unstable();
+        let median = amounts[amounts.len() / 2];
+
+        let mut values: Vec<u64> = self.notes.iter().map(|n| n.value).collect();
+        values.sort_unstable_by(|a, b| b.cmp(a));
+        let mut sum = 0u64;
+        let covering = values.iter().take_while(|&&v| {
+            let short = sum < median;
+            sum = sum.saturating_add(v);
+            short
+        });
+        covering.count().max(1)
+    }
+}
@@ -0,0 +1,135 @@
+//! Paying several invoices from one set of inputs, e.g. a payroll run. The
+//! mint spends the inputs once and makes the payments concurrently. Each
+//! payment succeeds or fails on its own: what was set aside for a failed
+//! one comes back as change together with the unused fee reserves.
+
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::MintTrait,
+    canonical::sort_inputs,
+    error::{MintError, WalletError},
+    melt::MeltQuote,
+    operation,
+    receipt::FeeReceipt,
+    types::{Amount, Note},
+    wallet::Wallet,
+    walletpolicy::SpendKind,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchMeltRequest {
+    pub quotes: Vec<String>,
+    pub inputs: Vec<Note>,
+    /// Blank outputs for the change of the whole batch.
+    #[serde(default)]
+    pub outputs: Vec<PublicKey>,
+    /// The wallet's operation ID, for tracing (see `operation`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation: Option<String>,
+}
+
+/// What became of one quote in a batch.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MeltOutcome {
+    pub quote: MeltQuote,
+    /// Why the payment failed; `None` if it was made.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// What of the amount and fee reserve set aside for this quote comes
+    /// back as change: the unused reserve, or all of it if the payment
+    /// failed.
+    pub change: u64,
+    /// What the mint charged for a payment made. The batch's input fee is
+    /// charged to the first one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<FeeReceipt>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchMeltResponse {
+    /// In request order.
+    pub outcomes: Vec<MeltOutcome>,
+    /// Signatures on the first blank outputs, as in `MeltResponse`.
+    pub change: Vec<(u64, PublicKey)>,
+}
+
+impl Wallet {
+    /// Pays several Lightning invoices from one pool of notes, returning
+    /// each invoice's preimage or error in order. Invoices the mint will not
+    /// quote fail alone; the call fails as a whole only if nothing can be
+    /// paid. Change for the whole batch, including what was set aside for
+    /// failed payments, comes back in one go.
+    pub fn pay_invoices(
+        &mut self,
+        mint: &impl MintTrait,
+        invoices: &[String],
+    ) -> Result<Vec<Result<String, WalletError>>, WalletError> {
+        let quotes: Vec<_> = invoices
+            .iter()
+            .map(|invoice| mint.melt_quote(invoice))
+            .collect();
+        let quoted: Vec<&MeltQuote> = quotes.iter().filter_map(|q| q.as_ref().ok()).collect();
+        if quoted.is_empty() {
+            return Ok(quotes
+                .into_iter()
+                .map(|q| Err(q.unwrap_err().into()))
+                .collect());
+        }
+        let due = Amount::checked_sum(quoted.iter().flat_map(|q| [q.amount, q.fee_reserve]))
+            .ok_or(MintError::AmountOverflow)?
+            .0;
+
+        let kind = SpendKind::MeltBatch {
+            requests: quoted.iter().map(|q| q.request.clone()).collect(),
+        };
+        let ids: Vec<String> = quoted.iter().map(|q| q.id.clone()).collect();
+        let (outcomes, mut input_fee) = self.guarded(kind, due, |w| {
+            let info = mint.info()?;
+            let keyset = mint.active_keyset()?;
+            let session = w.connect(mint)?;
+            let (mut inputs, _) = w.select_with_fee(&info, due)?;
+            sort_inputs(&mut inputs);
+            let in_sum: u64 = inputs.iter().map(|n| n.value).sum();
+            let input_fee = info.fee(inputs.len());
+            let blanks = w.blank_outputs(in_sum - input_fee);
+
+            let req = BatchMeltRequest {
+                quotes: ids,
+                inputs: inputs.clone(),
+                outputs: blanks.iter().map(|(_, _, b)| b.blinded_point).collect(),
+                operation: operation::current(),
+            };
+            let resp =
+                match (mint.melt_batch(session.request(req))).and_then(|r| r.accept(&session)) {
+                    Ok(resp) => resp,
+                    Err(e) => {
+                        w.notes.extend(inputs);
+                        return Err(e.into());
+                    }
+                };
+            w.keep_change(&keyset, resp.change, blanks)?;
+            Ok((resp.outcomes, input_fee))
+        })?;
+
+        let mut outcomes = outcomes.into_iter();
+        Ok(quotes
+            .into_iter()
+            .map(|quote| {
+                let quote = quote?;
+                let outcome = outcomes.next().ok_or_else(|| {
+                    MintError::PaymentFailed(format!("no outcome for quote {}", quote.id))
+                })?;
+                match outcome.error {
+                    Some(e) => Err(MintError::PaymentFailed(e).into()),
+                    // The input fee is charged to the first payment made.
+                    None => {
+                        let fee = outcome.quote.fee_paid + std::mem::take(&mut input_fee);
+                        self.record_melt(&outcome.quote, fee, outcome.receipt)
+                    }
+                }
+            })
+            .collect())
+    }
+}
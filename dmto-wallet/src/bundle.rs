@@ -0,0 +1,57 @@
+use std::{fs, io, path::Path};
+
+use secp256k1::{PublicKey, XOnlyPublicKey, schnorr::Signature};
+use serde::{Deserialize, Serialize};
+
+use crate::{keyset::KeysetIdVersion, signing};
+
+/// A mint's public keyset signed with its identity key, for carrying to
+/// wallets out of band (file, USB stick, QR) and verifying without network.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeysetBundle {
+    pub keyset_id: String,
+    pub keys: Vec<(u64, PublicKey)>,
+    pub identity: XOnlyPublicKey,
+    pub signature: Signature,
+}
+
+impl KeysetBundle {
+    /// The bytes the mint signs.
+    pub fn message(keyset_id: &str, keys: &[(u64, PublicKey)]) -> Vec<u8> {
+        serde_json::to_vec(&(keyset_id, keys)).unwrap()
+    }
+
+    /// Checks the signature and that the keyset ID matches the keys. Pass the
+    /// mint's identity key if it is known from elsewhere; otherwise only the
+    /// bundle's internal consistency is checked.
+    pub fn verify(&self, identity: Option<&XOnlyPublicKey>) -> bool {
+        if identity.is_some_and(|id| *id != self.identity) {
+            return false;
+        }
+        if !KeysetIdVersion::matches(&self.keyset_id, &self.keys) {
+            return false;
+        }
+        signing::verify(
+            &self.identity,
+            &Self::message(&self.keyset_id, &self.keys),
+            &self.signature,
+        )
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec(self).map_err(io::Error::other)?)
+    }
+
+    /// Reads and verifies a bundle from `path`.
+    pub fn import(path: &Path, identity: Option<&XOnlyPublicKey>) -> io::Result<Self> {
+        let bundle: KeysetBundle = serde_json::from_slice(&fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if !bundle.verify(identity) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "keyset bundle signature or id mismatch",
+            ));
+        }
+        Ok(bundle)
+    }
+}
@@ -0,0 +1,132 @@
+//! Canonical order of the inputs and outputs in a request. Inputs are
+//! sorted by `Y`, outputs by value and then blinded message, and the blank
+//! outputs of a melt by blinded message, points compared as compressed
+//! bytes. Every request then has one form however the wallet built it, so a
+//! signature over all of it (SIG_ALL) or an idempotency key derived from it
+//! does not depend on construction order, and the order leaks nothing about
+//! which outputs are the payment and which the change.
+//!
+//! Mints refuse swaps, melts and issuance out of canonical order while
+//! `Mint::canonical_order` is set, as it is by default. Wallets in this
+//! crate canonicalize every request and map the signatures back to the
+//! order they asked for them in.
+
+use secp256k1::PublicKey;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    batchmelt::BatchMeltRequest, issue::MintRequest, melt::MeltRequest, protocol::SwapRequest,
+    types::Note,
+};
+
+pub fn input_key(note: &Note) -> [u8; 33] {
+    note.y.serialize()
+}
+
+pub fn output_key(output: &(u64, PublicKey)) -> (u64, [u8; 33]) {
+    (output.0, output.1.serialize())
+}
+
+pub fn sort_inputs(inputs: &mut [Note]) {
+    inputs.sort_by_key(input_key);
+}
+
+pub fn sort_outputs(outputs: &mut [(u64, PublicKey)]) {
+    outputs.sort_by_key(output_key);
+}
+
+pub fn sort_blanks(blanks: &mut [PublicKey]) {
+    blanks.sort_by_key(PublicKey::serialize);
+}
+
+/// Positions of `outputs` in canonical order: `outputs[order[0]]` comes
+/// first.
+pub fn output_order(outputs: &[(u64, PublicKey)]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..outputs.len()).collect();
+    order.sort_by_key(|&i| output_key(&outputs[i]));
+    order
+}
+
+// Equal neighbours pass: a note or output given twice is refused by the
+// checks for that, with a clearer error.
+fn inputs_sorted(inputs: &[Note]) -> bool {
+    inputs.is_sorted_by_key(input_key)
+}
+
+fn outputs_sorted(outputs: &[(u64, PublicKey)]) -> bool {
+    outputs.is_sorted_by_key(output_key)
+}
+
+fn blanks_sorted(blanks: &[PublicKey]) -> bool {
+    blanks.is_sorted_by_key(PublicKey::serialize)
+}
+
+/// SHA-256 over `inputs` and `outputs` in canonical order, whatever order
+/// they are given in: what a signature over a whole request covers, and
+/// what idempotency keys are derived from.
+pub fn request_digest(inputs: &[Note], outputs: &[(u64, PublicKey)]) -> [u8; 32] {
+    let mut ys: Vec<[u8; 33]> = inputs.iter().map(input_key).collect();
+    ys.sort_unstable();
+    let mut outputs: Vec<(u64, [u8; 33])> = outputs.iter().map(output_key).collect();
+    outputs.sort_unstable();
+
+    let mut hasher = Sha256::new();
+    hasher.update(b"dmto-request");
+    hasher.update((ys.len() as u64).to_be_bytes());
+    for y in &ys {
+        hasher.update(y);
+    }
+    hasher.update((outputs.len() as u64).to_be_bytes());
+    for (value, blinded) in &outputs {
+        hasher.update(value.to_be_bytes());
+        hasher.update(blinded);
+    }
+    hasher.finalize().into()
+}
+
+impl SwapRequest {
+    pub fn canonicalize(&mut self) {
+        sort_inputs(&mut self.inputs);
+        sort_outputs(&mut self.outputs);
+    }
+
+    pub fn is_canonical(&self) -> bool {
+        inputs_sorted(&self.inputs) && outputs_sorted(&self.outputs)
+    }
+
+    pub fn digest(&self) -> [u8; 32] {
+        request_digest(&self.inputs, &self.outputs)
+    }
+}
+
+impl MeltRequest {
+    pub fn canonicalize(&mut self) {
+        sort_inputs(&mut self.inputs);
+        sort_blanks(&mut self.outputs);
+    }
+
+    pub fn is_canonical(&self) -> bool {
+        inputs_sorted(&self.inputs) && blanks_sorted(&self.outputs)
+    }
+}
+
+impl BatchMeltRequest {
+    pub fn canonicalize(&mut self) {
+        sort_inputs(&mut self.inputs);
+        sort_blanks(&mut self.outputs);
+    }
+
+    pub fn is_canonical(&self) -> bool {
+        inputs_sorted(&self.inputs) && blanks_sorted(&self.outputs)
+    }
+}
+
+impl MintRequest {
+    pub fn canonicalize(&mut self) {
+        sort_outputs(&mut self.outputs);
+    }
+
+    pub fn is_canonical(&self) -> bool {
+        outputs_sorted(&self.outputs)
+    }
+}
@@ -0,0 +1,305 @@
+//! Compact fixed-layout binary encoding of notes and blind signatures, used
+//! where JSON would be too slow or too large (journal, replication).
+//!
+//! Note layout:
+//!
+//! | bytes | field                         |
+//! | ----- | ----------------------------- |
+//! | 8     | value, big-endian             |
+//! | 8     | keyset id                     |
+//! | 33    | `Y`, compressed               |
+//! | 33    | `C`, compressed               |
+//! | 2     | secret length, big-endian     |
+//! | n     | secret                        |
+//! | 1     | DLEQ present (0 or 1)         |
+//! | 96    | DLEQ `e`, `s`, `r`, if present |
+//!
+//! Signature layout: 8 bytes value, then 33 bytes compressed `C'`.
+//!
+//! Keyset layout, for serving keys to wallets on slow links:
+//!
+//! | bytes      | field                                          |
+//! | ---------- | ---------------------------------------------- |
+//! | 1          | flags: 1 = x-only keys, 2 = power-of-two values |
+//! | 8          | keyset id                                      |
+//! | 2          | key count `n`, big-endian                      |
+//! | 8n         | values, big-endian, unless power-of-two         |
+//! | ceil(n/8)  | key parity bits (1 = odd), x-only only          |
+//! | 33n or 32n | keys, compressed or x-only                     |
+//!
+//! Power-of-two values are `1, 2, 4, ...` and are not sent. X-only keys
+//! keep their parity in the bitmap, so every key decodes exactly; the
+//! unblinding math needs the full point.
+
+use secp256k1::PublicKey;
+
+use secp256k1::SecretKey;
+
+use crate::{api::KeysetKeys, dleq::NoteDleq, keyset::KeysetIdVersion, types::Note};
+
+pub const NOTE_FIXED_LEN: usize = 8 + 8 + 33 + 33 + 2;
+const DLEQ_LEN: usize = 96;
+pub const SIGNATURE_LEN: usize = 8 + 33;
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Appends `v` as an LEB128 varint: 7 bits per byte, low bits first, high
+/// bit set on all but the last byte.
+pub fn put_varint(mut v: u64, out: &mut Vec<u8>) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+/// Reads a varint from the front of `buf`, returning it with the number of
+/// bytes consumed.
+pub fn get_varint(buf: &[u8]) -> Option<(u64, usize)> {
+    let mut v = 0u64;
+    for (i, shift) in (0..64).step_by(7).enumerate() {
+        let b = *buf.get(i)?;
+        v |= u64::from(b & 0x7f) << shift;
+        if b & 0x80 == 0 {
+            return Some((v, i + 1));
+        }
+    }
+    None
+}
+
+const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// URL-safe base64 without padding.
+pub fn to_base64url(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(BASE64URL[(n >> (18 - 6 * i)) as usize & 63] as char);
+        }
+    }
+    out
+}
+
+/// Decodes URL-safe base64 without padding.
+pub fn from_base64url(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for c in s.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'-' => 62,
+            b'_' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+pub fn encode_note(note: &Note, out: &mut Vec<u8>) {
+    let mut keyset_id = [0u8; 8];
+    if let Some(id) = from_hex(&note.keyset_id) {
+        let n = id.len().min(8);
+        keyset_id[..n].copy_from_slice(&id[..n]);
+    }
+
+    out.extend_from_slice(&note.value.to_be_bytes());
+    out.extend_from_slice(&keyset_id);
+    out.extend_from_slice(&note.y.serialize());
+    out.extend_from_slice(&note.c.serialize());
+    out.extend_from_slice(&(note.secret.len() as u16).to_be_bytes());
+    out.extend_from_slice(&note.secret);
+    match &note.dleq {
+        Some(p) => {
+            out.push(1);
+            for k in [p.e, p.s, p.r] {
+                out.extend_from_slice(&k.secret_bytes());
+            }
+        }
+        None => out.push(0),
+    }
+}
+
+/// Decodes one note from the front of `buf`, returning it with the number of
+/// bytes consumed.
+pub fn decode_note(buf: &[u8]) -> Option<(Note, usize)> {
+    if buf.len() < NOTE_FIXED_LEN {
+        return None;
+    }
+
+    let value = u64::from_be_bytes(buf[0..8].try_into().ok()?);
+    let keyset_id = to_hex(&buf[8..16]);
+    let y = PublicKey::from_slice(&buf[16..49]).ok()?;
+    let c = PublicKey::from_slice(&buf[49..82]).ok()?;
+    let secret_len = u16::from_be_bytes(buf[82..84].try_into().ok()?) as usize;
+
+    let mut end = NOTE_FIXED_LEN + secret_len;
+    let secret = buf.get(NOTE_FIXED_LEN..end)?.to_vec();
+
+    let dleq = match *buf.get(end)? {
+        0 => None,
+        1 => {
+            let raw = buf.get(end + 1..end + 1 + DLEQ_LEN)?;
+            let key = |i: usize| SecretKey::from_slice(&raw[i * 32..(i + 1) * 32]).ok();
+            Some(NoteDleq {
+                e: key(0)?,
+                s: key(1)?,
+                r: key(2)?,
+            })
+        }
+        _ => return None,
+    };
+    end += 1 + dleq.map_or(0, |_| DLEQ_LEN);
+
+    Some((
+        Note {
+            value,
+            keyset_id,
+            secret,
+            y,
+            c,
+            dleq,
+            witness: None,
+        },
+        end,
+    ))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyEncoding {
+    Compressed,
+    XOnly,
+}
+
+const KEYS_XONLY: u8 = 1;
+const KEYS_POWER2: u8 = 2;
+
+pub fn encode_keys(keyset: &KeysetKeys, encoding: KeyEncoding) -> Vec<u8> {
+    let mut keys = keyset.keys.clone();
+    keys.sort_by_key(|(v, _)| *v);
+    let power2 = keys.iter().enumerate().all(|(i, (v, _))| *v == 1 << i);
+
+    let mut flags = 0;
+    if encoding == KeyEncoding::XOnly {
+        flags |= KEYS_XONLY;
+    }
+    if power2 {
+        flags |= KEYS_POWER2;
+    }
+    let mut id = [0u8; 8];
+    if let Some(bytes) = from_hex(&keyset.keyset_id) {
+        let n = bytes.len().min(8);
+        id[..n].copy_from_slice(&bytes[..n]);
+    }
+
+    let mut out = vec![flags];
+    out.extend_from_slice(&id);
+    out.extend_from_slice(&(keys.len() as u16).to_be_bytes());
+    if !power2 {
+        for (v, _) in &keys {
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+    match encoding {
+        KeyEncoding::Compressed => {
+            for (_, k) in &keys {
+                out.extend_from_slice(&k.serialize());
+            }
+        }
+        KeyEncoding::XOnly => {
+            let mut parity = vec![0u8; keys.len().div_ceil(8)];
+            for (i, (_, k)) in keys.iter().enumerate() {
+                if k.serialize()[0] == 0x03 {
+                    parity[i / 8] |= 1 << (i % 8);
+                }
+            }
+            out.extend_from_slice(&parity);
+            for (_, k) in &keys {
+                out.extend_from_slice(&k.serialize()[1..]);
+            }
+        }
+    }
+    out
+}
+
+pub fn decode_keys(buf: &[u8]) -> Option<KeysetKeys> {
+    let flags = *buf.first()?;
+    let keyset_id = to_hex(buf.get(1..9)?);
+    let n = u16::from_be_bytes(buf.get(9..11)?.try_into().ok()?) as usize;
+    let mut pos = 11;
+
+    let values: Vec<u64> = if flags & KEYS_POWER2 != 0 {
+        (0..n)
+            .map(|i| 1u64.checked_shl(i as u32))
+            .collect::<Option<_>>()?
+    } else {
+        let raw = buf.get(pos..pos + 8 * n)?;
+        pos += 8 * n;
+        raw.chunks(8)
+            .map(|c| u64::from_be_bytes(c.try_into().unwrap()))
+            .collect()
+    };
+
+    let keys = if flags & KEYS_XONLY != 0 {
+        let parity = buf.get(pos..pos + n.div_ceil(8))?;
+        pos += parity.len();
+        (0..n)
+            .map(|i| {
+                let mut key = [0u8; 33];
+                key[0] = if parity[i / 8] & (1 << (i % 8)) != 0 {
+                    0x03
+                } else {
+                    0x02
+                };
+                key[1..].copy_from_slice(buf.get(pos + 32 * i..pos + 32 * (i + 1))?);
+                PublicKey::from_slice(&key).ok()
+            })
+            .collect::<Option<Vec<_>>>()?
+    } else {
+        (0..n)
+            .map(|i| PublicKey::from_slice(buf.get(pos + 33 * i..pos + 33 * (i + 1))?).ok())
+            .collect::<Option<Vec<_>>>()?
+    };
+
+    Some(KeysetKeys {
+        keyset_id,
+        id_version: KeysetIdVersion::V1,
+        keys: values.into_iter().zip(keys).collect(),
+    })
+}
+
+pub fn encode_signature(value: u64, sig: &PublicKey, out: &mut Vec<u8>) {
+    out.extend_from_slice(&value.to_be_bytes());
+    out.extend_from_slice(&sig.serialize());
+}
+
+pub fn decode_signature(buf: &[u8]) -> Option<(u64, PublicKey)> {
+    let buf = buf.get(..SIGNATURE_LEN)?;
+    let value = u64::from_be_bytes(buf[0..8].try_into().ok()?);
+    let sig = PublicKey::from_slice(&buf[8..]).ok()?;
+    Some((value, sig))
+}
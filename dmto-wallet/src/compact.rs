@@ -0,0 +1,345 @@
+//! Compact binary token serialization, for tokens with many notes where the
+//! JSON form (`uri`) repeats each keyset ID per note and spells out every
+//! secret and point. Mint URLs and units go in a string table and keyset IDs
+//! in a keyset table, each written once however many tokens and notes use
+//! them; notes refer to them by index. `Y` is dropped, since the reader
+//! recomputes it from the secret, and numbers are varints (see `codec`).
+//! Layout:
+//!
+//! | bytes | field                                                 |
+//! | ----- | ----------------------------------------------------- |
+//! | 1     | format version                                        |
+//! |       | varint string count; per string: varint length, bytes |
+//! |       | varint keyset count; per keyset: 0 and the 8-byte ID, |
+//! |       | or 1, varint length and the ID as text                |
+//! |       | varint token count                                    |
+//! |       | per token: varint URL index, varint unit index,       |
+//! |       | varint note count, the notes, memo flag (0 or 1),     |
+//! |       | varint length and memo if present                     |
+//! |       | per note: varint keyset index, varint value, varint   |
+//! |       | secret length, secret, 33 `C`, DLEQ flag, 96 DLEQ     |
+//! |       | `e`, `s`, `r` if present                              |
+//!
+//! Keyset IDs that are not 8 bytes of lowercase hex, such as the base64 IDs
+//! of older mints, are written as text. Witnesses are not included.
+
+use secp256k1::{PublicKey, SecretKey};
+
+use crate::{
+    codec::{from_hex, get_varint, put_varint, to_hex},
+    dleq::NoteDleq,
+    hash::try_hash_to_curve,
+    token::Token,
+    types::Note,
+};
+
+pub const FORMAT_VERSION: u8 = 1;
+
+const DLEQ_LEN: usize = 96;
+
+const KEYSET_HEX: u8 = 0;
+const KEYSET_TEXT: u8 = 1;
+
+/// Index of `item` in `table`, appending it if it is new.
+fn intern(table: &mut Vec<String>, item: &str) -> u64 {
+    let i = match table.iter().position(|s| s == item) {
+        Some(i) => i,
+        None => {
+            table.push(item.to_string());
+            table.len() - 1
+        }
+    };
+    i as u64
+}
+
+fn put_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    put_varint(bytes.len() as u64, out);
+    out.extend_from_slice(bytes);
+}
+
+fn keyset_bytes(id: &str) -> Option<[u8; 8]> {
+    let bytes = from_hex(id)?;
+    if to_hex(&bytes) != id {
+        return None;
+    }
+    bytes.try_into().ok()
+}
+
+/// Encodes `tokens` together, sharing mint URLs, units and keyset IDs.
+pub fn encode_tokens(tokens: &[Token]) -> Vec<u8> {
+    let mut strings = Vec::new();
+    let mut keysets = Vec::new();
+    let mut body = Vec::new();
+
+    put_varint(tokens.len() as u64, &mut body);
+    for token in tokens {
+        put_varint(intern(&mut strings, &token.mint_url), &mut body);
+        put_varint(intern(&mut strings, &token.unit), &mut body);
+        put_varint(token.notes.len() as u64, &mut body);
+        for n in &token.notes {
+            put_varint(intern(&mut keysets, &n.keyset_id), &mut body);
+            put_varint(n.value, &mut body);
+            put_bytes(&n.secret, &mut body);
+            body.extend_from_slice(&n.c.serialize());
+            match &n.dleq {
+                Some(p) => {
+                    body.push(1);
+                    for k in [p.e, p.s, p.r] {
+                        body.extend_from_slice(&k.secret_bytes());
+                    }
+                }
+                None => body.push(0),
+            }
+        }
+        match &token.memo {
+            Some(memo) => {
+                body.push(1);
+                put_bytes(memo.as_bytes(), &mut body);
+            }
+            None => body.push(0),
+        }
+    }
+
+    let mut out = vec![FORMAT_VERSION];
+    put_varint(strings.len() as u64, &mut out);
+    for s in &strings {
+        put_bytes(s.as_bytes(), &mut out);
+    }
+    put_varint(keysets.len() as u64, &mut out);
+    for id in &keysets {
+        match keyset_bytes(id) {
+            Some(bytes) => {
+                out.push(KEYSET_HEX);
+                out.extend_from_slice(&bytes);
+            }
+            None => {
+                out.push(KEYSET_TEXT);
+                put_bytes(id.as_bytes(), &mut out);
+            }
+        }
+    }
+    out.extend_from_slice(&body);
+    out
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.buf.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let (v, len) = get_varint(self.buf.get(self.pos..)?)?;
+        self.pos += len;
+        Some(v)
+    }
+
+    fn index<'t, T>(&mut self, table: &'t [T]) -> Option<&'t T> {
+        table.get(usize::try_from(self.varint()?).ok()?)
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = usize::try_from(self.varint()?).ok()?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        String::from_utf8(self.bytes()?.to_vec()).ok()
+    }
+}
+
+/// Decodes tokens written by `encode_tokens`. `None` if the buffer is
+/// truncated, malformed or of an unknown version.
+pub fn decode_tokens(buf: &[u8]) -> Option<Vec<Token>> {
+    let mut r = Reader { buf, pos: 0 };
+    if r.byte()? != FORMAT_VERSION {
+        return None;
+    }
+    // Every item takes at least a byte, so a count larger than what is left
+    // fails here rather than after looping over the rest of the buffer.
+    let count = |r: &mut Reader| {
+        let n = usize::try_from(r.varint()?).ok()?;
+        (n <= r.buf.len() - r.pos).then_some(n)
+    };
+
+    let strings = (0..count(&mut r)?)
+        .map(|_| r.string())
+        .collect::<Option<Vec<_>>>()?;
+    let keysets = (0..count(&mut r)?)
+        .map(|_| match r.byte()? {
+            KEYSET_HEX => Some(to_hex(r.take(8)?)),
+            KEYSET_TEXT => r.string(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut tokens = Vec::new();
+    for _ in 0..count(&mut r)? {
+        let mint_url = r.index(&strings)?.clone();
+        let unit = r.index(&strings)?.clone();
+        let mut notes = Vec::new();
+        for _ in 0..count(&mut r)? {
+            let keyset_id = r.index(&keysets)?.clone();
+            let value = r.varint()?;
+            let secret = r.bytes()?.to_vec();
+            let c = PublicKey::from_slice(r.take(33)?).ok()?;
+            let dleq = match r.byte()? {
+                0 => None,
+                1 => {
+                    let raw = r.take(DLEQ_LEN)?;
+                    let key = |i: usize| SecretKey::from_slice(&raw[i * 32..(i + 1) * 32]).ok();
+                    Some(NoteDleq {
+                        e: key(0)?,
+                        s: key(1)?,
+                        r: key(2)?,
+                    })
+                }
+                _ => return None,
+            };
+            notes.push(Note {
+                value,
+                keyset_id,
+                y: try_hash_to_curve(&secret).ok()?,
+                secret,
+                c,
+                dleq,
+                witness: None,
+            });
+        }
+        let memo = match r.byte()? {
+            0 => None,
+            1 => Some(r.string()?),
+            _ => return None,
+        };
+        tokens.push(Token {
+            mint_url,
+            unit,
+            notes,
+            memo,
+        });
+    }
+    (r.pos == buf.len()).then_some(tokens)
+}
+
+impl Token {
+    pub fn to_compact(&self) -> Vec<u8> {
+        encode_tokens(std::slice::from_ref(self))
+    }
+
+    /// Decodes a single token in the compact format.
+    pub fn from_compact(buf: &[u8]) -> Option<Token> {
+        let mut tokens = decode_tokens(buf)?;
+        (tokens.len() == 1).then(|| tokens.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, RngCore, rngs::ThreadRng};
+    use secp256k1::{Secp256k1, SecretKey};
+
+    use super::*;
+    use crate::hash::hash_to_curve;
+
+    fn random_note(rng: &mut ThreadRng, keyset_id: &str) -> Note {
+        let mut secret = vec![0; rng.gen_range(1..80)];
+        rng.fill_bytes(&mut secret);
+        let key = || SecretKey::new(&mut rand::thread_rng());
+        Note {
+            value: 1 << rng.gen_range(0..40),
+            keyset_id: keyset_id.to_string(),
+            y: hash_to_curve(&secret),
+            secret,
+            c: key().public_key(&Secp256k1::new()),
+            dleq: rng.gen_bool(0.5).then(|| NoteDleq {
+                e: key(),
+                s: key(),
+                r: key(),
+            }),
+            witness: None,
+        }
+    }
+
+    fn random_tokens(rng: &mut ThreadRng) -> Vec<Token> {
+        let mints = [
+            "https://mint.example",
+            "https://other.example:3338",
+            "http://localhost",
+        ];
+        let mut keysets: Vec<String> = (0..5)
+            .map(|_| {
+                let mut id = [0u8; 8];
+                rng.fill_bytes(&mut id);
+                to_hex(&id)
+            })
+            .collect();
+        keysets.push("9mlfd5vCzgGl".to_string());
+        keysets.push("00ABCDEF01234567".to_string());
+
+        (0..rng.gen_range(1..6))
+            .map(|i| {
+                let notes = (0..rng.gen_range(0..200))
+                    .map(|_| {
+                        let keyset = &keysets[rng.gen_range(0..keysets.len())];
+                        random_note(rng, keyset)
+                    })
+                    .collect();
+                let mut token = Token::new(mints[i % mints.len()], notes);
+                if rng.gen_bool(0.5) {
+                    token = token.with_memo("thanks for the coffee");
+                }
+                if i == 3 {
+                    token.unit = "usd".to_string();
+                }
+                token
+            })
+            .collect()
+    }
+
+    fn json(tokens: &[Token]) -> Vec<u8> {
+        serde_json::to_vec(tokens).unwrap()
+    }
+
+    #[test]
+    fn round_trips_match_the_json_form() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let tokens = random_tokens(&mut rng);
+            let decoded = decode_tokens(&encode_tokens(&tokens)).unwrap();
+            assert_eq!(json(&decoded), json(&tokens));
+
+            for token in &tokens {
+                let decoded = Token::from_compact(&token.to_compact()).unwrap();
+                assert_eq!(json(&[decoded]), json(std::slice::from_ref(token)));
+            }
+        }
+    }
+
+    #[test]
+    fn compact_form_is_smaller() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let tokens = random_tokens(&mut rng);
+            assert!(encode_tokens(&tokens).len() < json(&tokens).len());
+        }
+    }
+
+    #[test]
+    fn rejects_trailing_and_truncated_input() {
+        let tokens = random_tokens(&mut rand::thread_rng());
+        let mut buf = encode_tokens(&tokens);
+        assert!(decode_tokens(&buf[..buf.len() - 1]).is_none());
+        buf.push(0);
+        assert!(decode_tokens(&buf).is_none());
+    }
+}
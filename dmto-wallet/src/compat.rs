@@ -0,0 +1,320 @@
+//! Working with mints built by other implementations. The implementation is
+//! detected from `MintInfo::version` and mapped to the quirks the wallet
+//! has to adapt to:
+//!
+//! - field names: Cashu mints use the NUT names (`amount`, `id`, `C`) and
+//!   string secrets; `normalize_fields` rewrites their JSON into this
+//!   crate's layout before it is deserialized;
+//! - keyset IDs: mints from before keyset ID versioning use base64 IDs.
+//!   `CompatMint` shows the wallet the version `00` ID computed from the
+//!   keys and translates back on the way out;
+//! - fee rounding, for mints that round the input fee down.
+//!
+//! Quirks for a mint the table gets wrong can be set by hand with
+//! `CompatMint::with_quirks`. Built with the `compat` feature.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use secp256k1::PublicKey;
+use serde_json::Value;
+
+pub use crate::keyset::KeysetIdVersion;
+use crate::{
+    announce::Announcement,
+    anonymity::AnonymitySet,
+    api::{KeysetKeys, MintReader, MintTrait},
+    batchmelt::{BatchMeltRequest, BatchMeltResponse},
+    error::MintError,
+    issue::{MintQuote, MintRequest, MintResponse},
+    melt::{MeltQuote, MeltRequest, MeltResponse},
+    mint::{FeeRounding, MintInfo, ProofState},
+    protocol::{Hello, Request, Response, SwapRequest, SwapResponse},
+    restore::{RestoreRequest, RestoreResponse},
+    stats::DashboardStats,
+    types::Note,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Implementation {
+    Dmto,
+    Nutshell,
+    Cdk,
+    Other(String),
+}
+
+/// What `detect` made of a version string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Detected {
+    pub implementation: Implementation,
+    /// `major.minor.patch`, when the version string has one.
+    pub version: Option<(u32, u32, u32)>,
+}
+
+/// Reads strings like `Nutshell/0.15.3` or `cdk-mintd/0.4.0`.
+pub fn detect(version: &str) -> Detected {
+    let (name, number) = version.split_once('/').unwrap_or((version, ""));
+    let name = name.trim().to_lowercase();
+    let implementation = if name == "dmto" {
+        Implementation::Dmto
+    } else if name == "nutshell" {
+        Implementation::Nutshell
+    } else if name.starts_with("cdk") {
+        Implementation::Cdk
+    } else {
+        Implementation::Other(name)
+    };
+    let mut parts = number
+        .trim()
+        .trim_start_matches('v')
+        .split(['.', '-', '+'])
+        .map(|p| p.parse::<u32>().ok());
+    let version = match (parts.next(), parts.next(), parts.next()) {
+        (Some(Some(major)), Some(Some(minor)), Some(Some(patch))) => Some((major, minor, patch)),
+        _ => None,
+    };
+    Detected {
+        implementation,
+        version,
+    }
+}
+
+/// NUT field names and the names this crate uses for them.
+pub const NUT_FIELDS: [(&str, &str); 4] = [
+    ("amount", "value"),
+    ("id", "keyset_id"),
+    ("C", "c"),
+    ("Y", "y"),
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    pub keyset_ids: KeysetIdVersion,
+    pub fee_rounding: FeeRounding,
+    /// Field renames from the mint's JSON to this crate's.
+    pub field_names: Vec<(String, String)>,
+    /// Secrets are sent as strings rather than byte arrays.
+    pub string_secrets: bool,
+}
+
+impl Quirks {
+    /// A mint that behaves like this crate's.
+    pub fn none() -> Self {
+        Self {
+            keyset_ids: KeysetIdVersion::V1,
+            fee_rounding: FeeRounding::Up,
+            field_names: Vec::new(),
+            string_secrets: false,
+        }
+    }
+
+    pub fn for_mint(detected: &Detected) -> Self {
+        let cashu = Self {
+            field_names: NUT_FIELDS
+                .iter()
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+                .collect(),
+            string_secrets: true,
+            ..Self::none()
+        };
+        match detected.implementation {
+            Implementation::Dmto => Self::none(),
+            Implementation::Nutshell if detected.version.is_some_and(|v| v < (0, 15, 0)) => Self {
+                keyset_ids: KeysetIdVersion::Legacy,
+                ..cashu
+            },
+            _ => cashu,
+        }
+    }
+}
+
+/// Rewrites a mint's JSON into this crate's layout, for transports to call
+/// before deserializing a response.
+pub fn normalize_fields(value: &mut Value, quirks: &Quirks) {
+    match value {
+        Value::Object(map) => {
+            for (from, to) in &quirks.field_names {
+                if !map.contains_key(to)
+                    && let Some(v) = map.remove(from)
+                {
+                    map.insert(to.clone(), v);
+                }
+            }
+            if quirks.string_secrets
+                && let Some(Value::String(secret)) = map.get("secret")
+            {
+                let bytes = secret.bytes().map(Value::from).collect();
+                map.insert("secret".to_string(), Value::Array(bytes));
+            }
+            for v in map.values_mut() {
+                normalize_fields(v, quirks);
+            }
+        }
+        Value::Array(items) => {
+            for v in items {
+                normalize_fields(v, quirks);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A `MintTrait` that adapts another one's quirks for the wallet.
+pub struct CompatMint<M> {
+    pub inner: M,
+    pub quirks: Quirks,
+    /// The mint's own keyset IDs, by the ID the wallet sees.
+    native_ids: Mutex<HashMap<String, String>>,
+}
+
+impl<M: MintTrait> CompatMint<M> {
+    /// Detects the implementation from the mint's info.
+    pub fn new(inner: M) -> Result<Self, MintError> {
+        let quirks = Quirks::for_mint(&detect(&inner.info()?.version));
+        Ok(Self::with_quirks(inner, quirks))
+    }
+
+    pub fn with_quirks(inner: M, quirks: Quirks) -> Self {
+        Self {
+            inner,
+            quirks,
+            native_ids: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn translates(&self, keyset_id: &str) -> bool {
+        self.quirks.keyset_ids == KeysetIdVersion::Legacy
+            || KeysetIdVersion::of(keyset_id) != Some(KeysetIdVersion::V1)
+    }
+
+    /// The ID the wallet sees for a native one.
+    fn wallet_id(&self, native: &str) -> Result<String, MintError> {
+        if !self.translates(native) {
+            return Ok(native.to_string());
+        }
+        let known = |ids: &HashMap<String, String>| {
+            ids.iter()
+                .find(|(_, n)| n.as_str() == native)
+                .map(|(id, _)| id.clone())
+        };
+        if let Some(id) = known(&self.native_ids.lock().unwrap()) {
+            return Ok(id);
+        }
+        self.keysets()?;
+        Ok(known(&self.native_ids.lock().unwrap()).unwrap_or_else(|| native.to_string()))
+    }
+
+    fn to_native(&self, notes: &mut [Note]) {
+        let ids = self.native_ids.lock().unwrap();
+        for note in notes {
+            if let Some(native) = ids.get(&note.keyset_id) {
+                note.keyset_id = native.clone();
+            }
+        }
+    }
+}
+
+impl<M: MintTrait> MintReader for CompatMint<M> {
+    fn info(&self) -> Result<MintInfo, MintError> {
+        let mut info = self.inner.info()?;
+        info.keyset_id = self.wallet_id(&info.keyset_id)?;
+        info.keyset_id_version = KeysetIdVersion::of(&info.keyset_id).unwrap_or_default();
+        info.fee_rounding = self.quirks.fee_rounding;
+        Ok(info)
+    }
+
+    fn keysets(&self) -> Result<Vec<KeysetKeys>, MintError> {
+        let mut keysets = self.inner.keysets()?;
+        let mut ids = self.native_ids.lock().unwrap();
+        for keyset in &mut keysets {
+            if self.translates(&keyset.keyset_id) {
+                let id = KeysetIdVersion::V1.derive(&keyset.keys);
+                ids.insert(id.clone(), keyset.keyset_id.clone());
+                keyset.keyset_id = id;
+                keyset.id_version = KeysetIdVersion::V1;
+            }
+        }
+        Ok(keysets)
+    }
+
+    fn hello(&self) -> Result<Hello, MintError> {
+        self.inner.hello()
+    }
+
+    fn check_state(&self, ys: &[PublicKey]) -> Result<Vec<ProofState>, MintError> {
+        self.inner.check_state(ys)
+    }
+
+    fn restore_signatures(
+        &self,
+        req: Request<RestoreRequest>,
+    ) -> Result<Response<RestoreResponse>, MintError> {
+        self.inner.restore_signatures(req)
+    }
+}
+
+impl<M: MintTrait> MintTrait for CompatMint<M> {
+    fn handle_swap(
+        &self,
+        mut req: Request<SwapRequest>,
+    ) -> Result<Response<SwapResponse>, MintError> {
+        self.to_native(&mut req.body.inputs);
+        self.inner.handle_swap(req)
+    }
+
+    fn melt_quote(&self, request: &str) -> Result<MeltQuote, MintError> {
+        self.inner.melt_quote(request)
+    }
+
+    fn melt_quote_with_amount(&self, request: &str, amount: u64) -> Result<MeltQuote, MintError> {
+        self.inner.melt_quote_with_amount(request, amount)
+    }
+
+    fn melt(&self, mut req: Request<MeltRequest>) -> Result<Response<MeltResponse>, MintError> {
+        self.to_native(&mut req.body.inputs);
+        self.inner.melt(req)
+    }
+
+    fn melt_quote_state(&self, id: &str) -> Result<MeltQuote, MintError> {
+        self.inner.melt_quote_state(id)
+    }
+
+    fn melt_batch(
+        &self,
+        mut req: Request<BatchMeltRequest>,
+    ) -> Result<Response<BatchMeltResponse>, MintError> {
+        self.to_native(&mut req.body.inputs);
+        self.inner.melt_batch(req)
+    }
+
+    fn mint_quote(&self, amount: u64) -> Result<MintQuote, MintError> {
+        self.inner.mint_quote(amount)
+    }
+
+    fn mint_quote_onchain(&self, amount: u64) -> Result<MintQuote, MintError> {
+        self.inner.mint_quote_onchain(amount)
+    }
+
+    fn get_quote(&self, id: &str) -> Result<MintQuote, MintError> {
+        self.inner.get_quote(id)
+    }
+
+    fn mint(&self, req: Request<MintRequest>) -> Result<Response<MintResponse>, MintError> {
+        self.inner.mint(req)
+    }
+
+    fn anonymity_sets(&self) -> Result<Vec<AnonymitySet>, MintError> {
+        let mut sets = self.inner.anonymity_sets()?;
+        for set in &mut sets {
+            set.keyset_id = self.wallet_id(&set.keyset_id)?;
+        }
+        Ok(sets)
+    }
+
+    fn dashboard_stats(&self) -> Result<DashboardStats, MintError> {
+        self.inner.dashboard_stats()
+    }
+
+    fn announcements(&self) -> Result<Vec<Announcement>, MintError> {
+        self.inner.announcements()
+    }
+}
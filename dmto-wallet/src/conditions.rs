@@ -0,0 +1,177 @@
+//! Spending conditions as a small boolean tree over signature, hash-lock and
+//! time-lock leaves.
+//!
+//! Plain `P2PK` and `HTLC` secrets are mapped onto the same tree (including
+//! their `locktime`, `refund`, `pubkeys` and `n_sigs` tags), and `COMPOSITE`
+//! secrets carry a tree directly, e.g.
+//! `(P2PK alice AND P2PK bob) OR (after T AND P2PK carol)`.
+
+use secp256k1::{Keypair, PublicKey, schnorr::Signature};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    codec::{from_hex, to_hex},
+    secret::{Kind, WellKnownSecret},
+    signing,
+    types::Note,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// A Schnorr signature over the secret by this key.
+    P2PK(PublicKey),
+    /// A preimage whose SHA256 is this hex hash.
+    HTLC(String),
+    /// Satisfied once the clock reaches this unix time.
+    After(u64),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+    /// At least `n` of the conditions.
+    Threshold(usize, Vec<Condition>),
+}
+
+/// What a spender presents to satisfy a note's conditions.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Witness {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signatures: Vec<Signature>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preimage: Option<String>,
+}
+
+fn parse_pubkey(s: &str) -> Option<PublicKey> {
+    PublicKey::from_slice(&from_hex(s)?).ok()
+}
+
+impl Condition {
+    pub fn p2pk(pubkey: PublicKey) -> Self {
+        Condition::P2PK(pubkey)
+    }
+
+    pub fn htlc(hash: &[u8; 32]) -> Self {
+        Condition::HTLC(to_hex(hash))
+    }
+
+    pub fn and(self, other: Condition) -> Self {
+        Condition::And(vec![self, other])
+    }
+
+    pub fn or(self, other: Condition) -> Self {
+        Condition::Or(vec![self, other])
+    }
+
+    /// `self`, or `refund` once `locktime` has passed.
+    pub fn with_refund(self, locktime: u64, refund: PublicKey) -> Self {
+        self.or(Condition::After(locktime).and(Condition::P2PK(refund)))
+    }
+
+    /// Reads the conditions a secret carries, or `None` for plain secrets and
+    /// malformed well-known ones.
+    pub fn from_secret(secret: &[u8]) -> Option<Self> {
+        let wk = WellKnownSecret::from_bytes(secret)?;
+
+        let main = match wk.kind() {
+            Kind::Composite => return serde_json::from_str(&wk.1.data).ok(),
+            Kind::P2PK => {
+                let mut keys = vec![Condition::P2PK(parse_pubkey(&wk.1.data)?)];
+                for k in wk.tag("pubkeys").unwrap_or_default() {
+                    keys.push(Condition::P2PK(parse_pubkey(k)?));
+                }
+                let n = match wk.tag("n_sigs") {
+                    Some([n]) => n.parse().ok()?,
+                    _ => 1,
+                };
+                Condition::Threshold(n, keys)
+            }
+            Kind::HTLC => {
+                let mut all = vec![Condition::HTLC(wk.1.data.clone())];
+                for k in wk.tag("pubkeys").unwrap_or_default() {
+                    all.push(Condition::P2PK(parse_pubkey(k)?));
+                }
+                Condition::And(all)
+            }
+        };
+
+        let locktime = match wk.tag("locktime") {
+            Some([t]) => Some(t.parse().ok()?),
+            _ => None,
+        };
+        Some(match (locktime, wk.tag("refund")) {
+            (Some(t), Some(refunds)) => {
+                let refunds = refunds
+                    .iter()
+                    .map(|k| parse_pubkey(k).map(Condition::P2PK))
+                    .collect::<Option<Vec<_>>>()?;
+                main.or(Condition::After(t).and(Condition::Or(refunds)))
+            }
+            // No refund keys: anyone can spend after the locktime.
+            (Some(t), None) => main.or(Condition::After(t)),
+            _ => main,
+        })
+    }
+
+    /// Encodes the tree as a `COMPOSITE` secret with a fresh nonce.
+    pub fn to_secret(&self) -> Vec<u8> {
+        let data = serde_json::to_string(self).unwrap();
+        WellKnownSecret::new(Kind::Composite, data, vec![]).to_bytes()
+    }
+
+    /// Evaluates the tree for a spend of `secret` at unix time `now`.
+    pub fn evaluate(&self, secret: &[u8], witness: &Witness, now: u64) -> bool {
+        match self {
+            Condition::P2PK(pubkey) => {
+                let xonly = pubkey.x_only_public_key().0;
+                witness
+                    .signatures
+                    .iter()
+                    .any(|sig| signing::verify(&xonly, secret, sig))
+            }
+            Condition::HTLC(hash) => witness
+                .preimage
+                .as_deref()
+                .and_then(from_hex)
+                .is_some_and(|p| to_hex(&Sha256::digest(p)) == *hash),
+            Condition::After(locktime) => now >= *locktime,
+            Condition::And(cs) => cs.iter().all(|c| c.evaluate(secret, witness, now)),
+            Condition::Or(cs) => cs.iter().any(|c| c.evaluate(secret, witness, now)),
+            Condition::Threshold(n, cs) => {
+                cs.iter()
+                    .filter(|c| c.evaluate(secret, witness, now))
+                    .count()
+                    >= *n
+            }
+        }
+    }
+}
+
+impl Note {
+    /// Adds this key's signature over the secret to the note's witness.
+    pub fn sign_witness(&mut self, keypair: &Keypair) {
+        let sig = signing::sign(keypair, &self.secret);
+        self.witness.get_or_insert_default().signatures.push(sig);
+    }
+
+    pub fn set_preimage(&mut self, preimage: &[u8]) {
+        self.witness.get_or_insert_default().preimage = Some(to_hex(preimage));
+    }
+
+    /// Whether the note's own witness satisfies its conditions right now.
+    /// Notes without conditions are always spendable.
+    pub fn conditions_met(&self) -> bool {
+        self.conditions_met_at(SystemClock.now())
+    }
+
+    /// As `conditions_met`, with locktimes judged at unix time `now`.
+    pub fn conditions_met_at(&self, now: u64) -> bool {
+        match Condition::from_secret(&self.secret) {
+            Some(cond) => {
+                let witness = self.witness.clone().unwrap_or_default();
+                cond.evaluate(&self.secret, &witness, now)
+            }
+            None => WellKnownSecret::from_bytes(&self.secret).is_none(),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! The wallet's contact book: names mapped to the ways of paying someone.
+//! `Wallet::send_to` looks a name up and picks the locking key and the
+//! transport from what is on file, so repeat payments need no copy-pasting.
+//!
+//! The book exports to and imports from JSON, for moving it between
+//! devices or sharing it.
+
+use std::collections::BTreeMap;
+
+use secp256k1::{PublicKey, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::MintTrait, conditions::Condition, error::WalletError, token::Token, wallet::Wallet,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Endpoint {
+    /// Tokens are delivered as Nostr direct messages to this key.
+    Nostr(XOnlyPublicKey),
+    /// A `user@domain` address, paid over Lightning through a melt.
+    LightningAddress(String),
+    /// Tokens are locked to this key.
+    P2pk(PublicKey),
+}
+
+impl Endpoint {
+    /// Checks the `user@domain` shape; the address is not resolved.
+    pub fn lightning_address(address: &str) -> Option<Self> {
+        let (user, domain) = address.split_once('@')?;
+        let valid = !user.is_empty()
+            && domain.contains('.')
+            && !domain.starts_with('.')
+            && !domain.ends_with('.')
+            && !address.contains(char::is_whitespace)
+            && !domain.contains('@');
+        valid.then(|| Endpoint::LightningAddress(address.to_lowercase()))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contact {
+    pub name: String,
+    pub endpoints: Vec<Endpoint>,
+}
+
+impl Contact {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            endpoints: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, endpoint: Endpoint) -> Self {
+        if !self.endpoints.contains(&endpoint) {
+            self.endpoints.push(endpoint);
+        }
+        self
+    }
+
+    pub fn nostr(&self) -> Option<XOnlyPublicKey> {
+        self.endpoints.iter().find_map(|e| match e {
+            Endpoint::Nostr(key) => Some(*key),
+            _ => None,
+        })
+    }
+
+    pub fn lightning_address(&self) -> Option<&str> {
+        self.endpoints.iter().find_map(|e| match e {
+            Endpoint::LightningAddress(address) => Some(address.as_str()),
+            _ => None,
+        })
+    }
+
+    /// The key tokens for this contact are locked to: a P2PK key if one is
+    /// on file, otherwise the Nostr key read as an even-parity point.
+    pub fn locking_key(&self) -> Option<PublicKey> {
+        let p2pk = self.endpoints.iter().find_map(|e| match e {
+            Endpoint::P2pk(key) => Some(*key),
+            _ => None,
+        });
+        p2pk.or_else(|| {
+            self.nostr()
+                .map(|key| key.public_key(secp256k1::Parity::Even))
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContactBook {
+    contacts: BTreeMap<String, Contact>,
+}
+
+impl ContactBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the contact, replacing any with the same name.
+    pub fn add(&mut self, contact: Contact) {
+        self.contacts.insert(contact.name.clone(), contact);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Contact> {
+        self.contacts.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Contact> {
+        self.contacts.get(name)
+    }
+
+    /// Contacts in name order.
+    pub fn list(&self) -> impl Iterator<Item = &Contact> {
+        self.contacts.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.contacts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contacts.is_empty()
+    }
+
+    pub fn export(&self) -> String {
+        serde_json::to_string_pretty(&self.list().collect::<Vec<_>>()).unwrap()
+    }
+
+    /// Merges contacts from an `export`. Endpoints of a contact already in
+    /// the book are added to it rather than replacing it. Returns how many
+    /// contacts were new.
+    pub fn import(&mut self, json: &str) -> Result<usize, serde_json::Error> {
+        let contacts: Vec<Contact> = serde_json::from_str(json)?;
+        let mut added = 0;
+        for contact in contacts {
+            match self.contacts.get_mut(&contact.name) {
+                Some(existing) => {
+                    for endpoint in contact.endpoints {
+                        if !existing.endpoints.contains(&endpoint) {
+                            existing.endpoints.push(endpoint);
+                        }
+                    }
+                }
+                None => {
+                    added += 1;
+                    self.add(contact);
+                }
+            }
+        }
+        Ok(added)
+    }
+}
+
+/// How a payment to a contact is to be delivered.
+#[derive(Clone, Debug)]
+pub enum Delivery {
+    /// Send the token as a Nostr direct message.
+    Nostr { to: XOnlyPublicKey, token: Token },
+    /// Resolve the address to an invoice for `amount` and pass it to
+    /// `Wallet::melt`. No notes have been spent yet.
+    LightningAddress { address: String, amount: u64 },
+    /// Hand the token over directly.
+    Token(Token),
+}
+
+impl Wallet {
+    /// Pays the contact `name`. A token is locked to the contact's key when
+    /// one is known and sent over Nostr when they have a Nostr key; a
+    /// contact with only a Lightning address is paid through it.
+    pub fn send_to(
+        &mut self,
+        mint: &impl MintTrait,
+        mint_url: &str,
+        name: &str,
+        amount: u64,
+    ) -> Result<Delivery, WalletError> {
+        let contact = self
+            .contacts
+            .get(name)
+            .cloned()
+            .ok_or_else(|| WalletError::UnknownContact(name.to_string()))?;
+
+        if let (None, None, Some(address)) = (
+            contact.nostr(),
+            contact.locking_key(),
+            contact.lightning_address(),
+        ) {
+            return Ok(Delivery::LightningAddress {
+                address: address.to_string(),
+                amount,
+            });
+        }
+
+        let token = match contact.locking_key() {
+            Some(key) => {
+                let notes = self.send_locked(mint, amount, &Condition::p2pk(key))?;
+                Token::new(mint_url, notes)
+            }
+            None => self.send_offline(mint, mint_url, amount)?,
+        };
+        self.record_sent(&token)?;
+        Ok(match contact.nostr() {
+            Some(to) => Delivery::Nostr { to, token },
+            None => Delivery::Token(token),
+        })
+    }
+}
@@ -0,0 +1,492 @@
+//! Two-factor wallets: funds kept in a vault of notes locked to
+//! `P2PK device AND P2PK co-signer`, so a stolen or compromised device alone
+//! cannot spend them. The co-signer is a second phone, a hardware key or a
+//! hosted service that holds its own key and can apply its own checks.
+//!
+//! Every vault note is locked to fresh keys, derived from the two master
+//! keys and the note's index by adding `H(master || index)·G`. The device
+//! can lock new notes from the co-signer's master public key alone, and the
+//! notes cannot be linked to each other by their keys.
+//!
+//! Pairing: the device sends an offer with its master key and a nonce, the
+//! co-signer answers with its master key and a signature over both, and
+//! each side shows a confirmation code derived from the three for the user
+//! to compare.
+
+use std::{collections::HashMap, fmt};
+
+use rand::RngCore;
+use secp256k1::{Keypair, PublicKey, Scalar, Secp256k1, schnorr::Signature};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    api::MintTrait,
+    conditions::Condition,
+    error::{MintError, WalletError},
+    notestore::NoteStore,
+    signing,
+    types::Note,
+    wallet::{Wallet, split_amount},
+};
+
+fn tweak(master: &PublicKey, index: u32) -> Scalar {
+    (0u32..)
+        .find_map(|counter| {
+            let mut hasher = Sha256::new();
+            hasher.update(b"dmto-cosign");
+            hasher.update(master.serialize());
+            hasher.update(index.to_be_bytes());
+            hasher.update(counter.to_be_bytes());
+            Scalar::from_be_bytes(hasher.finalize().into()).ok()
+        })
+        .unwrap()
+}
+
+/// The public key for note `index` under `master`.
+pub fn child_pubkey(master: &PublicKey, index: u32) -> PublicKey {
+    master
+        .add_exp_tweak(&Secp256k1::new(), &tweak(master, index))
+        .expect("tweak is a valid scalar")
+}
+
+/// The key pair for note `index` under `master`, matching `child_pubkey`.
+pub fn child_keypair(master: &Keypair, index: u32) -> Keypair {
+    let secret = master
+        .secret_key()
+        .add_tweak(&tweak(&master.public_key(), index))
+        .expect("tweak is a valid scalar");
+    Keypair::from_secret_key(&Secp256k1::new(), &secret)
+}
+
+/// What locks vault note `index` of this pairing.
+pub fn vault_condition(device: &PublicKey, cosigner: &PublicKey, index: u32) -> Condition {
+    Condition::p2pk(child_pubkey(device, index)).and(Condition::p2pk(child_pubkey(cosigner, index)))
+}
+
+fn pairing_message(device: &PublicKey, cosigner: &PublicKey, nonce: &[u8; 16]) -> Vec<u8> {
+    let mut msg = b"dmto-cosign-pairing".to_vec();
+    msg.extend(device.serialize());
+    msg.extend(cosigner.serialize());
+    msg.extend(nonce);
+    msg
+}
+
+/// Six digits both sides show after pairing. They match only if neither
+/// message was swapped in transit.
+pub fn confirmation_code(device: &PublicKey, cosigner: &PublicKey, nonce: &[u8; 16]) -> String {
+    let digest = Sha256::digest(pairing_message(device, cosigner, nonce));
+    let n = u32::from_be_bytes(digest[..4].try_into().unwrap());
+    format!("{:06}", n % 1_000_000)
+}
+
+#[derive(Debug)]
+pub enum CosignError {
+    /// The pairing answer is not signed by the co-signer it names, or no
+    /// pairing was offered.
+    BadPairing,
+    NotPaired,
+    /// The request does not come from a device paired with this co-signer.
+    UnknownDevice,
+    BadSignature,
+    /// The note at this index is not locked to this pairing.
+    WrongLock(u32),
+    /// The co-signer's own checks refused, for this reason.
+    Refused(String),
+    Wallet(WalletError),
+}
+
+impl From<WalletError> for CosignError {
+    fn from(err: WalletError) -> Self {
+        CosignError::Wallet(err)
+    }
+}
+
+impl From<MintError> for CosignError {
+    fn from(err: MintError) -> Self {
+        CosignError::Wallet(err.into())
+    }
+}
+
+impl fmt::Display for CosignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CosignError::BadPairing => write!(f, "pairing answer does not verify"),
+            CosignError::NotPaired => write!(f, "no co-signer paired"),
+            CosignError::UnknownDevice => write!(f, "device is not paired"),
+            CosignError::BadSignature => write!(f, "bad signature"),
+            CosignError::WrongLock(index) => {
+                write!(f, "note {} is not locked to this pairing", index)
+            }
+            CosignError::Refused(reason) => write!(f, "co-signer refused: {}", reason),
+            CosignError::Wallet(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CosignError {}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PairingOffer {
+    pub device: PublicKey,
+    pub nonce: [u8; 16],
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PairingAccept {
+    pub cosigner: PublicKey,
+    pub signature: Signature,
+}
+
+/// One vault note the device wants co-signed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CosignItem {
+    pub index: u32,
+    pub secret: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CosignRequest {
+    pub device: PublicKey,
+    pub items: Vec<CosignItem>,
+    /// What the device says it is withdrawing. The co-signer cannot check
+    /// this against the notes, but can show it to the user or limit on it.
+    pub amount: u64,
+    pub timestamp: u64,
+    /// By the device's master key over everything above.
+    pub signature: Signature,
+}
+
+fn request_message(
+    device: &PublicKey,
+    items: &[CosignItem],
+    amount: u64,
+    timestamp: u64,
+) -> Vec<u8> {
+    serde_json::to_vec(&("dmto-cosign-request", device, items, amount, timestamp)).unwrap()
+}
+
+impl CosignRequest {
+    pub fn verify(&self) -> bool {
+        signing::verify(
+            &self.device.x_only_public_key().0,
+            &request_message(&self.device, &self.items, self.amount, self.timestamp),
+            &self.signature,
+        )
+    }
+}
+
+/// How a device reaches its co-signer: in process, or over whatever
+/// transport the app uses.
+pub trait CoSignerApi {
+    /// Signatures over each item's secret, in order.
+    fn cosign(&self, req: &CosignRequest) -> Result<Vec<Signature>, CosignError>;
+}
+
+type Approve = Box<dyn Fn(&CosignRequest) -> Result<(), String> + Send + Sync>;
+
+/// The co-signing side: its master key, the devices paired with it and an
+/// optional check run before every signature, e.g. a prompt or a limit.
+pub struct CoSigner {
+    key: Keypair,
+    /// Paired device master keys, with the time each was paired.
+    pub devices: HashMap<PublicKey, u64>,
+    approve: Option<Approve>,
+}
+
+impl CoSigner {
+    pub fn new(key: Keypair) -> Self {
+        Self {
+            key,
+            devices: HashMap::new(),
+            approve: None,
+        }
+    }
+
+    pub fn with_approval(
+        mut self,
+        approve: impl Fn(&CosignRequest) -> Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        self.approve = Some(Box::new(approve));
+        self
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.key.public_key()
+    }
+
+    /// Pairs with the offering device, returning the answer to send back and
+    /// the confirmation code to show.
+    pub fn accept(&mut self, offer: &PairingOffer, now: u64) -> (PairingAccept, String) {
+        let cosigner = self.public_key();
+        let accept = PairingAccept {
+            cosigner,
+            signature: signing::sign(
+                &self.key,
+                &pairing_message(&offer.device, &cosigner, &offer.nonce),
+            ),
+        };
+        self.devices.insert(offer.device, now);
+        (
+            accept,
+            confirmation_code(&offer.device, &cosigner, &offer.nonce),
+        )
+    }
+
+    pub fn unpair(&mut self, device: &PublicKey) -> bool {
+        self.devices.remove(device).is_some()
+    }
+}
+
+impl CoSignerApi for CoSigner {
+    fn cosign(&self, req: &CosignRequest) -> Result<Vec<Signature>, CosignError> {
+        if !self.devices.contains_key(&req.device) {
+            return Err(CosignError::UnknownDevice);
+        }
+        if !req.verify() {
+            return Err(CosignError::BadSignature);
+        }
+        let cosigner = self.public_key();
+        for item in &req.items {
+            let expected = vault_condition(&req.device, &cosigner, item.index);
+            if Condition::from_secret(&item.secret) != Some(expected) {
+                return Err(CosignError::WrongLock(item.index));
+            }
+        }
+        if let Some(approve) = &self.approve {
+            approve(req).map_err(CosignError::Refused)?;
+        }
+        Ok(req
+            .items
+            .iter()
+            .map(|item| signing::sign(&child_keypair(&self.key, item.index), &item.secret))
+            .collect())
+    }
+}
+
+/// The device side: its master key, the pairing and the vault.
+pub struct TwoFactor {
+    key: Keypair,
+    pub cosigner: Option<PublicKey>,
+    offered: Option<[u8; 16]>,
+    /// Index the next vault note is locked under.
+    pub next_index: u32,
+    pub vault: NoteStore,
+    /// Lock index of each vault note, by `Y`.
+    indices: HashMap<PublicKey, u32>,
+}
+
+impl TwoFactor {
+    pub fn new(key: Keypair) -> Self {
+        Self {
+            key,
+            cosigner: None,
+            offered: None,
+            next_index: 0,
+            vault: NoteStore::new(),
+            indices: HashMap::new(),
+        }
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.key.public_key()
+    }
+
+    pub fn is_paired(&self) -> bool {
+        self.cosigner.is_some()
+    }
+
+    pub fn balance(&self) -> u64 {
+        self.vault.total()
+    }
+
+    /// Starts pairing. A later offer replaces an unanswered one.
+    pub fn offer(&mut self) -> PairingOffer {
+        let mut nonce = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        self.offered = Some(nonce);
+        PairingOffer {
+            device: self.public_key(),
+            nonce,
+        }
+    }
+
+    /// Checks the co-signer's answer and pairs with it, returning the
+    /// confirmation code to compare with the one the co-signer shows.
+    pub fn complete(&mut self, accept: &PairingAccept) -> Result<String, CosignError> {
+        let nonce = self.offered.ok_or(CosignError::BadPairing)?;
+        let device = self.public_key();
+        if !signing::verify(
+            &accept.cosigner.x_only_public_key().0,
+            &pairing_message(&device, &accept.cosigner, &nonce),
+            &accept.signature,
+        ) {
+            return Err(CosignError::BadPairing);
+        }
+        self.offered = None;
+        self.cosigner = Some(accept.cosigner);
+        Ok(confirmation_code(&device, &accept.cosigner, &nonce))
+    }
+
+    /// Fresh vault-locked secrets for each value.
+    fn lock_outputs(
+        &mut self,
+        cosigner: &PublicKey,
+        values: &[u64],
+    ) -> (Vec<u32>, Vec<(u64, Vec<u8>)>) {
+        let device = self.public_key();
+        values
+            .iter()
+            .map(|&v| {
+                let index = self.next_index;
+                self.next_index += 1;
+                (
+                    index,
+                    (v, vault_condition(&device, cosigner, index).to_secret()),
+                )
+            })
+            .unzip()
+    }
+
+    fn store(&mut self, indices: Vec<u32>, notes: Vec<Note>) {
+        for (index, note) in indices.into_iter().zip(notes) {
+            self.indices.insert(note.y, index);
+            self.vault.push(note);
+        }
+    }
+
+    /// Moves `amount` from `wallet` into the vault.
+    pub fn protect(
+        &mut self,
+        wallet: &mut Wallet,
+        mint: &impl MintTrait,
+        amount: u64,
+    ) -> Result<(), CosignError> {
+        let cosigner = self.cosigner.ok_or(CosignError::NotPaired)?;
+        let values = split_amount(amount, &mint.info()?.denominations)
+            .ok_or(MintError::UnknownDenomination(amount))?;
+        let (indices, outputs) = self.lock_outputs(&cosigner, &values);
+        let notes = wallet.send_outputs(mint, outputs)?;
+        self.store(indices, notes);
+        Ok(())
+    }
+
+    /// Moves `amount` from the vault into `wallet` with the co-signer's
+    /// help. Change goes back into the vault under fresh keys.
+    pub fn withdraw(
+        &mut self,
+        wallet: &mut Wallet,
+        mint: &impl MintTrait,
+        cosigner_api: &impl CoSignerApi,
+        amount: u64,
+        now: u64,
+    ) -> Result<(), CosignError> {
+        let cosigner = self.cosigner.ok_or(CosignError::NotPaired)?;
+        let info = mint.info()?;
+        let values = split_amount(amount, &info.denominations)
+            .ok_or(MintError::UnknownDenomination(amount))?;
+
+        let mut inputs = Vec::new();
+        let mut sum: u64 = 0;
+        let needed = loop {
+            let Some(needed) = amount.checked_add(info.fee(inputs.len())) else {
+                self.vault.extend(inputs);
+                return Err(MintError::AmountOverflow.into());
+            };
+            if sum >= needed {
+                break needed;
+            }
+            let Some(note) = self.vault.pop() else {
+                self.vault.extend(inputs);
+                return Err(WalletError::InsufficientFunds {
+                    needed,
+                    available: sum,
+                }
+                .into());
+            };
+            let Some(total) = sum.checked_add(note.value) else {
+                self.vault.extend(inputs);
+                self.vault.push(note);
+                return Err(MintError::AmountOverflow.into());
+            };
+            sum = total;
+            inputs.push(note);
+        };
+        let change = sum - needed;
+        let Some(change_values) = split_amount(change, &info.denominations) else {
+            self.vault.extend(inputs);
+            return Err(MintError::UnknownDenomination(change).into());
+        };
+
+        if let Err(e) = self.sign_inputs(cosigner_api, &cosigner, &mut inputs, amount, now) {
+            self.restore(inputs);
+            return Err(e);
+        }
+
+        let (change_indices, change_outputs) = self.lock_outputs(&cosigner, &change_values);
+        let mut outputs: Vec<_> = values
+            .iter()
+            .map(|&v| (v, wallet.secret_policy.generate()))
+            .collect();
+        outputs.extend(change_outputs);
+        let mut notes = match wallet.swap_for(mint, inputs.clone(), outputs) {
+            Ok(notes) => notes,
+            Err(e) => {
+                self.restore(inputs);
+                return Err(e.into());
+            }
+        };
+        for note in &inputs {
+            self.indices.remove(&note.y);
+        }
+        self.store(change_indices, notes.split_off(values.len()));
+        wallet.notes.extend(notes);
+        Ok(())
+    }
+
+    /// Puts unspent inputs back into the vault without their signatures.
+    fn restore(&mut self, inputs: Vec<Note>) {
+        self.vault
+            .extend(inputs.into_iter().map(|n| Note { witness: None, ..n }));
+    }
+
+    /// Collects the co-signer's signatures for `inputs` and adds them to
+    /// each witness together with the device's own.
+    fn sign_inputs(
+        &self,
+        cosigner_api: &impl CoSignerApi,
+        cosigner: &PublicKey,
+        inputs: &mut [Note],
+        amount: u64,
+        now: u64,
+    ) -> Result<(), CosignError> {
+        let device = self.public_key();
+        let items: Vec<CosignItem> = inputs
+            .iter()
+            .map(|n| CosignItem {
+                index: self.indices[&n.y],
+                secret: n.secret.clone(),
+            })
+            .collect();
+        let req = CosignRequest {
+            signature: signing::sign(&self.key, &request_message(&device, &items, amount, now)),
+            device,
+            items,
+            amount,
+            timestamp: now,
+        };
+        let signatures = cosigner_api.cosign(&req)?;
+        if signatures.len() != inputs.len() {
+            return Err(CosignError::BadSignature);
+        }
+        for ((note, item), sig) in inputs.iter_mut().zip(&req.items).zip(signatures) {
+            let expected = child_pubkey(cosigner, item.index).x_only_public_key().0;
+            if !signing::verify(&expected, &note.secret, &sig) {
+                return Err(CosignError::BadSignature);
+            }
+            note.sign_witness(&child_keypair(&self.key, item.index));
+            note.witness.get_or_insert_default().signatures.push(sig);
+        }
+        Ok(())
+    }
+}
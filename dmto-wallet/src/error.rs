@@ -0,0 +1,487 @@
+use std::fmt;
+
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{operation, secret::Kind, spendrecord::SignedSpendRecord};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MintError {
+    UnknownDenomination(u64),
+    InvalidSignature,
+    /// With the mint's record of when the input was spent, if it keeps
+    /// them.
+    AlreadySpent(Option<Box<SignedSpendRecord>>),
+    AmountMismatch {
+        inputs: u64,
+        outputs: u64,
+        fee: u64,
+    },
+    TooManyInputs {
+        max: usize,
+    },
+    TooManyOutputs {
+        max: usize,
+    },
+    /// A sum of amounts in the request does not fit in 64 bits.
+    AmountOverflow,
+    /// This blinded message was signed before, or appears twice in the
+    /// request.
+    OutputAlreadySigned(PublicKey),
+    UnsupportedSecretKind(Kind),
+    ConditionsNotMet,
+    /// An input secret outside the mint's `SecretPolicy`.
+    InvalidSecret(String),
+    /// The mint's spend policy refused the note.
+    Refused(String),
+    UnsupportedVersion(u16),
+    Overloaded {
+        retry_after_ms: u64,
+    },
+    KeysetPaused(String),
+    /// An input of a keyset that is neither active nor being migrated.
+    KeysetUnknown(String),
+    QuoteUnknown(String),
+    QuoteAlreadyIssued(String),
+    QuoteUnpaid(String),
+    QuotePending(String),
+    QuoteAlreadyPaid(String),
+    QuoteExpired(String),
+    PaymentFailed(String),
+    /// The request body broke the mint's JSON limits.
+    BadRequest(String),
+    /// The route needs a credential and none was accepted.
+    Unauthorized(String),
+    /// The quote would take the account over its limit for the window.
+    AccountLimitExceeded {
+        remaining: u64,
+    },
+    /// A code this build does not know, as received from a newer mint.
+    Unknown {
+        code: u16,
+        detail: String,
+    },
+}
+
+/// Stable numeric codes sent over the wire. Values are never reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u16)]
+pub enum ErrorCode {
+    UnsupportedVersion = 10001,
+    Overloaded = 10002,
+    InvalidSignature = 10003,
+    BadRequest = 10004,
+    TokenAlreadySpent = 11001,
+    InsufficientInputs = 11002,
+    UnsupportedSecretKind = 11003,
+    ConditionsNotMet = 11004,
+    FeeMismatch = 11005,
+    TooManyInputs = 11006,
+    TooManyOutputs = 11007,
+    AmountOverflow = 11008,
+    InvalidSecret = 11009,
+    ProofRefused = 11010,
+    OutputAlreadySigned = 11011,
+    KeysetUnknown = 12001,
+    KeysetInactive = 12002,
+    UnknownDenomination = 12003,
+    QuoteUnknown = 20001,
+    QuoteAlreadyIssued = 20002,
+    QuoteUnpaid = 20003,
+    QuotePending = 20005,
+    QuoteAlreadyPaid = 20006,
+    QuoteExpired = 20007,
+    PaymentFailed = 20008,
+    AccountLimitExceeded = 20009,
+    Unauthorized = 30001,
+}
+
+impl ErrorCode {
+    pub fn from_u16(code: u16) -> Option<Self> {
+        Some(match code {
+            10001 => ErrorCode::UnsupportedVersion,
+            10002 => ErrorCode::Overloaded,
+            10003 => ErrorCode::InvalidSignature,
+            10004 => ErrorCode::BadRequest,
+            11001 => ErrorCode::TokenAlreadySpent,
+            11002 => ErrorCode::InsufficientInputs,
+            11003 => ErrorCode::UnsupportedSecretKind,
+            11004 => ErrorCode::ConditionsNotMet,
+            11005 => ErrorCode::FeeMismatch,
+            11006 => ErrorCode::TooManyInputs,
+            11007 => ErrorCode::TooManyOutputs,
+            11008 => ErrorCode::AmountOverflow,
+            11009 => ErrorCode::InvalidSecret,
+            11010 => ErrorCode::ProofRefused,
+            11011 => ErrorCode::OutputAlreadySigned,
+            12001 => ErrorCode::KeysetUnknown,
+            12002 => ErrorCode::KeysetInactive,
+            12003 => ErrorCode::UnknownDenomination,
+            20001 => ErrorCode::QuoteUnknown,
+            20002 => ErrorCode::QuoteAlreadyIssued,
+            20003 => ErrorCode::QuoteUnpaid,
+            20005 => ErrorCode::QuotePending,
+            20006 => ErrorCode::QuoteAlreadyPaid,
+            20007 => ErrorCode::QuoteExpired,
+            20008 => ErrorCode::PaymentFailed,
+            20009 => ErrorCode::AccountLimitExceeded,
+            30001 => ErrorCode::Unauthorized,
+            _ => return None,
+        })
+    }
+}
+
+/// Error body returned by the mint: a stable `code`, a human-readable
+/// `detail` and, where the error carries values, a structured `data` object.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub code: u16,
+    pub detail: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub data: Value,
+    /// The operation the failed request belonged to, if it gave one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation: Option<String>,
+}
+
+impl MintError {
+    pub fn code(&self) -> u16 {
+        let code = match self {
+            MintError::UnknownDenomination(_) => ErrorCode::UnknownDenomination,
+            MintError::InvalidSignature => ErrorCode::InvalidSignature,
+            MintError::AlreadySpent(_) => ErrorCode::TokenAlreadySpent,
+            // The inputs cover the outputs, so only the fee is off.
+            MintError::AmountMismatch {
+                inputs, outputs, ..
+            } if inputs >= outputs => ErrorCode::FeeMismatch,
+            MintError::AmountMismatch { .. } => ErrorCode::InsufficientInputs,
+            MintError::TooManyInputs { .. } => ErrorCode::TooManyInputs,
+            MintError::TooManyOutputs { .. } => ErrorCode::TooManyOutputs,
+            MintError::AmountOverflow => ErrorCode::AmountOverflow,
+            MintError::OutputAlreadySigned(_) => ErrorCode::OutputAlreadySigned,
+            MintError::UnsupportedSecretKind(_) => ErrorCode::UnsupportedSecretKind,
+            MintError::ConditionsNotMet => ErrorCode::ConditionsNotMet,
+            MintError::InvalidSecret(_) => ErrorCode::InvalidSecret,
+            MintError::Refused(_) => ErrorCode::ProofRefused,
+            MintError::UnsupportedVersion(_) => ErrorCode::UnsupportedVersion,
+            MintError::Overloaded { .. } => ErrorCode::Overloaded,
+            MintError::KeysetPaused(_) => ErrorCode::KeysetInactive,
+            MintError::KeysetUnknown(_) => ErrorCode::KeysetUnknown,
+            MintError::QuoteUnknown(_) => ErrorCode::QuoteUnknown,
+            MintError::QuoteAlreadyIssued(_) => ErrorCode::QuoteAlreadyIssued,
+            MintError::QuoteUnpaid(_) => ErrorCode::QuoteUnpaid,
+            MintError::QuotePending(_) => ErrorCode::QuotePending,
+            MintError::QuoteAlreadyPaid(_) => ErrorCode::QuoteAlreadyPaid,
+            MintError::QuoteExpired(_) => ErrorCode::QuoteExpired,
+            MintError::PaymentFailed(_) => ErrorCode::PaymentFailed,
+            MintError::BadRequest(_) => ErrorCode::BadRequest,
+            MintError::Unauthorized(_) => ErrorCode::Unauthorized,
+            MintError::AccountLimitExceeded { .. } => ErrorCode::AccountLimitExceeded,
+            MintError::Unknown { code, .. } => return *code,
+        };
+        code as u16
+    }
+
+    /// The error body, tagged with the operation current on this thread. A
+    /// server enters the request's operation before calling this.
+    pub fn to_response(&self) -> ErrorResponse {
+        let data = match self {
+            MintError::UnknownDenomination(v) => json!({ "denomination": v }),
+            MintError::AmountMismatch {
+                inputs,
+                outputs,
+                fee,
+            } => json!({ "inputs": inputs, "outputs": outputs, "fee": fee }),
+            MintError::TooManyInputs { max } | MintError::TooManyOutputs { max } => {
+                json!({ "max": max })
+            }
+            MintError::UnsupportedSecretKind(kind) => json!({ "kind": kind }),
+            MintError::OutputAlreadySigned(blinded) => json!({ "blinded": blinded }),
+            MintError::UnsupportedVersion(v) => json!({ "version": v }),
+            MintError::Overloaded { retry_after_ms } => json!({ "retry_after_ms": retry_after_ms }),
+            MintError::KeysetPaused(id) | MintError::KeysetUnknown(id) => {
+                json!({ "keyset_id": id })
+            }
+            MintError::AlreadySpent(Some(record)) => json!({ "spend_record": record }),
+            MintError::QuoteUnknown(id)
+            | MintError::QuoteAlreadyIssued(id)
+            | MintError::QuoteUnpaid(id)
+            | MintError::QuotePending(id)
+            | MintError::QuoteAlreadyPaid(id)
+            | MintError::QuoteExpired(id) => json!({ "quote": id }),
+            MintError::PaymentFailed(reason) => json!({ "reason": reason }),
+            MintError::AccountLimitExceeded { remaining } => json!({ "remaining": remaining }),
+            MintError::InvalidSecret(reason)
+            | MintError::Refused(reason)
+            | MintError::BadRequest(reason)
+            | MintError::Unauthorized(reason) => {
+                json!({ "reason": reason })
+            }
+            _ => Value::Null,
+        };
+
+        ErrorResponse {
+            code: self.code(),
+            detail: self.to_string(),
+            data,
+            operation: operation::current(),
+        }
+    }
+
+    /// Maps a mint's error body back into a typed error. Codes this build does
+    /// not recognise, or bodies missing their data, become `Unknown`.
+    pub fn from_response(resp: &ErrorResponse) -> Self {
+        let unknown = || MintError::Unknown {
+            code: resp.code,
+            detail: resp.detail.clone(),
+        };
+        let field = |name: &str| resp.data.get(name).and_then(Value::as_u64);
+        let quote = || {
+            resp.data
+                .get("quote")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        let keyset_id = || {
+            resp.data
+                .get("keyset_id")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+
+        let err = match ErrorCode::from_u16(resp.code) {
+            Some(ErrorCode::InvalidSignature) => Some(MintError::InvalidSignature),
+            Some(ErrorCode::TokenAlreadySpent) => Some(MintError::AlreadySpent(
+                resp.data
+                    .get("spend_record")
+                    .and_then(|r| serde_json::from_value(r.clone()).ok()),
+            )),
+            Some(ErrorCode::ConditionsNotMet) => Some(MintError::ConditionsNotMet),
+            Some(ErrorCode::AmountOverflow) => Some(MintError::AmountOverflow),
+            Some(ErrorCode::UnknownDenomination) => {
+                field("denomination").map(MintError::UnknownDenomination)
+            }
+            // Mints before `UnknownDenomination` sent it as `KeysetUnknown`.
+            Some(ErrorCode::KeysetUnknown) => keyset_id()
+                .map(MintError::KeysetUnknown)
+                .or_else(|| field("denomination").map(MintError::UnknownDenomination)),
+            Some(ErrorCode::InsufficientInputs | ErrorCode::FeeMismatch) => field("inputs")
+                .zip(field("outputs"))
+                .map(|(inputs, outputs)| MintError::AmountMismatch {
+                    inputs,
+                    outputs,
+                    fee: field("fee").unwrap_or(0),
+                }),
+            Some(ErrorCode::TooManyInputs) => {
+                field("max").map(|max| MintError::TooManyInputs { max: max as usize })
+            }
+            Some(ErrorCode::TooManyOutputs) => {
+                field("max").map(|max| MintError::TooManyOutputs { max: max as usize })
+            }
+            Some(ErrorCode::UnsupportedSecretKind) => resp
+                .data
+                .get("kind")
+                .and_then(|k| serde_json::from_value(k.clone()).ok())
+                .map(MintError::UnsupportedSecretKind),
+            Some(ErrorCode::OutputAlreadySigned) => resp
+                .data
+                .get("blinded")
+                .and_then(|b| serde_json::from_value(b.clone()).ok())
+                .map(MintError::OutputAlreadySigned),
+            Some(ErrorCode::UnsupportedVersion) => field("version")
+                .and_then(|v| u16::try_from(v).ok())
+                .map(MintError::UnsupportedVersion),
+            Some(ErrorCode::Overloaded) => field("retry_after_ms")
+                .map(|retry_after_ms| MintError::Overloaded { retry_after_ms }),
+            Some(ErrorCode::KeysetInactive) => keyset_id().map(MintError::KeysetPaused),
+            Some(ErrorCode::InvalidSecret) => resp
+                .data
+                .get("reason")
+                .and_then(Value::as_str)
+                .map(|r| MintError::InvalidSecret(r.to_string())),
+            Some(ErrorCode::ProofRefused) => resp
+                .data
+                .get("reason")
+                .and_then(Value::as_str)
+                .map(|r| MintError::Refused(r.to_string())),
+            Some(ErrorCode::QuoteUnknown) => quote().map(MintError::QuoteUnknown),
+            Some(ErrorCode::QuoteAlreadyIssued) => quote().map(MintError::QuoteAlreadyIssued),
+            Some(ErrorCode::QuoteUnpaid) => quote().map(MintError::QuoteUnpaid),
+            Some(ErrorCode::QuotePending) => quote().map(MintError::QuotePending),
+            Some(ErrorCode::QuoteAlreadyPaid) => quote().map(MintError::QuoteAlreadyPaid),
+            Some(ErrorCode::QuoteExpired) => quote().map(MintError::QuoteExpired),
+            Some(ErrorCode::PaymentFailed) => resp
+                .data
+                .get("reason")
+                .and_then(Value::as_str)
+                .map(|r| MintError::PaymentFailed(r.to_string())),
+            Some(ErrorCode::BadRequest) => resp
+                .data
+                .get("reason")
+                .and_then(Value::as_str)
+                .map(|r| MintError::BadRequest(r.to_string())),
+            Some(ErrorCode::AccountLimitExceeded) => {
+                field("remaining").map(|remaining| MintError::AccountLimitExceeded { remaining })
+            }
+            Some(ErrorCode::Unauthorized) => resp
+                .data
+                .get("reason")
+                .and_then(Value::as_str)
+                .map(|r| MintError::Unauthorized(r.to_string())),
+            _ => None,
+        };
+        err.unwrap_or_else(unknown)
+    }
+}
+
+impl fmt::Display for MintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MintError::UnknownDenomination(v) => write!(f, "no key for denomination {}", v),
+            MintError::InvalidSignature => write!(f, "invalid signature"),
+            MintError::AlreadySpent(None) => write!(f, "token already spent"),
+            MintError::AlreadySpent(Some(r)) => {
+                write!(f, "token already spent at #{}", r.record.seq)
+            }
+            MintError::AmountMismatch {
+                inputs,
+                outputs,
+                fee,
+            } => write!(
+                f,
+                "inputs ({}) do not match outputs ({}) plus fee ({})",
+                inputs, outputs, fee
+            ),
+            MintError::TooManyInputs { max } => write!(f, "more than {} inputs", max),
+            MintError::TooManyOutputs { max } => write!(f, "more than {} outputs", max),
+            MintError::AmountOverflow => write!(f, "amount overflow"),
+            MintError::OutputAlreadySigned(blinded) => {
+                write!(f, "blinded message {} already signed", blinded)
+            }
+            MintError::UnsupportedSecretKind(kind) => {
+                write!(f, "spending condition {:?} not accepted by this mint", kind)
+            }
+            MintError::ConditionsNotMet => write!(f, "spending conditions not met"),
+            MintError::InvalidSecret(reason) => write!(f, "invalid secret: {}", reason),
+            MintError::Refused(reason) => write!(f, "refused by mint policy: {}", reason),
+            MintError::UnsupportedVersion(v) => write!(f, "protocol version {} not supported", v),
+            MintError::Overloaded { retry_after_ms } => {
+                write!(f, "mint overloaded, retry after {} ms", retry_after_ms)
+            }
+            MintError::KeysetPaused(id) => write!(f, "keyset {} is paused", id),
+            MintError::KeysetUnknown(id) => write!(f, "unknown keyset {}", id),
+            MintError::QuoteUnknown(id) => write!(f, "unknown quote {}", id),
+            MintError::QuoteAlreadyIssued(id) => write!(f, "notes already issued for quote {}", id),
+            MintError::QuoteUnpaid(id) => write!(f, "quote {} not paid", id),
+            MintError::QuotePending(id) => write!(f, "quote {} is pending", id),
+            MintError::QuoteAlreadyPaid(id) => write!(f, "quote {} already paid", id),
+            MintError::QuoteExpired(id) => write!(f, "quote {} expired", id),
+            MintError::PaymentFailed(reason) => write!(f, "payment failed: {}", reason),
+            MintError::BadRequest(reason) => write!(f, "bad request: {}", reason),
+            MintError::Unauthorized(reason) => write!(f, "unauthorized: {}", reason),
+            MintError::AccountLimitExceeded { remaining } => {
+                write!(f, "account limit exceeded, {} remaining", remaining)
+            }
+            MintError::Unknown { code, detail } => write!(f, "mint error {}: {}", code, detail),
+        }
+    }
+}
+
+impl std::error::Error for MintError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WalletError {
+    Mint(MintError),
+    /// The token with this ID was already redeemed by this wallet.
+    AlreadyReceived(String),
+    /// The mint reports these proofs of a token as spent.
+    TokenSpent {
+        spent: Vec<PublicKey>,
+        proofs: usize,
+    },
+    InsufficientFunds {
+        needed: u64,
+        available: u64,
+    },
+    /// No contact of this name in the wallet's contact book.
+    UnknownContact(String),
+    /// The wallet's spend policy refused, for this reason.
+    SpendRefused(String),
+    /// No pending send with this token ID.
+    UnknownSend(String),
+    /// A payment request this wallet cannot pay as asked, for this reason.
+    RequestMismatch(String),
+    /// An invoice or token could not be exchanged with the payee.
+    Delivery(String),
+    /// Proofs claim a keyset the mint does not list.
+    UnknownKeyset(String),
+}
+
+impl From<MintError> for WalletError {
+    fn from(err: MintError) -> Self {
+        WalletError::Mint(err)
+    }
+}
+
+impl fmt::Display for WalletError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalletError::Mint(err) => write!(f, "{}", err),
+            WalletError::AlreadyReceived(id) => write!(f, "token {} already received", id),
+            WalletError::TokenSpent { spent, proofs } if spent.len() == *proofs => {
+                write!(f, "this token was already redeemed")
+            }
+            WalletError::TokenSpent { spent, proofs } => write!(
+                f,
+                "{} of the token's {} proofs were already spent",
+                spent.len(),
+                proofs
+            ),
+            WalletError::InsufficientFunds { needed, available } => {
+                write!(f, "need {} but only {} available", needed, available)
+            }
+            WalletError::UnknownContact(name) => write!(f, "no contact named {}", name),
+            WalletError::SpendRefused(reason) => write!(f, "spend refused: {}", reason),
+            WalletError::UnknownSend(id) => write!(f, "no pending send {}", id),
+            WalletError::RequestMismatch(reason) => {
+                write!(f, "cannot pay request: {}", reason)
+            }
+            WalletError::Delivery(reason) => write!(f, "delivery failed: {}", reason),
+            WalletError::UnknownKeyset(id) => write!(f, "mint has no keyset {}", id),
+        }
+    }
+}
+
+impl std::error::Error for WalletError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyset_unknown_round_trips() {
+        let err = MintError::KeysetUnknown("00deadbeef".to_string());
+        let resp = err.to_response();
+        assert_eq!(resp.code, 12001);
+        assert_eq!(resp.data, json!({ "keyset_id": "00deadbeef" }));
+
+        let wire: ErrorResponse =
+            serde_json::from_str(&serde_json::to_string(&resp).unwrap()).unwrap();
+        assert_eq!(MintError::from_response(&wire), err);
+    }
+
+    #[test]
+    fn legacy_keyset_unknown_is_an_unknown_denomination() {
+        let resp = ErrorResponse {
+            code: ErrorCode::KeysetUnknown as u16,
+            detail: "no key for denomination 3".to_string(),
+            data: json!({ "denomination": 3 }),
+            operation: None,
+        };
+        assert_eq!(
+            MintError::from_response(&resp),
+            MintError::UnknownDenomination(3)
+        );
+    }
+}
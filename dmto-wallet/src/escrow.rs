@@ -0,0 +1,92 @@
+//! Buyer/seller/arbiter escrow over locked notes.
+//!
+//! Funds are locked so that any two of the three parties can move them, and
+//! the buyer alone can take them back after `timeout`:
+//!
+//! - release: buyer and seller sign, seller claims;
+//! - dispute: arbiter signs together with whichever party it sides with, who
+//!   then claims;
+//! - refund: after `timeout` the buyer signs and claims.
+
+use secp256k1::{Keypair, PublicKey};
+
+use crate::{
+    api::MintTrait, conditions::Condition, error::WalletError, types::Note, wallet::Wallet,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Escrow {
+    pub buyer: PublicKey,
+    pub seller: PublicKey,
+    pub arbiter: PublicKey,
+    /// Unix time after which the buyer can refund unilaterally.
+    pub timeout: u64,
+}
+
+impl Escrow {
+    pub fn new(buyer: PublicKey, seller: PublicKey, arbiter: PublicKey, timeout: u64) -> Self {
+        Self {
+            buyer,
+            seller,
+            arbiter,
+            timeout,
+        }
+    }
+
+    pub fn condition(&self) -> Condition {
+        let two_of_three = Condition::Threshold(
+            2,
+            vec![
+                Condition::P2PK(self.buyer),
+                Condition::P2PK(self.seller),
+                Condition::P2PK(self.arbiter),
+            ],
+        );
+        two_of_three.with_refund(self.timeout, self.buyer)
+    }
+
+    /// Buyer side: locks `amount` from the buyer's wallet into escrow notes.
+    pub fn fund(
+        &self,
+        wallet: &mut Wallet,
+        mint: &impl MintTrait,
+        amount: u64,
+    ) -> Result<Vec<Note>, WalletError> {
+        wallet.send_locked(mint, amount, &self.condition())
+    }
+
+    /// Whether `notes` are all locked to exactly this escrow.
+    pub fn holds(&self, notes: &[Note]) -> bool {
+        let condition = self.condition();
+        notes
+            .iter()
+            .all(|n| Condition::from_secret(&n.secret).is_some_and(|c| c == condition))
+    }
+
+    /// Adds `party`'s approval to every note. Fails if `party` is not one of
+    /// the three escrow keys.
+    pub fn approve(&self, notes: &mut [Note], party: &Keypair) -> bool {
+        let pubkey = party.public_key();
+        if ![self.buyer, self.seller, self.arbiter].contains(&pubkey) {
+            return false;
+        }
+        for n in notes.iter_mut() {
+            n.sign_witness(party);
+        }
+        true
+    }
+
+    /// Whether the buyer may refund at unix time `now`.
+    pub fn refundable(&self, now: u64) -> bool {
+        now >= self.timeout
+    }
+
+    /// Redeems approved escrow notes into `wallet`. Fails without contacting
+    /// the mint if the notes' conditions are not yet satisfied.
+    pub fn claim(&self, wallet: &mut Wallet, mint: &impl MintTrait, notes: Vec<Note>) -> bool {
+        if !self.holds(&notes) || !notes.iter().all(|n| n.conditions_met()) {
+            return false;
+        }
+        wallet.receive(mint, notes)
+    }
+}
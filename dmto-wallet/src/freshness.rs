@@ -0,0 +1,39 @@
+use secp256k1::{PublicKey, XOnlyPublicKey, schnorr::Signature};
+use serde::{Deserialize, Serialize};
+
+use crate::{mint::ProofState, signing};
+
+/// The mint's signed statement that a proof had `state` at `timestamp`.
+///
+/// A receiver that is temporarily offline (a vending machine, say) can accept
+/// a note whose payer shows a recent `Unspent` attestation, bounding its risk
+/// to double spends made within `max_age` of the attestation.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FreshnessAttestation {
+    pub y: PublicKey,
+    pub state: ProofState,
+    pub timestamp: u64,
+    pub signature: Signature,
+}
+
+/// The bytes the mint signs.
+pub fn message(y: &PublicKey, state: ProofState, timestamp: u64) -> Vec<u8> {
+    serde_json::to_vec(&(y, state, timestamp)).unwrap()
+}
+
+impl FreshnessAttestation {
+    /// Accepts the attestation if the mint `identity` signed it, it vouches
+    /// for `y` being unspent, and it is at most `max_age` seconds older than
+    /// `now`.
+    pub fn accept(&self, identity: &XOnlyPublicKey, y: &PublicKey, max_age: u64, now: u64) -> bool {
+        self.y == *y
+            && self.state == ProofState::Unspent
+            && self.timestamp <= now
+            && now - self.timestamp <= max_age
+            && signing::verify(
+                identity,
+                &message(&self.y, self.state, self.timestamp),
+                &self.signature,
+            )
+    }
+}
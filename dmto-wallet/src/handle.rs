@@ -0,0 +1,144 @@
+//! A cloneable, `Send + Sync` wallet for sharing between threads, UI code
+//! and async tasks (via `spawn_blocking` or similar).
+//!
+//! The lock is held only to pick or store notes, never across a mint call.
+//! Notes picked for an operation are reserved: they leave the spendable
+//! balance until the operation finishes, so two concurrent spends can never
+//! select the same note.
+
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    api::MintTrait,
+    balance::Balance,
+    error::{MintError, WalletError},
+    history::Transaction,
+    mint::MintInfo,
+    token::Token,
+    types::Note,
+    wallet::{Wallet, split_amount},
+};
+
+#[derive(Default)]
+struct State {
+    wallet: Wallet,
+    reserved: u64,
+}
+
+#[derive(Clone, Default)]
+pub struct WalletHandle {
+    state: Arc<RwLock<State>>,
+}
+
+impl WalletHandle {
+    pub fn new(wallet: Wallet) -> Self {
+        Self {
+            state: Arc::new(RwLock::new(State {
+                wallet,
+                reserved: 0,
+            })),
+        }
+    }
+
+    /// Spendable balance, excluding notes reserved by operations in flight.
+    pub fn balance(&self) -> u64 {
+        self.state.read().unwrap().wallet.balance()
+    }
+
+    /// `Wallet::balances` of the notes not reserved.
+    pub fn balances(&self, info: &MintInfo) -> Balance {
+        self.state.read().unwrap().wallet.balances(info)
+    }
+
+    /// Value held by operations in flight.
+    pub fn reserved(&self) -> u64 {
+        self.state.read().unwrap().reserved
+    }
+
+    pub fn history(&self) -> Vec<Transaction> {
+        self.state.read().unwrap().wallet.history.clone()
+    }
+
+    /// Runs `f` with exclusive access to the wallet. Mint calls made inside
+    /// block every other user of the handle.
+    pub fn with<R>(&self, f: impl FnOnce(&mut Wallet) -> R) -> R {
+        f(&mut self.state.write().unwrap().wallet)
+    }
+
+    /// Takes notes covering `amount` plus fees out of the wallet.
+    fn reserve(&self, info: &MintInfo, amount: u64) -> Result<Wallet, WalletError> {
+        let mut state = self.state.write().unwrap();
+        let (notes, _) = state.wallet.select_with_fee(info, amount)?;
+        state.reserved += notes.iter().map(|n| n.value).sum::<u64>();
+        Ok(Wallet {
+            notes: notes.into_iter().collect(),
+            secret_policy: state.wallet.secret_policy.clone(),
+            spend_policy: state.wallet.spend_policy.clone(),
+            clock: state.wallet.clock.clone(),
+            session: state.wallet.session.clone(),
+            ..Wallet::default()
+        })
+    }
+
+    /// An empty wallet generating secrets the same way as the shared one.
+    fn scratch(&self) -> Wallet {
+        let state = self.state.read().unwrap();
+        Wallet {
+            secret_policy: state.wallet.secret_policy.clone(),
+            keysets: state.wallet.keysets.clone(),
+            clock: state.wallet.clock.clone(),
+            session: state.wallet.session.clone(),
+            ..Wallet::default()
+        }
+    }
+
+    /// Returns what is left of a reservation, plus any new notes, history,
+    /// received IDs, pending sends and mint session gathered in `scratch`.
+    fn settle(&self, reserved: u64, scratch: Wallet) {
+        let mut state = self.state.write().unwrap();
+        state.reserved -= reserved;
+        state.wallet.notes.extend(scratch.notes);
+        state.wallet.history.extend(scratch.history);
+        state.wallet.received.extend(scratch.received);
+        state.wallet.pending_sends.extend(scratch.pending_sends);
+        state.wallet.keysets.extend(scratch.keysets);
+        if let Some(session) = scratch.session.into_inner() {
+            let _ = state.wallet.session.set(session);
+        }
+    }
+
+    /// Swaps notes worth `amount` out of the wallet for handing to a payee.
+    pub fn send(&self, mint: &impl MintTrait, amount: u64) -> Result<Vec<Note>, WalletError> {
+        let info = mint.info()?;
+        let values = split_amount(amount, &info.denominations)
+            .ok_or(MintError::UnknownDenomination(amount))?;
+        let mut scratch = self.reserve(&info, amount)?;
+        let reserved = scratch.balance();
+
+        let result = scratch.split_out(mint, &values);
+        self.settle(reserved, scratch);
+        result
+    }
+
+    pub fn receive(&self, mint: &impl MintTrait, notes: Vec<Note>) -> bool {
+        let mut scratch = self.scratch();
+        let ok = scratch.receive(mint, notes);
+        self.settle(0, scratch);
+        ok
+    }
+
+    /// As `Wallet::receive_token`. Tokens already received through this
+    /// handle are refused before contacting the mint; two concurrent receives
+    /// of the same token are settled by the mint's spent check.
+    pub fn receive_token(&self, mint: &impl MintTrait, token: &Token) -> Result<u64, WalletError> {
+        let id = token.id();
+        if self.state.read().unwrap().wallet.received.contains(&id) {
+            return Err(WalletError::AlreadyReceived(id));
+        }
+
+        let mut scratch = self.scratch();
+        let result = scratch.receive_token(mint, token);
+        self.settle(0, scratch);
+        result
+    }
+}
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{operation, receipt::FeeReceipt};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+/// One entry in the wallet's transaction history. `id` is the token ID for
+/// token receives and sends, so the same payment is never listed twice.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub id: String,
+    pub direction: Direction,
+    pub amount: u64,
+    pub fee: u64,
+    pub timestamp: u64,
+    /// For melts, the Lightning preimage proving the invoice was paid.
+    pub preimage: Option<String>,
+    /// For on-chain melts, the transaction that paid the address.
+    #[serde(default)]
+    pub txid: Option<String>,
+    /// The sender's memo on a received token, or the one put on a sent one.
+    #[serde(default)]
+    pub memo: Option<String>,
+    /// The operation that made the entry, as sent to the mint.
+    #[serde(default)]
+    pub operation: Option<String>,
+    /// The mint's signed statement of the fees it charged. A receipt for a
+    /// batch of tokens received together is kept on each of their entries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<FeeReceipt>,
+}
+
+impl Transaction {
+    pub fn new(id: &str, direction: Direction, amount: u64, fee: u64, timestamp: u64) -> Self {
+        Self {
+            id: id.to_string(),
+            direction,
+            amount,
+            fee,
+            timestamp,
+            preimage: None,
+            txid: None,
+            memo: None,
+            operation: operation::current(),
+            receipt: None,
+        }
+    }
+}
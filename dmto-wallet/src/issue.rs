@@ -0,0 +1,90 @@
+//! Issuing notes against a paid Lightning invoice, or an on-chain deposit
+//! (see `onchain`). With a hold store, invoices are hold invoices settled
+//! only once the signatures are persisted (see `hold`). Quotes outlive the
+//! request that created them, so a wallet that crashed after paying can look
+//! its quote up again and still collect its notes.
+
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    codec::to_hex,
+    dleq::Dleq,
+    lightning::{Description, InvoiceOptions},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MintQuoteState {
+    Unpaid,
+    Paid,
+    Issued,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MintQuote {
+    pub id: String,
+    pub request: String,
+    pub amount: u64,
+    pub state: MintQuoteState,
+    /// Unpaid quotes cannot be paid into after this time.
+    pub expiry: u64,
+}
+
+/// How the mint describes the invoices of its mint quotes, so payments can
+/// be matched to quotes when inspecting the Lightning node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvoiceTemplate {
+    /// Description text; `{quote_id}` and `{amount}` are filled in.
+    pub description: String,
+    /// Put only the description's hash in the invoice.
+    pub description_hash: bool,
+    /// Seconds the invoice, and so the quote, stays payable. The mint's
+    /// `quote_ttl` when `None`.
+    pub expiry: Option<u64>,
+}
+
+impl Default for InvoiceTemplate {
+    fn default() -> Self {
+        Self {
+            description: "dmto mint quote {quote_id}".to_string(),
+            description_hash: false,
+            expiry: None,
+        }
+    }
+}
+
+impl InvoiceTemplate {
+    pub fn describe(&self, quote_id: &str, amount: u64) -> String {
+        self.description
+            .replace("{quote_id}", quote_id)
+            .replace("{amount}", &amount.to_string())
+    }
+
+    pub fn options(&self, quote_id: &str, amount: u64, quote_ttl: u64) -> InvoiceOptions {
+        let text = self.describe(quote_id, amount);
+        InvoiceOptions {
+            description: if self.description_hash {
+                Description::Hash(to_hex(&Sha256::digest(text.as_bytes())))
+            } else {
+                Description::Direct(text)
+            },
+            expiry: self.expiry.unwrap_or(quote_ttl),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MintRequest {
+    pub quote: String,
+    pub outputs: Vec<(u64, PublicKey)>,
+    /// The wallet's operation ID, for tracing (see `operation`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MintResponse {
+    pub signatures: Vec<PublicKey>,
+    pub dleqs: Vec<Dleq>,
+}
@@ -0,0 +1,101 @@
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{codec::to_base64url, mint::keyset_id_from_pubkeys};
+
+/// Denomination layout of a keyset: every power of two from 1 up to
+/// `2^max_order`. Mints publish `max_order` instead of the full list, and
+/// wallets rebuild the same list from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Keyset {
+    pub max_order: u32,
+}
+
+impl Keyset {
+    /// Highest order whose denomination fits in a `u64`.
+    pub const MAX_ORDER: u32 = 63;
+
+    pub fn power2(max_order: u32) -> Self {
+        Self {
+            max_order: max_order.min(Self::MAX_ORDER),
+        }
+    }
+
+    pub fn denominations(&self) -> Vec<u64> {
+        (0..=self.max_order).map(|i| 1u64 << i).collect()
+    }
+
+    /// The layout `denoms` follows, if it is a complete power-of-two set.
+    pub fn from_denominations(denoms: &[u64]) -> Option<Self> {
+        let mut sorted = denoms.to_vec();
+        sorted.sort();
+        let keyset = Self::power2(sorted.len().checked_sub(1)? as u32);
+        (keyset.denominations() == sorted).then_some(keyset)
+    }
+}
+
+/// How a keyset ID was derived from the keys. Hex IDs start with a version
+/// byte; the base64 IDs from before versioning have none.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KeysetIdVersion {
+    /// The first 12 characters of the base64 SHA-256 of the keys' hex,
+    /// concatenated in ascending denomination order.
+    Legacy,
+    /// Version byte `00` and the first 7 bytes of the SHA-256 of the keys
+    /// in ascending denomination order, as this crate computes them.
+    #[default]
+    V1,
+}
+
+impl KeysetIdVersion {
+    /// The version `keyset_id` was derived with, read from its format.
+    pub fn of(keyset_id: &str) -> Option<Self> {
+        let bytes = keyset_id.as_bytes();
+        if bytes.len() == 16
+            && keyset_id.starts_with("00")
+            && bytes.iter().all(|b| b.is_ascii_hexdigit())
+        {
+            Some(Self::V1)
+        } else if bytes.len() == 12
+            && bytes
+                .iter()
+                .all(|&b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/')
+        {
+            Some(Self::Legacy)
+        } else {
+            None
+        }
+    }
+
+    /// The byte IDs of this version start with, if they have one.
+    pub fn version_byte(self) -> Option<u8> {
+        match self {
+            Self::Legacy => None,
+            Self::V1 => Some(0x00),
+        }
+    }
+
+    /// The ID of the keyset `keys` under this version.
+    pub fn derive(self, keys: &[(u64, PublicKey)]) -> String {
+        match self {
+            Self::Legacy => legacy_keyset_id(keys),
+            Self::V1 => keyset_id_from_pubkeys(keys),
+        }
+    }
+
+    /// Whether `keyset_id` is the ID of `keys` under its own version, so
+    /// notes carrying it can be routed to those keys.
+    pub fn matches(keyset_id: &str, keys: &[(u64, PublicKey)]) -> bool {
+        Self::of(keyset_id).is_some_and(|version| version.derive(keys) == keyset_id)
+    }
+}
+
+pub fn legacy_keyset_id(keys: &[(u64, PublicKey)]) -> String {
+    let mut keys = keys.to_vec();
+    keys.sort_by_key(|(v, _)| *v);
+    let hex: String = keys.iter().map(|(_, k)| k.to_string()).collect();
+    to_base64url(&Sha256::digest(hex.as_bytes()))[..12]
+        .replace('-', "+")
+        .replace('_', "/")
+}
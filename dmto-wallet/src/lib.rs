@@ -0,0 +1,61 @@
+//! The wallet, and the protocol types it shares with the mint: notes and
+//! tokens, requests and responses, and the `MintTrait` interface wallets
+//! talk to a mint through. Nothing here holds mint state, so wallets built
+//! on it do not pull in the mint's storage or server dependencies.
+
+pub use dmto_crypto::{blind, dleq, hash, signing};
+
+pub mod announce;
+pub mod anonymity;
+pub mod api;
+pub mod atomic;
+pub mod balance;
+pub mod batchmelt;
+pub mod bundle;
+pub mod canonical;
+pub mod clock;
+pub mod codec;
+pub mod compact;
+#[cfg(feature = "compat")]
+pub mod compat;
+pub mod conditions;
+pub mod contacts;
+pub mod cosign;
+pub mod error;
+pub mod escrow;
+pub mod freshness;
+pub mod handle;
+pub mod history;
+pub mod issue;
+pub mod keyset;
+pub mod lightning;
+pub mod melt;
+pub mod mint;
+pub mod mixedsend;
+pub mod multimint;
+pub mod nfc;
+pub mod notestore;
+pub mod onchain;
+pub mod operation;
+pub mod payjoin;
+pub mod protocol;
+pub mod rates;
+pub mod receipt;
+pub mod remote;
+pub mod restore;
+pub mod rotation;
+pub mod rpc;
+pub mod secret;
+pub mod spendrecord;
+pub mod stats;
+pub mod streaming;
+pub mod strict;
+pub mod subscription;
+pub mod sync;
+pub mod token;
+pub mod types;
+pub mod uri;
+pub mod verifier;
+pub mod versioned;
+pub mod wallet;
+pub mod walletpolicy;
@@ -0,0 +1,325 @@
+//! The payment network the mint settles melts over, and an in-memory fake of
+//! it for tests and examples.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Mutex,
+};
+
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    codec::{from_hex, to_hex},
+    operation,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Invoice {
+    pub amount: u64,
+    pub payment_hash: String,
+}
+
+/// Proof of a completed payment: `preimage` hashes to the invoice's
+/// `payment_hash`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Payment {
+    pub preimage: String,
+    pub fee_paid: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PaymentError {
+    InvalidRequest,
+    /// The route needs more than the fee the payer allowed.
+    FeeTooHigh {
+        needed: u64,
+    },
+    Failed(String),
+}
+
+impl fmt::Display for PaymentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaymentError::InvalidRequest => write!(f, "invalid payment request"),
+            PaymentError::FeeTooHigh { needed } => write!(f, "route needs fee of {}", needed),
+            PaymentError::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PaymentError {}
+
+/// What a node puts in the description field of an invoice.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Description {
+    Direct(String),
+    /// The SHA-256 of a description published elsewhere, hex encoded.
+    Hash(String),
+}
+
+/// How to make an invoice the mint is paid through.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvoiceOptions {
+    pub description: Description,
+    /// Seconds the invoice stays payable.
+    pub expiry: u64,
+}
+
+pub trait LightningBackend: Send + Sync {
+    fn decode(&self, request: &str) -> Option<Invoice>;
+    /// Fee to hold back when quoting a payment of `amount`.
+    fn fee_reserve(&self, amount: u64) -> u64;
+    fn pay(&self, request: &str, max_fee: u64) -> Result<Payment, PaymentError>;
+    /// Pays `amount` to an invoice that carries no amount of its own.
+    fn pay_amountless(
+        &self,
+        _request: &str,
+        _amount: u64,
+        _max_fee: u64,
+    ) -> Result<Payment, PaymentError> {
+        Err(PaymentError::Failed(
+            "amountless invoices not supported".to_string(),
+        ))
+    }
+    /// Creates an invoice the mint is paid through when issuing notes.
+    fn create_invoice(&self, amount: u64, options: &InvoiceOptions)
+    -> Result<String, PaymentError>;
+    fn is_paid(&self, payment_hash: &str) -> bool;
+
+    /// Creates a hold invoice for `payment_hash`: a payment to it is held,
+    /// not settled, until `settle_hold` or `cancel_hold`.
+    fn create_hold_invoice(
+        &self,
+        _amount: u64,
+        _payment_hash: &str,
+        _options: &InvoiceOptions,
+    ) -> Result<String, PaymentError> {
+        Err(PaymentError::Failed(
+            "hold invoices not supported".to_string(),
+        ))
+    }
+
+    /// Whether a payment to the hold invoice for `payment_hash` arrived and
+    /// is waiting to be settled.
+    fn is_held(&self, _payment_hash: &str) -> bool {
+        false
+    }
+
+    /// Claims the held payment whose hash `preimage` hashes to.
+    fn settle_hold(&self, _preimage: &str) -> Result<(), PaymentError> {
+        Err(PaymentError::Failed(
+            "hold invoices not supported".to_string(),
+        ))
+    }
+
+    /// Refunds a held payment, or closes the invoice to new ones.
+    fn cancel_hold(&self, _payment_hash: &str) {}
+}
+
+/// Pays invoices instantly. Requests are `lnfake<amount>_<payment hash>`,
+/// with amount 0 for an invoice without one; invoices made with `invoice`
+/// pay out their real preimage, any others a made-up one. Invoices the mint creates count as paid once `settle` is
+/// called, or immediately with `auto_settle`; for hold invoices that only
+/// makes the payment held until the mint settles it.
+#[derive(Default)]
+pub struct FakeBackend {
+    /// Routing fee charged on every payment.
+    pub fee: u64,
+    pub auto_settle: bool,
+    preimages: Mutex<HashMap<String, String>>,
+    settled: Mutex<HashSet<String>>,
+    /// Hold invoices by payment hash, and whether a payment is held.
+    holds: Mutex<HashMap<String, bool>>,
+    /// Options of the invoices the mint created, by payment hash.
+    options: Mutex<HashMap<String, InvoiceOptions>>,
+    /// The operation each payment was made for, by payment hash.
+    operations: Mutex<HashMap<String, String>>,
+}
+
+impl FakeBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_fee(fee: u64) -> Self {
+        Self {
+            fee,
+            ..Self::default()
+        }
+    }
+
+    /// Creates an invoice for `amount` with a fresh preimage.
+    pub fn invoice(&self, amount: u64) -> String {
+        let mut preimage = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut preimage);
+        let hash = to_hex(&Sha256::digest(preimage));
+
+        self.preimages
+            .lock()
+            .unwrap()
+            .insert(hash.clone(), to_hex(&preimage));
+        format!("lnfake{}_{}", amount, hash)
+    }
+
+    /// The operation `request` was paid for, as a real backend would log it.
+    pub fn operation(&self, request: &str) -> Option<String> {
+        let invoice = self.decode(request)?;
+        self.operations
+            .lock()
+            .unwrap()
+            .get(&invoice.payment_hash)
+            .cloned()
+    }
+
+    /// The options the mint created `request` with.
+    pub fn options(&self, request: &str) -> Option<InvoiceOptions> {
+        let invoice = self.decode(request)?;
+        self.options
+            .lock()
+            .unwrap()
+            .get(&invoice.payment_hash)
+            .cloned()
+    }
+
+    /// Marks `request` as paid, as if someone paid the invoice.
+    pub fn settle(&self, request: &str) -> bool {
+        let Some(invoice) = self.decode(request) else {
+            return false;
+        };
+        if let Some(held) = self.holds.lock().unwrap().get_mut(&invoice.payment_hash) {
+            *held = true;
+            return true;
+        }
+        self.settled.lock().unwrap().insert(invoice.payment_hash);
+        true
+    }
+
+    fn pay_invoice(&self, invoice: Invoice, max_fee: u64) -> Result<Payment, PaymentError> {
+        if self.fee > max_fee {
+            return Err(PaymentError::FeeTooHigh { needed: self.fee });
+        }
+        if let Some(operation) = operation::current() {
+            self.operations
+                .lock()
+                .unwrap()
+                .insert(invoice.payment_hash.clone(), operation);
+        }
+
+        let preimage = self
+            .preimages
+            .lock()
+            .unwrap()
+            .get(&invoice.payment_hash)
+            .cloned()
+            .unwrap_or_else(|| {
+                let mut preimage = [0u8; 32];
+                rand::thread_rng().fill_bytes(&mut preimage);
+                to_hex(&preimage)
+            });
+        Ok(Payment {
+            preimage,
+            fee_paid: self.fee,
+        })
+    }
+}
+
+impl LightningBackend for FakeBackend {
+    fn decode(&self, request: &str) -> Option<Invoice> {
+        let (amount, hash) = request.strip_prefix("lnfake")?.split_once('_')?;
+        if from_hex(hash)?.len() != 32 {
+            return None;
+        }
+        Some(Invoice {
+            amount: amount.parse().ok()?,
+            payment_hash: hash.to_string(),
+        })
+    }
+
+    fn fee_reserve(&self, _amount: u64) -> u64 {
+        self.fee
+    }
+
+    fn pay(&self, request: &str, max_fee: u64) -> Result<Payment, PaymentError> {
+        let invoice = self.decode(request).ok_or(PaymentError::InvalidRequest)?;
+        if invoice.amount == 0 {
+            return Err(PaymentError::InvalidRequest);
+        }
+        self.pay_invoice(invoice, max_fee)
+    }
+
+    fn pay_amountless(
+        &self,
+        request: &str,
+        amount: u64,
+        max_fee: u64,
+    ) -> Result<Payment, PaymentError> {
+        let invoice = self.decode(request).ok_or(PaymentError::InvalidRequest)?;
+        if invoice.amount != 0 || amount == 0 {
+            return Err(PaymentError::InvalidRequest);
+        }
+        self.pay_invoice(invoice, max_fee)
+    }
+
+    fn create_invoice(
+        &self,
+        amount: u64,
+        options: &InvoiceOptions,
+    ) -> Result<String, PaymentError> {
+        let request = self.invoice(amount);
+        let hash = request.rsplit('_').next().unwrap_or_default();
+        self.options
+            .lock()
+            .unwrap()
+            .insert(hash.to_string(), options.clone());
+        if self.auto_settle {
+            self.settle(&request);
+        }
+        Ok(request)
+    }
+
+    fn is_paid(&self, payment_hash: &str) -> bool {
+        self.settled.lock().unwrap().contains(payment_hash)
+    }
+
+    fn create_hold_invoice(
+        &self,
+        amount: u64,
+        payment_hash: &str,
+        options: &InvoiceOptions,
+    ) -> Result<String, PaymentError> {
+        let request = format!("lnfake{}_{}", amount, payment_hash);
+        self.decode(&request).ok_or(PaymentError::InvalidRequest)?;
+        self.options
+            .lock()
+            .unwrap()
+            .insert(payment_hash.to_string(), options.clone());
+        self.holds
+            .lock()
+            .unwrap()
+            .insert(payment_hash.to_string(), false);
+        if self.auto_settle {
+            self.settle(&request);
+        }
+        Ok(request)
+    }
+
+    fn is_held(&self, payment_hash: &str) -> bool {
+        self.holds.lock().unwrap().get(payment_hash) == Some(&true)
+    }
+
+    fn settle_hold(&self, preimage: &str) -> Result<(), PaymentError> {
+        let preimage = from_hex(preimage).ok_or(PaymentError::InvalidRequest)?;
+        let hash = to_hex(&Sha256::digest(preimage));
+        if self.holds.lock().unwrap().remove(&hash) != Some(true) {
+            return Err(PaymentError::Failed("no held payment".to_string()));
+        }
+        self.settled.lock().unwrap().insert(hash);
+        Ok(())
+    }
+
+    fn cancel_hold(&self, payment_hash: &str) {
+        self.holds.lock().unwrap().remove(payment_hash);
+    }
+}
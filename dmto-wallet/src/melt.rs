@@ -0,0 +1,120 @@
+//! Melting: the mint pays a Lightning invoice on the wallet's behalf in
+//! exchange for notes. The payment preimage is kept with the quote and
+//! handed back as proof of payment. Payments to Bitcoin addresses take the
+//! same path; see `onchain`.
+//!
+//! An invoice the mint issued itself for a mint quote is settled internally:
+//! the mint quote is marked paid, with no Lightning payment and no fee
+//! reserve.
+//!
+//! A Lightning invoice without an amount is paid for the amount the wallet
+//! names when quoting (`melt_quote_with_amount`), as tipping flows use. The
+//! quote carries that amount through reservation and payment.
+//!
+//! Unused fee reserve is returned as change through blank outputs: blinded
+//! messages without a value, which the mint fills in from the largest
+//! denomination down.
+
+use rand::RngCore;
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    codec::to_hex,
+    receipt::FeeReceipt,
+    types::{Amount, Note},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MeltQuoteState {
+    Unpaid,
+    /// Inputs are spent and the payment is in flight.
+    Pending,
+    Paid,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeltQuote {
+    pub id: String,
+    pub request: String,
+    pub amount: u64,
+    pub fee_reserve: u64,
+    pub state: MeltQuoteState,
+    pub expiry: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preimage: Option<String>,
+    #[serde(default)]
+    pub fee_paid: u64,
+    /// For on-chain payments, the sat/vB rate the fee reserve was quoted at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_rate: Option<u64>,
+    /// For on-chain payments, the payout transaction once broadcast.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub txid: Option<String>,
+    #[serde(default)]
+    pub confirmations: u32,
+    /// The invoice was one of this mint's own and was settled by marking
+    /// its mint quote paid, without a Lightning payment or preimage.
+    #[serde(default)]
+    pub internal: bool,
+    /// The invoice carries no amount; `amount` is what the wallet asked to
+    /// pay to it.
+    #[serde(default)]
+    pub amountless: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MeltRequest {
+    pub quote: String,
+    pub inputs: Vec<Note>,
+    /// Blank outputs for returning unused fee reserve.
+    #[serde(default)]
+    pub outputs: Vec<PublicKey>,
+    /// The wallet's operation ID, for tracing (see `operation`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MeltResponse {
+    pub quote: MeltQuote,
+    /// Signatures on the first blank outputs with the value assigned to
+    /// each, in output order.
+    pub change: Vec<(u64, PublicKey)>,
+    /// What the mint charged, signed with its identity key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<FeeReceipt>,
+}
+
+/// The fee reserve on Lightning melt quotes, in place of the backend's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeReserve {
+    /// Parts per thousand of the amount, rounded up.
+    pub ppk: u64,
+    pub floor: u64,
+}
+
+impl FeeReserve {
+    pub fn reserve(&self, amount: u64) -> u64 {
+        Amount(amount)
+            .checked_mul(self.ppk)
+            .map_or(u64::MAX, |a| a.0.div_ceil(1000))
+            .max(self.floor)
+    }
+}
+
+pub fn quote_id() -> String {
+    let mut id = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut id);
+    to_hex(&id)
+}
+
+/// How many blank outputs can carry any change up to `max_change` in
+/// power-of-two denominations.
+pub fn blank_outputs_for(max_change: u64) -> usize {
+    if max_change == 0 {
+        0
+    } else {
+        (64 - max_change.leading_zeros()) as usize
+    }
+}
@@ -0,0 +1,76 @@
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    codec::to_hex,
+    keyset::KeysetIdVersion,
+    secret::{Kind, SecretPolicy},
+    types::Amount,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProofState {
+    Unspent,
+    Spent,
+}
+
+/// How a mint rounds the total input fee of a request to whole units.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FeeRounding {
+    #[default]
+    Up,
+    Down,
+}
+
+/// Public description of the mint that wallets fetch before using it.
+#[derive(Clone, Debug, Serialize)]
+pub struct MintInfo {
+    /// Implementation and version, e.g. `dmto/0.0.1`.
+    pub version: String,
+    pub keyset_id: String,
+    /// How `keyset_id` was derived from the keys.
+    pub keyset_id_version: KeysetIdVersion,
+    pub denominations: Vec<u64>,
+    /// Set when the denominations are a power-of-two `Keyset`.
+    pub max_order: Option<u32>,
+    pub accepted_kinds: Vec<Kind>,
+    pub input_fee_ppk: u64,
+    pub fee_rounding: FeeRounding,
+    pub max_inputs: usize,
+    pub max_outputs: usize,
+    pub secret_policy: SecretPolicy,
+    pub max_restore_batch: usize,
+}
+
+impl MintInfo {
+    pub fn fee(&self, inputs: usize) -> u64 {
+        match self.fee_rounding {
+            FeeRounding::Up => input_fee(inputs, self.input_fee_ppk),
+            FeeRounding::Down => Amount(inputs as u64)
+                .checked_mul(self.input_fee_ppk)
+                .map_or(u64::MAX, |fee| fee.0 / 1000),
+        }
+    }
+}
+
+/// Input fee for `inputs` inputs at `ppk` parts per thousand. Saturates
+/// rather than wrapping, so an absurd input count can never come out cheap.
+pub fn input_fee(inputs: usize, ppk: u64) -> u64 {
+    Amount(inputs as u64)
+        .checked_mul(ppk)
+        .map_or(u64::MAX, |fee| fee.0.div_ceil(1000))
+}
+
+pub fn keyset_id_from_pubkeys(keys: &[(u64, PublicKey)]) -> String {
+    let mut keys = keys.to_vec();
+    keys.sort_by_key(|(v, _)| *v);
+
+    let mut hasher = Sha256::new();
+    for (_, pubkey) in &keys {
+        hasher.update(pubkey.serialize());
+    }
+    let hash = hasher.finalize();
+
+    format!("00{}", to_hex(&hash[..7]))
+}
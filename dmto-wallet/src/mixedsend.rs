@@ -0,0 +1,107 @@
+//! Sends that mix locked and plain outputs in one swap, e.g. paying a
+//! recipient part of an amount locked to their key and the rest as plain
+//! notes to hand on or keep, instead of a `send_locked` followed by a
+//! `split_out`. The wallet's change comes back in the same swap.
+//!
+//! ```no_run
+//! use dmto_wallet::{api::MintTrait, conditions::Condition, mixedsend::MixedSend, wallet::Wallet};
+//! # fn pay(wallet: &mut Wallet, mint: &impl MintTrait, recipient: secp256k1::PublicKey)
+//! # -> Result<(), dmto_wallet::error::WalletError> {
+//! let sent = wallet.send_mixed(
+//!     mint,
+//!     MixedSend::new()
+//!         .locked(80, Condition::p2pk(recipient))
+//!         .plain(20),
+//! )?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    api::MintTrait,
+    conditions::Condition,
+    error::{MintError, WalletError},
+    types::{Amount, Note},
+    wallet::{Wallet, split_amount},
+    walletpolicy::SpendKind,
+};
+
+/// What a mixed send produces: amounts locked to spending conditions and a
+/// plain amount.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MixedSend {
+    pub locked: Vec<(u64, Condition)>,
+    pub plain: u64,
+}
+
+/// The notes of a mixed send, each group in the order it was asked for.
+#[derive(Clone, Debug, Default)]
+pub struct MixedNotes {
+    /// Per `locked` entry, the notes locked to its condition.
+    pub locked: Vec<Vec<Note>>,
+    pub plain: Vec<Note>,
+}
+
+impl MixedSend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `amount` locked to `condition`.
+    pub fn locked(mut self, amount: u64, condition: Condition) -> Self {
+        self.locked.push((amount, condition));
+        self
+    }
+
+    /// Adds `amount` to the plain part.
+    pub fn plain(mut self, amount: u64) -> Self {
+        self.plain += amount;
+        self
+    }
+
+    pub fn total(&self) -> Option<u64> {
+        Amount::checked_sum(self.locked.iter().map(|(a, _)| *a).chain([self.plain])).map(|a| a.0)
+    }
+}
+
+impl Wallet {
+    /// Selects notes covering everything `send` asks for plus the input fee
+    /// and swaps them, in one request, for the locked and plain outputs and
+    /// the wallet's change.
+    pub fn send_mixed(
+        &mut self,
+        mint: &impl MintTrait,
+        send: MixedSend,
+    ) -> Result<MixedNotes, WalletError> {
+        let total = send.total().ok_or(MintError::AmountOverflow)?;
+        let denominations = mint.info()?.denominations;
+        let split = |amount: u64| {
+            split_amount(amount, &denominations).ok_or(MintError::UnknownDenomination(amount))
+        };
+
+        let mut outputs = Vec::new();
+        let mut counts = Vec::with_capacity(send.locked.len());
+        for (amount, condition) in &send.locked {
+            let values = split(*amount)?;
+            counts.push(values.len());
+            outputs.extend(values.into_iter().map(|v| (v, condition.to_secret())));
+        }
+        outputs.extend(self.random_outputs(&split(send.plain)?));
+
+        let kind = if send.locked.is_empty() {
+            SpendKind::Send
+        } else {
+            SpendKind::SendLocked
+        };
+        let mut notes = self
+            .guarded(kind, total, |w| w.send_outputs(mint, outputs))?
+            .into_iter();
+        Ok(MixedNotes {
+            locked: counts
+                .into_iter()
+                .map(|n| notes.by_ref().take(n).collect())
+                .collect(),
+            plain: notes.collect(),
+        })
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use crate::{api::MintTrait, types::Note, wallet::Wallet};
+
+/// Raised when a mint's balance goes over the cap the user configured for it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TrustEvent {
+    LimitExceeded {
+        mint_url: String,
+        balance: u64,
+        limit: u64,
+    },
+}
+
+/// One `Wallet` per mint, keyed by mint URL, with optional per-mint caps on
+/// how much the user is willing to hold there.
+#[derive(Default)]
+pub struct MultiMintWallet {
+    pub wallets: HashMap<String, Wallet>,
+    pub trust_limits: HashMap<String, u64>,
+    pub events: Vec<TrustEvent>,
+}
+
+impl MultiMintWallet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_trust_limit(&mut self, mint_url: &str, limit: u64) {
+        self.trust_limits.insert(mint_url.to_string(), limit);
+    }
+
+    pub fn balance(&self, mint_url: &str) -> u64 {
+        self.wallets.get(mint_url).map_or(0, |w| w.balance())
+    }
+
+    pub fn total_balance(&self) -> u64 {
+        self.wallets.values().map(|w| w.balance()).sum()
+    }
+
+    /// Receives notes into the wallet for `mint_url`. The receive itself is
+    /// never blocked by a trust limit; going over it queues a
+    /// `TrustEvent::LimitExceeded` so the caller can move the excess out.
+    pub fn receive(&mut self, mint_url: &str, mint: &impl MintTrait, notes: Vec<Note>) -> bool {
+        let wallet = self.wallets.entry(mint_url.to_string()).or_default();
+        if !wallet.receive(mint, notes) {
+            return false;
+        }
+
+        let balance = wallet.balance();
+        if let Some(&limit) = self.trust_limits.get(mint_url)
+            && balance > limit
+        {
+            self.events.push(TrustEvent::LimitExceeded {
+                mint_url: mint_url.to_string(),
+                balance,
+                limit,
+            });
+        }
+
+        true
+    }
+
+    pub fn take_events(&mut self) -> Vec<TrustEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
@@ -0,0 +1,309 @@
+//! Compact binary tokens for NFC NDEF records.
+//!
+//! Notes are grouped by keyset so each 8-byte keyset ID is written once, `Y`
+//! is dropped (the reader recomputes it from the secret) and values and
+//! lengths are varints. Layout:
+//!
+//! | bytes | field                                   |
+//! | ----- | --------------------------------------- |
+//! | 1     | format version                          |
+//! | 1 + n | mint URL, length-prefixed               |
+//! | 1 + n | unit, length-prefixed                   |
+//! | 1     | keyset group count                      |
+//! |       | per group: 8 keyset ID, varint count    |
+//! |       | per note: varint value, varint secret   |
+//! |       | length, secret, 33 `C`, DLEQ flag, 96   |
+//! |       | DLEQ `e`, `s`, `r` if present           |
+//! | 1 + n | memo, length-prefixed (version 2 only)  |
+//!
+//! Version 2 is written only for tokens with a memo, so tokens without one
+//! still read on version 1 decoders.
+//!
+//! Tokens larger than one record are split with `chunk` and put back
+//! together with `Reassembler`.
+
+use std::fmt;
+
+use secp256k1::{PublicKey, SecretKey};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    codec::{from_hex, put_varint, to_hex},
+    dleq::NoteDleq,
+    hash::try_hash_to_curve,
+    token::Token,
+    types::Note,
+};
+
+pub const FORMAT_VERSION: u8 = 2;
+/// Version written for tokens without a memo.
+const FORMAT_VERSION_NO_MEMO: u8 = 1;
+
+/// Payload that fits a single record on the common NTAG215 tags.
+pub const DEFAULT_RECORD_LEN: usize = 480;
+
+/// Chunk header: token tag (4), index (1), total (1).
+pub const CHUNK_HEADER_LEN: usize = 6;
+
+const DLEQ_LEN: usize = 96;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum NfcError {
+    Truncated,
+    UnsupportedVersion(u8),
+    Malformed,
+    /// The token needs more than 255 chunks at this record size.
+    TooLarge,
+    /// A chunk belongs to a different token or disagrees with earlier ones.
+    ChunkMismatch,
+}
+
+impl fmt::Display for NfcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NfcError::Truncated => write!(f, "payload truncated"),
+            NfcError::UnsupportedVersion(v) => write!(f, "unsupported NFC format version {}", v),
+            NfcError::Malformed => write!(f, "malformed NFC payload"),
+            NfcError::TooLarge => write!(f, "token too large to chunk"),
+            NfcError::ChunkMismatch => write!(f, "chunk does not belong to this token"),
+        }
+    }
+}
+
+impl std::error::Error for NfcError {}
+
+fn put_str(s: &str, out: &mut Vec<u8>) -> Result<(), NfcError> {
+    let len = u8::try_from(s.len()).map_err(|_| NfcError::TooLarge)?;
+    out.push(len);
+    out.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], NfcError> {
+        let end = self.pos.checked_add(n).ok_or(NfcError::Truncated)?;
+        let bytes = self.buf.get(self.pos..end).ok_or(NfcError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, NfcError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, NfcError> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            v |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(NfcError::Malformed)
+    }
+
+    fn string(&mut self) -> Result<String, NfcError> {
+        let len = self.byte()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| NfcError::Malformed)
+    }
+}
+
+/// Encodes `token` in the compact format. Witnesses are not included.
+pub fn encode(token: &Token) -> Result<Vec<u8>, NfcError> {
+    let mut groups: Vec<(&str, Vec<&Note>)> = Vec::new();
+    for n in &token.notes {
+        match groups.iter_mut().find(|(id, _)| *id == n.keyset_id) {
+            Some((_, notes)) => notes.push(n),
+            None => groups.push((&n.keyset_id, vec![n])),
+        }
+    }
+
+    let version = if token.memo.is_some() {
+        FORMAT_VERSION
+    } else {
+        FORMAT_VERSION_NO_MEMO
+    };
+    let mut out = vec![version];
+    put_str(&token.mint_url, &mut out)?;
+    put_str(&token.unit, &mut out)?;
+    out.push(u8::try_from(groups.len()).map_err(|_| NfcError::TooLarge)?);
+
+    for (keyset_id, notes) in groups {
+        let id = from_hex(keyset_id).ok_or(NfcError::Malformed)?;
+        let id: [u8; 8] = id.try_into().map_err(|_| NfcError::Malformed)?;
+        out.extend_from_slice(&id);
+        put_varint(notes.len() as u64, &mut out);
+
+        for n in notes {
+            put_varint(n.value, &mut out);
+            put_varint(n.secret.len() as u64, &mut out);
+            out.extend_from_slice(&n.secret);
+            out.extend_from_slice(&n.c.serialize());
+            match &n.dleq {
+                Some(p) => {
+                    out.push(1);
+                    for k in [p.e, p.s, p.r] {
+                        out.extend_from_slice(&k.secret_bytes());
+                    }
+                }
+                None => out.push(0),
+            }
+        }
+    }
+    if let Some(memo) = &token.memo {
+        put_str(memo, &mut out)?;
+    }
+    Ok(out)
+}
+
+pub fn decode(buf: &[u8]) -> Result<Token, NfcError> {
+    let mut r = Reader { buf, pos: 0 };
+    let version = r.byte()?;
+    if !(FORMAT_VERSION_NO_MEMO..=FORMAT_VERSION).contains(&version) {
+        return Err(NfcError::UnsupportedVersion(version));
+    }
+    let mint_url = r.string()?;
+    let unit = r.string()?;
+
+    let mut notes = Vec::new();
+    for _ in 0..r.byte()? {
+        let keyset_id = to_hex(r.take(8)?);
+        for _ in 0..r.varint()? {
+            let value = r.varint()?;
+            let secret_len = usize::try_from(r.varint()?).map_err(|_| NfcError::Malformed)?;
+            let secret = r.take(secret_len)?.to_vec();
+            let c = PublicKey::from_slice(r.take(33)?).map_err(|_| NfcError::Malformed)?;
+            let dleq = match r.byte()? {
+                0 => None,
+                1 => {
+                    let raw = r.take(DLEQ_LEN)?;
+                    let key = |i: usize| {
+                        SecretKey::from_slice(&raw[i * 32..(i + 1) * 32])
+                            .map_err(|_| NfcError::Malformed)
+                    };
+                    Some(NoteDleq {
+                        e: key(0)?,
+                        s: key(1)?,
+                        r: key(2)?,
+                    })
+                }
+                _ => return Err(NfcError::Malformed),
+            };
+            notes.push(Note {
+                value,
+                keyset_id: keyset_id.clone(),
+                y: try_hash_to_curve(&secret).map_err(|_| NfcError::Malformed)?,
+                secret,
+                c,
+                dleq,
+                witness: None,
+            });
+        }
+    }
+    let memo = if version == FORMAT_VERSION {
+        Some(r.string()?)
+    } else {
+        None
+    };
+    if r.pos != buf.len() {
+        return Err(NfcError::Malformed);
+    }
+
+    Ok(Token {
+        mint_url,
+        unit,
+        notes,
+        memo,
+    })
+}
+
+/// Splits an encoded token into records of at most `record_len` bytes. Each
+/// record carries a tag derived from the payload so chunks of different
+/// tokens are not mixed.
+pub fn chunk(payload: &[u8], record_len: usize) -> Result<Vec<Vec<u8>>, NfcError> {
+    let body = record_len
+        .checked_sub(CHUNK_HEADER_LEN)
+        .filter(|&n| n > 0)
+        .ok_or(NfcError::TooLarge)?;
+    let parts: Vec<&[u8]> = payload.chunks(body).collect();
+    let total = u8::try_from(parts.len()).map_err(|_| NfcError::TooLarge)?;
+    let tag = tag(payload);
+
+    Ok(parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let mut record = tag.to_vec();
+            record.push(i as u8);
+            record.push(total);
+            record.extend_from_slice(part);
+            record
+        })
+        .collect())
+}
+
+fn tag(payload: &[u8]) -> [u8; 4] {
+    let hash = Sha256::digest(payload);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Collects chunks in any order and yields the payload once all have been
+/// read.
+#[derive(Default)]
+pub struct Reassembler {
+    tag: Option<[u8; 4]>,
+    parts: Vec<Option<Vec<u8>>>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds one record. Returns the full payload once the last missing chunk
+    /// arrives; re-reading a chunk is harmless.
+    pub fn push(&mut self, record: &[u8]) -> Result<Option<Vec<u8>>, NfcError> {
+        if record.len() < CHUNK_HEADER_LEN {
+            return Err(NfcError::Truncated);
+        }
+        let tag: [u8; 4] = record[..4].try_into().unwrap();
+        let (index, total) = (record[4] as usize, record[5] as usize);
+        if total == 0 || index >= total {
+            return Err(NfcError::Malformed);
+        }
+
+        match self.tag {
+            None => {
+                self.tag = Some(tag);
+                self.parts = vec![None; total];
+            }
+            Some(t) if t != tag || self.parts.len() != total => {
+                return Err(NfcError::ChunkMismatch);
+            }
+            Some(_) => {}
+        }
+        self.parts[index] = Some(record[CHUNK_HEADER_LEN..].to_vec());
+
+        if self.parts.iter().any(Option::is_none) {
+            return Ok(None);
+        }
+        let payload: Vec<u8> = self.parts.iter().flatten().flatten().copied().collect();
+        if self::tag(&payload) != tag {
+            return Err(NfcError::ChunkMismatch);
+        }
+        Ok(Some(payload))
+    }
+
+    /// Chunks still missing, for prompting the user to tap again.
+    pub fn missing(&self) -> Vec<usize> {
+        (0..self.parts.len())
+            .filter(|&i| self.parts[i].is_none())
+            .collect()
+    }
+}
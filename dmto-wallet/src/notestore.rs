@@ -0,0 +1,229 @@
+//! The wallet's notes, indexed by `Y`, by denomination and by keyset so
+//! lookups, removals and balance queries stay cheap with thousands of notes.
+//!
+//! Iteration goes by denomination, largest first, and is deterministic.
+//! `pick_weighted` draws notes at random for wallets that use
+//! `Selection::Random`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+use rand::Rng;
+use secp256k1::PublicKey;
+
+use crate::types::Note;
+
+/// How a wallet picks the notes it spends.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Selection {
+    /// Largest denomination first, always the same notes for the same
+    /// wallet state.
+    #[default]
+    LargestFirst,
+    /// At random, weighted by denomination. Larger notes are still likelier
+    /// to be picked, which keeps input counts down, but which notes are
+    /// spent together differs from spend to spend, so the mint cannot
+    /// cluster them by the order they were received in.
+    Random,
+}
+
+#[derive(Clone, Default)]
+pub struct NoteStore {
+    by_y: HashMap<PublicKey, Note>,
+    by_value: BTreeMap<u64, BTreeSet<PublicKey>>,
+    by_keyset: HashMap<String, BTreeSet<PublicKey>>,
+    total: u64,
+}
+
+impl NoteStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_y.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_y.is_empty()
+    }
+
+    /// Sum of all note values.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Adds a note. A note with the same `Y` already held is kept and
+    /// `false` returned.
+    pub fn push(&mut self, note: Note) -> bool {
+        if self.by_y.contains_key(&note.y) {
+            return false;
+        }
+        self.by_value.entry(note.value).or_default().insert(note.y);
+        self.by_keyset
+            .entry(note.keyset_id.clone())
+            .or_default()
+            .insert(note.y);
+        self.total += note.value;
+        self.by_y.insert(note.y, note);
+        true
+    }
+
+    pub fn contains(&self, y: &PublicKey) -> bool {
+        self.by_y.contains_key(y)
+    }
+
+    pub fn get(&self, y: &PublicKey) -> Option<&Note> {
+        self.by_y.get(y)
+    }
+
+    pub fn remove(&mut self, y: &PublicKey) -> Option<Note> {
+        let note = self.by_y.remove(y)?;
+        if let Some(ys) = self.by_value.get_mut(&note.value) {
+            ys.remove(y);
+            if ys.is_empty() {
+                self.by_value.remove(&note.value);
+            }
+        }
+        if let Some(ys) = self.by_keyset.get_mut(&note.keyset_id) {
+            ys.remove(y);
+            if ys.is_empty() {
+                self.by_keyset.remove(&note.keyset_id);
+            }
+        }
+        self.total -= note.value;
+        Some(note)
+    }
+
+    /// Removes and returns a note of the largest denomination held.
+    pub fn pop(&mut self) -> Option<Note> {
+        let y = *self.by_value.values().next_back()?.first()?;
+        self.remove(&y)
+    }
+
+    /// A note drawn at random with probability proportional to its value.
+    pub fn pick_weighted(&self, rng: &mut impl Rng) -> Option<&Note> {
+        if self.total == 0 {
+            return None;
+        }
+        let mut r = rng.gen_range(0..self.total);
+        for (&value, ys) in &self.by_value {
+            let weight = value * ys.len() as u64;
+            if r < weight {
+                let y = ys.iter().nth((r / value) as usize)?;
+                return self.by_y.get(y);
+            }
+            r -= weight;
+        }
+        None
+    }
+
+    /// `n` notes of `value` drawn uniformly at random, or fewer if fewer are
+    /// held.
+    pub fn pick_with_value(&self, value: u64, n: usize, rng: &mut impl Rng) -> Vec<&Note> {
+        let ys: Vec<&PublicKey> = self.by_value.get(&value).into_iter().flatten().collect();
+        rand::seq::index::sample(rng, ys.len(), n.min(ys.len()))
+            .into_iter()
+            .map(|i| &self.by_y[ys[i]])
+            .collect()
+    }
+
+    pub fn to_vec(&self) -> Vec<Note> {
+        self.iter().cloned().collect()
+    }
+
+    /// Removes and returns every note.
+    pub fn take_all(&mut self) -> Vec<Note> {
+        let notes = self.to_vec();
+        *self = Self::default();
+        notes
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&Note) -> bool) {
+        let drop: Vec<PublicKey> = self
+            .by_y
+            .values()
+            .filter(|n| !keep(n))
+            .map(|n| n.y)
+            .collect();
+        for y in drop {
+            self.remove(&y);
+        }
+    }
+
+    /// Largest denomination first.
+    pub fn iter(&self) -> impl Iterator<Item = &Note> {
+        self.by_value
+            .values()
+            .rev()
+            .flatten()
+            .map(|y| &self.by_y[y])
+    }
+
+    pub fn with_value(&self, value: u64) -> impl Iterator<Item = &Note> {
+        self.by_value
+            .get(&value)
+            .into_iter()
+            .flatten()
+            .map(|y| &self.by_y[y])
+    }
+
+    pub fn in_keyset(&self, keyset_id: &str) -> impl Iterator<Item = &Note> {
+        self.by_keyset
+            .get(keyset_id)
+            .into_iter()
+            .flatten()
+            .map(|y| &self.by_y[y])
+    }
+
+    /// Number of notes held per denomination.
+    pub fn counts(&self) -> BTreeMap<u64, usize> {
+        self.by_value.iter().map(|(v, ys)| (*v, ys.len())).collect()
+    }
+}
+
+impl fmt::Debug for NoteStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl Extend<Note> for NoteStore {
+    fn extend<I: IntoIterator<Item = Note>>(&mut self, notes: I) {
+        for note in notes {
+            self.push(note);
+        }
+    }
+}
+
+impl FromIterator<Note> for NoteStore {
+    fn from_iter<I: IntoIterator<Item = Note>>(notes: I) -> Self {
+        let mut store = Self::new();
+        store.extend(notes);
+        store
+    }
+}
+
+impl IntoIterator for NoteStore {
+    type Item = Note;
+    type IntoIter = std::vec::IntoIter<Note>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        self.take_all().into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a NoteStore {
+    type Item = &'a Note;
+    type IntoIter = Box<dyn Iterator<Item = &'a Note> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
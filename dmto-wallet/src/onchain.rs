@@ -0,0 +1,276 @@
+//! Minting from and melting to Bitcoin addresses. Both directions use BIP21
+//! URIs (`bitcoin:<address>?amount=<btc>`) where Lightning uses invoices,
+//! so the rest of the quote flows are unchanged.
+//!
+//! For deposits, the quote's request is a fresh address from the mint's
+//! `BitcoinBackend`. The quote counts as paid once the address has received
+//! the amount with at least `Mint::deposit_confirmations` confirmations.
+//!
+//! For melts, the wallet passes a BIP21 URI as the melt request and the mint
+//! pays it through the backend instead of Lightning.
+//! The fee reserve is the backend's current fee rate times the expected size
+//! of the payout transaction, and unused reserve comes back as change. The
+//! transaction ID is kept with the quote as proof of payment, and the
+//! quote's confirmations are refreshed from the backend when its state is
+//! read.
+//!
+//! Backends wrap a node's RPC or an Esplora server; this crate only ships
+//! `FakeBitcoin`.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    api::MintTrait,
+    codec::to_hex,
+    error::{MintError, WalletError},
+    issue::MintQuote,
+    lightning::PaymentError,
+    wallet::Wallet,
+};
+
+/// Expected virtual size in bytes of the mint's payout transaction: one
+/// segwit input, the payment and change.
+pub const PAYOUT_VSIZE: u64 = 141;
+
+const SATS_PER_BTC: u64 = 100_000_000;
+
+/// Proof of a broadcast payment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OnchainPayment {
+    pub txid: String,
+    pub fee_paid: u64,
+}
+
+pub trait BitcoinBackend: Send + Sync {
+    fn check_address(&self, address: &str) -> bool;
+    /// Fee rate in sat/vB for timely confirmation.
+    fn fee_rate(&self) -> u64;
+    /// Broadcasts a payment of `amount` to `address` at `fee_rate`, failing
+    /// before broadcast if the fee would exceed `max_fee`.
+    fn send(
+        &self,
+        address: &str,
+        amount: u64,
+        fee_rate: u64,
+        max_fee: u64,
+    ) -> Result<OnchainPayment, PaymentError>;
+    /// `None` for transactions the backend does not know.
+    fn confirmations(&self, txid: &str) -> Option<u32>;
+    /// A fresh address of the mint's wallet to take a deposit on.
+    fn new_address(&self) -> Result<String, PaymentError>;
+    /// Total received by `address` in transactions with at least
+    /// `min_confirmations` confirmations.
+    fn received(&self, address: &str, min_confirmations: u32) -> u64;
+}
+
+/// Accepts `bc1`, `tb1` and `bcrt1` addresses and broadcasts into an
+/// in-memory chain that only moves when `mine` is called. Deposits to the
+/// mint are made with `deposit`.
+pub struct FakeBitcoin {
+    pub fee_rate: u64,
+    height: Mutex<u32>,
+    /// Height each transaction was broadcast at.
+    txs: Mutex<HashMap<String, u32>>,
+    /// Address, amount and height of each deposit.
+    deposits: Mutex<Vec<(String, u64, u32)>>,
+    addresses: Mutex<u64>,
+}
+
+impl Default for FakeBitcoin {
+    fn default() -> Self {
+        Self {
+            fee_rate: 2,
+            height: Mutex::new(0),
+            txs: Mutex::new(HashMap::new()),
+            deposits: Mutex::new(Vec::new()),
+            addresses: Mutex::new(0),
+        }
+    }
+}
+
+impl FakeBitcoin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_fee_rate(fee_rate: u64) -> Self {
+        Self {
+            fee_rate,
+            ..Self::default()
+        }
+    }
+
+    /// Mines `blocks` blocks, confirming everything broadcast so far.
+    pub fn mine(&self, blocks: u32) {
+        *self.height.lock().unwrap() += blocks;
+    }
+
+    /// Broadcasts a payment of `amount` to `address`, as if someone paid a
+    /// deposit. It confirms with the next block mined.
+    pub fn deposit(&self, address: &str, amount: u64) {
+        let height = *self.height.lock().unwrap();
+        self.deposits
+            .lock()
+            .unwrap()
+            .push((address.to_string(), amount, height));
+    }
+}
+
+impl BitcoinBackend for FakeBitcoin {
+    fn check_address(&self, address: &str) -> bool {
+        let lower = address.to_ascii_lowercase();
+        ["bc1", "tb1", "bcrt1"].iter().any(|p| lower.starts_with(p))
+            && (14..=90).contains(&address.len())
+            && address.bytes().all(|b| b.is_ascii_alphanumeric())
+    }
+
+    fn fee_rate(&self) -> u64 {
+        self.fee_rate
+    }
+
+    fn send(
+        &self,
+        address: &str,
+        amount: u64,
+        fee_rate: u64,
+        max_fee: u64,
+    ) -> Result<OnchainPayment, PaymentError> {
+        if !self.check_address(address) {
+            return Err(PaymentError::InvalidRequest);
+        }
+        let fee = fee_rate.saturating_mul(PAYOUT_VSIZE);
+        if fee > max_fee {
+            return Err(PaymentError::FeeTooHigh { needed: fee });
+        }
+
+        let mut txs = self.txs.lock().unwrap();
+        let mut hasher = Sha256::new();
+        hasher.update(address.as_bytes());
+        hasher.update(amount.to_be_bytes());
+        hasher.update((txs.len() as u64).to_be_bytes());
+        let txid = to_hex(&hasher.finalize());
+        txs.insert(txid.clone(), *self.height.lock().unwrap());
+        Ok(OnchainPayment {
+            txid,
+            fee_paid: fee,
+        })
+    }
+
+    fn confirmations(&self, txid: &str) -> Option<u32> {
+        let broadcast = *self.txs.lock().unwrap().get(txid)?;
+        Some(*self.height.lock().unwrap() - broadcast)
+    }
+
+    fn new_address(&self) -> Result<String, PaymentError> {
+        let mut n = self.addresses.lock().unwrap();
+        *n += 1;
+        let hash = Sha256::digest(n.to_be_bytes());
+        Ok(format!("bcrt1q{}", to_hex(&hash[..20])))
+    }
+
+    fn received(&self, address: &str, min_confirmations: u32) -> u64 {
+        let height = *self.height.lock().unwrap();
+        self.deposits
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(a, _, h)| a == address && height - h >= min_confirmations)
+            .map(|(_, amount, _)| amount)
+            .sum()
+    }
+}
+
+/// A BIP21 URI asking for `amount` sats at `address`.
+pub fn payment_uri(address: &str, amount: u64) -> String {
+    let btc = format!("{}.{:08}", amount / SATS_PER_BTC, amount % SATS_PER_BTC);
+    format!(
+        "bitcoin:{}?amount={}",
+        address,
+        btc.trim_end_matches('0').trim_end_matches('.')
+    )
+}
+
+fn parse_btc(s: &str) -> Option<u64> {
+    let (whole, frac) = s.split_once('.').unwrap_or((s, ""));
+    if whole.is_empty() && frac.is_empty() || frac.len() > 8 {
+        return None;
+    }
+    let digits = |d: &str| d.is_empty() || d.bytes().all(|b| b.is_ascii_digit());
+    if !digits(whole) || !digits(frac) {
+        return None;
+    }
+    let whole: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().ok()?
+    };
+    let frac: u64 = format!("{:0<8}", frac).parse().ok()?;
+    whole.checked_mul(SATS_PER_BTC)?.checked_add(frac)
+}
+
+/// Reads a BIP21 URI into its address and amount in sats. `None` for
+/// anything else, including URIs without an amount.
+pub fn parse_payment_uri(uri: &str) -> Option<(String, u64)> {
+    let scheme = "bitcoin:";
+    if uri.len() < scheme.len()
+        || !uri.is_char_boundary(scheme.len())
+        || !uri[..scheme.len()].eq_ignore_ascii_case(scheme)
+    {
+        return None;
+    }
+    let (address, query) = uri[scheme.len()..].split_once('?')?;
+    let amount = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("amount="))
+        .and_then(parse_btc)?;
+    Some((address.to_string(), amount))
+}
+
+pub fn is_onchain(request: &str) -> bool {
+    request
+        .get(..8)
+        .is_some_and(|s| s.eq_ignore_ascii_case("bitcoin:"))
+}
+
+impl Wallet {
+    /// Asks the mint for a deposit address for `amount`. The quote's request
+    /// is a BIP21 URI to pay; once it confirms, `resume_quote` collects the
+    /// notes.
+    pub fn request_mint_onchain(
+        &mut self,
+        mint: &impl MintTrait,
+        amount: u64,
+    ) -> Result<MintQuote, WalletError> {
+        let quote = mint.mint_quote_onchain(amount)?;
+        self.pending_quotes.push(quote.id.clone());
+        Ok(quote)
+    }
+
+    /// Pays `amount` to a Bitcoin `address` through the mint, receiving any
+    /// unused fee reserve back as change. Returns the ID of the transaction,
+    /// which is also kept in the history; `melt_confirmations` follows it.
+    pub fn melt_onchain(
+        &mut self,
+        mint: &impl MintTrait,
+        address: &str,
+        amount: u64,
+    ) -> Result<String, WalletError> {
+        self.melt(mint, &payment_uri(address, amount))
+    }
+
+    /// Confirmations so far of an on-chain melt's transaction.
+    pub fn melt_confirmations(
+        &self,
+        mint: &impl MintTrait,
+        txid: &str,
+    ) -> Result<u32, WalletError> {
+        let tx = self
+            .history
+            .iter()
+            .find(|tx| tx.txid.as_deref() == Some(txid))
+            .ok_or_else(|| MintError::QuoteUnknown(txid.to_string()))?;
+        Ok(mint.melt_quote_state(&tx.id)?.confirmations)
+    }
+}
@@ -0,0 +1,75 @@
+//! Operation IDs for tracing one payment across the wallet, the mint and the
+//! Lightning backend.
+//!
+//! The wallet starts an operation for each send, melt, receive or issuance
+//! and sends its ID with the swap, mint and melt requests it makes. The mint
+//! enters the request's operation while handling it, so everything on that
+//! thread can read it with `current`: journal entries and error responses
+//! carry it, the mint's `OperationLog` records each request under it, and a
+//! Lightning backend can tag its own logs with it.
+
+use std::cell::RefCell;
+
+use rand::RngCore;
+
+use crate::{codec::to_hex, error::MintError};
+
+thread_local! {
+    static CURRENT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+pub fn new_operation_id() -> String {
+    let mut id = [0u8; 8];
+    rand::thread_rng().fill_bytes(&mut id);
+    to_hex(&id)
+}
+
+/// Whether `id` is acceptable from a client: 1 to 64 ASCII letters,
+/// digits, `-` or `_`.
+pub fn valid_id(id: &str) -> bool {
+    (1..=64).contains(&id.len())
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// The operation being handled on this thread, if any.
+pub fn current() -> Option<String> {
+    CURRENT.with(|c| c.borrow().clone())
+}
+
+/// Makes `id` the current operation until the guard is dropped. `None`
+/// leaves the current one as it is.
+pub fn enter(id: Option<&str>) -> OperationGuard {
+    let previous = id.map(|id| CURRENT.with(|c| c.replace(Some(id.to_string()))));
+    OperationGuard { previous }
+}
+
+/// Starts a new operation unless one is already current, so nested calls
+/// share their caller's.
+pub fn begin() -> OperationGuard {
+    match current() {
+        Some(_) => OperationGuard { previous: None },
+        None => enter(Some(&new_operation_id())),
+    }
+}
+
+/// Restores the previous operation when dropped.
+pub struct OperationGuard {
+    previous: Option<Option<String>>,
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            CURRENT.with(|c| *c.borrow_mut() = previous);
+        }
+    }
+}
+
+/// Where the mint records each request made under an operation.
+pub trait OperationLog: Send + Sync {
+    /// `action` is the request handled, e.g. `melt`; `error` is why it
+    /// failed, if it did.
+    fn record(&self, operation: &str, action: &str, error: Option<&MintError>);
+}
@@ -0,0 +1,272 @@
+//! Payjoin: the receiver of a payment adds its own inputs to the sender's
+//! swap, so a single swap at the mint spends both parties' notes and
+//! creates both parties' outputs, in canonical order. The mint can no longer
+//! tell which inputs paid whom, or how much.
+//!
+//! The sender proposes its inputs together with blinded outputs for its
+//! change. The receiver adds inputs and blinded outputs of its own, submits
+//! the combined swap and returns the signatures on the sender's change. As
+//! with sending a token, the sender trusts the receiver with the full value
+//! of its inputs until the change comes back.
+
+use std::fmt;
+
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{KeysetKeys, MintTrait},
+    canonical::{output_key, sort_inputs},
+    error::{MintError, WalletError},
+    history::{Direction, Transaction},
+    operation,
+    protocol::{SwapRequest, SwapResponse},
+    token::Token,
+    types::Note,
+    wallet::{PendingOutput, Wallet, blind_outputs, split_amount, unblind_outputs},
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum PayjoinMessage {
+    /// The sender's inputs, which cover `amount` plus the fee for spending
+    /// them, and its blinded change outputs.
+    Proposal {
+        amount: u64,
+        inputs: Vec<Note>,
+        change: Vec<(u64, PublicKey)>,
+    },
+    /// The mint's signatures on the sender's change, in proposal order.
+    Signed { change: SwapResponse },
+}
+
+#[derive(Debug)]
+pub enum PayjoinError {
+    UnexpectedMessage,
+    /// The proposal's inputs minus its change and fee do not equal `amount`.
+    AmountMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    Wallet(WalletError),
+}
+
+impl From<WalletError> for PayjoinError {
+    fn from(err: WalletError) -> Self {
+        PayjoinError::Wallet(err)
+    }
+}
+
+impl From<MintError> for PayjoinError {
+    fn from(err: MintError) -> Self {
+        PayjoinError::Wallet(err.into())
+    }
+}
+
+impl fmt::Display for PayjoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayjoinError::UnexpectedMessage => write!(f, "unexpected message"),
+            PayjoinError::AmountMismatch { expected, actual } => {
+                write!(f, "proposal pays {} but claims {}", actual, expected)
+            }
+            PayjoinError::Wallet(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for PayjoinError {}
+
+/// The sender's side of a payjoin, waiting for its change.
+pub struct PayjoinSender {
+    pub amount: u64,
+    inputs: Vec<Note>,
+    fee: u64,
+    keyset: KeysetKeys,
+    pending: Vec<PendingOutput>,
+}
+
+impl PayjoinSender {
+    /// Unblinds the change returned by the receiver into `wallet`.
+    pub fn finish(self, wallet: &mut Wallet, msg: PayjoinMessage) -> Result<(), PayjoinError> {
+        let PayjoinMessage::Signed { change } = msg else {
+            return Err(PayjoinError::UnexpectedMessage);
+        };
+        let notes = unblind_outputs(&self.keyset, self.pending, &change)?;
+        wallet.notes.extend(notes);
+
+        let id = Token::new("", self.inputs).id();
+        wallet.history.push(Transaction::new(
+            &id,
+            Direction::Outgoing,
+            self.amount,
+            self.fee,
+            wallet.clock.now(),
+        ));
+        Ok(())
+    }
+
+    /// Takes the proposed inputs back if the receiver never submitted the
+    /// swap. Fails if they were already spent.
+    pub fn abort(self, wallet: &mut Wallet, mint: &impl MintTrait) -> bool {
+        wallet.receive(mint, self.inputs)
+    }
+}
+
+impl Wallet {
+    /// Starts a payjoin paying `amount`. The inputs leave the wallet until
+    /// the payjoin finishes or is aborted.
+    pub fn propose_payjoin(
+        &mut self,
+        mint: &impl MintTrait,
+        amount: u64,
+    ) -> Result<(PayjoinSender, PayjoinMessage), WalletError> {
+        let info = mint.info()?;
+        let keyset = mint.active_keyset()?;
+        let (inputs, change) = self.select_with_fee(&info, amount)?;
+        let Some(change_values) = split_amount(change, &info.denominations) else {
+            self.notes.extend(inputs);
+            return Err(MintError::UnknownDenomination(change).into());
+        };
+
+        let (outputs, pending) = blind_outputs(
+            change_values
+                .iter()
+                .map(|&v| (v, self.secret_policy.generate()))
+                .collect(),
+        );
+        let msg = PayjoinMessage::Proposal {
+            amount,
+            inputs: inputs.clone(),
+            change: outputs,
+        };
+        let sender = PayjoinSender {
+            amount,
+            fee: info.fee(inputs.len()),
+            inputs,
+            keyset,
+            pending,
+        };
+        Ok((sender, msg))
+    }
+
+    /// Completes a payjoin proposal, adding inputs worth at least
+    /// `contribute` from this wallet. The receiver pays the extra fee for
+    /// the inputs it adds. Returns the amount received and the reply for the
+    /// sender.
+    pub fn accept_payjoin(
+        &mut self,
+        mint: &impl MintTrait,
+        msg: PayjoinMessage,
+        contribute: u64,
+    ) -> Result<(u64, PayjoinMessage), PayjoinError> {
+        let PayjoinMessage::Proposal {
+            amount,
+            inputs: theirs,
+            change,
+        } = msg
+        else {
+            return Err(PayjoinError::UnexpectedMessage);
+        };
+
+        let info = mint.info()?;
+        let keyset = mint.active_keyset()?;
+        let their_in: u64 = theirs.iter().map(|n| n.value).sum();
+        let their_change: u64 = change.iter().map(|(v, _)| v).sum();
+        let paid = their_in
+            .checked_sub(their_change)
+            .and_then(|v| v.checked_sub(info.fee(theirs.len())))
+            .ok_or(MintError::AmountOverflow)?;
+        if paid != amount {
+            return Err(PayjoinError::AmountMismatch {
+                expected: amount,
+                actual: paid,
+            });
+        }
+
+        let ours = if contribute > 0 {
+            self.select_with_fee(&info, contribute)?.0
+        } else {
+            Vec::new()
+        };
+        let our_in: u64 = ours.iter().map(|n| n.value).sum();
+        let fee = info.fee(theirs.len() + ours.len());
+        let Some(received) = (amount + info.fee(theirs.len())).checked_sub(fee) else {
+            self.notes.extend(ours);
+            return Err(MintError::AmountOverflow.into());
+        };
+        let total = our_in + received;
+        let Some(values) = split_amount(total, &info.denominations) else {
+            self.notes.extend(ours);
+            return Err(MintError::UnknownDenomination(total).into());
+        };
+
+        let (our_outputs, pending) = blind_outputs(
+            values
+                .iter()
+                .map(|&v| (v, self.secret_policy.generate()))
+                .collect(),
+        );
+
+        // Sort both parties' outputs together, remembering where each came
+        // from so the signatures can be handed back.
+        let output = |(mine, i): (bool, usize)| if mine { our_outputs[i] } else { change[i] };
+        let mut order: Vec<(bool, usize)> = (0..our_outputs.len())
+            .map(|i| (true, i))
+            .chain((0..change.len()).map(|i| (false, i)))
+            .collect();
+        order.sort_by_key(|&slot| output_key(&output(slot)));
+        let outputs = order.iter().map(|&slot| output(slot)).collect();
+
+        let mut inputs = theirs.clone();
+        inputs.extend(ours.iter().cloned());
+        sort_inputs(&mut inputs);
+        let req = SwapRequest {
+            inputs,
+            outputs,
+            operation: operation::current(),
+        };
+        let resp = match self
+            .connect(mint)
+            .and_then(|session| mint.handle_swap(session.request(req))?.accept(&session))
+        {
+            Ok(resp) => resp,
+            Err(e) => {
+                self.notes.extend(ours);
+                return Err(e.into());
+            }
+        };
+        if resp.signatures.len() != order.len() {
+            return Err(MintError::InvalidSignature.into());
+        }
+
+        let empty = || SwapResponse {
+            signatures: Vec::new(),
+            dleqs: Vec::new(),
+            receipt: None,
+        };
+        let (mut mine, mut other) = (empty(), empty());
+        let mut slots: Vec<_> = order
+            .iter()
+            .enumerate()
+            .map(|(pos, &(is_mine, i))| (is_mine, i, pos))
+            .collect();
+        slots.sort_unstable();
+        for (is_mine, _, pos) in slots {
+            let part = if is_mine { &mut mine } else { &mut other };
+            part.signatures.push(resp.signatures[pos]);
+            part.dleqs.extend(resp.dleqs.get(pos).cloned());
+        }
+
+        let notes = unblind_outputs(&keyset, pending, &mine)?;
+        self.notes.extend(notes);
+
+        self.history.push(Transaction::new(
+            &Token::new("", theirs).id(),
+            Direction::Incoming,
+            received,
+            amount - received,
+            self.clock.now(),
+        ));
+        Ok((received, PayjoinMessage::Signed { change: other }))
+    }
+}
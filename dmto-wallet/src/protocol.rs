@@ -0,0 +1,137 @@
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+
+use crate::{dleq::Dleq, error::MintError, receipt::FeeReceipt, types::Note};
+
+/// Version spoken by this build. Bump whenever swap or quote semantics change.
+pub const PROTOCOL_VERSION: u16 = 1;
+/// Oldest version this build still understands.
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Capability {
+    Swap,
+    SpendingConditions,
+}
+
+/// Sent by each side on connect.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Hello {
+    pub min_version: u16,
+    pub max_version: u16,
+    pub capabilities: Vec<Capability>,
+}
+
+/// What both sides agreed on; every later request is tagged with `version`
+/// and every response must come back in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Session {
+    pub version: u16,
+    pub capabilities: Vec<Capability>,
+}
+
+/// A request body tagged with the protocol version it was written for.
+/// Swaps, issuance, melts and restores all travel in one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Request<T> {
+    pub version: u16,
+    pub body: T,
+}
+
+/// A response body in the version of the request it answers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Response<T> {
+    pub version: u16,
+    pub body: T,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SwapRequest {
+    pub inputs: Vec<Note>,
+    pub outputs: Vec<(u64, PublicKey)>,
+    /// The wallet's operation ID, for tracing (see `operation`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SwapResponse {
+    pub signatures: Vec<PublicKey>,
+    /// One proof per signature, in the same order.
+    #[serde(default)]
+    pub dleqs: Vec<Dleq>,
+    /// What the mint charged for the swap, signed with its identity key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<FeeReceipt>,
+}
+
+impl Hello {
+    pub fn new(capabilities: Vec<Capability>) -> Self {
+        Self {
+            min_version: MIN_PROTOCOL_VERSION,
+            max_version: PROTOCOL_VERSION,
+            capabilities,
+        }
+    }
+}
+
+/// Picks the highest version both sides support and the capabilities both
+/// advertise.
+pub fn negotiate(ours: &Hello, theirs: &Hello) -> Result<Session, MintError> {
+    let version = ours.max_version.min(theirs.max_version);
+    if version < ours.min_version || version < theirs.min_version {
+        return Err(MintError::UnsupportedVersion(theirs.max_version));
+    }
+
+    let capabilities = ours
+        .capabilities
+        .iter()
+        .filter(|c| theirs.capabilities.contains(c))
+        .copied()
+        .collect();
+
+    Ok(Session {
+        version,
+        capabilities,
+    })
+}
+
+impl Session {
+    pub fn request<T>(&self, body: T) -> Request<T> {
+        Request {
+            version: self.version,
+            body,
+        }
+    }
+
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
+
+impl<T> Request<T> {
+    /// Runs `f` on the body if this build speaks the request's version,
+    /// answering in the same version.
+    pub fn handle<U>(
+        self,
+        f: impl FnOnce(T) -> Result<U, MintError>,
+    ) -> Result<Response<U>, MintError> {
+        if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&self.version) {
+            return Err(MintError::UnsupportedVersion(self.version));
+        }
+        Ok(Response {
+            version: self.version,
+            body: f(self.body)?,
+        })
+    }
+}
+
+impl<T> Response<T> {
+    /// The body, if it is in the version agreed in `session`.
+    pub fn accept(self, session: &Session) -> Result<T, MintError> {
+        if self.version != session.version {
+            return Err(MintError::UnsupportedVersion(self.version));
+        }
+        Ok(self.body)
+    }
+}
@@ -0,0 +1,230 @@
+//! Exchange rates for showing amounts in fiat next to sats. Rates come from
+//! a `PriceSource` and are cached for `ttl` seconds, so a busy balance
+//! display does not hit the provider on every refresh.
+//!
+//! Rates are for display only; nothing in the mint or wallet is priced
+//! with them.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{Read, Write},
+    net::TcpStream,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde_json::Value;
+
+use crate::clock::{Clock, SystemClock};
+
+pub const SATS_PER_BTC: f64 = 100_000_000.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RateError {
+    UnknownCurrency(String),
+    Fetch(String),
+    /// The provider answered with something other than a positive number.
+    BadResponse(String),
+}
+
+impl fmt::Display for RateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateError::UnknownCurrency(c) => write!(f, "no rate for {}", c),
+            RateError::Fetch(e) => write!(f, "fetching rate failed: {}", e),
+            RateError::BadResponse(e) => write!(f, "bad rate response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RateError {}
+
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> &str;
+    /// Price of one bitcoin in `currency`, an upper-case ISO 4217 code.
+    fn fetch(&self, currency: &str) -> Result<f64, RateError>;
+}
+
+/// Fixed rates, for tests, demos and offline devices.
+#[derive(Clone, Debug, Default)]
+pub struct StaticSource {
+    pub rates: HashMap<String, f64>,
+}
+
+impl StaticSource {
+    pub fn new(rates: &[(&str, f64)]) -> Self {
+        Self {
+            rates: rates.iter().map(|(c, r)| (c.to_uppercase(), *r)).collect(),
+        }
+    }
+}
+
+impl PriceSource for StaticSource {
+    fn name(&self) -> &str {
+        "static"
+    }
+
+    fn fetch(&self, currency: &str) -> Result<f64, RateError> {
+        self.rates
+            .get(currency)
+            .copied()
+            .ok_or_else(|| RateError::UnknownCurrency(currency.to_string()))
+    }
+}
+
+/// A plain-HTTP JSON price API. `path` may contain `{currency}` (upper
+/// case) or `{currency_lower}`; `pointer` is a JSON pointer to the price
+/// in the response, with the same placeholders. TLS endpoints need a
+/// local proxy.
+#[derive(Clone, Debug)]
+pub struct HttpSource {
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+    pub pointer: String,
+    pub timeout: Duration,
+}
+
+impl HttpSource {
+    pub fn new(host: &str, port: u16, path: &str, pointer: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+            pointer: pointer.to_string(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    fn get(&self, path: &str) -> Result<String, RateError> {
+        let fetch_err = |e: std::io::Error| RateError::Fetch(e.to_string());
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port)).map_err(fetch_err)?;
+        stream
+            .set_read_timeout(Some(self.timeout))
+            .map_err(fetch_err)?;
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: {}\r\nAccept: application/json\r\nConnection: close\r\n\r\n",
+            path, self.host
+        )
+        .map_err(fetch_err)?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).map_err(fetch_err)?;
+        let (head, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| RateError::BadResponse("no HTTP header".to_string()))?;
+        let status = head.lines().next().unwrap_or_default();
+        if status.split_whitespace().nth(1) != Some("200") {
+            return Err(RateError::Fetch(status.to_string()));
+        }
+        if head
+            .to_ascii_lowercase()
+            .contains("transfer-encoding: chunked")
+        {
+            return Err(RateError::BadResponse(
+                "chunked responses are not supported".to_string(),
+            ));
+        }
+        Ok(body.to_string())
+    }
+}
+
+impl PriceSource for HttpSource {
+    fn name(&self) -> &str {
+        &self.host
+    }
+
+    fn fetch(&self, currency: &str) -> Result<f64, RateError> {
+        let fill = |s: &str| {
+            s.replace("{currency}", currency)
+                .replace("{currency_lower}", &currency.to_lowercase())
+        };
+        let body = self.get(&fill(&self.path))?;
+        let json: Value =
+            serde_json::from_str(&body).map_err(|e| RateError::BadResponse(e.to_string()))?;
+        let price = match json.pointer(&fill(&self.pointer)) {
+            Some(Value::Number(n)) => n.as_f64(),
+            Some(Value::String(s)) => s.parse().ok(),
+            Some(_) => None,
+            None => return Err(RateError::UnknownCurrency(currency.to_string())),
+        };
+        price
+            .filter(|p: &f64| p.is_finite() && *p > 0.0)
+            .ok_or_else(|| RateError::BadResponse(format!("price at {}", self.pointer)))
+    }
+}
+
+/// An amount converted for display.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fiat {
+    pub currency: String,
+    pub amount: f64,
+}
+
+impl fmt::Display for Fiat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2} {}", self.amount, self.currency)
+    }
+}
+
+/// Caches rates from a `PriceSource` for `ttl` seconds.
+pub struct Rates {
+    pub source: Box<dyn PriceSource>,
+    pub ttl: u64,
+    pub clock: Arc<dyn Clock>,
+    /// Price per bitcoin and when it was fetched, by currency.
+    cache: Mutex<HashMap<String, (f64, u64)>>,
+}
+
+impl Rates {
+    pub fn new(source: impl PriceSource + 'static, ttl: u64) -> Self {
+        Self {
+            source: Box::new(source),
+            ttl,
+            clock: Arc::new(SystemClock),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Price of one bitcoin in `currency`. A stale cached rate is used if
+    /// the source fails.
+    pub fn rate(&self, currency: &str) -> Result<f64, RateError> {
+        let currency = currency.to_uppercase();
+        let now = self.clock.now();
+        let cached = self.cache.lock().unwrap().get(&currency).copied();
+        if let Some((rate, at)) = cached
+            && now.saturating_sub(at) < self.ttl
+        {
+            return Ok(rate);
+        }
+        match self.source.fetch(&currency) {
+            Ok(rate) => {
+                self.cache.lock().unwrap().insert(currency, (rate, now));
+                Ok(rate)
+            }
+            Err(e) => cached.map(|(rate, _)| rate).ok_or(e),
+        }
+    }
+
+    pub fn to_fiat(&self, sats: u64, currency: &str) -> Result<Fiat, RateError> {
+        Ok(Fiat {
+            currency: currency.to_uppercase(),
+            amount: sats as f64 * self.rate(currency)? / SATS_PER_BTC,
+        })
+    }
+
+    /// Sats worth `amount` of `currency`, rounded down.
+    pub fn to_sats(&self, amount: f64, currency: &str) -> Result<u64, RateError> {
+        Ok((amount * SATS_PER_BTC / self.rate(currency)?) as u64)
+    }
+
+    /// `"1000 sats (0.65 USD)"`, or just the sats if there is no rate.
+    pub fn display(&self, sats: u64, currency: &str) -> String {
+        match self.to_fiat(sats, currency) {
+            Ok(fiat) => format!("{} sats ({})", sats, fiat),
+            Err(_) => format!("{} sats", sats),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Fee receipts: a statement signed with the mint's identity key of what it
+//! charged for a swap or melt. A receipt names the inputs it covers by a
+//! hash of their `Y`s, the input fee, and for melts the fee reserve held
+//! back and the network fee actually paid out of it. Wallets keep receipts
+//! in their history, so what the mint took can be checked later against
+//! its published identity.
+
+use secp256k1::{XOnlyPublicKey, schnorr::Signature};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{codec::to_hex, signing, types::Note, wallet::Wallet};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReceiptKind {
+    Swap,
+    Melt,
+}
+
+/// What a receipt states. The signature covers all of it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeTerms {
+    pub kind: ReceiptKind,
+    /// SHA-256 over the inputs' `Y`s in request order, hex encoded.
+    pub inputs: String,
+    pub input_total: u64,
+    pub input_fee: u64,
+    /// The melt quote paid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote: Option<String>,
+    /// For melts, the fee reserve held back and the Lightning or mining fee
+    /// paid out of it.
+    #[serde(default)]
+    pub fee_reserve: u64,
+    #[serde(default)]
+    pub network_fee: u64,
+    /// The wallet's operation ID, when it sent one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation: Option<String>,
+    pub issued_at: u64,
+}
+
+impl FeeTerms {
+    /// The bytes the mint signs.
+    pub fn message(&self) -> Vec<u8> {
+        serde_json::to_vec(&("dmto-fee-receipt", self)).unwrap()
+    }
+
+    /// Everything the mint kept: the input fee and the network fee.
+    pub fn total_fee(&self) -> u64 {
+        self.input_fee.saturating_add(self.network_fee)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeReceipt {
+    pub terms: FeeTerms,
+    pub signature: Signature,
+}
+
+/// Hash of the inputs' `Y`s, as a receipt names them.
+pub fn inputs_hash(inputs: &[Note]) -> String {
+    let mut hasher = Sha256::new();
+    for note in inputs {
+        hasher.update(note.y.serialize());
+    }
+    to_hex(&hasher.finalize())
+}
+
+impl FeeReceipt {
+    pub fn verify(&self, identity: &XOnlyPublicKey) -> bool {
+        signing::verify(identity, &self.terms.message(), &self.signature)
+    }
+
+    /// Whether this receipt is for exactly `inputs`, in that order.
+    pub fn covers(&self, inputs: &[Note]) -> bool {
+        self.terms.inputs == inputs_hash(inputs)
+    }
+}
+
+impl Wallet {
+    /// IDs of history entries whose fee receipt is not signed by
+    /// `identity`.
+    pub fn unverified_receipts(&self, identity: &XOnlyPublicKey) -> Vec<String> {
+        self.history
+            .iter()
+            .filter(|tx| tx.receipt.as_ref().is_some_and(|r| !r.verify(identity)))
+            .map(|tx| tx.id.clone())
+            .collect()
+    }
+}
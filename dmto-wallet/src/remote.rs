@@ -0,0 +1,272 @@
+//! Remote control of a running wallet by other applications, in the style
+//! of Nostr Wallet Connect. An app holds only a keypair; the wallet owner
+//! grants that key a set of permissions and an optional spending budget.
+//! Requests are signed by the app, carry a unique ID and a timestamp, and
+//! are refused when stale or replayed.
+//!
+//! Messages are plain serde values, so they can travel over a Nostr relay,
+//! a WebSocket or anything else.
+
+use std::collections::HashMap;
+
+use secp256k1::{Keypair, XOnlyPublicKey, schnorr::Signature};
+use serde::{Deserialize, Serialize};
+
+use crate::{api::MintTrait, handle::WalletHandle, signing, token::Token};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Permission {
+    GetBalance,
+    MakeToken,
+    PayInvoice,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Method {
+    GetBalance,
+    /// Cut a token of `amount` for the app to hand on.
+    MakeToken {
+        amount: u64,
+    },
+    /// Pay a Lightning invoice through the mint; `amount` for an invoice
+    /// without one.
+    PayInvoice {
+        request: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        amount: Option<u64>,
+    },
+}
+
+impl Method {
+    fn permission(&self) -> Permission {
+        match self {
+            Method::GetBalance => Permission::GetBalance,
+            Method::MakeToken { .. } => Permission::MakeToken,
+            Method::PayInvoice { .. } => Permission::PayInvoice,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemoteRequest {
+    pub app: XOnlyPublicKey,
+    /// Unique per app; a repeated ID is refused.
+    pub id: String,
+    pub created_at: u64,
+    pub method: Method,
+    pub signature: Signature,
+}
+
+fn message(id: &str, created_at: u64, method: &Method) -> Vec<u8> {
+    serde_json::to_vec(&(id, created_at, method)).unwrap()
+}
+
+impl RemoteRequest {
+    pub fn new(app: &Keypair, id: &str, created_at: u64, method: Method) -> Self {
+        Self {
+            app: app.x_only_public_key().0,
+            id: id.to_string(),
+            created_at,
+            signature: signing::sign(app, &message(id, created_at, &method)),
+            method,
+        }
+    }
+
+    pub fn verify(&self) -> bool {
+        signing::verify(
+            &self.app,
+            &message(&self.id, self.created_at, &self.method),
+            &self.signature,
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RemoteError {
+    BadSignature,
+    /// The app has no grant.
+    Unauthorized,
+    PermissionDenied(Permission),
+    /// The request would take the app over its budget.
+    BudgetExceeded {
+        remaining: u64,
+    },
+    /// Older than the wallet's `max_age`, or from the future.
+    Expired,
+    Replayed,
+    Wallet(String),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RemoteResult {
+    Balance(u64),
+    Token(Token),
+    Paid { preimage: String },
+    Error(RemoteError),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RemoteResponse {
+    pub id: String,
+    pub result: RemoteResult,
+}
+
+/// Spending allowed per `period` seconds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Budget {
+    pub limit: u64,
+    pub period: u64,
+    pub spent: u64,
+    pub period_start: u64,
+}
+
+impl Budget {
+    pub fn new(limit: u64, period: u64) -> Self {
+        Self {
+            limit,
+            period,
+            spent: 0,
+            period_start: 0,
+        }
+    }
+
+    pub fn remaining(&self, now: u64) -> u64 {
+        if now.saturating_sub(self.period_start) >= self.period {
+            self.limit
+        } else {
+            self.limit.saturating_sub(self.spent)
+        }
+    }
+
+    fn charge(&mut self, amount: u64, now: u64) -> Result<(), RemoteError> {
+        if now.saturating_sub(self.period_start) >= self.period {
+            self.spent = 0;
+            self.period_start = now;
+        }
+        let remaining = self.limit.saturating_sub(self.spent);
+        if amount > remaining {
+            return Err(RemoteError::BudgetExceeded { remaining });
+        }
+        self.spent += amount;
+        Ok(())
+    }
+
+    fn refund(&mut self, amount: u64) {
+        self.spent = self.spent.saturating_sub(amount);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grant {
+    pub permissions: Vec<Permission>,
+    /// `None` lets the app spend the whole balance.
+    pub budget: Option<Budget>,
+}
+
+/// Serves remote requests against a shared wallet.
+pub struct RemoteWallet<M> {
+    pub handle: WalletHandle,
+    pub mint: M,
+    pub mint_url: String,
+    pub grants: HashMap<XOnlyPublicKey, Grant>,
+    /// Seconds a request stays acceptable after `created_at`.
+    pub max_age: u64,
+    /// Request IDs within `max_age`, for replay protection.
+    seen: HashMap<(XOnlyPublicKey, String), u64>,
+}
+
+impl<M: MintTrait> RemoteWallet<M> {
+    pub fn new(handle: WalletHandle, mint: M, mint_url: &str) -> Self {
+        Self {
+            handle,
+            mint,
+            mint_url: mint_url.to_string(),
+            grants: HashMap::new(),
+            max_age: 60,
+            seen: HashMap::new(),
+        }
+    }
+
+    pub fn authorize(&mut self, app: XOnlyPublicKey, grant: Grant) {
+        self.grants.insert(app, grant);
+    }
+
+    pub fn revoke(&mut self, app: &XOnlyPublicKey) {
+        self.grants.remove(app);
+    }
+
+    pub fn handle(&mut self, req: RemoteRequest, now: u64) -> RemoteResponse {
+        let result = match self.dispatch(&req, now) {
+            Ok(result) => result,
+            Err(e) => RemoteResult::Error(e),
+        };
+        RemoteResponse { id: req.id, result }
+    }
+
+    fn dispatch(&mut self, req: &RemoteRequest, now: u64) -> Result<RemoteResult, RemoteError> {
+        if !req.verify() {
+            return Err(RemoteError::BadSignature);
+        }
+        let grant = self
+            .grants
+            .get_mut(&req.app)
+            .ok_or(RemoteError::Unauthorized)?;
+        let permission = req.method.permission();
+        if !grant.permissions.contains(&permission) {
+            return Err(RemoteError::PermissionDenied(permission));
+        }
+        if req.created_at > now || now - req.created_at > self.max_age {
+            return Err(RemoteError::Expired);
+        }
+        let max_age = self.max_age;
+        self.seen.retain(|_, t| now - *t <= max_age);
+        if self
+            .seen
+            .insert((req.app, req.id.clone()), req.created_at)
+            .is_some()
+        {
+            return Err(RemoteError::Replayed);
+        }
+
+        let wallet_err = |e: &dyn std::fmt::Display| RemoteError::Wallet(e.to_string());
+        match &req.method {
+            Method::GetBalance => Ok(RemoteResult::Balance(self.handle.balance())),
+            Method::MakeToken { amount } => {
+                if let Some(budget) = &mut grant.budget {
+                    budget.charge(*amount, now)?;
+                }
+                match self.handle.send(&self.mint, *amount) {
+                    Ok(notes) => Ok(RemoteResult::Token(Token::new(&self.mint_url, notes))),
+                    Err(e) => {
+                        if let Some(budget) = &mut grant.budget {
+                            budget.refund(*amount);
+                        }
+                        Err(wallet_err(&e))
+                    }
+                }
+            }
+            Method::PayInvoice { request, amount } => {
+                // Charged at the quoted amount plus the full fee reserve.
+                let quote = match amount {
+                    Some(amount) => self.mint.melt_quote_with_amount(request, *amount),
+                    None => self.mint.melt_quote(request),
+                }
+                .map_err(|e| wallet_err(&e))?;
+                let cost = quote.amount.saturating_add(quote.fee_reserve);
+                if let Some(budget) = &mut grant.budget {
+                    budget.charge(cost, now)?;
+                }
+                let mint = &self.mint;
+                match self.handle.with(|w| w.melt_quoted(mint, &quote)) {
+                    Ok(preimage) => Ok(RemoteResult::Paid { preimage }),
+                    Err(e) => {
+                        if let Some(budget) = &mut grant.budget {
+                            budget.refund(cost);
+                        }
+                        Err(wallet_err(&e))
+                    }
+                }
+            }
+        }
+    }
+}
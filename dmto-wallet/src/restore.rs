@@ -0,0 +1,187 @@
+//! Recovering notes from the mint's record of what it signed. A wallet that
+//! can re-derive its secrets and blinding factors sends the blinded messages
+//! again; the mint returns signatures for the ones it has signed before.
+//!
+//! A restore may probe thousands of messages, so the mint takes them in
+//! batches of at most `max_batch` and throttles the total per window,
+//! answering `Overloaded` with a retry hint. The wallet pages through
+//! candidates in batches, backs off when told to and stops after `gap`
+//! consecutive empty batches.
+
+use std::{thread, time::Duration};
+
+use secp256k1::{PublicKey, Scalar};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::MintTrait,
+    blind::blind_message_with,
+    dleq::Dleq,
+    error::{MintError, WalletError},
+    hash::hash_to_curve,
+    mint::ProofState,
+    protocol::SwapResponse,
+    wallet::{PendingOutput, Wallet, unblind_outputs},
+};
+
+#[derive(Clone, Debug)]
+pub struct RestoreLimits {
+    /// Most blinded messages in one request.
+    pub max_batch: usize,
+    /// Most blinded messages across all requests per `window`.
+    pub max_per_window: usize,
+    pub window: Duration,
+}
+
+impl Default for RestoreLimits {
+    fn default() -> Self {
+        Self {
+            max_batch: 100,
+            max_per_window: 10_000,
+            window: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RestoreRequest {
+    pub outputs: Vec<PublicKey>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RestoredSignature {
+    /// Position of the blinded message in the request.
+    pub index: usize,
+    pub value: u64,
+    pub signature: PublicKey,
+    pub dleq: Dleq,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RestoreResponse {
+    /// Signatures for the messages the mint has signed, in request order.
+    pub signatures: Vec<RestoredSignature>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RestoreOptions {
+    /// Candidates per request; capped by the mint's `max_restore_batch`.
+    pub batch: usize,
+    /// Consecutive batches without any signature before giving up.
+    pub gap: usize,
+    /// Retries of one batch after `Overloaded`, doubling the wait each time.
+    pub max_retries: u32,
+}
+
+impl Default for RestoreOptions {
+    fn default() -> Self {
+        Self {
+            batch: 100,
+            gap: 3,
+            max_retries: 5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RestoreSummary {
+    pub probed: u64,
+    /// Notes the mint had signed, spent or not.
+    pub found: u64,
+    /// Unspent notes added to the wallet, and their total.
+    pub restored: u64,
+    pub amount: u64,
+}
+
+/// Calls `f`, sleeping and retrying while the mint reports `Overloaded`.
+fn with_backoff<T>(
+    max_retries: u32,
+    mut f: impl FnMut() -> Result<T, MintError>,
+) -> Result<T, MintError> {
+    let mut retries = 0;
+    loop {
+        match f() {
+            Err(MintError::Overloaded { retry_after_ms }) if retries < max_retries => {
+                thread::sleep(Duration::from_millis(retry_after_ms.max(1) << retries));
+                retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+impl Wallet {
+    /// Restores notes whose secrets and blinding factors `derive` produces
+    /// for counters 0, 1, 2, ..., adding those still unspent to the wallet.
+    pub fn restore(
+        &mut self,
+        mint: &impl MintTrait,
+        mut derive: impl FnMut(u64) -> (Vec<u8>, Scalar),
+        opts: &RestoreOptions,
+    ) -> Result<RestoreSummary, WalletError> {
+        let info = mint.info()?;
+        let keyset = mint.active_keyset()?;
+        let batch = opts.batch.clamp(1, info.max_restore_batch.max(1));
+        let session = self.connect(mint)?;
+
+        let mut summary = RestoreSummary::default();
+        let mut empty = 0;
+        while empty < opts.gap {
+            let candidates: Vec<_> = (summary.probed..summary.probed + batch as u64)
+                .map(|counter| {
+                    let (secret, r) = derive(counter);
+                    let y = hash_to_curve(&secret);
+                    (secret, y, blind_message_with(&y, r))
+                })
+                .collect();
+            summary.probed += batch as u64;
+
+            let req = RestoreRequest {
+                outputs: candidates.iter().map(|(_, _, b)| b.blinded_point).collect(),
+            };
+            let resp = with_backoff(opts.max_retries, || {
+                mint.restore_signatures(session.request(req.clone()))?
+                    .accept(&session)
+            })?;
+            if resp.signatures.is_empty() {
+                empty += 1;
+                continue;
+            }
+            empty = 0;
+            summary.found += resp.signatures.len() as u64;
+
+            let mut pending = Vec::new();
+            let mut signed = SwapResponse {
+                signatures: Vec::new(),
+                dleqs: Vec::new(),
+                receipt: None,
+            };
+            for s in resp.signatures {
+                let (secret, y, blinded) = candidates
+                    .get(s.index)
+                    .cloned()
+                    .ok_or(MintError::InvalidSignature)?;
+                pending.push(PendingOutput {
+                    value: s.value,
+                    secret,
+                    y,
+                    blinded,
+                });
+                signed.signatures.push(s.signature);
+                signed.dleqs.push(s.dleq);
+            }
+            let notes = unblind_outputs(&keyset, pending, &signed)?;
+
+            let ys: Vec<_> = notes.iter().map(|n| n.y).collect();
+            let states = mint.check_state(&ys)?;
+            for (note, state) in notes.into_iter().zip(states) {
+                if state == ProofState::Unspent && !self.notes.contains(&note.y) {
+                    summary.restored += 1;
+                    summary.amount += note.value;
+                    self.notes.push(note);
+                }
+            }
+        }
+        Ok(summary)
+    }
+}
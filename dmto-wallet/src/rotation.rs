@@ -0,0 +1,87 @@
+//! Recovery from a compromised keyset. `Mint::emergency_rotate` runs the
+//! whole runbook at once: it revokes the active keyset, starts signing with
+//! a new one, opens a migration window for the revoked keyset's notes and
+//! tells wallets with a critical announcement. Every step is journaled
+//! under one operation ID.
+//!
+//! While the window is open, notes of the revoked keyset are accepted as
+//! swap inputs only, so their value can move to the new keyset but never
+//! leave the mint through a melt. Whoever holds the leaked key can forge
+//! such notes, so each denomination may migrate at most as many notes as
+//! the keyset ever signed for it; swaps past that are refused and journaled
+//! as refusals, an alarm that forged notes are being redeemed. Once the
+//! window ends, or `close_migration` is called, the keyset is refused
+//! outright.
+//!
+//! `Wallet::migrate` swaps a wallet's notes of revoked keysets.
+
+use crate::{
+    announce::Announcement,
+    api::MintTrait,
+    error::{MintError, WalletError},
+    mint::MintInfo,
+    types::Note,
+    wallet::{Wallet, split_amount},
+};
+
+/// What `emergency_rotate` did.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rotation {
+    /// The operation its journal entries are recorded under.
+    pub operation: String,
+    pub revoked: String,
+    pub activated: String,
+    /// Unix time the migration window closes at.
+    pub until: u64,
+    pub announcement: Announcement,
+}
+
+impl Wallet {
+    /// Swaps the wallet's notes of keysets other than the mint's active one
+    /// for notes of the active keyset, as after an `emergency_rotate`.
+    /// Returns the value migrated, after fees.
+    pub fn migrate(&mut self, mint: &impl MintTrait) -> Result<u64, WalletError> {
+        let _op = self.begin_operation();
+        let info = mint.info()?;
+        let active = mint.active_keyset()?;
+        let old: Vec<_> = self
+            .notes
+            .iter()
+            .filter(|n| !active.matches(&n.keyset_id))
+            .map(|n| n.y)
+            .collect();
+        if old.is_empty() {
+            return Ok(0);
+        }
+        let notes: Vec<Note> = old.iter().filter_map(|y| self.notes.remove(y)).collect();
+
+        let result = self.migrate_notes(mint, &info, notes.clone());
+        if result.is_err() {
+            self.notes.extend(notes);
+        }
+        result
+    }
+
+    fn migrate_notes(
+        &mut self,
+        mint: &impl MintTrait,
+        info: &MintInfo,
+        notes: Vec<Note>,
+    ) -> Result<u64, WalletError> {
+        let total: u64 = notes.iter().map(|n| n.value).sum();
+        let fee = info.fee(notes.len());
+        if total < fee {
+            return Err(MintError::AmountMismatch {
+                inputs: total,
+                outputs: 0,
+                fee,
+            }
+            .into());
+        }
+        let values = split_amount(total - fee, &info.denominations)
+            .ok_or(MintError::UnknownDenomination(total - fee))?;
+        let inputs = self.resolve_inputs(mint, notes)?;
+        self.swap_into(mint, inputs, &values)?;
+        Ok(total - fee)
+    }
+}
@@ -0,0 +1,282 @@
+//! JSON-RPC 2.0 over line-delimited streams, for driving a wallet from other
+//! processes. Used by `dmto-walletd`, which serves it on a Unix socket.
+//!
+//! Methods:
+//!
+//! | method         | params                  | result                            |
+//! | -------------- | ----------------------- | --------------------------------- |
+//! | `balance`      | `{"currency"}`          | `{"balance", "reserved", "fiat"}` |
+//! | `send`         | `{"amount", "memo"}`    | token                             |
+//! | `receive`      | `{"token"}`             | `{"amount", "memo"}`              |
+//! | `melt`         | `{"request", "amount"}` | `{"preimage"}`                    |
+//! | `melt_onchain` | `{"address", "amount"}` | `{"txid"}`                        |
+//! | `pay_invoices` | `{"requests"}`          | `[{"preimage"} or {"error"}]`     |
+//! | `cancel_send`  | `{"id"}`                | `{"amount"}`                      |
+//! | `history`      |                         | transactions                      |
+//! | `events`       | `{"since"}`             | `{"next", "events"}`              |
+//!
+//! Each call is one operation (see `operation`), and wallet errors end with
+//! its ID so they can be found in the mint's logs.
+//!
+//! `melt` takes an `amount` only for an invoice without one.
+//!
+//! `events` returns history entries from index `since` on, so a client can
+//! poll with the returned `next` to follow new activity. `balance` only
+//! includes `fiat` when a currency is asked for and the server has `rates`.
+
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Write},
+};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{api::MintTrait, handle::WalletHandle, operation, rates::Rates, token::Token};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The wallet or mint refused the operation.
+pub const WALLET_ERROR: i64 = -32000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl fmt::Display) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    #[serde(default)]
+    id: Value,
+}
+
+fn params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e))
+}
+
+pub struct RpcServer<M> {
+    pub handle: WalletHandle,
+    pub mint: M,
+    pub mint_url: String,
+    pub rates: Option<Rates>,
+}
+
+impl<M: MintTrait> RpcServer<M> {
+    pub fn new(handle: WalletHandle, mint: M, mint_url: &str) -> Self {
+        Self {
+            handle,
+            mint,
+            mint_url: mint_url.to_string(),
+            rates: None,
+        }
+    }
+
+    pub fn call(&self, method: &str, p: Value) -> Result<Value, RpcError> {
+        let _op = operation::begin();
+        let wallet_err = |e: &dyn fmt::Display| {
+            let operation = operation::current().unwrap_or_default();
+            RpcError::new(WALLET_ERROR, format!("{} (operation {})", e, operation))
+        };
+        match method {
+            "balance" => {
+                #[derive(Deserialize)]
+                struct P {
+                    #[serde(default)]
+                    currency: Option<String>,
+                }
+                let P { currency } = params(if p.is_null() { json!({}) } else { p })?;
+                let balance = self.handle.balance();
+                let fiat = match (&self.rates, currency) {
+                    (Some(rates), Some(currency)) => Some(
+                        rates
+                            .to_fiat(balance, &currency)
+                            .map_err(|e| wallet_err(&e))?,
+                    ),
+                    _ => None,
+                };
+                Ok(json!({
+                    "balance": balance,
+                    "reserved": self.handle.reserved(),
+                    "fiat": fiat.map(|f| json!({ "currency": f.currency, "amount": f.amount })),
+                }))
+            }
+            "send" => {
+                #[derive(Deserialize)]
+                struct P {
+                    amount: u64,
+                    #[serde(default)]
+                    memo: Option<String>,
+                }
+                let P { amount, memo } = params(p)?;
+                let notes = self
+                    .handle
+                    .send(&self.mint, amount)
+                    .map_err(|e| wallet_err(&e))?;
+                let mut token = Token::new(&self.mint_url, notes);
+                token.memo = memo;
+                self.handle
+                    .with(|w| w.record_sent(&token))
+                    .map_err(|e| wallet_err(&e))?;
+                Ok(json!(token))
+            }
+            "receive" => {
+                #[derive(Deserialize)]
+                struct P {
+                    token: Token,
+                }
+                let P { token } = params(p)?;
+                let amount = self
+                    .handle
+                    .receive_token(&self.mint, &token)
+                    .map_err(|e| wallet_err(&e))?;
+                Ok(json!({ "amount": amount, "memo": token.memo }))
+            }
+            "melt" => {
+                #[derive(Deserialize)]
+                struct P {
+                    request: String,
+                    amount: Option<u64>,
+                }
+                let P { request, amount } = params(p)?;
+                let mint = &self.mint;
+                let preimage = self
+                    .handle
+                    .with(|w| match amount {
+                        Some(amount) => w.melt_amount(mint, &request, amount),
+                        None => w.melt(mint, &request),
+                    })
+                    .map_err(|e| wallet_err(&e))?;
+                Ok(json!({ "preimage": preimage }))
+            }
+            "melt_onchain" => {
+                #[derive(Deserialize)]
+                struct P {
+                    address: String,
+                    amount: u64,
+                }
+                let P { address, amount } = params(p)?;
+                let mint = &self.mint;
+                let txid = self
+                    .handle
+                    .with(|w| w.melt_onchain(mint, &address, amount))
+                    .map_err(|e| wallet_err(&e))?;
+                Ok(json!({ "txid": txid }))
+            }
+            "pay_invoices" => {
+                #[derive(Deserialize)]
+                struct P {
+                    requests: Vec<String>,
+                }
+                let P { requests } = params(p)?;
+                let mint = &self.mint;
+                let results = self
+                    .handle
+                    .with(|w| w.pay_invoices(mint, &requests))
+                    .map_err(|e| wallet_err(&e))?;
+                Ok(Value::Array(
+                    results
+                        .into_iter()
+                        .map(|r| match r {
+                            Ok(preimage) => json!({ "preimage": preimage }),
+                            Err(e) => json!({ "error": e.to_string() }),
+                        })
+                        .collect(),
+                ))
+            }
+            "cancel_send" => {
+                #[derive(Deserialize)]
+                struct P {
+                    id: String,
+                }
+                let P { id } = params(p)?;
+                let mint = &self.mint;
+                let amount = self
+                    .handle
+                    .with(|w| w.cancel_send(mint, &id))
+                    .map_err(|e| wallet_err(&e))?;
+                Ok(json!({ "amount": amount }))
+            }
+            "history" => Ok(json!(self.handle.history())),
+            "events" => {
+                #[derive(Deserialize)]
+                struct P {
+                    #[serde(default)]
+                    since: usize,
+                }
+                let P { since } = params(if p.is_null() { json!({}) } else { p })?;
+                let history = self.handle.history();
+                let events = history.get(since..).unwrap_or_default();
+                Ok(json!({ "next": history.len(), "events": events }))
+            }
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, "method not found")),
+        }
+    }
+
+    /// Handles one JSON-RPC request and returns the response line.
+    pub fn handle_line(&self, line: &str) -> String {
+        let (id, result) = match serde_json::from_str::<RpcRequest>(line) {
+            Err(e) if serde_json::from_str::<Value>(line).is_err() => {
+                (Value::Null, Err(RpcError::new(PARSE_ERROR, e)))
+            }
+            Err(e) => (Value::Null, Err(RpcError::new(INVALID_REQUEST, e))),
+            Ok(req) if req.jsonrpc != "2.0" => (
+                req.id,
+                Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\"")),
+            ),
+            Ok(req) => (req.id, self.call(&req.method, req.params)),
+        };
+
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": e.code, "message": e.message },
+            }),
+        };
+        response.to_string()
+    }
+
+    /// Serves requests from `stream`, one per line, until it closes.
+    pub fn serve_stream(&self, stream: impl io::Read + Write) -> io::Result<()> {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = self.handle_line(line.trim());
+            let stream = reader.get_mut();
+            stream.write_all(response.as_bytes())?;
+            stream.write_all(b"\n")?;
+        }
+    }
+}
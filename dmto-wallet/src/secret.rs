@@ -0,0 +1,171 @@
+use std::fmt;
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::codec::to_hex;
+
+/// Spending-condition kinds a structured secret can carry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Kind {
+    P2PK,
+    HTLC,
+    /// `data` holds a JSON `Condition` tree.
+    #[serde(rename = "COMPOSITE")]
+    Composite,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretData {
+    pub nonce: String,
+    pub data: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<Vec<String>>,
+}
+
+/// A well-known secret, encoded as the JSON array `[kind, {nonce, data, tags}]`.
+/// Plain random secrets simply fail to parse as one.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WellKnownSecret(pub Kind, pub SecretData);
+
+impl WellKnownSecret {
+    /// Builds a secret with a fresh random nonce.
+    pub fn new(kind: Kind, data: String, tags: Vec<Vec<String>>) -> Self {
+        let mut nonce = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        WellKnownSecret(
+            kind,
+            SecretData {
+                nonce: to_hex(&nonce),
+                data,
+                tags,
+            },
+        )
+    }
+
+    /// Values of the first tag named `name`, without the name itself.
+    pub fn tag(&self, name: &str) -> Option<&[String]> {
+        self.1
+            .tags
+            .iter()
+            .find(|t| t.first().is_some_and(|n| n == name))
+            .map(|t| &t[1..])
+    }
+
+    pub fn from_bytes(secret: &[u8]) -> Option<Self> {
+        serde_json::from_slice(secret).ok()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretFormat {
+    /// Arbitrary bytes.
+    Raw,
+    /// Lowercase hex text, as well-known-secret compatible wallets expect.
+    Hex,
+}
+
+/// Which plain secrets a wallet generates and a mint accepts. Lengths are in
+/// bytes of the encoded secret. Well-known secrets are JSON and only checked
+/// against `max_len`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretPolicy {
+    pub format: SecretFormat,
+    pub min_len: usize,
+    pub max_len: usize,
+    pub allow_well_known: bool,
+}
+
+impl Default for SecretPolicy {
+    fn default() -> Self {
+        Self {
+            format: SecretFormat::Raw,
+            min_len: 32,
+            max_len: 1024,
+            allow_well_known: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SecretError {
+    TooShort { min: usize },
+    TooLong { max: usize },
+    NotHex,
+    WellKnownNotAllowed,
+}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretError::TooShort { min } => write!(f, "secret shorter than {} bytes", min),
+            SecretError::TooLong { max } => write!(f, "secret longer than {} bytes", max),
+            SecretError::NotHex => write!(f, "secret is not lowercase hex"),
+            SecretError::WellKnownNotAllowed => write!(f, "well-known secrets not allowed"),
+        }
+    }
+}
+
+impl std::error::Error for SecretError {}
+
+impl SecretPolicy {
+    /// Hex secrets of exactly `len` characters.
+    pub fn hex(len: usize) -> Self {
+        Self {
+            format: SecretFormat::Hex,
+            min_len: len,
+            max_len: len,
+            allow_well_known: true,
+        }
+    }
+
+    /// A fresh random secret of `min_len` bytes in this policy's format.
+    pub fn generate(&self) -> Vec<u8> {
+        match self.format {
+            SecretFormat::Raw => {
+                let mut secret = vec![0u8; self.min_len];
+                rand::thread_rng().fill_bytes(&mut secret);
+                secret
+            }
+            SecretFormat::Hex => {
+                let mut bytes = vec![0u8; self.min_len.div_ceil(2)];
+                rand::thread_rng().fill_bytes(&mut bytes);
+                let mut hex = to_hex(&bytes).into_bytes();
+                hex.truncate(self.min_len);
+                hex
+            }
+        }
+    }
+
+    pub fn validate(&self, secret: &[u8]) -> Result<(), SecretError> {
+        if secret.len() > self.max_len {
+            return Err(SecretError::TooLong { max: self.max_len });
+        }
+        if WellKnownSecret::from_bytes(secret).is_some() {
+            if !self.allow_well_known {
+                return Err(SecretError::WellKnownNotAllowed);
+            }
+            return Ok(());
+        }
+        if secret.len() < self.min_len {
+            return Err(SecretError::TooShort { min: self.min_len });
+        }
+        if self.format == SecretFormat::Hex
+            && !secret
+                .iter()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(b))
+        {
+            return Err(SecretError::NotHex);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+//! Signed records of when a note was spent. With `Mint::spend_records` on,
+//! the mint remembers the journal entry that spent each note, and a swap
+//! or melt refused because an input was already spent carries a record of
+//! that entry signed with the mint's identity key: its sequence number,
+//! timestamp and the operation that spent it. A payer told that a token
+//! never arrived can show the receiver, or anyone else, that it was
+//! redeemed and when.
+//!
+//! Notes spent before the option was turned on have no record.
+
+use secp256k1::{PublicKey, XOnlyPublicKey, schnorr::Signature};
+use serde::{Deserialize, Serialize};
+
+use crate::{signing, types::Note};
+
+/// The journal entry that spent a note. The signature covers all of it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpendRecord {
+    /// `Y` of the note spent.
+    pub y: PublicKey,
+    pub seq: u64,
+    pub timestamp: u64,
+    /// The operation whose request spent it, if it gave one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation: Option<String>,
+}
+
+impl SpendRecord {
+    /// The bytes the mint signs.
+    pub fn message(&self) -> Vec<u8> {
+        serde_json::to_vec(&("dmto-spend-record", self)).unwrap()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedSpendRecord {
+    pub record: SpendRecord,
+    pub signature: Signature,
+}
+
+impl SignedSpendRecord {
+    pub fn verify(&self, identity: &XOnlyPublicKey) -> bool {
+        signing::verify(identity, &self.record.message(), &self.signature)
+    }
+
+    /// Whether this is the record of `note`.
+    pub fn covers(&self, note: &Note) -> bool {
+        self.record.y == note.y
+    }
+}
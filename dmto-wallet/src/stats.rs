@@ -0,0 +1,37 @@
+//! Aggregate figures for operator dashboards, computed from the journal:
+//! issued and redeemed volume and fees per UTC day, and the outstanding
+//! liability at the end of each day.
+//!
+//! Only entries the journal still holds are counted. A mint restored from
+//! a snapshot starts its series at the snapshot, so the liability figures
+//! are relative to whatever was outstanding then.
+
+use serde::{Deserialize, Serialize};
+
+pub const SECS_PER_DAY: u64 = 86_400;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DayStats {
+    /// Unix time of the day's start, UTC.
+    pub day: u64,
+    /// Value of blinded messages signed.
+    pub issued: u64,
+    /// Value of notes spent, less notes released after failed melts.
+    pub redeemed: u64,
+    pub fees: u64,
+    pub notes_spent: u64,
+    /// Issued minus redeemed since the start of the series, at day end.
+    pub outstanding: i128,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DashboardStats {
+    /// Keysets the mint is currently signing from.
+    pub active_keysets: Vec<String>,
+    /// Days with journal activity, oldest first.
+    pub days: Vec<DayStats>,
+    pub fees_collected: u64,
+    pub outstanding: i128,
+    /// Journal sequence number the figures are current to.
+    pub journal_seq: u64,
+}
@@ -0,0 +1,194 @@
+//! Pay-per-interval streaming: the payer pre-splits funds into one chunk per
+//! tick and releases chunks as time passes; the payee checks each chunk
+//! offline and redeems in bulk. Chunks are serde values, so any transport
+//! (WebSocket, Nostr) can carry them.
+
+use std::{collections::VecDeque, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::MintTrait,
+    error::{MintError, WalletError},
+    types::Note,
+    verifier::{Verifier, VerifyError},
+    wallet::{Wallet, split_amount},
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StreamChunk {
+    pub seq: u64,
+    pub notes: Vec<Note>,
+}
+
+#[derive(Debug)]
+pub enum StreamError {
+    /// A chunk arrived out of order or was replayed.
+    OutOfSequence {
+        expected: u64,
+        got: u64,
+    },
+    WrongAmount {
+        expected: u64,
+        got: u64,
+    },
+    Invalid(VerifyError),
+    Wallet(WalletError),
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::OutOfSequence { expected, got } => {
+                write!(f, "expected chunk {}, got {}", expected, got)
+            }
+            StreamError::WrongAmount { expected, got } => {
+                write!(f, "expected {} per chunk, got {}", expected, got)
+            }
+            StreamError::Invalid(err) => write!(f, "{}", err),
+            StreamError::Wallet(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+pub struct StreamPayer {
+    pub per_tick: u64,
+    /// Seconds between chunks.
+    pub interval: u64,
+    pub sent: u64,
+    chunks: VecDeque<Vec<Note>>,
+    next_due: u64,
+    next_seq: u64,
+    paused: bool,
+}
+
+impl StreamPayer {
+    /// Splits `total` into chunks of `per_tick` out of `wallet`, the first
+    /// due at `start`. Any remainder below `per_tick` stays in the wallet.
+    pub fn prepare(
+        wallet: &mut Wallet,
+        mint: &impl MintTrait,
+        total: u64,
+        per_tick: u64,
+        interval: u64,
+        start: u64,
+    ) -> Result<Self, WalletError> {
+        let chunk_values = split_amount(per_tick, &mint.info()?.denominations)
+            .filter(|_| per_tick > 0)
+            .ok_or(MintError::UnknownDenomination(per_tick))?;
+        let ticks = (total / per_tick) as usize;
+
+        let values: Vec<u64> = chunk_values.repeat(ticks);
+        let mut notes = wallet.split_out(mint, &values)?.into_iter();
+        let chunks = (0..ticks)
+            .map(|_| notes.by_ref().take(chunk_values.len()).collect())
+            .collect();
+
+        Ok(Self {
+            per_tick,
+            interval,
+            sent: 0,
+            chunks,
+            next_due: start,
+            next_seq: 0,
+            paused: false,
+        })
+    }
+
+    /// Releases every chunk due by `now`.
+    pub fn poll(&mut self, now: u64) -> Vec<StreamChunk> {
+        let mut out = Vec::new();
+        while !self.paused && self.next_due <= now {
+            let Some(notes) = self.chunks.pop_front() else {
+                break;
+            };
+            out.push(StreamChunk {
+                seq: self.next_seq,
+                notes,
+            });
+            self.next_seq += 1;
+            self.next_due += self.interval;
+            self.sent += self.per_tick;
+        }
+        out
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resumes with the next chunk due at `now`; time spent paused is not
+    /// billed.
+    pub fn resume(&mut self, now: u64) {
+        self.paused = false;
+        self.next_due = self.next_due.max(now);
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.chunks.len() as u64 * self.per_tick
+    }
+
+    /// Ends the stream, handing back unreleased notes to return to the wallet.
+    pub fn stop(self) -> Vec<Note> {
+        self.chunks.into_iter().flatten().collect()
+    }
+}
+
+pub struct StreamReceiver {
+    pub per_tick: u64,
+    pub received: u64,
+    next_seq: u64,
+    pending: Vec<Note>,
+}
+
+impl StreamReceiver {
+    pub fn new(per_tick: u64) -> Self {
+        Self {
+            per_tick,
+            received: 0,
+            next_seq: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Checks a chunk's sequence, amount and signatures without contacting
+    /// the mint, and queues it for redemption.
+    pub fn accept(&mut self, chunk: StreamChunk, verifier: &Verifier) -> Result<u64, StreamError> {
+        if chunk.seq != self.next_seq {
+            return Err(StreamError::OutOfSequence {
+                expected: self.next_seq,
+                got: chunk.seq,
+            });
+        }
+        let amount: u64 = chunk.notes.iter().map(|n| n.value).sum();
+        if amount != self.per_tick {
+            return Err(StreamError::WrongAmount {
+                expected: self.per_tick,
+                got: amount,
+            });
+        }
+        for n in &chunk.notes {
+            verifier.verify_note(n).map_err(StreamError::Invalid)?;
+        }
+
+        self.next_seq += 1;
+        self.received += amount;
+        self.pending.extend(chunk.notes);
+        Ok(self.received)
+    }
+
+    /// Swaps everything accepted so far into `wallet`.
+    pub fn redeem(&mut self, wallet: &mut Wallet, mint: &impl MintTrait) -> bool {
+        let notes = std::mem::take(&mut self.pending);
+        if notes.is_empty() {
+            return true;
+        }
+        if !wallet.receive(mint, notes.clone()) {
+            self.pending = notes;
+            return false;
+        }
+        true
+    }
+}
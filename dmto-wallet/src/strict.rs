@@ -0,0 +1,155 @@
+//! Strict decoding of untrusted JSON request bodies. Before anything is
+//! allocated the body is checked against a size limit and scanned for its
+//! nesting depth, so neither a huge body nor `[[[[...` can exhaust memory or
+//! stack. Fields the target type does not know are refused or ignored by
+//! policy.
+//!
+//! Unknown fields are found by decoding, encoding the result again and
+//! looking for input keys that did not survive. A key missing from the
+//! encoding is tolerated if its value is `null`, `[]` or `{}`, since fields
+//! skipped when empty are written that way by clients.
+
+use std::fmt;
+
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnknownFields {
+    Ignore,
+    Reject,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonLimits {
+    pub max_bytes: usize,
+    /// Deepest nesting of arrays and objects; the top level counts as 1.
+    pub max_depth: usize,
+    pub unknown_fields: UnknownFields,
+}
+
+impl Default for JsonLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 1 << 20,
+            max_depth: 32,
+            unknown_fields: UnknownFields::Reject,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JsonError {
+    TooLarge {
+        max: usize,
+    },
+    TooDeep {
+        max: usize,
+    },
+    /// Path of the first unknown field, like `body.inputs[0].extra`.
+    UnknownField(String),
+    Malformed(String),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::TooLarge { max } => write!(f, "body larger than {} bytes", max),
+            JsonError::TooDeep { max } => write!(f, "nested deeper than {}", max),
+            JsonError::UnknownField(path) => write!(f, "unknown field `{}`", path),
+            JsonError::Malformed(e) => write!(f, "malformed JSON: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// Deepest nesting of `body`, ignoring brackets inside strings. Stops early
+/// once `max` is exceeded.
+fn depth(body: &[u8], max: usize) -> Result<(), JsonError> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for &b in body {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match b {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max {
+                    return Err(JsonError::TooDeep { max });
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn is_empty(v: &Value) -> bool {
+    match v {
+        Value::Null => true,
+        Value::Array(a) => a.is_empty(),
+        Value::Object(o) => o.is_empty(),
+        _ => false,
+    }
+}
+
+/// First key in `input` with no counterpart in `known`.
+fn unknown_field(input: &Value, known: &Value, path: &str) -> Option<String> {
+    match (input, known) {
+        (Value::Object(input), Value::Object(known)) => input.iter().find_map(|(k, v)| {
+            let path = if path.is_empty() {
+                k.clone()
+            } else {
+                format!("{}.{}", path, k)
+            };
+            match known.get(k) {
+                Some(known) => unknown_field(v, known, &path),
+                None if is_empty(v) => None,
+                None => Some(path),
+            }
+        }),
+        (Value::Array(input), Value::Array(known)) if input.len() == known.len() => input
+            .iter()
+            .zip(known)
+            .enumerate()
+            .find_map(|(i, (v, known))| unknown_field(v, known, &format!("{}[{}]", path, i))),
+        _ => None,
+    }
+}
+
+/// Decodes `body` as `T` within `limits`.
+pub fn decode<T: Serialize + DeserializeOwned>(
+    body: &[u8],
+    limits: &JsonLimits,
+) -> Result<T, JsonError> {
+    if body.len() > limits.max_bytes {
+        return Err(JsonError::TooLarge {
+            max: limits.max_bytes,
+        });
+    }
+    depth(body, limits.max_depth)?;
+
+    let malformed = |e: serde_json::Error| JsonError::Malformed(e.to_string());
+    if limits.unknown_fields == UnknownFields::Ignore {
+        return serde_json::from_slice(body).map_err(malformed);
+    }
+
+    let input: Value = serde_json::from_slice(body).map_err(malformed)?;
+    let value: T = serde_json::from_value(input.clone()).map_err(malformed)?;
+    let known = serde_json::to_value(&value).map_err(malformed)?;
+    match unknown_field(&input, &known, "") {
+        Some(path) => Err(JsonError::UnknownField(path)),
+        None => Ok(value),
+    }
+}
@@ -0,0 +1,302 @@
+//! Recurring payments. A `Subscription` pays a fixed amount every interval
+//! to a Lightning address or a contact; the wallet keeps them and a
+//! `SubscriptionScheduler` makes the payments as they fall due, in the
+//! background or from the app's own loop with `run_due`.
+//!
+//! Resolving addresses and delivering tokens needs the network, so the app
+//! supplies them through `Payout`. Contacts are paid with tokens locked to
+//! their key (see `Wallet::send_to`). A token that could not be delivered
+//! is kept and delivery retried, so a flaky transport never pays twice.
+//!
+//! A failed payment is retried after `retry_delay`, doubling each time, and
+//! skipped until the next period after `max_attempts`; a token still not
+//! delivered by then stays among the wallet's pending sends. Periods missed
+//! while the scheduler was not running are not paid in arrears.
+//!
+//! Payments are made under the `WalletHandle` lock, so other users of the
+//! handle wait for them.
+
+use std::{
+    sync::{
+        Arc,
+        mpsc::{self, RecvTimeoutError, Sender},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::MintTrait,
+    clock::{Clock, SystemClock},
+    contacts::{Contact, Delivery},
+    error::WalletError,
+    handle::WalletHandle,
+    melt::quote_id,
+    token::Token,
+    wallet::Wallet,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Payee {
+    /// A `user@domain` address, paid over Lightning through a melt.
+    LightningAddress(String),
+    /// A contact by name, paid with a token locked to their key.
+    Contact(String),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: String,
+    pub amount: u64,
+    /// Seconds between payments.
+    pub interval: u64,
+    pub payee: Payee,
+    /// Put on the history entry of each payment.
+    pub memo: Option<String>,
+    /// When the next payment is due.
+    pub next_due: u64,
+    /// When a failed payment is tried again, if one is.
+    pub retry_at: Option<u64>,
+    /// Failed attempts at the payment currently due.
+    pub failures: u32,
+    pub last_error: Option<String>,
+    /// A paid token the payee has not received yet.
+    pub undelivered: Option<Token>,
+    pub paused: bool,
+}
+
+impl Subscription {
+    /// Pays `amount` every `interval` seconds, the first time at `start`.
+    pub fn new(payee: Payee, amount: u64, interval: u64, start: u64) -> Self {
+        Self {
+            id: quote_id(),
+            amount,
+            interval,
+            payee,
+            memo: None,
+            next_due: start,
+            retry_at: None,
+            failures: 0,
+            last_error: None,
+            undelivered: None,
+            paused: false,
+        }
+    }
+
+    pub fn memo(mut self, memo: &str) -> Self {
+        self.memo = Some(memo.to_string());
+        self
+    }
+
+    pub fn is_due(&self, now: u64) -> bool {
+        !self.paused && self.retry_at.unwrap_or(self.next_due) <= now
+    }
+
+    /// Moves to the first period after `now`.
+    fn advance(&mut self, now: u64) {
+        let missed = (now.saturating_sub(self.next_due) / self.interval.max(1)) + 1;
+        self.next_due = self
+            .next_due
+            .saturating_add(missed.saturating_mul(self.interval.max(1)));
+        self.retry_at = None;
+        self.failures = 0;
+    }
+}
+
+/// What the scheduler needs from the outside world to pay.
+pub trait Payout: Send + Sync {
+    /// Resolves a Lightning address to an invoice for `amount`.
+    fn invoice(&self, address: &str, amount: u64) -> Result<String, String>;
+    /// Hands `token` to `contact`, e.g. as a Nostr direct message.
+    fn deliver(&self, contact: &Contact, token: &Token) -> Result<(), String>;
+}
+
+impl Wallet {
+    /// Adds a subscription, returning its ID.
+    pub fn subscribe(&mut self, subscription: Subscription) -> String {
+        let id = subscription.id.clone();
+        self.subscriptions.push(subscription);
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: &str) -> Option<Subscription> {
+        let index = self.subscriptions.iter().position(|s| s.id == id)?;
+        Some(self.subscriptions.remove(index))
+    }
+}
+
+pub struct SubscriptionScheduler<M> {
+    pub wallet: WalletHandle,
+    pub mint: M,
+    pub mint_url: String,
+    pub payout: Arc<dyn Payout>,
+    pub clock: Arc<dyn Clock>,
+    /// Seconds before the first retry of a failed payment.
+    pub retry_delay: u64,
+    /// Failed attempts after which a payment is skipped.
+    pub max_attempts: u32,
+}
+
+/// A scheduler running on its own thread.
+pub struct SchedulerThread {
+    stop: Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl SchedulerThread {
+    /// Stops the scheduler once any payment in progress is done.
+    pub fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.thread.join();
+    }
+}
+
+impl<M: MintTrait> SubscriptionScheduler<M> {
+    pub fn new(wallet: WalletHandle, mint: M, mint_url: &str, payout: Arc<dyn Payout>) -> Self {
+        Self {
+            wallet,
+            mint,
+            mint_url: mint_url.to_string(),
+            payout,
+            clock: Arc::new(SystemClock),
+            retry_delay: 60,
+            max_attempts: 5,
+        }
+    }
+
+    /// Makes every payment due now, returning the outcome of each by
+    /// subscription ID.
+    pub fn run_due(&self) -> Vec<(String, Result<(), WalletError>)> {
+        let now = self.clock.now();
+        let due: Vec<String> = self.wallet.with(|w| {
+            w.subscriptions
+                .iter()
+                .filter(|s| s.is_due(now))
+                .map(|s| s.id.clone())
+                .collect()
+        });
+        due.into_iter()
+            .map(|id| {
+                let result = self.wallet.with(|w| self.run(w, &id, now));
+                (id, result)
+            })
+            .collect()
+    }
+
+    /// Calls `run_due` every `poll` until stopped.
+    pub fn spawn(self, poll: Duration) -> SchedulerThread
+    where
+        M: Send + 'static,
+    {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            loop {
+                self.run_due();
+                match stopped.recv_timeout(poll) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => break,
+                }
+            }
+        });
+        SchedulerThread { stop, thread }
+    }
+
+    fn run(&self, w: &mut Wallet, id: &str, now: u64) -> Result<(), WalletError> {
+        let Some(mut sub) = w.subscriptions.iter().find(|s| s.id == id).cloned() else {
+            return Ok(());
+        };
+        let result = self.pay(w, &mut sub);
+        match &result {
+            Ok(()) => {
+                sub.last_error = None;
+                sub.advance(now);
+            }
+            Err(e) => {
+                sub.failures += 1;
+                sub.last_error = Some(e.to_string());
+                if sub.failures >= self.max_attempts {
+                    sub.undelivered = None;
+                    sub.advance(now);
+                } else {
+                    let delay = self
+                        .retry_delay
+                        .saturating_mul(1 << (sub.failures - 1).min(16));
+                    sub.retry_at = Some(now.saturating_add(delay));
+                }
+            }
+        }
+        if let Some(stored) = w.subscriptions.iter_mut().find(|s| s.id == id) {
+            *stored = sub;
+        }
+        result
+    }
+
+    fn pay(&self, w: &mut Wallet, sub: &mut Subscription) -> Result<(), WalletError> {
+        let memo = sub
+            .memo
+            .clone()
+            .unwrap_or_else(|| format!("subscription {}", sub.id));
+        let contact = match &sub.payee {
+            Payee::LightningAddress(address) => {
+                return self.melt_to(w, address, sub.amount, memo);
+            }
+            Payee::Contact(name) => w
+                .contacts
+                .get(name)
+                .cloned()
+                .ok_or_else(|| WalletError::UnknownContact(name.clone()))?,
+        };
+
+        if sub.undelivered.is_none() {
+            let token = match w.send_to(&self.mint, &self.mint_url, &contact.name, sub.amount)? {
+                Delivery::LightningAddress { address, amount } => {
+                    return self.melt_to(w, &address, amount, memo);
+                }
+                Delivery::Nostr { token, .. } | Delivery::Token(token) => token,
+            };
+            if let Some(tx) = w.history.iter_mut().rev().find(|t| t.id == token.id()) {
+                tx.memo = Some(memo);
+            }
+            sub.undelivered = Some(token);
+        }
+        if let Some(token) = &sub.undelivered {
+            self.payout
+                .deliver(&contact, token)
+                .map_err(WalletError::Delivery)?;
+        }
+        sub.undelivered = None;
+        Ok(())
+    }
+
+    fn melt_to(
+        &self,
+        w: &mut Wallet,
+        address: &str,
+        amount: u64,
+        memo: String,
+    ) -> Result<(), WalletError> {
+        let invoice = self
+            .payout
+            .invoice(address, amount)
+            .map_err(WalletError::Delivery)?;
+        let quote = self.mint.melt_quote(&invoice)?;
+        if quote.amount != amount {
+            return Err(WalletError::RequestMismatch(format!(
+                "{} resolved to an invoice for {}, not {}",
+                address, quote.amount, amount
+            )));
+        }
+        let preimage = w.melt_quoted(&self.mint, &quote)?;
+        if let Some(tx) = w
+            .history
+            .iter_mut()
+            .rev()
+            .find(|t| t.preimage.as_ref() == Some(&preimage))
+        {
+            tx.memo = Some(memo);
+        }
+        Ok(())
+    }
+}
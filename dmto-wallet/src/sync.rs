@@ -0,0 +1,296 @@
+//! Keeping one wallet in step across devices that share its seed. Each
+//! device sends the others deltas (notes added, notes gone, tokens redeemed,
+//! the derivation counter) encrypted under a key derived from the seed, so
+//! any transport will do: a relay, a file, a QR code.
+//!
+//! Devices never spend each other's notes. A device owns the notes it
+//! minted or received and only reports them to its peers, who track them
+//! as `peer_notes` for display. Moving spending power to another device is
+//! an explicit `hand_over`, which takes the notes out of the sender's
+//! wallet before the delta carrying them is sealed.
+//!
+//! Deltas are sealed with XChaCha20-Poly1305 under a key derived from the
+//! seed with HKDF-SHA256, with the sender and sequence number as associated
+//! data, and are applied strictly in sequence per device.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use chacha20poly1305::{
+    KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, Payload},
+};
+use hkdf::Hkdf;
+use rand::RngCore;
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{types::Note, wallet::Wallet};
+
+const NONCE_LEN: usize = 24;
+
+/// Associated data binding a delta's ciphertext to its sender and place in
+/// the sender's sequence.
+fn aad(device: &str, seq: u64) -> Vec<u8> {
+    let mut aad = (device.len() as u64).to_be_bytes().to_vec();
+    aad.extend_from_slice(device.as_bytes());
+    aad.extend_from_slice(&seq.to_be_bytes());
+    aad
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyncError {
+    /// The delta did not authenticate: wrong seed, or it was altered.
+    BadTag,
+    Malformed(String),
+    /// A delta from `device` is missing; resend from `expected`.
+    Gap {
+        device: String,
+        expected: u64,
+    },
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncError::BadTag => write!(f, "delta failed authentication"),
+            SyncError::Malformed(e) => write!(f, "malformed delta: {}", e),
+            SyncError::Gap { device, expected } => {
+                write!(f, "missing delta {} from {}", expected, device)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SyncError {}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum SyncOp {
+    /// Notes the sending device now holds.
+    Added(Vec<Note>),
+    /// `Y`s of the sender's notes that were spent or handed over.
+    Removed(Vec<PublicKey>),
+    /// IDs of tokens the sender redeemed.
+    Received(Vec<String>),
+    /// Notes the device `to` may spend from now on.
+    HandOver { to: String, notes: Vec<Note> },
+    /// The sender's derivation counter; everyone moves to the highest.
+    Counter(u64),
+}
+
+/// An encrypted, authenticated batch of operations.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedDelta {
+    pub device: String,
+    pub seq: u64,
+    pub nonce: [u8; NONCE_LEN],
+    /// The encrypted operations followed by the Poly1305 tag.
+    pub ciphertext: Vec<u8>,
+}
+
+/// One device's view of the sync: what it has told its peers and what it
+/// has applied from them.
+pub struct WalletSync {
+    pub device: String,
+    cipher: XChaCha20Poly1305,
+    /// Sequence number of the last delta this device sealed.
+    pub seq: u64,
+    /// Last sequence number applied per peer device.
+    pub applied: HashMap<String, u64>,
+    /// Next derivation counter this device may use, raised to the highest
+    /// any peer has reported. Devices should sync before deriving, or two
+    /// of them can take the same counter.
+    pub counter: u64,
+    counter_sent: u64,
+    /// `Y`s of this device's notes the peers know about.
+    shared: HashSet<PublicKey>,
+    received_shared: HashSet<String>,
+    handing_over: HashMap<String, Vec<Note>>,
+    /// Notes held by each peer device, by `Y`.
+    pub peer_notes: HashMap<String, HashMap<PublicKey, Note>>,
+    /// Every delta sealed so far, for resending after a gap.
+    pub sent: Vec<SealedDelta>,
+}
+
+impl WalletSync {
+    pub fn new(seed: &[u8], device: &str) -> Self {
+        let mut key = [0u8; 32];
+        Hkdf::<Sha256>::new(None, seed)
+            .expand(b"dmto-sync-key", &mut key)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        Self {
+            device: device.to_string(),
+            cipher: XChaCha20Poly1305::new(&key.into()),
+            seq: 0,
+            applied: HashMap::new(),
+            counter: 0,
+            counter_sent: 0,
+            shared: HashSet::new(),
+            received_shared: HashSet::new(),
+            handing_over: HashMap::new(),
+            peer_notes: HashMap::new(),
+            sent: Vec::new(),
+        }
+    }
+
+    pub fn next_counter(&mut self) -> u64 {
+        self.counter += 1;
+        self.counter - 1
+    }
+
+    pub fn peer_balance(&self) -> u64 {
+        self.peer_notes
+            .values()
+            .flat_map(|notes| notes.values())
+            .map(|n| n.value)
+            .sum()
+    }
+
+    /// Sealed deltas after `seq`, for a peer that reported a gap.
+    pub fn resend(&self, seq: u64) -> Vec<SealedDelta> {
+        self.sent.iter().filter(|d| d.seq > seq).cloned().collect()
+    }
+
+    fn seal(&mut self, ops: &[SyncOp]) -> SealedDelta {
+        self.seq += 1;
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let plaintext = serde_json::to_vec(ops).unwrap();
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: &aad(&self.device, self.seq),
+                },
+            )
+            .expect("plaintext within the cipher's limit");
+        let delta = SealedDelta {
+            device: self.device.clone(),
+            seq: self.seq,
+            nonce,
+            ciphertext,
+        };
+        self.sent.push(delta.clone());
+        delta
+    }
+
+    fn open(&self, delta: &SealedDelta) -> Result<Vec<SyncOp>, SyncError> {
+        let plaintext = self
+            .cipher
+            .decrypt(
+                XNonce::from_slice(&delta.nonce),
+                Payload {
+                    msg: &delta.ciphertext,
+                    aad: &aad(&delta.device, delta.seq),
+                },
+            )
+            .map_err(|_| SyncError::BadTag)?;
+        serde_json::from_slice(&plaintext).map_err(|e| SyncError::Malformed(e.to_string()))
+    }
+}
+
+impl Wallet {
+    /// Takes the notes with these `Y`s out of the wallet; the next
+    /// `sync_outgoing` hands them to device `to`. Returns their total.
+    pub fn hand_over(&mut self, sync: &mut WalletSync, to: &str, ys: &[PublicKey]) -> u64 {
+        let out: Vec<Note> = ys.iter().filter_map(|y| self.notes.remove(y)).collect();
+        let total = out.iter().map(|n| n.value).sum();
+        sync.handing_over
+            .entry(to.to_string())
+            .or_default()
+            .extend(out);
+        total
+    }
+
+    /// Seals what changed since the last delta, or `None` if nothing did.
+    pub fn sync_outgoing(&mut self, sync: &mut WalletSync) -> Option<SealedDelta> {
+        let current: HashSet<PublicKey> = self.notes.iter().map(|n| n.y).collect();
+        let added: Vec<Note> = self
+            .notes
+            .iter()
+            .filter(|n| !sync.shared.contains(&n.y))
+            .cloned()
+            .collect();
+        let removed: Vec<PublicKey> = sync.shared.difference(&current).copied().collect();
+        let received: Vec<String> = self
+            .received
+            .difference(&sync.received_shared)
+            .cloned()
+            .collect();
+
+        let mut ops = Vec::new();
+        if !added.is_empty() {
+            ops.push(SyncOp::Added(added));
+        }
+        if !removed.is_empty() {
+            ops.push(SyncOp::Removed(removed));
+        }
+        if !received.is_empty() {
+            sync.received_shared.extend(received.iter().cloned());
+            ops.push(SyncOp::Received(received));
+        }
+        for (to, notes) in std::mem::take(&mut sync.handing_over) {
+            ops.push(SyncOp::HandOver { to, notes });
+        }
+        if sync.counter > sync.counter_sent {
+            sync.counter_sent = sync.counter;
+            ops.push(SyncOp::Counter(sync.counter));
+        }
+        if ops.is_empty() {
+            return None;
+        }
+        sync.shared = current;
+        Some(sync.seal(&ops))
+    }
+
+    /// Verifies and applies a peer's delta. Deltas already applied are
+    /// ignored; out-of-order ones are refused with `Gap`.
+    pub fn sync_incoming(
+        &mut self,
+        sync: &mut WalletSync,
+        delta: &SealedDelta,
+    ) -> Result<(), SyncError> {
+        let ops = sync.open(delta)?;
+        let last = sync.applied.get(&delta.device).copied().unwrap_or(0);
+        if delta.device == sync.device || delta.seq <= last {
+            return Ok(());
+        }
+        if delta.seq != last + 1 {
+            return Err(SyncError::Gap {
+                device: delta.device.clone(),
+                expected: last + 1,
+            });
+        }
+
+        let peer = sync.peer_notes.entry(delta.device.clone()).or_default();
+        for op in ops {
+            match op {
+                SyncOp::Added(notes) => peer.extend(notes.into_iter().map(|n| (n.y, n))),
+                SyncOp::Removed(ys) => {
+                    for y in ys {
+                        peer.remove(&y);
+                    }
+                }
+                SyncOp::Received(ids) => {
+                    // Marked as shared too, so they are not echoed back.
+                    sync.received_shared.extend(ids.iter().cloned());
+                    self.received.extend(ids);
+                }
+                SyncOp::HandOver { to, notes } if to == sync.device => {
+                    for note in notes {
+                        self.notes.push(note);
+                    }
+                }
+                SyncOp::HandOver { .. } => {}
+                SyncOp::Counter(counter) => sync.counter = sync.counter.max(counter),
+            }
+        }
+        sync.applied.insert(delta.device.clone(), delta.seq);
+        Ok(())
+    }
+}
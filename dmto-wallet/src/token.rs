@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    codec::to_hex,
+    error::MintError,
+    secret::{Kind, WellKnownSecret},
+    types::{Amount, Note},
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Token {
+    pub mint_url: String,
+    pub unit: String,
+    pub notes: Vec<Note>,
+    /// Free text from the sender, like the description on an invoice.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// What a UI needs to preview an incoming token before redeeming it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenInfo {
+    pub mint_url: String,
+    pub unit: String,
+    pub amount: u64,
+    pub proof_count: usize,
+    pub keyset_ids: Vec<String>,
+    pub locking_conditions: Vec<Kind>,
+    /// Every note carries a DLEQ proof, so the token can be checked offline.
+    pub has_dleq: bool,
+    pub memo: Option<String>,
+}
+
+impl Token {
+    pub fn new(mint_url: &str, notes: Vec<Note>) -> Self {
+        Self {
+            mint_url: mint_url.to_string(),
+            unit: "sat".to_string(),
+            notes,
+            memo: None,
+        }
+    }
+
+    pub fn with_memo(mut self, memo: &str) -> Self {
+        self.memo = Some(memo.to_string());
+        self
+    }
+
+    /// Stable identifier derived from the notes' `Y` points, so every device
+    /// scanning the same token computes the same ID.
+    pub fn id(&self) -> String {
+        let mut ys: Vec<[u8; 33]> = self.notes.iter().map(|n| n.y.serialize()).collect();
+        ys.sort_unstable();
+
+        let mut hasher = Sha256::new();
+        for y in &ys {
+            hasher.update(y);
+        }
+        to_hex(&hasher.finalize())
+    }
+
+    /// The sum of the notes' values. Fails with `AmountOverflow` on tokens
+    /// whose notes add up to more than 64 bits can hold.
+    pub fn amount(&self) -> Result<u64, MintError> {
+        Amount::checked_sum(self.notes.iter().map(|n| n.value))
+            .map(|a| a.0)
+            .ok_or(MintError::AmountOverflow)
+    }
+
+    pub fn inspect(&self) -> Result<TokenInfo, MintError> {
+        let mut keyset_ids = Vec::new();
+        let mut locking_conditions = Vec::new();
+
+        for n in &self.notes {
+            if !keyset_ids.contains(&n.keyset_id) {
+                keyset_ids.push(n.keyset_id.clone());
+            }
+            if let Some(secret) = WellKnownSecret::from_bytes(&n.secret)
+                && !locking_conditions.contains(&secret.kind())
+            {
+                locking_conditions.push(secret.kind());
+            }
+        }
+
+        Ok(TokenInfo {
+            mint_url: self.mint_url.clone(),
+            unit: self.unit.clone(),
+            amount: self.amount()?,
+            proof_count: self.notes.len(),
+            keyset_ids,
+            locking_conditions,
+            has_dleq: self.notes.iter().all(|n| n.dleq.is_some()),
+            memo: self.memo.clone(),
+        })
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+
+use crate::{conditions::Witness, dleq::NoteDleq};
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Note {
+    pub value: u64,
+    pub keyset_id: String,
+    pub secret: Vec<u8>,
+    /// `hash_to_curve(secret)`, computed once when the note is created so
+    /// wallets never recompute it. Untrusted notes are checked against the
+    /// secret by the mint and `Verifier`.
+    pub y: PublicKey,
+    pub c: PublicKey,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dleq: Option<NoteDleq>,
+    /// Signatures and preimages satisfying the secret's spending conditions.
+    /// Attached only when spending; not part of the binary codec.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub witness: Option<Witness>,
+}
+
+/// Notes are hashed by `Y`, which determines the secret and so the note.
+impl Hash for Note {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.y.hash(state);
+    }
+}
+
+/// Leaves the secret out, so notes can be logged without handing them over.
+impl fmt::Debug for Note {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Note")
+            .field("value", &self.value)
+            .field("keyset_id", &self.keyset_id)
+            .field("secret", &"<redacted>")
+            .field("y", &self.y)
+            .field("c", &self.c)
+            .field("dleq", &self.dleq)
+            .field("witness", &self.witness)
+            .finish()
+    }
+}
+
+/// A quantity of the smallest unit. All arithmetic is checked so that
+/// overflow is an error rather than a wrap that creates value.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Amount(pub u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+
+    pub fn checked_mul(self, factor: u64) -> Option<Amount> {
+        self.0.checked_mul(factor).map(Amount)
+    }
+
+    /// Sums `values`, or `None` if the total does not fit.
+    pub fn checked_sum<I: IntoIterator<Item = u64>>(values: I) -> Option<Amount> {
+        values
+            .into_iter()
+            .try_fold(Amount::ZERO, |acc, v| acc.checked_add(Amount(v)))
+    }
+}
+
+impl From<u64> for Amount {
+    fn from(value: u64) -> Self {
+        Amount(value)
+    }
+}
+
+impl From<Amount> for u64 {
+    fn from(amount: Amount) -> Self {
+        amount.0
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -0,0 +1,316 @@
+//! `cashu:` URIs, so mobile apps can register one handler for every ecash
+//! payload and pass what they receive straight to the wallet:
+//!
+//! - `cashu:cashuA<token>` — a token to redeem, or `cashu:cashuC<token>`
+//!   for one in the compact binary form (see `compact`);
+//! - `cashu:creqA<request>` — a payment request to pay;
+//! - `cashu://mint?url=<mint URL>&action=<info|mint|melt>&amount=<n>` — a
+//!   mint to open, with what the user came to do there.
+//!
+//! Tokens and requests are this crate's JSON in URL-safe base64 without
+//! padding; they are not the Cashu NUT-00 and NUT-18 layouts. Parsing also
+//! accepts `cashu://` and `web+cashu:` for the payloads, any case for the
+//! scheme, padded or standard base64, and payloads pasted without a scheme.
+
+use std::{fmt, str::FromStr};
+
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::MintTrait,
+    codec::{from_base64url, to_base64url},
+    conditions::Condition,
+    error::WalletError,
+    token::Token,
+    wallet::Wallet,
+};
+
+const TOKEN_PREFIX: &str = "cashuA";
+const COMPACT_TOKEN_PREFIX: &str = "cashuC";
+const REQUEST_PREFIX: &str = "creqA";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UriError {
+    /// Not a `cashu:` URI or payload.
+    UnknownScheme,
+    UnknownAction(String),
+    MissingParam(&'static str),
+    Malformed(String),
+}
+
+impl fmt::Display for UriError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UriError::UnknownScheme => write!(f, "not a cashu URI"),
+            UriError::UnknownAction(action) => write!(f, "unknown action {}", action),
+            UriError::MissingParam(name) => write!(f, "missing parameter {}", name),
+            UriError::Malformed(e) => write!(f, "malformed cashu URI: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for UriError {}
+
+/// Someone asking to be paid: how much, at which mints, and whether the
+/// notes should be locked to their key.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// `None` lets the payer choose.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<u64>,
+    pub unit: String,
+    /// Mints the payee accepts; empty accepts any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mints: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pubkey: Option<PublicKey>,
+}
+
+impl PaymentRequest {
+    pub fn new(amount: u64) -> Self {
+        Self {
+            amount: Some(amount),
+            unit: "sat".to_string(),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MintAction {
+    #[default]
+    Info,
+    Mint,
+    Melt,
+}
+
+impl MintAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MintAction::Info => "info",
+            MintAction::Mint => "mint",
+            MintAction::Melt => "melt",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum CashuUri {
+    Token(Token),
+    Request(PaymentRequest),
+    Mint {
+        url: String,
+        action: MintAction,
+        amount: Option<u64>,
+    },
+}
+
+fn encode_payload(prefix: &str, value: &impl Serialize) -> String {
+    format!(
+        "{}{}",
+        prefix,
+        to_base64url(&serde_json::to_vec(value).unwrap())
+    )
+}
+
+fn decode_base64(data: &str) -> Result<Vec<u8>, UriError> {
+    let data: String = data
+        .trim_end_matches('=')
+        .chars()
+        .map(|c| match c {
+            '+' => '-',
+            '/' => '_',
+            c => c,
+        })
+        .collect();
+    from_base64url(&data).ok_or_else(|| UriError::Malformed("bad base64".to_string()))
+}
+
+fn decode_payload<T: for<'de> Deserialize<'de>>(data: &str) -> Result<T, UriError> {
+    serde_json::from_slice(&decode_base64(data)?).map_err(|e| UriError::Malformed(e.to_string()))
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn percent_decode(s: &str) -> Result<String, UriError> {
+    let bad = || UriError::Malformed("bad percent-encoding".to_string());
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'%' => {
+                let hex = [bytes.next().ok_or_else(bad)?, bytes.next().ok_or_else(bad)?];
+                let hex = std::str::from_utf8(&hex).map_err(|_| bad())?;
+                out.push(u8::from_str_radix(hex, 16).map_err(|_| bad())?);
+            }
+            b'+' => out.push(b' '),
+            b => out.push(b),
+        }
+    }
+    String::from_utf8(out).map_err(|_| bad())
+}
+
+/// The part after the scheme, or the whole string for a bare payload.
+fn strip_scheme(s: &str) -> Option<&str> {
+    for scheme in ["web+cashu:", "cashu:"] {
+        if s.len() >= scheme.len()
+            && s.is_char_boundary(scheme.len())
+            && s[..scheme.len()].eq_ignore_ascii_case(scheme)
+        {
+            let rest = &s[scheme.len()..];
+            return Some(rest.strip_prefix("//").unwrap_or(rest));
+        }
+    }
+    [TOKEN_PREFIX, COMPACT_TOKEN_PREFIX, REQUEST_PREFIX]
+        .iter()
+        .any(|p| s.starts_with(p))
+        .then_some(s)
+}
+
+impl CashuUri {
+    pub fn parse(s: &str) -> Result<Self, UriError> {
+        let rest = strip_scheme(s.trim()).ok_or(UriError::UnknownScheme)?;
+        if let Some(data) = rest.strip_prefix(TOKEN_PREFIX) {
+            return Ok(CashuUri::Token(decode_payload(data)?));
+        }
+        if let Some(data) = rest.strip_prefix(COMPACT_TOKEN_PREFIX) {
+            let token = Token::from_compact(&decode_base64(data)?)
+                .ok_or_else(|| UriError::Malformed("bad compact token".to_string()))?;
+            return Ok(CashuUri::Token(token));
+        }
+        if let Some(data) = rest.strip_prefix(REQUEST_PREFIX) {
+            return Ok(CashuUri::Request(decode_payload(data)?));
+        }
+
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+        if path.trim_end_matches('/') != "mint" {
+            return Err(UriError::UnknownScheme);
+        }
+        let mut url = None;
+        let mut action = MintAction::Info;
+        let mut amount = None;
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value)?;
+            match key {
+                "url" => url = Some(value),
+                "action" => {
+                    action = match value.as_str() {
+                        "info" => MintAction::Info,
+                        "mint" => MintAction::Mint,
+                        "melt" => MintAction::Melt,
+                        _ => return Err(UriError::UnknownAction(value)),
+                    }
+                }
+                "amount" => {
+                    amount = Some(
+                        value
+                            .parse()
+                            .map_err(|_| UriError::Malformed("bad amount".to_string()))?,
+                    )
+                }
+                // Unknown hints are left for newer apps.
+                _ => {}
+            }
+        }
+        Ok(CashuUri::Mint {
+            url: url.ok_or(UriError::MissingParam("url"))?,
+            action,
+            amount,
+        })
+    }
+
+    /// Like `to_string`, but a token is written in the compact form, which
+    /// wallets from before it existed cannot read.
+    pub fn to_compact_string(&self) -> String {
+        match self {
+            CashuUri::Token(token) => format!(
+                "cashu:{}{}",
+                COMPACT_TOKEN_PREFIX,
+                to_base64url(&token.to_compact())
+            ),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl FromStr for CashuUri {
+    type Err = UriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for CashuUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CashuUri::Token(token) => write!(f, "cashu:{}", encode_payload(TOKEN_PREFIX, token)),
+            CashuUri::Request(req) => write!(f, "cashu:{}", encode_payload(REQUEST_PREFIX, req)),
+            CashuUri::Mint {
+                url,
+                action,
+                amount,
+            } => {
+                write!(
+                    f,
+                    "cashu://mint?url={}&action={}",
+                    percent_encode(url),
+                    action.name()
+                )?;
+                if let Some(amount) = amount {
+                    write!(f, "&amount={}", amount)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Wallet {
+    /// Pays a payment request from this wallet's notes at `mint_url`,
+    /// locking them to the payee's key if it gave one. `amount` is used when
+    /// the request leaves the amount open. The token is recorded as sent.
+    pub fn pay_request(
+        &mut self,
+        mint: &impl MintTrait,
+        mint_url: &str,
+        req: &PaymentRequest,
+        amount: Option<u64>,
+    ) -> Result<Token, WalletError> {
+        if !req.mints.is_empty() && !req.mints.iter().any(|m| m == mint_url) {
+            return Err(WalletError::RequestMismatch(format!(
+                "{} is not an accepted mint",
+                mint_url
+            )));
+        }
+        let amount = req
+            .amount
+            .or(amount)
+            .ok_or_else(|| WalletError::RequestMismatch("no amount".to_string()))?;
+        let mut token = match req.pubkey {
+            Some(pubkey) => Token::new(
+                mint_url,
+                self.send_locked(mint, amount, &Condition::p2pk(pubkey))?,
+            ),
+            None => self.send_offline(mint, mint_url, amount)?,
+        };
+        token.memo = req.description.clone();
+        self.record_sent(&token)?;
+        Ok(token)
+    }
+}
@@ -0,0 +1,147 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use secp256k1::{PublicKey, XOnlyPublicKey};
+
+use crate::{
+    bundle::KeysetBundle,
+    clock::{Clock, SystemClock},
+    dleq,
+    dleq::Dleq,
+    hash::try_hash_to_curve,
+    keyset::KeysetIdVersion,
+    token::Token,
+    types::Note,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerifyError {
+    UnknownKeyset(String),
+    UnknownDenomination(u64),
+    /// `Y` does not match `hash_to_curve(secret)`.
+    SecretMismatch,
+    /// The note carries no DLEQ proof, so its signature cannot be checked
+    /// without the mint.
+    MissingDleq,
+    InvalidDleq,
+    /// The note's witness does not satisfy its spending conditions.
+    ConditionsNotMet,
+    /// The notes' values add up to more than 64 bits can hold.
+    AmountOverflow,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::UnknownKeyset(id) => write!(f, "unknown keyset {}", id),
+            VerifyError::UnknownDenomination(v) => write!(f, "no key for denomination {}", v),
+            VerifyError::SecretMismatch => write!(f, "Y does not match secret"),
+            VerifyError::MissingDleq => write!(f, "no DLEQ proof"),
+            VerifyError::InvalidDleq => write!(f, "invalid DLEQ proof"),
+            VerifyError::ConditionsNotMet => write!(f, "spending conditions not met"),
+            VerifyError::AmountOverflow => write!(f, "token amount overflows"),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Checks notes using only mints' public keysets, with no connection to any
+/// mint.
+///
+/// A note that passes is correctly formed, was signed by the mint and, if
+/// locked, carries a witness meeting its conditions, but may already have
+/// been spent: only the mint's spent set can rule out a double spend.
+pub struct Verifier {
+    pub keysets: HashMap<String, HashMap<u64, PublicKey>>,
+    /// Each keyset's IDs under other versions, e.g. the legacy base64 ID
+    /// older tokens carry, mapped to its key in `keysets`.
+    pub aliases: HashMap<String, String>,
+    /// Time source for locktimes in spending conditions.
+    pub clock: Arc<dyn Clock>,
+}
+
+impl Default for Verifier {
+    fn default() -> Self {
+        Self {
+            keysets: HashMap::new(),
+            aliases: HashMap::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl Verifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a keyset from a bundle after checking that it is signed by
+    /// `identity`, the mint's identity key as known from elsewhere.
+    pub fn import_bundle(&mut self, bundle: &KeysetBundle, identity: &XOnlyPublicKey) -> bool {
+        if !bundle.verify(Some(identity)) {
+            return false;
+        }
+        for version in [KeysetIdVersion::Legacy, KeysetIdVersion::V1] {
+            let id = version.derive(&bundle.keys);
+            if id != bundle.keyset_id {
+                self.aliases.insert(id, bundle.keyset_id.clone());
+            }
+        }
+        self.keysets.insert(
+            bundle.keyset_id.clone(),
+            bundle.keys.iter().copied().collect(),
+        );
+        true
+    }
+
+    fn key(&self, keyset_id: &str, value: u64) -> Result<&PublicKey, VerifyError> {
+        let keyset_id = self.aliases.get(keyset_id).map_or(keyset_id, |id| id);
+        self.keysets
+            .get(keyset_id)
+            .ok_or_else(|| VerifyError::UnknownKeyset(keyset_id.to_string()))?
+            .get(&value)
+            .ok_or(VerifyError::UnknownDenomination(value))
+    }
+
+    /// Checks a blind signature `C'` on `B'` as returned by the mint.
+    pub fn verify_blind_signature(
+        &self,
+        keyset_id: &str,
+        value: u64,
+        blinded: &PublicKey,
+        blind_sig: &PublicKey,
+        proof: &Dleq,
+    ) -> Result<(), VerifyError> {
+        let key = self.key(keyset_id, value)?;
+        if !dleq::verify(key, blinded, blind_sig, proof) {
+            return Err(VerifyError::InvalidDleq);
+        }
+        Ok(())
+    }
+
+    /// Checks the note's `Y`, its DLEQ proof and, for locked notes, that its
+    /// witness satisfies the conditions. Sign notes locked to you before
+    /// checking them.
+    pub fn verify_note(&self, note: &Note) -> Result<(), VerifyError> {
+        let key = self.key(&note.keyset_id, note.value)?;
+        if try_hash_to_curve(&note.secret).ok() != Some(note.y) {
+            return Err(VerifyError::SecretMismatch);
+        }
+        let proof = note.dleq.as_ref().ok_or(VerifyError::MissingDleq)?;
+        if !dleq::verify_unblinded(key, &note.y, &note.c, proof) {
+            return Err(VerifyError::InvalidDleq);
+        }
+        if !note.conditions_met_at(self.clock.now()) {
+            return Err(VerifyError::ConditionsNotMet);
+        }
+        Ok(())
+    }
+
+    /// Verifies every note and returns the token's total amount.
+    pub fn verify_token(&self, token: &Token) -> Result<u64, VerifyError> {
+        for n in &token.notes {
+            self.verify_note(n)?;
+        }
+        token.amount().map_err(|_| VerifyError::AmountOverflow)
+    }
+}
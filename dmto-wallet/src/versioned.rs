@@ -0,0 +1,116 @@
+//! Version envelopes for the JSON records the crate persists. Every record
+//! is written as `{"kind", "version", "data"}`. Reading upgrades older
+//! versions one step at a time through `Persisted::migrate`, and accepts
+//! versions up to `MAX_READ_VERSION` so that a record written by the next
+//! release, which may only add optional fields, still loads.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+use crate::{token::Token, types::Note};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum VersionError {
+    WrongKind { expected: String, found: String },
+    TooOld { version: u16, min: u16 },
+    TooNew { version: u16, max: u16 },
+    Malformed(String),
+}
+
+impl fmt::Display for VersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VersionError::WrongKind { expected, found } => {
+                write!(f, "expected a {} record, found {}", expected, found)
+            }
+            VersionError::TooOld { version, min } => {
+                write!(f, "version {} is older than supported ({})", version, min)
+            }
+            VersionError::TooNew { version, max } => {
+                write!(f, "version {} is newer than supported ({})", version, max)
+            }
+            VersionError::Malformed(msg) => write!(f, "malformed record: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for VersionError {}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    kind: String,
+    version: u16,
+    data: Value,
+}
+
+pub trait Persisted: Serialize + DeserializeOwned {
+    const KIND: &'static str;
+    /// Version this build writes.
+    const VERSION: u16;
+    /// Oldest version this build can migrate from. Version 0 is the bare
+    /// JSON written before records were enveloped.
+    const MIN_VERSION: u16 = 0;
+    const MAX_READ_VERSION: u16 = Self::VERSION + 1;
+
+    /// Upgrades `data` from `version` to `version + 1`.
+    fn migrate(_version: u16, data: Value) -> Result<Value, VersionError> {
+        Ok(data)
+    }
+}
+
+pub fn save<T: Persisted>(value: &T) -> Vec<u8> {
+    serde_json::to_vec(&Envelope {
+        kind: T::KIND.to_string(),
+        version: T::VERSION,
+        data: serde_json::to_value(value).unwrap(),
+    })
+    .unwrap()
+}
+
+pub fn load<T: Persisted>(bytes: &[u8]) -> Result<T, VersionError> {
+    let malformed = |e: serde_json::Error| VersionError::Malformed(e.to_string());
+
+    let value: Value = serde_json::from_slice(bytes).map_err(malformed)?;
+    let envelope = serde_json::from_value::<Envelope>(value.clone()).unwrap_or(Envelope {
+        kind: T::KIND.to_string(),
+        version: 0,
+        data: value,
+    });
+
+    if envelope.kind != T::KIND {
+        return Err(VersionError::WrongKind {
+            expected: T::KIND.to_string(),
+            found: envelope.kind,
+        });
+    }
+    if envelope.version < T::MIN_VERSION {
+        return Err(VersionError::TooOld {
+            version: envelope.version,
+            min: T::MIN_VERSION,
+        });
+    }
+    if envelope.version > T::MAX_READ_VERSION {
+        return Err(VersionError::TooNew {
+            version: envelope.version,
+            max: T::MAX_READ_VERSION,
+        });
+    }
+
+    let mut data = envelope.data;
+    for version in envelope.version..T::VERSION {
+        data = T::migrate(version, data)?;
+    }
+    serde_json::from_value(data).map_err(malformed)
+}
+
+impl Persisted for Note {
+    const KIND: &'static str = "note";
+    const VERSION: u16 = 1;
+}
+
+impl Persisted for Token {
+    const KIND: &'static str = "token";
+    const VERSION: u16 = 1;
+}
@@ -0,0 +1,1279 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, OnceLock},
+};
+
+use secp256k1::{PublicKey, SecretKey};
+
+use crate::{
+    announce::Announcement,
+    api::{KeysetKeys, LocalMint, MintTrait},
+    blind::{BlindedMessage, blind_message, unblind_signature},
+    canonical::{output_key, sort_inputs},
+    clock::{Clock, SystemClock},
+    conditions::Condition,
+    contacts::ContactBook,
+    dleq::{self, NoteDleq},
+    error::{MintError, WalletError},
+    hash::{hash_to_curve, hash_to_curve_batch},
+    history::{Direction, Transaction},
+    issue::{MintQuote, MintQuoteState, MintRequest},
+    melt::{MeltQuote, MeltRequest, blank_outputs_for},
+    mint::{MintInfo, ProofState},
+    notestore::{NoteStore, Selection},
+    operation::{self, OperationGuard},
+    protocol::{Capability, Hello, Session, SwapRequest, SwapResponse, negotiate},
+    receipt::FeeReceipt,
+    secret::SecretPolicy,
+    subscription::Subscription,
+    token::Token,
+    types::{Amount, Note},
+    walletpolicy::{SpendKind, SpendRequest, WalletSpendPolicy},
+};
+
+/// Outcome of `Wallet::receive_many`, by token ID.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BatchReceipt {
+    /// Tokens redeemed and the amount credited for each after fees.
+    pub received: Vec<(String, u64)>,
+    pub failed: Vec<(String, WalletError)>,
+    pub swaps: usize,
+    pub fee: u64,
+}
+
+impl BatchReceipt {
+    pub fn amount(&self) -> u64 {
+        self.received.iter().map(|(_, a)| a).sum()
+    }
+}
+
+pub struct Wallet {
+    pub notes: NoteStore,
+    pub history: Vec<Transaction>,
+    /// IDs of every token this wallet has redeemed. Shared between devices
+    /// so a re-scanned or replayed token is recognised before any swap.
+    pub received: HashSet<String>,
+    /// Mint quotes requested but not yet issued, kept so a paid quote can
+    /// be collected after a crash.
+    pub pending_quotes: Vec<String>,
+    /// Tokens sent but not yet redeemed by the payee, by token ID. Their
+    /// notes can be taken back with `cancel_send`; locked sends are not kept.
+    pub pending_sends: HashMap<String, Token>,
+    /// Format of the secrets this wallet generates. Should match the mint's.
+    pub secret_policy: SecretPolicy,
+    /// Denominations with fewer unspent notes than this at the mint are
+    /// flagged by `anonymity_warnings`. 0 disables the check.
+    pub min_anonymity_set: u64,
+    pub contacts: ContactBook,
+    /// The mint's announcements as of the last `check_announcements`.
+    pub notices: HashMap<String, Announcement>,
+    /// Asked before any send or melt; `None` allows everything.
+    pub spend_policy: Option<Arc<dyn WalletSpendPolicy>>,
+    /// How notes are picked for sends and melts.
+    pub selection: Selection,
+    /// Recurring payments, made by a `SubscriptionScheduler`.
+    pub subscriptions: Vec<Subscription>,
+    /// ID of the latest send, melt, receive or issuance, sent with its
+    /// requests to the mint. Quote it to trace a failed call in the mint's
+    /// logs.
+    pub last_operation: Option<String>,
+    /// Every keyset of the mint seen so far, active or not, by ID. Refreshed
+    /// when received proofs name one not in it.
+    pub keysets: HashMap<String, KeysetKeys>,
+    /// Time source for history entries and spend-policy checks.
+    pub clock: Arc<dyn Clock>,
+    /// What `connect` agreed with the mint, negotiated on first contact.
+    pub session: OnceLock<Session>,
+}
+
+impl Default for Wallet {
+    fn default() -> Self {
+        Self {
+            notes: Default::default(),
+            history: Default::default(),
+            received: Default::default(),
+            pending_quotes: Default::default(),
+            pending_sends: Default::default(),
+            secret_policy: Default::default(),
+            min_anonymity_set: Default::default(),
+            contacts: Default::default(),
+            notices: Default::default(),
+            spend_policy: Default::default(),
+            selection: Default::default(),
+            subscriptions: Default::default(),
+            last_operation: Default::default(),
+            keysets: Default::default(),
+            clock: Arc::new(SystemClock),
+            session: OnceLock::new(),
+        }
+    }
+}
+
+impl Wallet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn balance(&self) -> u64 {
+        self.notes.total()
+    }
+
+    /// Agrees on a protocol version and capability set with the mint, once;
+    /// later calls return the cached session. Every request to the mint is
+    /// sent in its version.
+    pub fn connect(&self, mint: &impl MintTrait) -> Result<Session, MintError> {
+        if let Some(session) = self.session.get() {
+            return Ok(session.clone());
+        }
+        let ours = Hello::new(vec![Capability::Swap, Capability::SpendingConditions]);
+        let session = negotiate(&ours, &mint.hello()?)?;
+        Ok(self.session.get_or_init(|| session).clone())
+    }
+
+    pub fn mint_note(&mut self, mint: &impl LocalMint, value: u64) {
+        let secret = self.secret_policy.generate();
+
+        let y = hash_to_curve(&secret);
+        let (keyset_id, c) = mint.sign_direct(value, &y).unwrap();
+
+        self.notes.push(Note {
+            value,
+            keyset_id,
+            secret,
+            y,
+            c,
+            dleq: None,
+            witness: None,
+        });
+    }
+
+    /// Redeems incoming notes by swapping them at the mint for fresh notes
+    /// only this wallet knows the secrets of. The mint's input fee is taken
+    /// out of the received amount.
+    pub fn receive(&mut self, mint: &impl MintTrait, notes: Vec<Note>) -> bool {
+        let _op = self.begin_operation();
+        let Ok(info) = mint.info() else {
+            return false;
+        };
+        let Some(Amount(total)) = Amount::checked_sum(notes.iter().map(|n| n.value)) else {
+            return false;
+        };
+        let fee = info.fee(notes.len());
+        if total < fee {
+            return false;
+        }
+
+        let values = match split_amount(total - fee, &info.denominations) {
+            Some(values) => values,
+            None => return false,
+        };
+        let Ok(inputs) = self.resolve_inputs(mint, notes) else {
+            return false;
+        };
+        self.swap_into(mint, inputs, &values).is_ok()
+    }
+
+    /// Redeems a token once. Receiving the same token again, from this or a
+    /// device sharing `received`, fails with `AlreadyReceived` and leaves the
+    /// history untouched. Returns the amount credited after fees.
+    pub fn receive_token(
+        &mut self,
+        mint: &impl MintTrait,
+        token: &Token,
+    ) -> Result<u64, WalletError> {
+        let _op = self.begin_operation();
+        let id = token.id();
+        if self.received.contains(&id) {
+            return Err(WalletError::AlreadyReceived(id));
+        }
+
+        let spent = self.spent_proofs(mint, token)?;
+        if !spent.is_empty() {
+            return Err(WalletError::TokenSpent {
+                spent,
+                proofs: token.notes.len(),
+            });
+        }
+
+        let info = mint.info()?;
+        let total = token.amount()?;
+        let fee = info.fee(token.notes.len());
+        if total < fee {
+            return Err(MintError::AmountMismatch {
+                inputs: total,
+                outputs: 0,
+                fee,
+            }
+            .into());
+        }
+        let values = split_amount(total - fee, &info.denominations)
+            .ok_or(MintError::UnknownDenomination(total - fee))?;
+        let inputs = self.resolve_inputs(mint, token.notes.clone())?;
+        let receipt = self.swap_into(mint, inputs, &values)?;
+
+        self.received.insert(id.clone());
+        let mut tx = Transaction::new(&id, Direction::Incoming, total - fee, fee, self.clock.now());
+        tx.memo = token.memo.clone();
+        tx.receipt = receipt;
+        self.history.push(tx);
+        Ok(total - fee)
+    }
+
+    /// Redeems a backlog of tokens with as few swaps as possible: the
+    /// proofs of all tokens are checked in one state query, then swapped
+    /// together per keyset, up to the mint's input limit per swap. Tokens
+    /// already received or with spent proofs are reported and left out.
+    ///
+    /// Each token gets its own history entry, with its share of the batch
+    /// fee.
+    pub fn receive_many(
+        &mut self,
+        mint: &impl MintTrait,
+        tokens: &[Token],
+    ) -> Result<BatchReceipt, WalletError> {
+        let _op = self.begin_operation();
+        let mut receipt = BatchReceipt::default();
+        let mut ids = HashSet::new();
+        let mut pending: Vec<&Token> = Vec::new();
+        for token in tokens {
+            let id = token.id();
+            if self.received.contains(&id) || !ids.insert(id.clone()) {
+                receipt
+                    .failed
+                    .push((id.clone(), WalletError::AlreadyReceived(id)));
+            } else {
+                pending.push(token);
+            }
+        }
+
+        let ys: Vec<PublicKey> = pending
+            .iter()
+            .flat_map(|t| t.notes.iter().map(|n| n.y))
+            .collect();
+        let spent: HashSet<PublicKey> = ys
+            .iter()
+            .zip(mint.check_state(&ys)?)
+            .filter(|(_, s)| *s == ProofState::Spent)
+            .map(|(y, _)| *y)
+            .collect();
+
+        let mut groups: BTreeMap<Vec<&str>, Vec<&Token>> = BTreeMap::new();
+        for token in pending {
+            let hit: Vec<PublicKey> = (token.notes.iter())
+                .filter(|n| spent.contains(&n.y))
+                .map(|n| n.y)
+                .collect();
+            if !hit.is_empty() {
+                let proofs = token.notes.len();
+                receipt
+                    .failed
+                    .push((token.id(), WalletError::TokenSpent { spent: hit, proofs }));
+                continue;
+            }
+            let mut keysets: Vec<&str> = token.notes.iter().map(|n| n.keyset_id.as_str()).collect();
+            keysets.sort_unstable();
+            keysets.dedup();
+            groups.entry(keysets).or_default().push(token);
+        }
+
+        let info = mint.info()?;
+        for group in groups.into_values() {
+            let mut batch: Vec<&Token> = Vec::new();
+            let mut inputs = 0;
+            for token in group {
+                if !batch.is_empty() && inputs + token.notes.len() > info.max_inputs {
+                    self.receive_batch(mint, &info, &batch, &mut receipt);
+                    batch.clear();
+                    inputs = 0;
+                }
+                inputs += token.notes.len();
+                batch.push(token);
+            }
+            if !batch.is_empty() {
+                self.receive_batch(mint, &info, &batch, &mut receipt);
+            }
+        }
+        Ok(receipt)
+    }
+
+    fn receive_batch(
+        &mut self,
+        mint: &impl MintTrait,
+        info: &MintInfo,
+        batch: &[&Token],
+        receipt: &mut BatchReceipt,
+    ) {
+        let inputs: Vec<Note> = batch.iter().flat_map(|t| t.notes.clone()).collect();
+        let fee = info.fee(inputs.len());
+        let result = Amount::checked_sum(inputs.iter().map(|n| n.value))
+            .ok_or(WalletError::from(MintError::AmountOverflow))
+            .and_then(|Amount(total)| {
+                total
+                    .checked_sub(fee)
+                    .and_then(|amount| split_amount(amount, &info.denominations))
+                    .ok_or(WalletError::from(MintError::AmountMismatch {
+                        inputs: total,
+                        outputs: 0,
+                        fee,
+                    }))
+            })
+            .and_then(|values| {
+                let inputs = self.resolve_inputs(mint, inputs)?;
+                Ok(self.swap_into(mint, inputs, &values)?)
+            });
+        let fee_receipt = match result {
+            Ok(fee_receipt) => fee_receipt,
+            Err(e) => {
+                for token in batch {
+                    receipt.failed.push((token.id(), e.clone()));
+                }
+                return;
+            }
+        };
+
+        receipt.swaps += 1;
+        receipt.fee += fee;
+        let mut counted = 0;
+        for token in batch {
+            let share = info.fee(counted + token.notes.len()) - info.fee(counted);
+            counted += token.notes.len();
+            // Each token's notes are part of the checked batch total.
+            let amount = token.amount().unwrap_or_default().saturating_sub(share);
+
+            let id = token.id();
+            self.received.insert(id.clone());
+            let mut tx =
+                Transaction::new(&id, Direction::Incoming, amount, share, self.clock.now());
+            tx.memo = token.memo.clone();
+            tx.receipt = fee_receipt.clone();
+            self.history.push(tx);
+            receipt.received.push((id, amount));
+        }
+    }
+
+    /// Lists a token handed to a payee in the history, with its memo, and
+    /// keeps it as pending until the payee redeems it. The send paths record
+    /// what they return; recording the same notes again, e.g. once wrapped
+    /// in a token with a mint URL and memo, updates that entry. Tokens with
+    /// notes whose conditions their own witness does not meet, such as
+    /// P2PK-locked sends, are only listed: `cancel_send` could not swap them
+    /// back.
+    pub fn record_sent(&mut self, token: &Token) -> Result<(), MintError> {
+        let id = token.id();
+        let recorded = (self.history.iter_mut().rev())
+            .find(|tx| tx.id == id && tx.direction == Direction::Outgoing);
+        match recorded {
+            Some(tx) => tx.memo = token.memo.clone(),
+            None => {
+                let mut tx = Transaction::new(
+                    &id,
+                    Direction::Outgoing,
+                    token.amount()?,
+                    0,
+                    self.clock.now(),
+                );
+                tx.memo = token.memo.clone();
+                self.history.push(tx);
+            }
+        }
+        let now = self.clock.now();
+        if token.notes.iter().all(|n| n.conditions_met_at(now)) {
+            self.pending_sends.insert(id, token.clone());
+        }
+        Ok(())
+    }
+
+    /// Drops the pending sends whose proofs the mint reports as all spent,
+    /// returning their token IDs.
+    pub fn settle_sends(&mut self, mint: &impl MintTrait) -> Result<Vec<String>, MintError> {
+        let ys: Vec<PublicKey> = self
+            .pending_sends
+            .values()
+            .flat_map(|t| t.notes.iter().map(|n| n.y))
+            .collect();
+        if ys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let spent: HashSet<PublicKey> = ys
+            .iter()
+            .zip(mint.check_state(&ys)?)
+            .filter(|(_, s)| *s == ProofState::Spent)
+            .map(|(y, _)| *y)
+            .collect();
+        let settled: Vec<String> = self
+            .pending_sends
+            .iter()
+            .filter(|(_, t)| t.notes.iter().all(|n| spent.contains(&n.y)))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &settled {
+            self.pending_sends.remove(id);
+        }
+        Ok(settled)
+    }
+
+    /// Takes back a sent token by swapping its proofs the payee has not
+    /// redeemed into the wallet. The history entry of the send is reduced
+    /// by what came back. Returns the amount reclaimed after fees; fails
+    /// with `TokenSpent` if the payee already redeemed all of it.
+    pub fn cancel_send(&mut self, mint: &impl MintTrait, id: &str) -> Result<u64, WalletError> {
+        let token = self
+            .pending_sends
+            .get(id)
+            .cloned()
+            .ok_or_else(|| WalletError::UnknownSend(id.to_string()))?;
+        let spent = self.spent_proofs(mint, &token)?;
+        let unspent: Vec<Note> = token
+            .notes
+            .iter()
+            .filter(|n| !spent.contains(&n.y))
+            .cloned()
+            .collect();
+        if unspent.is_empty() {
+            self.pending_sends.remove(id);
+            return Err(WalletError::TokenSpent {
+                spent,
+                proofs: token.notes.len(),
+            });
+        }
+
+        let info = mint.info()?;
+        let total: u64 = unspent.iter().map(|n| n.value).sum();
+        let fee = info.fee(unspent.len());
+        if total < fee {
+            return Err(MintError::AmountMismatch {
+                inputs: total,
+                outputs: 0,
+                fee,
+            }
+            .into());
+        }
+        let values = split_amount(total - fee, &info.denominations)
+            .ok_or(MintError::UnknownDenomination(total - fee))?;
+        let receipt = self.swap_into(mint, unspent, &values)?;
+
+        self.pending_sends.remove(id);
+        if let Some(tx) = self
+            .history
+            .iter_mut()
+            .find(|tx| tx.id == id && tx.direction == Direction::Outgoing)
+        {
+            tx.amount -= total;
+            tx.fee += fee;
+            tx.receipt = receipt;
+        }
+        self.history
+            .retain(|tx| tx.id != id || tx.amount > 0 || tx.fee > 0);
+        Ok(total - fee)
+    }
+
+    /// The `Y`s of the token's proofs that the mint reports as spent.
+    pub fn spent_proofs(
+        &self,
+        mint: &impl MintTrait,
+        token: &Token,
+    ) -> Result<Vec<PublicKey>, MintError> {
+        let ys: Vec<PublicKey> = token.notes.iter().map(|n| n.y).collect();
+        let states = mint.check_state(&ys)?;
+        Ok(ys
+            .into_iter()
+            .zip(states)
+            .filter(|(_, s)| *s == ProofState::Spent)
+            .map(|(y, _)| y)
+            .collect())
+    }
+
+    /// Swaps notes smaller than `min_denom` into as few notes as the mint's
+    /// denominations allow, in batches no larger than the mint's input limit.
+    /// Batches whose fee would eat their whole value are left alone. Returns
+    /// the total fee paid.
+    pub fn consolidate(&mut self, mint: &impl MintTrait, min_denom: u64) -> Result<u64, MintError> {
+        let info = mint.info()?;
+        let small: Vec<PublicKey> = self
+            .notes
+            .iter()
+            .filter(|n| n.value < min_denom)
+            .map(|n| n.y)
+            .collect();
+        let dust: Vec<Note> = small.iter().filter_map(|y| self.notes.remove(y)).collect();
+
+        let mut fee_paid = 0;
+        for batch in dust.chunks(info.max_inputs.max(1)) {
+            let total: u64 = batch.iter().map(|n| n.value).sum();
+            let fee = info.fee(batch.len());
+
+            let values = if batch.len() > 1 && total > fee {
+                split_amount(total - fee, &info.denominations)
+            } else {
+                None
+            };
+            let Some(values) = values else {
+                self.notes.extend(batch.iter().cloned());
+                continue;
+            };
+
+            if let Err(e) = self.swap_into(mint, batch.to_vec(), &values) {
+                self.notes.extend(batch.iter().cloned());
+                return Err(e);
+            }
+            fee_paid += fee;
+        }
+
+        Ok(fee_paid)
+    }
+
+    /// Selects notes covering `amount` plus the input fee they incur and
+    /// swaps them for outputs locked to `condition` worth `amount`, keeping
+    /// the change. Returns the locked notes for handing to the recipient,
+    /// recorded as a send.
+    pub fn send_locked(
+        &mut self,
+        mint: &impl MintTrait,
+        amount: u64,
+        condition: &Condition,
+    ) -> Result<Vec<Note>, WalletError> {
+        let values = split_amount(amount, &mint.info()?.denominations)
+            .ok_or(MintError::UnknownDenomination(amount))?;
+        let outputs = values
+            .into_iter()
+            .map(|v| (v, condition.to_secret()))
+            .collect();
+        self.guarded(SpendKind::SendLocked, amount, |w| {
+            let notes = w.send_outputs(mint, outputs)?;
+            w.record_sent(&Token::new("", notes.clone()))?;
+            Ok(notes)
+        })
+    }
+
+    /// Starts an operation for a call, unless one is already current, and
+    /// notes its ID in `last_operation`.
+    pub(crate) fn begin_operation(&mut self) -> OperationGuard {
+        let guard = operation::begin();
+        self.last_operation = operation::current();
+        guard
+    }
+
+    /// Runs `f` as an operation if the spend policy allows spending
+    /// `amount`, refunding the policy when `f` fails.
+    pub(crate) fn guarded<T>(
+        &mut self,
+        kind: SpendKind,
+        amount: u64,
+        f: impl FnOnce(&mut Self) -> Result<T, WalletError>,
+    ) -> Result<T, WalletError> {
+        let _op = self.begin_operation();
+        let Some(policy) = self.spend_policy.clone() else {
+            return f(self);
+        };
+        let req = SpendRequest {
+            kind,
+            amount,
+            timestamp: self.clock.now(),
+        };
+        policy.authorize(&req).map_err(WalletError::SpendRefused)?;
+        let result = f(self);
+        if result.is_err() {
+            policy.refund(&req);
+        }
+        result
+    }
+
+    /// Sends `amount` as a token. If some of the wallet's notes add up to
+    /// exactly `amount` they are handed over as they are, without contacting
+    /// the mint; otherwise this swaps for exact notes like `split_out`.
+    ///
+    /// Notes are picked largest first, which always finds a combination when
+    /// the denominations are powers of two.
+    pub fn send_offline(
+        &mut self,
+        mint: &impl MintTrait,
+        mint_url: &str,
+        amount: u64,
+    ) -> Result<Token, WalletError> {
+        self.guarded(SpendKind::Send, amount, |w| {
+            let notes = match w.take_exact(amount) {
+                Some(notes) => notes,
+                None => {
+                    let values = split_amount(amount, &mint.info()?.denominations)
+                        .ok_or(MintError::UnknownDenomination(amount))?;
+                    w.send_outputs(mint, w.random_outputs(&values))?
+                }
+            };
+            let token = Token::new(mint_url, notes);
+            w.record_sent(&token)?;
+            Ok(token)
+        })
+    }
+
+    /// Removes and returns notes worth exactly `amount`, if there are some.
+    fn take_exact(&mut self, amount: u64) -> Option<Vec<Note>> {
+        let mut remaining = amount;
+        let mut picked = Vec::new();
+        let mut rng = rand::thread_rng();
+        for (&value, &count) in self.notes.counts().iter().rev() {
+            let n = count.min((remaining / value) as usize);
+            remaining -= value * n as u64;
+            match self.selection {
+                Selection::LargestFirst => {
+                    picked.extend(self.notes.with_value(value).take(n).map(|n| n.y))
+                }
+                Selection::Random => picked.extend(
+                    self.notes
+                        .pick_with_value(value, n, &mut rng)
+                        .into_iter()
+                        .map(|n| n.y),
+                ),
+            }
+        }
+        if remaining != 0 || picked.is_empty() {
+            return None;
+        }
+
+        picked.iter().map(|y| self.notes.remove(y)).collect()
+    }
+
+    /// Swaps wallet notes for fresh notes of exactly `values`, which are
+    /// returned instead of kept and recorded as a pending send. Change stays
+    /// in the wallet.
+    pub fn split_out(
+        &mut self,
+        mint: &impl MintTrait,
+        values: &[u64],
+    ) -> Result<Vec<Note>, WalletError> {
+        let outputs = self.random_outputs(values);
+        self.guarded(SpendKind::Send, values.iter().sum(), |w| {
+            let notes = w.send_outputs(mint, outputs)?;
+            w.record_sent(&Token::new("", notes.clone()))?;
+            Ok(notes)
+        })
+    }
+
+    pub(crate) fn send_outputs(
+        &mut self,
+        mint: &impl MintTrait,
+        mut outputs: Vec<(u64, Vec<u8>)>,
+    ) -> Result<Vec<Note>, WalletError> {
+        let info = mint.info()?;
+        let amount = outputs.iter().map(|(v, _)| v).sum();
+        let (inputs, change) = self.select_with_fee(&info, amount)?;
+
+        let Some(change_values) = split_amount(change, &info.denominations) else {
+            self.notes.extend(inputs);
+            return Err(MintError::UnknownDenomination(change).into());
+        };
+        let sent = outputs.len();
+        outputs.extend(self.random_outputs(&change_values));
+
+        let mut notes = match self.swap_for(mint, inputs.clone(), outputs) {
+            Ok(notes) => notes,
+            Err(e) => {
+                self.notes.extend(inputs);
+                return Err(e.into());
+            }
+        };
+        self.notes.extend(notes.split_off(sent));
+        Ok(notes)
+    }
+
+    /// Requests an invoice for `amount`. Once it is paid, `resume_quote`
+    /// collects the notes.
+    pub fn request_mint(
+        &mut self,
+        mint: &impl MintTrait,
+        amount: u64,
+    ) -> Result<MintQuote, WalletError> {
+        let quote = mint.mint_quote(amount)?;
+        self.pending_quotes.push(quote.id.clone());
+        Ok(quote)
+    }
+
+    /// Collects the notes for a paid quote, whether or not this wallet
+    /// requested it. Returns the amount issued.
+    pub fn resume_quote(&mut self, mint: &impl MintTrait, id: &str) -> Result<u64, WalletError> {
+        let _op = self.begin_operation();
+        let quote = mint.get_quote(id)?;
+        match quote.state {
+            MintQuoteState::Unpaid => return Err(MintError::QuoteUnpaid(quote.id).into()),
+            MintQuoteState::Issued => {
+                self.pending_quotes.retain(|q| q != id);
+                return Err(MintError::QuoteAlreadyIssued(quote.id).into());
+            }
+            MintQuoteState::Paid => {}
+        }
+
+        let values = split_amount(quote.amount, &mint.info()?.denominations)
+            .ok_or(MintError::UnknownDenomination(quote.amount))?;
+        let session = self.connect(mint)?;
+        let notes = self.sign_outputs(mint, self.random_outputs(&values), |outputs| {
+            let resp = mint
+                .mint(session.request(MintRequest {
+                    quote: quote.id.clone(),
+                    outputs,
+                    operation: operation::current(),
+                }))?
+                .accept(&session)?;
+            Ok(SwapResponse {
+                signatures: resp.signatures,
+                dleqs: resp.dleqs,
+                receipt: None,
+            })
+        })?;
+
+        self.notes.extend(notes);
+        self.pending_quotes.retain(|q| q != id);
+        self.history.push(Transaction::new(
+            id,
+            Direction::Incoming,
+            quote.amount,
+            0,
+            self.clock.now(),
+        ));
+        Ok(quote.amount)
+    }
+
+    /// Tries every pending quote, returning the total issued. Unpaid quotes
+    /// stay pending.
+    pub fn resume_pending(&mut self, mint: &impl MintTrait) -> u64 {
+        self.pending_quotes
+            .clone()
+            .iter()
+            .filter_map(|id| self.resume_quote(mint, id).ok())
+            .sum()
+    }
+
+    /// Pays a Lightning `request` through the mint, receiving any unused fee
+    /// reserve back as change. Returns the payment preimage, which is also
+    /// kept in the history as proof of payment. A BIP21 `request` is paid
+    /// on-chain and returns the transaction ID instead, and an invoice the
+    /// mint settles internally returns the quote ID.
+    pub fn melt(&mut self, mint: &impl MintTrait, request: &str) -> Result<String, WalletError> {
+        let quote = mint.melt_quote(request)?;
+        self.melt_quoted(mint, &quote)
+    }
+
+    /// Pays `amount` to a Lightning invoice that carries none, as `melt`.
+    pub fn melt_amount(
+        &mut self,
+        mint: &impl MintTrait,
+        request: &str,
+        amount: u64,
+    ) -> Result<String, WalletError> {
+        let quote = mint.melt_quote_with_amount(request, amount)?;
+        self.melt_quoted(mint, &quote)
+    }
+
+    /// Pays an unpaid melt `quote`, as `melt`.
+    pub(crate) fn melt_quoted(
+        &mut self,
+        mint: &impl MintTrait,
+        quote: &MeltQuote,
+    ) -> Result<String, WalletError> {
+        let due = quote
+            .amount
+            .checked_add(quote.fee_reserve)
+            .ok_or(MintError::AmountOverflow)?;
+        let kind = SpendKind::Melt {
+            request: quote.request.clone(),
+        };
+        self.guarded(kind, due, |w| w.pay_quote(mint, quote, due))
+    }
+
+    /// Melts notes covering `due` against an unpaid `quote`.
+    fn pay_quote(
+        &mut self,
+        mint: &impl MintTrait,
+        quote: &MeltQuote,
+        due: u64,
+    ) -> Result<String, WalletError> {
+        let info = mint.info()?;
+        let keyset = mint.active_keyset()?;
+        let session = self.connect(mint)?;
+        let (mut inputs, _) = self.select_with_fee(&info, due)?;
+        sort_inputs(&mut inputs);
+
+        let in_sum: u64 = inputs.iter().map(|n| n.value).sum();
+        let blanks = self.blank_outputs(in_sum - quote.amount - info.fee(inputs.len()));
+
+        let req = MeltRequest {
+            quote: quote.id.clone(),
+            inputs: inputs.clone(),
+            outputs: blanks.iter().map(|(_, _, b)| b.blinded_point).collect(),
+            operation: operation::current(),
+        };
+        let resp = match mint
+            .melt(session.request(req))
+            .and_then(|r| r.accept(&session))
+        {
+            Ok(resp) => resp,
+            Err(e) => {
+                self.notes.extend(inputs);
+                return Err(e.into());
+            }
+        };
+
+        let change = self.keep_change(&keyset, resp.change, blanks)?;
+        self.record_melt(&resp.quote, in_sum - quote.amount - change, resp.receipt)
+    }
+
+    /// Secrets and blinded messages for enough blank outputs to carry
+    /// `max_change` back from a melt, in canonical order.
+    pub(crate) fn blank_outputs(
+        &self,
+        max_change: u64,
+    ) -> Vec<(Vec<u8>, PublicKey, BlindedMessage)> {
+        let mut blanks: Vec<_> = (0..blank_outputs_for(max_change))
+            .map(|_| {
+                let secret = self.secret_policy.generate();
+                let y = hash_to_curve(&secret);
+                (secret, y, blind_message(&y))
+            })
+            .collect();
+        blanks.sort_by_key(|(_, _, b)| b.blinded_point.serialize());
+        blanks
+    }
+
+    /// Unblinds the change a melt signed on `blanks` into notes, returning
+    /// its total.
+    pub(crate) fn keep_change(
+        &mut self,
+        keyset: &KeysetKeys,
+        change: Vec<(u64, PublicKey)>,
+        blanks: Vec<(Vec<u8>, PublicKey, BlindedMessage)>,
+    ) -> Result<u64, WalletError> {
+        let mut total = 0;
+        for ((value, blind_sig), (secret, y, blinded)) in change.into_iter().zip(blanks) {
+            let key = keyset
+                .key(value)
+                .ok_or(MintError::UnknownDenomination(value))?;
+            self.notes.push(Note {
+                value,
+                keyset_id: keyset.keyset_id.clone(),
+                secret,
+                y,
+                c: unblind_signature(&blind_sig, &blinded.blind_factor, key),
+                dleq: None,
+                witness: None,
+            });
+            total += value;
+        }
+        Ok(total)
+    }
+
+    /// Adds a paid melt quote to the history and returns its proof of
+    /// payment.
+    pub(crate) fn record_melt(
+        &mut self,
+        quote: &MeltQuote,
+        fee: u64,
+        receipt: Option<FeeReceipt>,
+    ) -> Result<String, WalletError> {
+        let mut tx = Transaction::new(
+            &quote.id,
+            Direction::Outgoing,
+            quote.amount,
+            fee,
+            self.clock.now(),
+        );
+        tx.receipt = receipt;
+        let proof = match (quote.preimage.clone(), quote.txid.clone()) {
+            (Some(preimage), _) => {
+                tx.preimage = Some(preimage.clone());
+                preimage
+            }
+            (None, Some(txid)) => {
+                tx.txid = Some(txid.clone());
+                txid
+            }
+            (None, None) if quote.internal => quote.id.clone(),
+            (None, None) => {
+                return Err(
+                    MintError::PaymentFailed("no proof of payment returned".to_string()).into(),
+                );
+            }
+        };
+        self.history.push(tx);
+        Ok(proof)
+    }
+
+    /// Takes notes out of the wallet until they cover `amount` plus the fee
+    /// for spending them, returning them with the change left over.
+    pub(crate) fn select_with_fee(
+        &mut self,
+        info: &MintInfo,
+        amount: u64,
+    ) -> Result<(Vec<Note>, u64), WalletError> {
+        let mut selected = Vec::new();
+        let mut sum: u64 = 0;
+        let mut rng = rand::thread_rng();
+        let needed = loop {
+            let Some(needed) = amount.checked_add(info.fee(selected.len())) else {
+                self.notes.extend(selected);
+                return Err(MintError::AmountOverflow.into());
+            };
+            if sum >= needed {
+                break needed;
+            }
+            let next = match self.selection {
+                Selection::LargestFirst => self.notes.pop(),
+                Selection::Random => self
+                    .notes
+                    .pick_weighted(&mut rng)
+                    .map(|n| n.y)
+                    .and_then(|y| self.notes.remove(&y)),
+            };
+            let Some(n) = next else {
+                self.notes.extend(selected);
+                return Err(WalletError::InsufficientFunds {
+                    needed,
+                    available: sum,
+                });
+            };
+            let Some(total) = sum.checked_add(n.value) else {
+                self.notes.extend(selected);
+                self.notes.push(n);
+                return Err(MintError::AmountOverflow.into());
+            };
+            sum = total;
+            selected.push(n);
+        };
+        Ok((selected, sum - needed))
+    }
+
+    /// The keyset notes carrying `keyset_id` belong to, fetching the mint's
+    /// keysets if it is not among those already seen. Legacy IDs resolve to
+    /// the keyset they were derived from.
+    pub fn keyset_for(
+        &mut self,
+        mint: &impl MintTrait,
+        keyset_id: &str,
+    ) -> Result<KeysetKeys, WalletError> {
+        let find = |keysets: &HashMap<String, KeysetKeys>| {
+            keysets
+                .get(keyset_id)
+                .or_else(|| keysets.values().find(|k| k.matches(keyset_id)))
+                .cloned()
+        };
+        if let Some(keyset) = find(&self.keysets) {
+            return Ok(keyset);
+        }
+        for keyset in mint.keysets()? {
+            self.keysets.insert(keyset.keyset_id.clone(), keyset);
+        }
+        find(&self.keysets).ok_or_else(|| WalletError::UnknownKeyset(keyset_id.to_string()))
+    }
+
+    /// Groups received notes by keyset and checks each group against its
+    /// keyset's keys, so one swap can redeem proofs from active and
+    /// inactive keysets together. Notes are given the ID the mint lists
+    /// their keyset under.
+    pub(crate) fn resolve_inputs(
+        &mut self,
+        mint: &impl MintTrait,
+        notes: Vec<Note>,
+    ) -> Result<Vec<Note>, WalletError> {
+        let mut groups: BTreeMap<String, Vec<Note>> = BTreeMap::new();
+        for note in notes {
+            groups.entry(note.keyset_id.clone()).or_default().push(note);
+        }
+        let mut inputs = Vec::new();
+        for (keyset_id, notes) in groups {
+            let keyset = self.keyset_for(mint, &keyset_id)?;
+            for mut note in notes {
+                if keyset.key(note.value).is_none() {
+                    return Err(MintError::UnknownDenomination(note.value).into());
+                }
+                note.keyset_id = keyset.keyset_id.clone();
+                inputs.push(note);
+            }
+        }
+        Ok(inputs)
+    }
+
+    /// Swaps `inputs` at the mint for new notes of the given `values` and
+    /// stores them. Returns the mint's fee receipt, if it sent one.
+    pub(crate) fn swap_into(
+        &mut self,
+        mint: &impl MintTrait,
+        inputs: Vec<Note>,
+        values: &[u64],
+    ) -> Result<Option<FeeReceipt>, MintError> {
+        let (notes, receipt) = self.swap_receipted(mint, inputs, self.random_outputs(values))?;
+        self.notes.extend(notes);
+        Ok(receipt)
+    }
+
+    /// Swaps `inputs` at the mint for notes with the given values and secrets,
+    /// returned in the same order.
+    pub(crate) fn swap_for(
+        &self,
+        mint: &impl MintTrait,
+        inputs: Vec<Note>,
+        secrets: Vec<(u64, Vec<u8>)>,
+    ) -> Result<Vec<Note>, MintError> {
+        Ok(self.swap_receipted(mint, inputs, secrets)?.0)
+    }
+
+    /// As `swap_for`, also returning the mint's fee receipt.
+    fn swap_receipted(
+        &self,
+        mint: &impl MintTrait,
+        inputs: Vec<Note>,
+        secrets: Vec<(u64, Vec<u8>)>,
+    ) -> Result<(Vec<Note>, Option<FeeReceipt>), MintError> {
+        let mut inputs = inputs;
+        sort_inputs(&mut inputs);
+        let session = self.connect(mint)?;
+        let mut receipt = None;
+        let notes = self.sign_outputs(mint, secrets, |outputs| {
+            let mut body = mint
+                .handle_swap(session.request(SwapRequest {
+                    inputs,
+                    outputs,
+                    operation: operation::current(),
+                }))?
+                .accept(&session)?;
+            receipt = body.receipt.take();
+            Ok(body)
+        })?;
+        Ok((notes, receipt))
+    }
+
+    /// Blinds `secrets`, has `sign` obtain the mint's signatures on them and
+    /// unblinds the results into notes, in the same order. `sign` is given
+    /// the outputs in canonical order.
+    fn sign_outputs(
+        &self,
+        mint: &impl MintTrait,
+        secrets: Vec<(u64, Vec<u8>)>,
+        sign: impl FnOnce(Vec<(u64, PublicKey)>) -> Result<SwapResponse, MintError>,
+    ) -> Result<Vec<Note>, MintError> {
+        let (outputs, pending) = blind_outputs(secrets);
+        let mut sorted: Vec<_> = outputs.into_iter().zip(pending).enumerate().collect();
+        sorted.sort_by_key(|(_, (output, _))| output_key(output));
+        let (positions, (outputs, pending)): (Vec<usize>, (Vec<_>, Vec<_>)) =
+            sorted.into_iter().unzip();
+
+        let keyset = mint.active_keyset()?;
+        let resp = sign(outputs)?;
+        let notes = unblind_outputs(&keyset, pending, &resp)?;
+        let mut notes: Vec<(usize, Note)> = positions.into_iter().zip(notes).collect();
+        notes.sort_unstable_by_key(|(i, _)| *i);
+        Ok(notes.into_iter().map(|(_, n)| n).collect())
+    }
+
+    /// Fresh random secrets for each value.
+    pub(crate) fn random_outputs(&self, values: &[u64]) -> Vec<(u64, Vec<u8>)> {
+        values
+            .iter()
+            .map(|&v| (v, self.secret_policy.generate()))
+            .collect()
+    }
+
+    pub fn spend(&mut self, mint: &impl LocalMint, amount: u64) -> bool {
+        self.guarded(SpendKind::Send, amount, |w| w.spend_direct(mint, amount))
+            .is_ok()
+    }
+
+    fn spend_direct(&mut self, mint: &impl LocalMint, amount: u64) -> Result<(), WalletError> {
+        let mut selected = Vec::new();
+        let mut sum = 0;
+
+        for n in &self.notes {
+            if sum >= amount {
+                break;
+            }
+            selected.push(n.clone());
+            sum += n.value;
+        }
+
+        if sum != amount {
+            return Err(WalletError::InsufficientFunds {
+                needed: amount,
+                available: sum,
+            });
+        }
+
+        for n in &selected {
+            mint.verify_and_spend(n)?;
+        }
+
+        for n in &selected {
+            self.notes.remove(&n.y);
+        }
+
+        Ok(())
+    }
+}
+
+/// An output blinded for the mint, with what is needed to unblind its
+/// signature.
+pub(crate) struct PendingOutput {
+    pub(crate) value: u64,
+    pub(crate) secret: Vec<u8>,
+    pub(crate) y: PublicKey,
+    pub(crate) blinded: BlindedMessage,
+}
+
+/// Blinds `secrets`, returning the outputs for the mint and the matching
+/// pending outputs, in the same order.
+pub(crate) fn blind_outputs(
+    secrets: Vec<(u64, Vec<u8>)>,
+) -> (Vec<(u64, PublicKey)>, Vec<PendingOutput>) {
+    let ys = hash_to_curve_batch(&secrets.iter().map(|(_, s)| s).collect::<Vec<_>>());
+    secrets
+        .into_iter()
+        .zip(ys)
+        .map(|((value, secret), y)| {
+            let blinded = blind_message(&y);
+            let output = (value, blinded.blinded_point);
+            (
+                output,
+                PendingOutput {
+                    value,
+                    secret,
+                    y,
+                    blinded,
+                },
+            )
+        })
+        .unzip()
+}
+
+/// Checks and unblinds the mint's signatures on `pending` into notes.
+pub(crate) fn unblind_outputs(
+    keyset: &KeysetKeys,
+    pending: Vec<PendingOutput>,
+    resp: &SwapResponse,
+) -> Result<Vec<Note>, MintError> {
+    let mut notes = Vec::new();
+    for (i, p) in pending.into_iter().enumerate() {
+        let key = keyset
+            .key(p.value)
+            .ok_or(MintError::UnknownDenomination(p.value))?;
+        let blind_sig = *resp.signatures.get(i).ok_or(MintError::InvalidSignature)?;
+        if resp
+            .dleqs
+            .get(i)
+            .is_some_and(|d| !dleq::verify(key, &p.blinded.blinded_point, &blind_sig, d))
+        {
+            return Err(MintError::InvalidSignature);
+        }
+        let c = unblind_signature(&blind_sig, &p.blinded.blind_factor, key);
+        let dleq = resp.dleqs.get(i).map(|d| NoteDleq {
+            e: d.e,
+            s: d.s,
+            r: SecretKey::from_slice(&p.blinded.blind_factor.to_be_bytes()).unwrap(),
+        });
+
+        notes.push(Note {
+            value: p.value,
+            keyset_id: keyset.keyset_id.clone(),
+            secret: p.secret,
+            y: p.y,
+            c,
+            dleq,
+            witness: None,
+        });
+    }
+    Ok(notes)
+}
+
+/// Splits `amount` into the given denominations, largest first. Returns
+/// `None` if the denominations cannot represent it exactly.
+pub fn split_amount(amount: u64, denoms: &[u64]) -> Option<Vec<u64>> {
+    let mut denoms = denoms.to_vec();
+    denoms.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut remaining = amount;
+    let mut parts = Vec::new();
+    for d in denoms {
+        while d > 0 && remaining >= d {
+            parts.push(d);
+            remaining -= d;
+        }
+    }
+
+    (remaining == 0).then_some(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::RngCore;
+    use secp256k1::Secp256k1;
+
+    use super::*;
+    use crate::{keyset::KeysetIdVersion, mint::FeeRounding};
+
+    fn wallet_with(values: &[u64]) -> Wallet {
+        let mut wallet = Wallet::new();
+        for &value in values {
+            let mut secret = vec![0; 32];
+            rand::thread_rng().fill_bytes(&mut secret);
+            wallet.notes.push(Note {
+                value,
+                keyset_id: "00".to_string(),
+                y: hash_to_curve(&secret),
+                secret,
+                c: SecretKey::new(&mut rand::thread_rng()).public_key(&Secp256k1::new()),
+                dleq: None,
+                witness: None,
+            });
+        }
+        wallet
+    }
+
+    /// A mint charging `input_fee_ppk` per input, rounded up.
+    fn info(input_fee_ppk: u64) -> MintInfo {
+        MintInfo {
+            version: "dmto/test".to_string(),
+            keyset_id: "00".to_string(),
+            keyset_id_version: KeysetIdVersion::default(),
+            denominations: vec![1, 2, 4, 8],
+            max_order: None,
+            accepted_kinds: Vec::new(),
+            input_fee_ppk,
+            fee_rounding: FeeRounding::Up,
+            max_inputs: 100,
+            max_outputs: 100,
+            secret_policy: SecretPolicy::default(),
+            max_restore_batch: 100,
+        }
+    }
+
+    fn values(notes: &[Note]) -> Vec<u64> {
+        notes.iter().map(|n| n.value).collect()
+    }
+
+    #[test]
+    fn select_with_fee_covers_the_fee_of_each_input() {
+        let mut wallet = wallet_with(&[8, 4, 2, 1]);
+        // 8 + 4 pays 10 and a fee of 1 per input.
+        let (selected, change) = wallet.select_with_fee(&info(1000), 10).unwrap();
+        assert_eq!((values(&selected), change), (vec![8, 4], 0));
+        assert_eq!(wallet.balance(), 3);
+
+        // 8 + 4 would pay 11 but not the fee for 2 inputs, so a third is
+        // taken and the fee for it paid too.
+        let mut wallet = wallet_with(&[8, 4, 2, 1]);
+        let (selected, change) = wallet.select_with_fee(&info(1000), 11).unwrap();
+        assert_eq!((values(&selected), change), (vec![8, 4, 2], 0));
+
+        // A fee below 1 per input still rounds up to 1.
+        let mut wallet = wallet_with(&[8, 4, 2, 1]);
+        let (selected, change) = wallet.select_with_fee(&info(100), 8).unwrap();
+        assert_eq!((values(&selected), change), (vec![8, 4], 3));
+    }
+
+    #[test]
+    fn select_with_fee_restores_the_notes_when_short() {
+        let mut wallet = wallet_with(&[8, 4, 2, 1]);
+        assert_eq!(
+            wallet.select_with_fee(&info(1000), 14),
+            Err(WalletError::InsufficientFunds {
+                needed: 18,
+                available: 15,
+            })
+        );
+        assert_eq!(wallet.balance(), 15);
+    }
+}
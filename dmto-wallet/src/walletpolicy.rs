@@ -0,0 +1,165 @@
+//! Rules a wallet checks before giving notes away: sending, locked sends
+//! and melts all ask the installed `WalletSpendPolicy` first. Parental
+//! controls and managed wallets install one centrally instead of wrapping
+//! every call site.
+//!
+//! This is the wallet-side counterpart of the mint's `policy::SpendPolicy`.
+
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpendKind {
+    /// Notes for a token, swapped out or handed over as they are.
+    Send,
+    /// Notes locked to spending conditions.
+    SendLocked,
+    /// A Lightning payment through the mint.
+    Melt { request: String },
+    /// Several payments melted from one set of notes.
+    MeltBatch { requests: Vec<String> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpendRequest {
+    pub kind: SpendKind,
+    /// What leaves the wallet; for melts, the quote plus its fee reserve.
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+pub trait WalletSpendPolicy: Send + Sync {
+    /// `Err` with a reason refuses the spend.
+    fn authorize(&self, req: &SpendRequest) -> Result<(), String>;
+
+    /// Called when an authorized spend failed and no notes left the wallet,
+    /// so budgets can give the amount back.
+    fn refund(&self, _req: &SpendRequest) {}
+}
+
+impl<P: WalletSpendPolicy> WalletSpendPolicy for Arc<P> {
+    fn authorize(&self, req: &SpendRequest) -> Result<(), String> {
+        (**self).authorize(req)
+    }
+
+    fn refund(&self, req: &SpendRequest) {
+        (**self).refund(req)
+    }
+}
+
+/// Refuses any single spend above `max`.
+#[derive(Clone, Copy, Debug)]
+pub struct AmountLimit {
+    pub max: u64,
+}
+
+impl WalletSpendPolicy for AmountLimit {
+    fn authorize(&self, req: &SpendRequest) -> Result<(), String> {
+        if req.amount > self.max {
+            return Err(format!("{} is over the limit of {}", req.amount, self.max));
+        }
+        Ok(())
+    }
+}
+
+/// Allows `limit` per `period` seconds, counted from the first spend of
+/// each period.
+pub struct DailyBudget {
+    pub limit: u64,
+    pub period: u64,
+    /// Start of the current period and what was spent in it.
+    state: Mutex<(u64, u64)>,
+}
+
+impl DailyBudget {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            period: 86_400,
+            state: Mutex::new((0, 0)),
+        }
+    }
+
+    pub fn remaining(&self, now: u64) -> u64 {
+        let (start, spent) = *self.state.lock().unwrap();
+        if now.saturating_sub(start) >= self.period {
+            self.limit
+        } else {
+            self.limit.saturating_sub(spent)
+        }
+    }
+}
+
+impl WalletSpendPolicy for DailyBudget {
+    fn authorize(&self, req: &SpendRequest) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if req.timestamp.saturating_sub(state.0) >= self.period {
+            *state = (req.timestamp, 0);
+        }
+        let remaining = self.limit.saturating_sub(state.1);
+        if req.amount > remaining {
+            return Err(format!("only {} left in today's budget", remaining));
+        }
+        state.1 += req.amount;
+        Ok(())
+    }
+
+    fn refund(&self, req: &SpendRequest) {
+        let mut state = self.state.lock().unwrap();
+        state.1 = state.1.saturating_sub(req.amount);
+    }
+}
+
+/// Asks `confirm`, typically a prompt to the user, for every spend at or
+/// above `threshold`.
+pub struct Confirm<F> {
+    pub threshold: u64,
+    pub confirm: F,
+}
+
+impl<F: Fn(&SpendRequest) -> bool + Send + Sync> WalletSpendPolicy for Confirm<F> {
+    fn authorize(&self, req: &SpendRequest) -> Result<(), String> {
+        if req.amount < self.threshold || (self.confirm)(req) {
+            Ok(())
+        } else {
+            Err("not confirmed".to_string())
+        }
+    }
+}
+
+/// Every policy must allow the spend. Policies that allowed it are refunded
+/// when a later one refuses.
+#[derive(Default)]
+pub struct AllOf {
+    pub policies: Vec<Box<dyn WalletSpendPolicy>>,
+}
+
+impl AllOf {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, policy: impl WalletSpendPolicy + 'static) -> Self {
+        self.policies.push(Box::new(policy));
+        self
+    }
+}
+
+impl WalletSpendPolicy for AllOf {
+    fn authorize(&self, req: &SpendRequest) -> Result<(), String> {
+        for (i, policy) in self.policies.iter().enumerate() {
+            if let Err(reason) = policy.authorize(req) {
+                for allowed in &self.policies[..i] {
+                    allowed.refund(req);
+                }
+                return Err(reason);
+            }
+        }
+        Ok(())
+    }
+
+    fn refund(&self, req: &SpendRequest) {
+        for policy in &self.policies {
+            policy.refund(req);
+        }
+    }
+}